@@ -3,7 +3,7 @@ use wreq::{
     Client, EmulationProvider, OriginalHeaders,
     http1::Http1Config,
     http2::{
-        Http2Config, Priorities, Priority, PseudoId, PseudoOrder, SettingId, SettingsOrder,
+        Http2Config, PriorityTree, PseudoId, PseudoOrder, SettingId, SettingsOrder,
         StreamDependency, StreamId,
     },
     tls::{AlpnProtocol, CertificateCompressionAlgorithm, ExtensionType, TlsConfig, TlsVersion},
@@ -140,34 +140,7 @@ async fn main() -> wreq::Result<()> {
             .build();
 
         // HTTP/2 Priority frames
-        let priorities = Priorities::builder()
-            .extend([
-                Priority::new(
-                    StreamId::from(3),
-                    StreamDependency::new(StreamId::zero(), 200, false),
-                ),
-                Priority::new(
-                    StreamId::from(5),
-                    StreamDependency::new(StreamId::zero(), 100, false),
-                ),
-                Priority::new(
-                    StreamId::from(7),
-                    StreamDependency::new(StreamId::zero(), 0, false),
-                ),
-                Priority::new(
-                    StreamId::from(9),
-                    StreamDependency::new(StreamId::from(7), 0, false),
-                ),
-                Priority::new(
-                    StreamId::from(11),
-                    StreamDependency::new(StreamId::from(3), 0, false),
-                ),
-                Priority::new(
-                    StreamId::from(13),
-                    StreamDependency::new(StreamId::zero(), 240, false),
-                ),
-            ])
-            .build();
+        let priorities = PriorityTree::firefox().build()?;
 
         Http2Config::builder()
             .initial_stream_id(15)
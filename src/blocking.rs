@@ -0,0 +1,71 @@
+//! A blocking connect, for tools that have no async runtime of their own.
+//!
+//! ```no_run
+//! # fn run() -> wreq::Result<()> {
+//! let info = wreq::blocking::connect(wreq::Client::builder(), "https://example.com")?;
+//! if let Some(tls_info) = info.tls_info() {
+//!     println!("ALPN: {:?}", tls_info.alpn_selected());
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use tokio::runtime;
+
+use crate::{ClientBuilder, Error, IntoUrl, tls::TlsInfo};
+
+/// What is known about a connection made by [`connect`].
+#[derive(Debug, Clone)]
+pub struct ConnectInfo {
+    pub(crate) tls_info: Option<TlsInfo>,
+    pub(crate) proxied: bool,
+    pub(crate) negotiated_h2: bool,
+}
+
+impl ConnectInfo {
+    /// Returns the TLS information of the connection, if it uses TLS.
+    pub fn tls_info(&self) -> Option<&TlsInfo> {
+        self.tls_info.as_ref()
+    }
+
+    /// Returns whether the connection goes to an HTTP proxy that forwards
+    /// requests, rather than to the host itself.
+    pub fn is_proxied(&self) -> bool {
+        self.proxied
+    }
+
+    /// Returns whether HTTP/2 was negotiated on the connection.
+    pub fn is_negotiated_h2(&self) -> bool {
+        self.negotiated_h2
+    }
+}
+
+/// Connects to the host of `url` without sending a request, blocking until
+/// the connection is established or fails.
+///
+/// The connection is made by a client built from `builder`, like that of
+/// any request: through the configured proxies, DNS and TLS settings, and
+/// within the connect timeout. TLS information is always recorded. The
+/// connect runs on a current-thread runtime created for the call, and the
+/// connection is closed before returning.
+///
+/// # Errors
+///
+/// This function fails if the client cannot be built, if `url` cannot be
+/// parsed or uses an unsupported scheme, or if the connect fails or times
+/// out.
+///
+/// # Panics
+///
+/// This function panics if called from within an async runtime.
+pub fn connect<U: IntoUrl>(builder: ClientBuilder, url: U) -> crate::Result<ConnectInfo> {
+    let rt = runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .map_err(Error::builder)?;
+
+    rt.block_on(async move {
+        let client = builder.tls_info(true).build()?;
+        client.connect(url).await
+    })
+}
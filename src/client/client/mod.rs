@@ -13,10 +13,13 @@ use std::{
 
 use future::Pending;
 use http::{
-    Request as HttpRequest, Response as HttpResponse,
+    Request as HttpRequest, Response as HttpResponse, Version,
     header::{HeaderMap, HeaderValue, USER_AGENT},
 };
 use service::ClientService;
+use tokio::sync::mpsc;
+#[cfg(feature = "cancellation")]
+use tokio_util::sync::CancellationToken;
 use tower::{
     Layer, Service, ServiceBuilder,
     retry::RetryLayer,
@@ -46,21 +49,29 @@ use super::{
     },
     request::{Request, RequestBuilder},
     response::Response,
+    upgrade::Upgraded,
 };
 #[cfg(feature = "hickory-dns")]
 use crate::dns::hickory::{HickoryDnsResolver, LookupIpStrategy};
 use crate::{
-    IntoUrl, Method, OriginalHeaders, Proxy,
+    ConnEvent, Dst, IntoUrl, Method, OriginalHeaders, Proxy, ProxyMatchPolicy,
     connect::{
-        BoxedConnectorLayer, BoxedConnectorService, Connector,
+        BoxedConnectorLayer, BoxedConnectorService, Connector, IoDirection, IoInspector,
+        MapConnectError,
         sealed::{Conn, Unnameable},
     },
     core::{
         body::Incoming,
-        client::{Builder, Client as HyperClient},
+        client::{
+            Builder, Client as HyperClient,
+            connect::{
+                SocketHook,
+                proxy::{ClientInfo, ProxyProtocolVersion},
+            },
+        },
         rt::{TokioExecutor, tokio::TokioTimer},
     },
-    dns::{DnsResolverWithOverrides, DynResolver, Resolve, gai::GaiResolver},
+    dns::{AddressOrder, DnsResolverWithOverrides, DynResolver, Resolve, gai::GaiResolver},
     error::{self, BoxError, Error},
     http1::Http1Config,
     http2::Http2Config,
@@ -68,7 +79,9 @@ use crate::{
     proxy::Matcher as ProxyMatcher,
     redirect::{self, RedirectPolicy},
     tls::{
-        AlpnProtocol, CertStore, CertificateInput, Identity, KeyLogPolicy, TlsConfig, TlsVersion,
+        AlpnProtocol, CertStore, CertificateInput, ClientHelloHook, HandshakeProgressHook,
+        HandshakeState, Identity, KeyLogPolicy, Nid, SniCallback, TlsConfig, TlsConnector,
+        TlsVersion,
     },
 };
 
@@ -119,6 +132,9 @@ pub struct Client {
 /// A reference to the `Client` that is used internally.
 struct ClientRef {
     service: BoxedClientService,
+    pool: HyperClient<Connector, Body>,
+    tls: TlsConnector,
+    tls_fingerprint: u64,
     https_only: bool,
 }
 
@@ -148,16 +164,44 @@ struct Config {
     ))]
     accepts: AcceptEncoding,
     connect_timeout: Option<Duration>,
+    #[cfg(feature = "socks")]
+    socks_handshake_timeout: Option<Duration>,
+    #[cfg(feature = "socks")]
+    socks_retries: u32,
+    #[cfg(feature = "cancellation")]
+    connect_cancellation: Option<CancellationToken>,
+    map_connect_error: Option<MapConnectError>,
+    max_concurrent_connects: usize,
+    connect_retries: u32,
+    connect_retry_backoff: Option<Duration>,
+    read_idle_timeout: Option<Duration>,
+    write_stall_timeout: Option<Duration>,
+    write_coalesce: Option<usize>,
+    count_bytes: bool,
+    inspect_io: Option<IoInspector>,
+    proxy_protocol: Option<(ProxyProtocolVersion, ClientInfo)>,
+    on_socket: Option<SocketHook>,
+    forced_http_versions: Vec<(String, Version)>,
     connection_verbose: bool,
+    connection_events: Option<mpsc::Sender<ConnEvent>>,
     pool_idle_timeout: Option<Duration>,
     pool_max_idle_per_host: usize,
     pool_max_size: Option<NonZeroUsize>,
+    check_connection_on_reuse: bool,
+    check_connection_timeout: Duration,
     tcp_keepalive: Option<Duration>,
     tcp_keepalive_interval: Option<Duration>,
     tcp_keepalive_retries: Option<u32>,
+    tcp_linger: Option<Duration>,
     #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
     tcp_user_timeout: Option<Duration>,
+    tcp_maxseg: Option<u32>,
+    #[cfg(target_os = "linux")]
+    tcp_fastopen: bool,
+    #[cfg(target_os = "linux")]
+    netns: Option<std::path::PathBuf>,
     proxies: Vec<ProxyMatcher>,
+    proxy_match_policy: ProxyMatchPolicy,
     auto_sys_proxy: bool,
     redirect_policy: redirect::Policy,
     referer: bool,
@@ -179,27 +223,55 @@ struct Config {
     local_ipv4_address: Option<Ipv4Addr>,
     local_ipv6_address: Option<Ipv6Addr>,
     nodelay: bool,
+    auto_nodelay_for_tls: bool,
+    handshake_nodelay_only: bool,
     #[cfg(feature = "cookies")]
     cookie_store: Option<Arc<dyn cookie::CookieStore>>,
     #[cfg(feature = "hickory-dns")]
     hickory_dns: bool,
     dns_overrides: HashMap<String, Vec<SocketAddr>>,
     dns_resolver: Option<Arc<dyn Resolve>>,
+    address_order: AddressOrder,
+    happy_eyeballs_timeout: Option<Duration>,
     http_version_pref: HttpVersionPref,
+    http2_prior_knowledge: bool,
+    http2_fallback: bool,
+    race_protocols: bool,
     https_only: bool,
+    enforce_http: bool,
     http1_config: Http1Config,
+    http1_pipelining: Option<usize>,
     http2_config: Http2Config,
     http2_max_retry: usize,
     request_layers: Option<Vec<BoxedClientServiceLayer>>,
     connector_layers: Option<Vec<BoxedConnectorLayer>>,
     builder: Builder,
     tls_keylog_policy: Option<KeyLogPolicy>,
+    tls_client_hello_hook: Option<ClientHelloHook>,
+    tls_compute_ja4: bool,
+    tls_collect_timings: bool,
+    tls_handshake_progress: Option<HandshakeProgressHook>,
+    tls_sni_callback: Option<SniCallback>,
+    tls_server_name: Option<String>,
     tls_info: bool,
+    tls_require_alpn: bool,
+    tls_alpn_protocols: Option<Vec<Vec<u8>>>,
+    tls_cipher_list: Option<std::borrow::Cow<'static, str>>,
+    tls_min_rsa_bits: Option<u16>,
+    tls_spki_pins: Vec<[u8; 32]>,
+    tls_min_cert_validity_remaining: Option<Duration>,
+    tls_ocsp_stapling: bool,
+    tls_allowed_curves_for_leaf: Option<Vec<Nid>>,
+    tls_custom_extensions: Vec<(u16, Vec<u8>)>,
+    tls_read_buffer_size: Option<usize>,
     tls_sni: bool,
     tls_verify_hostname: bool,
     tls_identity: Option<Identity>,
     tls_cert_store: CertStore,
     tls_cert_verification: bool,
+    tls_false_start: bool,
+    tls_early_data: bool,
+    tls_session_cache_capacity: Option<usize>,
     min_tls_version: Option<TlsVersion>,
     max_tls_version: Option<TlsVersion>,
     tls_config: TlsConfig,
@@ -229,18 +301,46 @@ impl ClientBuilder {
                 ))]
                 accepts: AcceptEncoding::default(),
                 connect_timeout: None,
+                #[cfg(feature = "socks")]
+                socks_handshake_timeout: None,
+                #[cfg(feature = "socks")]
+                socks_retries: 0,
+                #[cfg(feature = "cancellation")]
+                connect_cancellation: None,
+                map_connect_error: None,
+                max_concurrent_connects: 0,
+                connect_retries: 0,
+                connect_retry_backoff: None,
+                read_idle_timeout: None,
+                write_stall_timeout: None,
+                write_coalesce: None,
+                count_bytes: false,
+                inspect_io: None,
+                proxy_protocol: None,
+                on_socket: None,
+                forced_http_versions: Vec::new(),
                 connection_verbose: false,
+                connection_events: None,
                 pool_idle_timeout: Some(Duration::from_secs(90)),
                 pool_max_idle_per_host: usize::MAX,
                 pool_max_size: None,
+                check_connection_on_reuse: false,
+                check_connection_timeout: Duration::from_secs(1),
                 // TODO: Re-enable default duration once hyper's HttpConnector is fixed
                 // to no longer error when an option fails.
                 tcp_keepalive: None,
                 tcp_keepalive_interval: None,
                 tcp_keepalive_retries: None,
+                tcp_linger: None,
                 #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
                 tcp_user_timeout: None,
+                tcp_maxseg: None,
+                #[cfg(target_os = "linux")]
+                tcp_fastopen: false,
+                #[cfg(target_os = "linux")]
+                netns: None,
                 proxies: Vec::new(),
+                proxy_match_policy: ProxyMatchPolicy::FirstWins,
                 auto_sys_proxy: true,
                 redirect_policy: redirect::Policy::default(),
                 referer: true,
@@ -262,27 +362,55 @@ impl ClientBuilder {
                 local_ipv4_address: None,
                 local_ipv6_address: None,
                 nodelay: true,
+                auto_nodelay_for_tls: true,
+                handshake_nodelay_only: true,
                 #[cfg(feature = "hickory-dns")]
                 hickory_dns: cfg!(feature = "hickory-dns"),
                 #[cfg(feature = "cookies")]
                 cookie_store: None,
                 dns_overrides: HashMap::new(),
                 dns_resolver: None,
+                address_order: AddressOrder::AsResolved,
+                happy_eyeballs_timeout: Some(Duration::from_millis(300)),
                 http_version_pref: HttpVersionPref::All,
+                http2_prior_knowledge: false,
+                http2_fallback: false,
+                race_protocols: false,
                 builder: HyperClient::builder(TokioExecutor::new()),
                 https_only: false,
+                enforce_http: false,
                 http1_config: Http1Config::default(),
+                http1_pipelining: None,
                 http2_config: Http2Config::default(),
                 http2_max_retry: 2,
                 request_layers: None,
                 connector_layers: None,
                 tls_keylog_policy: None,
+                tls_client_hello_hook: None,
+                tls_compute_ja4: false,
+                tls_collect_timings: false,
+                tls_handshake_progress: None,
+                tls_sni_callback: None,
+                tls_server_name: None,
                 tls_info: false,
+                tls_require_alpn: false,
+                tls_alpn_protocols: None,
+                tls_cipher_list: None,
+                tls_min_rsa_bits: None,
+                tls_spki_pins: Vec::new(),
+                tls_min_cert_validity_remaining: None,
+                tls_ocsp_stapling: false,
+                tls_allowed_curves_for_leaf: None,
+                tls_custom_extensions: Vec::new(),
+                tls_read_buffer_size: None,
                 tls_sni: true,
                 tls_verify_hostname: true,
                 tls_identity: None,
                 tls_cert_store: CertStore::default(),
                 tls_cert_verification: true,
+                tls_false_start: false,
+                tls_early_data: false,
+                tls_session_cache_capacity: None,
                 min_tls_version: None,
                 max_tls_version: None,
                 tls_config: TlsConfig::default(),
@@ -303,6 +431,36 @@ impl ClientBuilder {
             return Err(err);
         }
 
+        // Client-initiated HTTP/2 streams must use odd ids, which then increment by 2.
+        if let Some(id) = config.http2_config.h2_builder.initial_stream_id {
+            if id % 2 == 0 {
+                return Err(Error::builder(
+                    "http2 initial stream id must be odd and non-zero",
+                ));
+            }
+        }
+
+        // Flow control windows are at most 2^31-1 bytes.
+        if config.http2_config.h2_builder.initial_conn_window_size > (1 << 31) - 1 {
+            return Err(Error::builder("http2 connection window is too large"));
+        }
+
+        // The override would otherwise leave an HTTP/2 only client with
+        // nothing it can speak.
+        if let Some(ref protocols) = config.tls_alpn_protocols {
+            if matches!(config.http_version_pref, HttpVersionPref::Http2)
+                && !protocols.iter().any(|protocol| protocol == b"h2")
+            {
+                return Err(Error::builder(
+                    "custom ALPN protocols leave out h2, which http2_only needs",
+                ));
+            }
+        }
+
+        if let Some(depth) = config.http1_pipelining {
+            config.http1_config.h1_pipeline_depth = Some(depth);
+        }
+
         let mut proxies = config.proxies;
         if config.auto_sys_proxy {
             proxies.push(ProxyMatcher::system());
@@ -322,9 +480,10 @@ impl ClientBuilder {
             .pool_timer(TokioTimer::new())
             .pool_idle_timeout(config.pool_idle_timeout)
             .pool_max_idle_per_host(config.pool_max_idle_per_host)
-            .pool_max_size(config.pool_max_size);
+            .pool_max_size(config.pool_max_size)
+            .reuse_ping_timeout(config.check_connection_timeout);
 
-        let connector = {
+        let (connector, tls, tls_fingerprint) = {
             let resolver = {
                 let mut resolver: Arc<dyn Resolve> = match config.dns_resolver {
                     Some(dns_resolver) => dns_resolver,
@@ -354,23 +513,81 @@ impl ClientBuilder {
                 _ => {}
             }
 
-            Connector::builder(proxies.clone(), resolver)
+            let connector = Connector::builder(proxies.clone(), resolver)
                 .connect_timeout(config.connect_timeout)
+                .socks_handshake_timeout(
+                    #[cfg(feature = "socks")]
+                    config.socks_handshake_timeout,
+                )
+                .socks_retries(
+                    #[cfg(feature = "socks")]
+                    config.socks_retries,
+                )
+                .enforce_http(config.enforce_http)
+                .cancellation_token(
+                    #[cfg(feature = "cancellation")]
+                    config.connect_cancellation,
+                )
+                .map_connect_error(config.map_connect_error)
+                .max_concurrent_connects(config.max_concurrent_connects)
+                .connect_retries(config.connect_retries)
+                .connect_retry_backoff(config.connect_retry_backoff)
+                .read_idle_timeout(config.read_idle_timeout)
+                .write_stall_timeout(config.write_stall_timeout)
+                .write_coalesce(config.write_coalesce)
+                .count_bytes(config.count_bytes)
+                .inspect_io(config.inspect_io)
+                .check_connection_on_reuse(config.check_connection_on_reuse)
                 .keepalive(config.tcp_keepalive)
                 .tcp_keepalive_interval(config.tcp_keepalive_interval)
                 .tcp_keepalive_retries(config.tcp_keepalive_retries)
+                .linger(config.tcp_linger)
                 .local_addresses(config.local_ipv4_address, config.local_ipv6_address)
                 .nodelay(config.nodelay)
+                .proxy_match_policy(config.proxy_match_policy)
+                .auto_nodelay_for_tls(config.auto_nodelay_for_tls)
+                .handshake_nodelay_only(config.handshake_nodelay_only)
+                .address_order(config.address_order)
+                .happy_eyeballs_timeout(config.happy_eyeballs_timeout)
+                .http2_prior_knowledge(config.http2_prior_knowledge)
+                .h2_to_h1_fallback(
+                    config.http2_fallback
+                        && !matches!(config.http_version_pref, HttpVersionPref::Http2),
+                )
+                .race_protocols(
+                    config.race_protocols
+                        && matches!(config.http_version_pref, HttpVersionPref::All),
+                )
                 .verbose(config.connection_verbose)
+                .events(config.connection_events)
                 .tls_max_version(config.max_tls_version)
                 .tls_min_version(config.min_tls_version)
                 .tls_info(config.tls_info)
+                .tls_read_buffer_size(config.tls_read_buffer_size)
                 .tls_sni(config.tls_sni)
                 .tls_verify_hostname(config.tls_verify_hostname)
                 .tls_cert_verification(config.tls_cert_verification)
                 .tls_cert_store(config.tls_cert_store)
                 .tls_identity(config.tls_identity)
                 .tls_keylog_policy(config.tls_keylog_policy)
+                .tls_client_hello_hook(config.tls_client_hello_hook)
+                .tls_compute_ja4(config.tls_compute_ja4)
+                .collect_timings(config.tls_collect_timings)
+                .tls_handshake_progress(config.tls_handshake_progress)
+                .tls_sni_callback(config.tls_sni_callback)
+                .tls_server_name(config.tls_server_name)
+                .tls_false_start(config.tls_false_start)
+                .tls_early_data(config.tls_early_data)
+                .tls_session_cache_capacity(config.tls_session_cache_capacity)
+                .require_alpn(config.tls_require_alpn)
+                .tls_alpn_protocols(config.tls_alpn_protocols)
+                .tls_cipher_list(config.tls_cipher_list)
+                .min_rsa_bits(config.tls_min_rsa_bits)
+                .tls_spki_pins(config.tls_spki_pins)
+                .tls_min_cert_validity_remaining(config.tls_min_cert_validity_remaining)
+                .tls_ocsp_stapling(config.tls_ocsp_stapling)
+                .allowed_curves_for_leaf(config.tls_allowed_curves_for_leaf)
+                .tls_custom_extensions(config.tls_custom_extensions)
                 .interface(
                     #[cfg(any(
                         target_os = "android",
@@ -390,12 +607,39 @@ impl ClientBuilder {
                     #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
                     config.tcp_user_timeout,
                 )
-                .build(config.tls_config, config.connector_layers)?
+                .tcp_maxseg(config.tcp_maxseg)
+                .tcp_fastopen(
+                    #[cfg(target_os = "linux")]
+                    config.tcp_fastopen,
+                )
+                .netns(
+                    #[cfg(target_os = "linux")]
+                    config.netns,
+                )
+                .on_socket(config.on_socket);
+
+            let mut connector = match config.proxy_protocol {
+                Some((version, client)) => connector.send_proxy_protocol(version, client),
+                None => connector,
+            };
+            for (host, version) in config.forced_http_versions {
+                connector = connector.force_http_version(&host, version);
+            }
+
+            let tls_fingerprint = connector.tls_config_fingerprint(&config.tls_config);
+            let tls = connector.build_tls(config.tls_config)?;
+            (
+                connector.build(tls.clone(), config.connector_layers)?,
+                tls,
+                tls_fingerprint,
+            )
         };
 
+        let pool = config.builder.build(connector);
+
         let service = {
             let service = ClientService::new(
-                config.builder.build(connector),
+                pool.clone(),
                 config.headers,
                 config.original_headers,
                 proxies,
@@ -475,6 +719,9 @@ impl ClientBuilder {
         Ok(Client {
             inner: Arc::new(ClientRef {
                 service,
+                pool,
+                tls,
+                tls_fingerprint,
                 https_only: config.https_only,
             }),
         })
@@ -800,6 +1047,20 @@ impl ClientBuilder {
         self
     }
 
+    /// Sets what to do when more than one proxy matches a destination.
+    ///
+    /// By default, the first proxy added that matches is used, and a warning
+    /// names the others. With [`ProxyMatchPolicy::Strict`], connecting to
+    /// such a destination fails instead, with an error naming every proxy
+    /// that matches, so that overlapping proxy rules don't silently route
+    /// traffic through the wrong one.
+    ///
+    /// A proxy set on the request itself is used as is.
+    pub fn proxy_match_policy(mut self, policy: ProxyMatchPolicy) -> ClientBuilder {
+        self.config.proxy_match_policy = policy;
+        self
+    }
+
     /// Clear all `Proxies`, so `Client` will use no proxy anymore.
     ///
     /// # Note
@@ -836,6 +1097,10 @@ impl ClientBuilder {
 
     /// Set a timeout for only the connect phase of a `Client`.
     ///
+    /// If a domain resolves to multiple addresses, the timeout is divided
+    /// evenly across them. A request may override it with
+    /// `RequestBuilder::connect_timeout()`.
+    ///
     /// Default is `None`.
     ///
     /// # Note
@@ -847,10 +1112,181 @@ impl ClientBuilder {
         self
     }
 
+    /// Set how long a connection may wait for incoming bytes before it is
+    /// closed.
+    ///
+    /// Unlike [`ClientBuilder::read_timeout`], this applies to the transport
+    /// itself: whenever a read on the connection is pending, and no bytes
+    /// arrive within `timeout`, the connection fails with an error for which
+    /// [`Error::is_timeout`] returns `true`. This catches servers that accept
+    /// a connection and then never answer.
+    ///
+    /// Connections waiting in the pool are read from as well, so an idle
+    /// pooled connection is closed once it has been quiet for this long.
+    ///
+    /// Default is `None`.
+    pub fn read_idle_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.config.read_idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Set how long a write to a connection may make no progress before the
+    /// connection is closed.
+    ///
+    /// This guards against a peer that stops reading, for example during an
+    /// upload, leaving its receive window closed and the local socket buffer
+    /// full. When a pending write or flush makes no progress within `timeout`,
+    /// the connection fails with an error for which [`Error::is_timeout`]
+    /// returns `true`. Waiting for HTTP/2 flow control credit is not covered.
+    ///
+    /// Default is `None`.
+    pub fn write_stall_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.config.write_stall_timeout = Some(timeout);
+        self
+    }
+
+    /// Gather small writes to HTTP/1.1 connections into a buffer of `size`
+    /// bytes before writing them to the socket.
+    ///
+    /// A request may be written in several small pieces, such as its head
+    /// and a short body, and with [`ClientBuilder::tcp_nodelay`] each of them
+    /// can go out as its own packet. With this buffer, pieces are held until
+    /// it is full or the request is flushed, so they leave in one write.
+    /// Writes of `size` bytes or more are not copied. HTTP/2 connections are
+    /// left alone, as their frames are buffered already.
+    ///
+    /// Default is `None`.
+    pub fn write_coalesce(mut self, size: usize) -> ClientBuilder {
+        self.config.write_coalesce = Some(size);
+        self
+    }
+
+    /// Count the bytes read from and written to every connection.
+    ///
+    /// Responses carry a [`ByteCounts`] extension, also returned by
+    /// [`Response::byte_counts`], shared with their connection. Connections
+    /// that aren't counted pay nothing for it.
+    ///
+    /// Default is `false`.
+    ///
+    /// [`ByteCounts`]: crate::ByteCounts
+    /// [`Response::byte_counts`]: crate::Response::byte_counts
+    pub fn count_bytes(mut self, enabled: bool) -> ClientBuilder {
+        self.config.count_bytes = enabled;
+        self
+    }
+
+    /// Set a timeout for connecting to a SOCKS proxy and completing its
+    /// handshake.
+    ///
+    /// This lets a slow or unresponsive SOCKS proxy fail fast while
+    /// [`connect_timeout`](ClientBuilder::connect_timeout) stays generous. The
+    /// connect timeout still bounds the whole connect, including the SOCKS
+    /// handshake, and whatever it has left goes to the TLS handshake with the
+    /// origin. Hitting this timeout is an error for which
+    /// [`Error::is_timeout`] returns `true`.
+    ///
+    /// Default is `None`.
+    #[cfg(feature = "socks")]
+    pub fn socks_handshake_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.config.socks_handshake_timeout = Some(timeout);
+        self
+    }
+
+    /// Set how many more addresses of a SOCKS proxy to try when its handshake
+    /// fails transiently.
+    ///
+    /// This is meant for pools of SOCKS5 proxies behind a single name. When a
+    /// proxy answers with a general failure or an expired TTL, the handshake
+    /// is tried again on the next address the proxy name resolves to, up to
+    /// `retries` times. Other failures, such as a connection not allowed by
+    /// the ruleset or an unreachable host, fail right away. All attempts
+    /// share the [`connect_timeout`](ClientBuilder::connect_timeout).
+    ///
+    /// Default is `0`.
+    #[cfg(feature = "socks")]
+    pub fn socks_retries(mut self, retries: u32) -> ClientBuilder {
+        self.config.socks_retries = retries;
+        self
+    }
+
+    /// Set a token which aborts every in-flight connect once cancelled.
+    ///
+    /// This is meant for graceful shutdown: connects fail promptly with an
+    /// error for which [`Error::is_cancelled`] returns `true`, rather than
+    /// waiting out the connect timeout. Only the connect phase is affected.
+    ///
+    /// Default is `None`.
+    #[cfg(feature = "cancellation")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "cancellation")))]
+    pub fn connect_cancellation_token(mut self, token: CancellationToken) -> ClientBuilder {
+        self.config.connect_cancellation = Some(token);
+        self
+    }
+
+    /// Set how many connects may be in flight at once, across all hosts.
+    ///
+    /// This keeps a burst of requests to hosts without idle connections from
+    /// opening so many sockets at once that file descriptors run out. Further
+    /// connects wait until one finishes, whether it succeeds or fails. Only
+    /// establishing connections is limited, not how many stay open.
+    ///
+    /// Default is `0`, unlimited.
+    pub fn max_concurrent_connects(mut self, max: usize) -> ClientBuilder {
+        self.config.max_concurrent_connects = max;
+        self
+    }
+
+    /// Set how many more times to connect when a connect fails at the
+    /// connection level.
+    ///
+    /// A connect is tried again when it was refused, reset or closed before
+    /// the connection was established, with the host resolved anew each
+    /// time. Failures that another attempt won't fix, such as an invalid
+    /// certificate, are returned right away, and so are timeouts: all
+    /// attempts share the [`connect_timeout`](ClientBuilder::connect_timeout).
+    /// [`Error::is_connect_retryable`] tells which connect errors qualify.
+    ///
+    /// Default is `0`.
+    pub fn connect_retries(mut self, retries: u32) -> ClientBuilder {
+        self.config.connect_retries = retries;
+        self
+    }
+
+    /// Set how long to wait before the first retry of a connect, doubled for
+    /// every retry after it.
+    ///
+    /// Only takes effect along with [`connect_retries`](ClientBuilder::connect_retries).
+    ///
+    /// Default is `None`, retrying right away.
+    pub fn connect_retry_backoff(mut self, backoff: Duration) -> ClientBuilder {
+        self.config.connect_retry_backoff = Some(backoff);
+        self
+    }
+
+    /// Set a function that every error from the connect phase is passed through.
+    ///
+    /// This is the place to turn transport failures into an application's own
+    /// error types, for example to tell retryable errors from fatal ones. The
+    /// function runs last, after any [`connector_layer`](Self::connector_layer)s,
+    /// so connect timeouts reach it already converted to the error that
+    /// [`Error::is_timeout`] recognizes. Whatever it returns becomes the source
+    /// of the connect [`Error`].
+    ///
+    /// Default is `None`.
+    pub fn map_connect_error<F>(mut self, f: F) -> ClientBuilder
+    where
+        F: Fn(BoxError) -> BoxError + Send + Sync + 'static,
+    {
+        self.config.map_connect_error = Some(Arc::new(f));
+        self
+    }
+
     /// Set whether connections should emit verbose logs.
     ///
     /// Enabling this option will emit [log][] messages at the `TRACE` level
-    /// for read and write operations on connections.
+    /// for read and write operations on connections. It does nothing without
+    /// the `tracing` feature, unlike [`inspect_io`](ClientBuilder::inspect_io).
     ///
     /// [log]: https://crates.io/crates/log
     pub fn connection_verbose(mut self, verbose: bool) -> ClientBuilder {
@@ -858,6 +1294,63 @@ impl ClientBuilder {
         self
     }
 
+    /// Set a function called with every chunk of bytes read from or written to
+    /// a connection.
+    ///
+    /// This sends the traffic of connections to a sink of the application's
+    /// choosing, without the `tracing` feature. Over TLS, the bytes are those
+    /// of HTTP, after decryption. The function runs on the I/O path, so it
+    /// should hand the bytes off rather than block.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wreq::IoDirection;
+    ///
+    /// let client = wreq::Client::builder()
+    ///     .inspect_io(|direction, bytes| match direction {
+    ///         IoDirection::Read => eprintln!("<< {}", String::from_utf8_lossy(bytes)),
+    ///         IoDirection::Write => eprintln!(">> {}", String::from_utf8_lossy(bytes)),
+    ///     })
+    ///     .build()?;
+    /// # Ok::<(), wreq::Error>(())
+    /// ```
+    pub fn inspect_io<F>(mut self, inspector: F) -> ClientBuilder
+    where
+        F: Fn(IoDirection, &[u8]) + Send + Sync + 'static,
+    {
+        self.config.inspect_io = Some(Arc::new(inspector));
+        self
+    }
+
+    /// Report the life of every connection to `events`.
+    ///
+    /// A [`ConnEvent`] is sent as a connect resolves the host, opens TCP
+    /// connections, goes through a proxy and performs the TLS handshake,
+    /// once the connection is established, and when it is closed. Events
+    /// are sent without waiting: they are dropped if the channel is full,
+    /// and connects carry on if the receiver is gone.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # async fn doc() -> wreq::Result<()> {
+    /// let (tx, mut rx) = tokio::sync::mpsc::channel(64);
+    /// let client = wreq::Client::builder().connection_events(tx).build()?;
+    ///
+    /// tokio::spawn(async move {
+    ///     while let Some(event) = rx.recv().await {
+    ///         println!("{event:?}");
+    ///     }
+    /// });
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn connection_events(mut self, events: mpsc::Sender<ConnEvent>) -> ClientBuilder {
+        self.config.connection_events = Some(events);
+        self
+    }
+
     // HTTP options
 
     /// Set an optional timeout for idle sockets being kept-alive.
@@ -885,6 +1378,37 @@ impl ClientBuilder {
         self
     }
 
+    /// Check that a pooled connection is still alive before reusing it.
+    ///
+    /// HTTP/2 connections must answer a PING within
+    /// [`check_connection_timeout`](ClientBuilder::check_connection_timeout),
+    /// HTTP/1 connections must not have been closed by the peer, as seen by a
+    /// read that does not wait. A connection failing the check is retired and
+    /// the request goes out on a fresh connection instead, so a server closing
+    /// idle connections no longer fails the request that picks one up.
+    ///
+    /// This costs a round trip to the server before every request reusing an
+    /// HTTP/2 connection, and a system call for HTTP/1. Since the check reads
+    /// from outside the task driving the connection, every read and write of
+    /// an HTTP/1 connection also takes an uncontended lock.
+    ///
+    /// Default is `false`.
+    pub fn check_connection_on_reuse(mut self, enabled: bool) -> ClientBuilder {
+        self.config.check_connection_on_reuse = enabled;
+        self
+    }
+
+    /// Set how long a pooled HTTP/2 connection has to answer the PING of
+    /// [`check_connection_on_reuse`](ClientBuilder::check_connection_on_reuse).
+    ///
+    /// A connection that does not answer in time is retired.
+    ///
+    /// Default is 1 second.
+    pub fn check_connection_timeout(mut self, timeout: Duration) -> ClientBuilder {
+        self.config.check_connection_timeout = timeout;
+        self
+    }
+
     /// Disable keep-alive for the client.
     pub fn no_keepalive(mut self) -> ClientBuilder {
         self.config.pool_max_idle_per_host = 0;
@@ -898,12 +1422,111 @@ impl ClientBuilder {
         self
     }
 
+    /// Pipeline requests on HTTP/1.1 connections, up to `depth` at a time.
+    ///
+    /// A connection then sends its next request as soon as the previous one
+    /// is written, without waiting for its response. Responses come back in
+    /// the order the requests were sent. Only requests with idempotent methods,
+    /// such as `GET`, are pipelined; others wait for the responses ahead of
+    /// them. If the connection fails, requests whose responses are still due
+    /// fail too.
+    ///
+    /// Many servers and intermediaries handle pipelining poorly, so this is
+    /// meant for benchmarking and interoperability testing. A `depth` of 1 or
+    /// less disables it.
+    ///
+    /// Default is no pipelining.
+    pub fn http1_pipelining(mut self, depth: usize) -> ClientBuilder {
+        self.config.http1_pipelining = Some(depth.max(1));
+        self
+    }
+
     /// Only use HTTP/2.
     pub fn http2_only(mut self) -> ClientBuilder {
         self.config.http_version_pref = HttpVersionPref::Http2;
         self
     }
 
+    /// Use HTTP/2 with prior knowledge on cleartext (`http://`) connections.
+    ///
+    /// The client sends the HTTP/2 connection preface straight away, without
+    /// trying HTTP/1.1 or an `Upgrade: h2c` request first. This suits servers
+    /// known to speak h2c, such as internal gRPC services. `https://` requests
+    /// are unaffected and keep negotiating the protocol through ALPN, unlike
+    /// with [`http2_only`](ClientBuilder::http2_only).
+    ///
+    /// If the server answers with HTTP/1.x, the request fails with a
+    /// connection error.
+    ///
+    /// Requests sent through a plain HTTP proxy are unaffected.
+    ///
+    /// Defaults to `false`.
+    pub fn http2_prior_knowledge(mut self, enabled: bool) -> ClientBuilder {
+        self.config.http2_prior_knowledge = enabled;
+        self
+    }
+
+    /// Fall back to HTTP/1.1 for hosts where HTTP/2 breaks at the protocol level.
+    ///
+    /// Some middleboxes mangle HTTP/2 frames, which shows up as connection
+    /// errors such as `PROTOCOL_ERROR` or `FRAME_SIZE_ERROR`, or as a server
+    /// asking for `HTTP_1_1_REQUIRED`. With this enabled, such a failure makes
+    /// the next TLS connections to that host offer only HTTP/1.1 through ALPN.
+    /// Ordinary stream resets don't count.
+    ///
+    /// The downgrade is remembered per host for five minutes, after which
+    /// HTTP/2 is tried again. The request that hit the error still fails.
+    /// Requests asking for a specific HTTP version, and clients set to
+    /// [`http2_only`](ClientBuilder::http2_only), are unaffected.
+    ///
+    /// Defaults to `false`.
+    pub fn http2_fallback(mut self, enabled: bool) -> ClientBuilder {
+        self.config.http2_fallback = enabled;
+        self
+    }
+
+    /// Race HTTP/2 against HTTP/1.1 when opening TLS connections.
+    ///
+    /// Each new connection is made twice in parallel, once offering only
+    /// `h2` and once only `http/1.1` through ALPN. Whichever handshake
+    /// completes first is used and the other is cancelled, so servers that
+    /// stall on one protocol don't hold up requests. If one attempt fails,
+    /// the other still gets its chance. Both attempts share the
+    /// [`connect_timeout`](ClientBuilder::connect_timeout).
+    ///
+    /// Only connections made directly to the origin race. Requests asking
+    /// for a specific HTTP version, hosts with a forced version, and clients
+    /// set to [`http1_only`](ClientBuilder::http1_only) or
+    /// [`http2_only`](ClientBuilder::http2_only) are unaffected.
+    ///
+    /// Defaults to `false`.
+    pub fn race_protocols(mut self, enabled: bool) -> ClientBuilder {
+        self.config.race_protocols = enabled;
+        self
+    }
+
+    /// Negotiate a specific HTTP version with `host` over TLS.
+    ///
+    /// TLS handshakes with `host` offer only `http/1.1` through ALPN for
+    /// `Version::HTTP_11`, or only `h2` for `Version::HTTP_2`, while other
+    /// hosts keep the protocols configured for the client. This is a fixed
+    /// escape hatch for hosts known to break on a protocol, unlike
+    /// [`http2_fallback`](ClientBuilder::http2_fallback), which reacts to
+    /// errors. Requests asking for a specific HTTP version are unaffected.
+    /// Other versions are ignored.
+    ///
+    /// The version is forced by what is offered, since a server that selected
+    /// `h2` expects HTTP/2 frames on the connection, and can't be sent HTTP/1.1
+    /// requests instead. Hosts are matched case-insensitively.
+    ///
+    /// Can be called multiple times to force versions for several hosts.
+    pub fn force_http_version(mut self, host: &str, version: Version) -> ClientBuilder {
+        self.config
+            .forced_http_versions
+            .push((host.to_owned(), version));
+        self
+    }
+
     /// Sets the maximum number of safe retries for HTTP/2 connections.
     pub fn http2_max_retry(mut self, max: usize) -> ClientBuilder {
         self.config.http2_max_retry = max;
@@ -920,8 +1543,96 @@ impl ClientBuilder {
         self
     }
 
+    /// Set whether `TCP_NODELAY` is enabled for the TLS handshake of HTTPS
+    /// connections when [`tcp_nodelay`](ClientBuilder::tcp_nodelay) is
+    /// disabled.
+    ///
+    /// With Nagle's algorithm on, the small records of the handshake can wait
+    /// for the ack of the previous write, which adds a round trip or more to
+    /// every new connection. Disabling this keeps `tcp_nodelay(false)` in
+    /// effect throughout, which only pays off when handshake latency doesn't
+    /// matter, such as for bulk uploads over few connections.
+    ///
+    /// Default is `true`.
+    pub fn auto_nodelay_for_tls(mut self, enabled: bool) -> ClientBuilder {
+        self.config.auto_nodelay_for_tls = enabled;
+        self
+    }
+
+    /// Set whether `TCP_NODELAY` is turned off again once the TLS handshake
+    /// enabled by [`auto_nodelay_for_tls`](ClientBuilder::auto_nodelay_for_tls)
+    /// is done.
+    ///
+    /// Turning it off can race with the first writes of the request on a fast
+    /// server. Disabling this keeps `TCP_NODELAY` on for the whole connection
+    /// instead. This applies to direct connections and to those through HTTP
+    /// proxies.
+    ///
+    /// Default is `true`.
+    pub fn handshake_nodelay_only(mut self, enabled: bool) -> ClientBuilder {
+        self.config.handshake_nodelay_only = enabled;
+        self
+    }
+
+    /// Send a PROXY protocol header on every connection.
+    ///
+    /// The header is written once, as the very first bytes after the TCP
+    /// connect and before any TLS handshake, so that a load balancer in front
+    /// of the server can pass on the address of `client`. When going through
+    /// a proxy, the header is sent to the proxy.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wreq::{ClientInfo, ProxyProtocolVersion};
+    ///
+    /// let client = wreq::Client::builder()
+    ///     .proxy_protocol(
+    ///         ProxyProtocolVersion::V2,
+    ///         ClientInfo::new("192.0.2.1:56324".parse().unwrap()),
+    ///     )
+    ///     .build()
+    ///     .unwrap();
+    /// ```
+    pub fn proxy_protocol(
+        mut self,
+        version: ProxyProtocolVersion,
+        client: ClientInfo,
+    ) -> ClientBuilder {
+        self.config.proxy_protocol = Some((version, client));
+        self
+    }
+
+    /// Sets a hook called with every TCP socket right after it connects.
+    ///
+    /// This allows setting socket options the builder doesn't offer. The hook
+    /// runs before anything is sent on the socket, so before the TLS
+    /// handshake and any PROXY protocol header. When going through a proxy,
+    /// it is called with the socket connected to the proxy. An error from the
+    /// hook fails the connect with that error as its source.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let client = wreq::Client::builder()
+    ///     .on_socket(|socket| socket.set_ttl(16))
+    ///     .build()?;
+    /// # Ok::<(), wreq::Error>(())
+    /// ```
+    pub fn on_socket<F>(mut self, hook: F) -> ClientBuilder
+    where
+        F: Fn(&tokio::net::TcpStream) -> std::io::Result<()> + Send + Sync + 'static,
+    {
+        self.config.on_socket = Some(Arc::new(hook));
+        self
+    }
+
     /// Bind to a local IP Address.
     ///
+    /// Connections to proxies are bound the same way, the control connection
+    /// of a SOCKS proxy included, so no traffic leaves through the default
+    /// route instead.
+    ///
     /// # Example
     ///
     /// ```
@@ -958,6 +1669,9 @@ impl ClientBuilder {
 
     /// Bind to an interface by `SO_BINDTODEVICE`.
     ///
+    /// Connections to proxies are bound to it as well, the control connection
+    /// of a SOCKS proxy included.
+    ///
     /// # Example
     ///
     /// ```
@@ -1020,6 +1734,24 @@ impl ClientBuilder {
         self
     }
 
+    /// Set that all sockets have `SO_LINGER` set with the supplied duration.
+    ///
+    /// With `Some(Duration::ZERO)`, closing a connection sends a RST rather
+    /// than a FIN, which frees the connection on the server right away. Any
+    /// data still waiting in the socket's send buffer is discarded, so a
+    /// request the server has not fully received yet is lost. A non-zero
+    /// duration lets closing wait up to that long for pending data to be
+    /// sent. This also applies to connections to a proxy.
+    ///
+    /// If `None`, the option will not be set and the OS default is used.
+    pub fn tcp_linger<D>(mut self, val: D) -> ClientBuilder
+    where
+        D: Into<Option<Duration>>,
+    {
+        self.config.tcp_linger = val.into();
+        self
+    }
+
     /// Set that all sockets have `TCP_USER_TIMEOUT` set with the supplied duration.
     ///
     /// This option controls how long transmitted data may remain unacknowledged before
@@ -1035,6 +1767,57 @@ impl ClientBuilder {
         self
     }
 
+    /// Set that all sockets have `TCP_MAXSEG` set to the supplied value
+    /// before connecting.
+    ///
+    /// This clamps the maximum segment size of connections, including those
+    /// to a proxy, which avoids fragmentation on paths with a smaller MTU
+    /// than the endpoints see, such as tunnels. It is only supported on
+    /// Linux, and ignored elsewhere.
+    ///
+    /// If `None`, the option will not be set and the OS default is used.
+    pub fn tcp_maxseg<M>(mut self, mss: M) -> ClientBuilder
+    where
+        M: Into<Option<u32>>,
+    {
+        self.config.tcp_maxseg = mss.into();
+        self
+    }
+
+    /// Set whether sockets connect with TCP Fast Open.
+    ///
+    /// Once a server has handed out a Fast Open cookie, later connections to
+    /// it send the first bytes of the request, or the TLS ClientHello, in the
+    /// SYN, which saves a round trip. This sets `TCP_FASTOPEN_CONNECT`, which
+    /// needs Linux 4.11 or later and `net.ipv4.tcp_fastopen` to allow client
+    /// use. Where the socket refuses it, connecting carries on without.
+    ///
+    /// Default is `false`.
+    #[cfg(target_os = "linux")]
+    pub fn tcp_fastopen(mut self, enabled: bool) -> ClientBuilder {
+        self.config.tcp_fastopen = enabled;
+        self
+    }
+
+    /// Create sockets inside the Linux network namespace at `path`, such as
+    /// `/var/run/netns/<name>`.
+    ///
+    /// Each socket is created on the connecting thread after switching it
+    /// into the namespace with `setns(2)`, and the thread is switched back
+    /// right after. This needs `CAP_SYS_ADMIN`. DNS resolution still happens
+    /// in the process' own namespace, so a namespace with different name
+    /// servers needs its own [`dns_resolver`](ClientBuilder::dns_resolver).
+    ///
+    /// The current default is `None` (the process' namespace).
+    #[cfg(target_os = "linux")]
+    pub fn netns<P>(mut self, path: P) -> ClientBuilder
+    where
+        P: Into<std::path::PathBuf>,
+    {
+        self.config.netns = Some(path.into());
+        self
+    }
+
     // TLS/HTTP2 emulation options
 
     /// Configures the client builder to emulation the specified HTTP context.
@@ -1136,7 +1919,9 @@ impl ClientBuilder {
     /// invalid certificates are trusted, *any* certificate for *any* site
     /// will be trusted for use. This includes expired certificates. This
     /// introduces significant vulnerabilities, and should only be used
-    /// as a last resort.
+    /// as a last resort. Keys pinned with
+    /// [`tls_pin_spki_sha256`](ClientBuilder::tls_pin_spki_sha256) are still
+    /// enforced.
     pub fn cert_verification(mut self, cert_verification: bool) -> ClientBuilder {
         self.config.tls_cert_verification = cert_verification;
         self
@@ -1171,12 +1956,165 @@ impl ClientBuilder {
         self
     }
 
+    /// Sets the server name used for every TLS connection in place of the
+    /// host of the URL.
+    ///
+    /// The name is sent as SNI and the server certificate is verified
+    /// against it, while the connection still goes to the host of the URL.
+    /// This suits testing a server under another name, or domain fronting.
+    /// To only change the name sent, per destination, see
+    /// [`sni_callback`](ClientBuilder::sni_callback).
+    ///
+    /// Building the client fails if [`tls_sni`](ClientBuilder::tls_sni) is
+    /// disabled as well.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let client = wreq::Client::builder()
+    ///     .tls_server_name("front.example.net")
+    ///     .build()?;
+    /// # Ok::<(), wreq::Error>(())
+    /// ```
+    pub fn tls_server_name<S>(mut self, name: S) -> ClientBuilder
+    where
+        S: Into<String>,
+    {
+        self.config.tls_server_name = Some(name.into());
+        self
+    }
+
     /// Configures TLS key logging policy for the client.
     pub fn keylog(mut self, policy: KeyLogPolicy) -> ClientBuilder {
         self.config.tls_keylog_policy = Some(policy);
         self
     }
 
+    /// Sets a hook invoked with every TLS `ClientHello` the client sends.
+    ///
+    /// The hook receives the serialized handshake message, starting with its
+    /// 4-byte header, which makes it possible to check a JA3 or JA4
+    /// fingerprint of the emulated client without capturing traffic. It is
+    /// called from within BoringSSL during the handshake, so it must not
+    /// panic or block.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    ///
+    /// let hellos = Arc::new(Mutex::new(Vec::new()));
+    /// let client = wreq::Client::builder()
+    ///     .on_client_hello({
+    ///         let hellos = hellos.clone();
+    ///         move |hello| hellos.lock().unwrap().push(hello.to_vec())
+    ///     })
+    ///     .build()?;
+    /// # Ok::<(), wreq::Error>(())
+    /// ```
+    pub fn on_client_hello<F>(mut self, hook: F) -> ClientBuilder
+    where
+        F: Fn(&[u8]) + Send + Sync + 'static,
+    {
+        self.config.tls_client_hello_hook = Some(Arc::new(hook));
+        self
+    }
+
+    /// Computes the JA4 and JA3 fingerprints of every TLS `ClientHello` the
+    /// client sends.
+    ///
+    /// The fingerprints are reported by [`TlsInfo::ja4`] and [`TlsInfo::ja3`],
+    /// so [`ClientBuilder::tls_info`] must be enabled as well.
+    ///
+    /// Default is `false`.
+    ///
+    /// [`TlsInfo::ja4`]: crate::tls::TlsInfo::ja4
+    /// [`TlsInfo::ja3`]: crate::tls::TlsInfo::ja3
+    pub fn compute_ja4(mut self, enabled: bool) -> ClientBuilder {
+        self.config.tls_compute_ja4 = enabled;
+        self
+    }
+
+    /// Times the steps of establishing every connection the client makes.
+    ///
+    /// Responses carry a [`ConnectTiming`] extension, also returned by
+    /// [`Response::connect_timing`], with the duration of name resolution,
+    /// the TCP connect, the tunnel through a proxy and the TLS handshake.
+    ///
+    /// Responses over TLS also carry a [`ConnectTimings`] extension with the
+    /// duration of the handshake, and of the certificate verification within
+    /// it. When verification dominates, as with servers that send an
+    /// incomplete chain, caching intermediates or stapling may be worth a
+    /// look.
+    ///
+    /// Default is `false`.
+    ///
+    /// [`ConnectTiming`]: crate::ConnectTiming
+    /// [`Response::connect_timing`]: crate::Response::connect_timing
+    /// [`ConnectTimings`]: crate::tls::ConnectTimings
+    pub fn collect_timings(mut self, enabled: bool) -> ClientBuilder {
+        self.config.tls_collect_timings = enabled;
+        self
+    }
+
+    /// Sets a hook invoked as each TLS handshake progresses.
+    ///
+    /// The hook is called with every [`HandshakeState`] a handshake reaches,
+    /// from [`Started`](HandshakeState::Started) to
+    /// [`Finished`](HandshakeState::Finished), which helps tell where a slow
+    /// handshake spends its time or show that a connection is being secured.
+    /// It is called from within BoringSSL during the handshake, so it must
+    /// not panic or block.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use wreq::tls::HandshakeState;
+    ///
+    /// let client = wreq::Client::builder()
+    ///     .on_handshake_progress(|state| {
+    ///         if state == HandshakeState::Started {
+    ///             println!("Securing connection…");
+    ///         }
+    ///     })
+    ///     .build()?;
+    /// # Ok::<(), wreq::Error>(())
+    /// ```
+    pub fn on_handshake_progress<F>(mut self, hook: F) -> ClientBuilder
+    where
+        F: Fn(HandshakeState) + Send + Sync + 'static,
+    {
+        self.config.tls_handshake_progress = Some(Arc::new(hook));
+        self
+    }
+
+    /// Computes the server name (SNI) sent to each TLS destination.
+    ///
+    /// The callback is invoked with the destination of every new connection,
+    /// right before connecting, and returns the name to send in place of the
+    /// host, or `None` to send the host as usual. This suits CDNs that route
+    /// on a name other than the one in the URL. The server certificate is
+    /// still verified against the host of the URL, unless
+    /// [`verify_hostname`](ClientBuilder::verify_hostname) is disabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let client = wreq::Client::builder()
+    ///     .sni_callback(|dst| {
+    ///         (dst.uri().host() == Some("example.com")).then(|| "edge.example.net".to_owned())
+    ///     })
+    ///     .build()?;
+    /// # Ok::<(), wreq::Error>(())
+    /// ```
+    pub fn sni_callback<F>(mut self, callback: F) -> ClientBuilder
+    where
+        F: Fn(&Dst) -> Option<String> + Send + Sync + 'static,
+    {
+        self.config.tls_sni_callback = Some(Arc::new(callback));
+        self
+    }
+
     /// Configures the use of hostname verification when connecting.
     ///
     /// Defaults to `true`.
@@ -1190,6 +2128,63 @@ impl ClientBuilder {
         self
     }
 
+    /// Allows TLS False Start on full TLS 1.2 handshakes.
+    ///
+    /// With False Start the request is sent before the server's `Finished`
+    /// message arrives, saving a round trip. BoringSSL only false starts with a
+    /// forward-secret AEAD cipher suite and a negotiated ALPN protocol.
+    ///
+    /// Defaults to `false`.
+    pub fn tls_false_start(mut self, enabled: bool) -> ClientBuilder {
+        self.config.tls_false_start = enabled;
+        self
+    }
+
+    /// Sends the first request of a resumed TLS 1.3 connection as early data (0-RTT).
+    ///
+    /// This saves a round trip when reconnecting to a host whose session is
+    /// cached, and enables the TLS session cache. Whether the server accepted
+    /// the early data is reported by [`TlsInfo::early_data_accepted`] when
+    /// [`tls_info`](ClientBuilder::tls_info) is enabled.
+    ///
+    /// # Warning
+    ///
+    /// Early data is not protected against replay: an attacker can capture it
+    /// and resend it, and the server may process it more than once. It is
+    /// therefore only offered on connections opened for idempotent requests,
+    /// such as `GET`, `HEAD` or `PUT`, and never when HTTP/2 is offered, as
+    /// other requests could be multiplexed into it. A connection opened in
+    /// early data but not used by the request that opened it is not pooled.
+    ///
+    /// If the server rejects the early data, it discarded the request, which
+    /// is sent again once the handshake completes.
+    ///
+    /// Defaults to `false`.
+    ///
+    /// [`TlsInfo::early_data_accepted`]: crate::tls::TlsInfo::early_data_accepted
+    pub fn tls_early_data(mut self, enabled: bool) -> ClientBuilder {
+        self.config.tls_early_data = enabled;
+        self
+    }
+
+    /// Sets how many TLS sessions are cached for each host and port.
+    ///
+    /// Sessions are cached to resume later handshakes, when `pre_shared_key`
+    /// is set in the [`TlsConfig`] of the emulation, or with
+    /// [`tls_early_data`](ClientBuilder::tls_early_data). Whether a
+    /// connection resumed one is reported by [`TlsInfo::session_reused`]. A
+    /// TLS 1.3 session is only used once, so a workload opening many
+    /// connections to a host at once needs room for as many sessions.
+    ///
+    /// Default is 8. A value of 0 is treated as 1.
+    ///
+    /// [`TlsConfig`]: crate::tls::TlsConfig
+    /// [`TlsInfo::session_reused`]: crate::tls::TlsInfo::session_reused
+    pub fn tls_session_cache_capacity(mut self, capacity: usize) -> ClientBuilder {
+        self.config.tls_session_cache_capacity = Some(capacity);
+        self
+    }
+
     /// Set the minimum required TLS version for connections.
     ///
     /// By default the TLS backend's own default is used.
@@ -1216,6 +2211,235 @@ impl ClientBuilder {
         self
     }
 
+    /// Requires the server to select one of the ALPN protocols offered.
+    ///
+    /// By default, a server that ignores ALPN, or selects a protocol that was
+    /// not offered, silently gets HTTP/1.1. With this enabled, such a
+    /// connection fails instead, with an error for which
+    /// [`Error::is_alpn_mismatch`](crate::Error::is_alpn_mismatch) returns
+    /// true and which names the protocols offered and the one selected.
+    ///
+    /// Has no effect when no ALPN protocols are offered.
+    ///
+    /// Defaults to `false`.
+    pub fn tls_require_alpn(mut self, required: bool) -> ClientBuilder {
+        self.config.tls_require_alpn = required;
+        self
+    }
+
+    /// Sets the ALPN protocols offered to TLS servers, in order of
+    /// preference.
+    ///
+    /// This replaces the list of the TLS configuration, and may hold any
+    /// protocol, such as a custom token a server negotiates. Connections
+    /// still speak HTTP/2 only when `h2` is selected, and HTTP/1.1 otherwise;
+    /// the selected protocol can be read from [`TlsInfo::alpn_selected`].
+    /// Hosts pinned to an HTTP version, and the HTTP/1.1 fallback, offer
+    /// their own protocol instead.
+    ///
+    /// Building the client fails if the list is empty, if a protocol is
+    /// empty or longer than 255 bytes, or if it leaves out `h2` while
+    /// [`http2_only`](ClientBuilder::http2_only) is set.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let client = wreq::Client::builder()
+    ///     .tls_alpn_protocols(["h2", "http/1.1", "custom/1"])
+    ///     .build()?;
+    /// # Ok::<(), wreq::Error>(())
+    /// ```
+    ///
+    /// [`TlsInfo::alpn_selected`]: crate::tls::TlsInfo::alpn_selected
+    pub fn tls_alpn_protocols<I, P>(mut self, protocols: I) -> ClientBuilder
+    where
+        I: IntoIterator<Item = P>,
+        P: Into<Vec<u8>>,
+    {
+        self.config.tls_alpn_protocols = Some(protocols.into_iter().map(Into::into).collect());
+        self
+    }
+
+    /// Sets the cipher suites offered to TLS servers, in order of preference.
+    ///
+    /// This replaces the cipher list of the TLS configuration, to match the
+    /// `ClientHello` of a given client. The list is in BoringSSL's format,
+    /// names separated by `:`, and orders the TLS 1.3 suites along with the
+    /// TLS 1.2 ones; BoringSSL has no separate list for TLS 1.3.
+    ///
+    /// Building the client fails if a name selects no cipher suite BoringSSL
+    /// supports, with an error for which [`Error::is_unsupported_cipher`]
+    /// returns true and that lists the names.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// let client = wreq::Client::builder()
+    ///     .tls_cipher_list(
+    ///         "TLS_AES_128_GCM_SHA256:TLS_CHACHA20_POLY1305_SHA256:\
+    ///          ECDHE-ECDSA-AES128-GCM-SHA256:ECDHE-RSA-AES128-GCM-SHA256",
+    ///     )
+    ///     .build()?;
+    /// # Ok::<(), wreq::Error>(())
+    /// ```
+    ///
+    /// [`Error::is_unsupported_cipher`]: crate::Error::is_unsupported_cipher
+    pub fn tls_cipher_list<T>(mut self, ciphers: T) -> ClientBuilder
+    where
+        T: Into<std::borrow::Cow<'static, str>>,
+    {
+        self.config.tls_cipher_list = Some(ciphers.into());
+        self
+    }
+
+    /// Sets the minimum size, in bits, of an RSA key in the server's
+    /// certificate.
+    ///
+    /// A server presenting a smaller key is rejected even if its chain
+    /// validates, with an error for which
+    /// [`Error::is_weak_key`](crate::Error::is_weak_key) returns true. Only
+    /// the leaf certificate is checked.
+    ///
+    /// Defaults to no minimum beyond what BoringSSL enforces.
+    pub fn tls_min_rsa_bits(mut self, bits: u16) -> ClientBuilder {
+        self.config.tls_min_rsa_bits = Some(bits);
+        self
+    }
+
+    /// Sets the curves an EC key in the server's certificate may be on.
+    ///
+    /// A server presenting a key on another curve is rejected even if its
+    /// chain validates, with an error for which
+    /// [`Error::is_weak_key`](crate::Error::is_weak_key) returns true. Only
+    /// the leaf certificate is checked, and keys that are neither RSA nor EC
+    /// are not restricted. This is unrelated to the groups offered for the
+    /// key exchange.
+    ///
+    /// Defaults to any curve BoringSSL accepts.
+    pub fn tls_allowed_curves_for_leaf(mut self, curves: Vec<Nid>) -> ClientBuilder {
+        self.config.tls_allowed_curves_for_leaf = Some(curves);
+        self
+    }
+
+    /// Pins the key of the server's certificate to `sha256`, the SHA-256
+    /// digest of its DER `SubjectPublicKeyInfo`.
+    ///
+    /// Can be called multiple times, for servers with different keys or for
+    /// a key rotation: a server whose key matches none of the pins is
+    /// rejected, with an error for which
+    /// [`Error::is_pin_mismatch`](crate::Error::is_pin_mismatch) returns
+    /// true. Only the leaf certificate is checked.
+    ///
+    /// Pins are enforced even with
+    /// [`cert_verification(false)`](ClientBuilder::cert_verification), which
+    /// then only skips validating the chain and the name. This suits servers
+    /// reached by IP address, with a self-signed certificate, that should
+    /// still be protected from impersonation:
+    ///
+    /// ```
+    /// # fn doc(pin: [u8; 32]) -> wreq::Result<()> {
+    /// let client = wreq::Client::builder()
+    ///     .cert_verification(false)
+    ///     .tls_pin_spki_sha256(pin)
+    ///     .build()?;
+    /// # drop(client);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// Defaults to no pins, any key.
+    pub fn tls_pin_spki_sha256(mut self, sha256: [u8; 32]) -> ClientBuilder {
+        self.config.tls_spki_pins.push(sha256);
+        self
+    }
+
+    /// Pins the key of the server's certificate to the set `sha256s`,
+    /// replacing any pins set before.
+    ///
+    /// This is [`tls_pin_spki_sha256`](ClientBuilder::tls_pin_spki_sha256)
+    /// for a whole set at once, as loaded from configuration. An empty set
+    /// would pin nothing, so it fails [`build`](ClientBuilder::build) rather
+    /// than accepting any key.
+    pub fn tls_pinned_spki<I>(mut self, sha256s: I) -> ClientBuilder
+    where
+        I: IntoIterator<Item = [u8; 32]>,
+    {
+        self.config.tls_spki_pins = sha256s.into_iter().collect();
+        if self.config.tls_spki_pins.is_empty() {
+            self.config.error = Some(Error::builder("no SPKI pins in the pinned set"));
+        }
+        self
+    }
+
+    /// Fails connections to servers whose certificate expires within `min`.
+    ///
+    /// The `notAfter` time of the server's leaf certificate is checked once
+    /// the handshake completes, and a certificate that expires sooner fails
+    /// the connection with an error for which
+    /// [`Error::is_cert_expiring_soon`](crate::Error::is_cert_expiring_soon)
+    /// returns true. This catches a renewal that didn't happen before it
+    /// turns into an outage. The check holds even with
+    /// [`cert_verification(false)`](ClientBuilder::cert_verification).
+    ///
+    /// Connections already in the pool are not checked again. The validity
+    /// of the certificate is also reported by
+    /// [`TlsInfo::peer_certificate_validity`](crate::tls::TlsInfo::peer_certificate_validity).
+    ///
+    /// Defaults to no requirement.
+    pub fn tls_min_cert_validity_remaining(mut self, min: Duration) -> ClientBuilder {
+        self.config.tls_min_cert_validity_remaining = Some(min);
+        self
+    }
+
+    /// Requests a stapled OCSP response from servers, and checks their
+    /// certificate against it.
+    ///
+    /// A response that revokes the server's leaf certificate fails the
+    /// connection with an error for which
+    /// [`Error::is_cert_revoked`](crate::Error::is_cert_revoked) returns
+    /// true. Stapling is requested, not required: servers that staple nothing
+    /// are still connected to. The signature of the response is not verified,
+    /// so a staple can only reject a certificate, never vouch for one.
+    ///
+    /// The response is reported by
+    /// [`TlsInfo::ocsp_response`](crate::tls::TlsInfo::ocsp_response), for
+    /// callers applying a policy of their own.
+    ///
+    /// Defaults to `false`.
+    pub fn tls_ocsp_stapling(mut self, enabled: bool) -> ClientBuilder {
+        self.config.tls_ocsp_stapling = enabled;
+        self
+    }
+
+    /// Appends a TLS extension of type `ext_type` with contents `data` to the
+    /// `ClientHello`.
+    ///
+    /// This covers clients whose fingerprint includes a vendor-specific or
+    /// experimental extension BoringSSL does not know. Custom extensions
+    /// follow the ones BoringSSL sends, in the order they were added, and
+    /// the server's answer to them is ignored.
+    ///
+    /// Can be called multiple times to add several extensions. Building the
+    /// client fails for a type BoringSSL already handles, or one added twice.
+    pub fn tls_custom_extension(mut self, ext_type: u16, data: Vec<u8>) -> ClientBuilder {
+        self.config.tls_custom_extensions.push((ext_type, data));
+        self
+    }
+
+    /// Sets the size of the buffer used for reads from TLS connections.
+    ///
+    /// A TLS connection returns at most one record, about 16 KiB, per read.
+    /// With a read buffer, each read instead keeps taking records until the
+    /// caller's buffer, or this buffer for callers asking for less, is full
+    /// or no more data is ready. Large bodies then arrive in fewer, larger
+    /// reads, at the cost of `size` bytes of memory per connection.
+    ///
+    /// Defaults to no buffer.
+    pub fn tls_read_buffer_size(mut self, size: usize) -> ClientBuilder {
+        self.config.tls_read_buffer_size = Some(size);
+        self
+    }
+
     /// Restrict the Client to be used with HTTPS only requests.
     ///
     /// Defaults to false.
@@ -1224,6 +2448,16 @@ impl ClientBuilder {
         self
     }
 
+    /// Reject connections to destinations whose scheme is not `http` or
+    /// `https`, and through proxies whose scheme is not supported, before
+    /// any connection is attempted.
+    ///
+    /// Defaults to `false`.
+    pub fn enforce_http(mut self, enabled: bool) -> ClientBuilder {
+        self.config.enforce_http = enabled;
+        self
+    }
+
     // DNS options
 
     /// Disables the hickory-dns async resolver.
@@ -1274,6 +2508,40 @@ impl ClientBuilder {
         self
     }
 
+    /// Set the order in which the addresses a host resolves to are tried.
+    ///
+    /// [`AddressOrder::Shuffle`] spreads connects across every address of a
+    /// host, rather than sending them all to the first one that answers. The
+    /// first address in this order also picks the address family that Happy
+    /// Eyeballs tries first.
+    ///
+    /// Default is [`AddressOrder::AsResolved`].
+    pub fn address_order(mut self, order: AddressOrder) -> ClientBuilder {
+        self.config.address_order = order;
+        self
+    }
+
+    /// Set how long to wait for a connection over the preferred address
+    /// family before racing one over the other, as in Happy Eyeballs
+    /// ([RFC 8305]).
+    ///
+    /// This only matters for hosts that resolve to both IPv4 and IPv6
+    /// addresses. The delay is cut to the
+    /// [`connect_timeout`](ClientBuilder::connect_timeout), so that the other
+    /// family is always tried within it. Pass `None` to try the addresses one
+    /// after the other instead.
+    ///
+    /// Default is 300 milliseconds.
+    ///
+    /// [RFC 8305]: https://www.rfc-editor.org/rfc/rfc8305
+    pub fn happy_eyeballs_timeout<D>(mut self, timeout: D) -> ClientBuilder
+    where
+        D: Into<Option<Duration>>,
+    {
+        self.config.happy_eyeballs_timeout = timeout.into();
+        self
+    }
+
     /// Adds a new Tower [`Layer`](https://docs.rs/tower/latest/tower/trait.Layer.html) to the
     /// request [`Service`](https://docs.rs/tower/latest/tower/trait.Service.html) which is responsible
     /// for request processing.
@@ -1503,6 +2771,190 @@ impl Client {
 
         Pending::new(url, in_flight)
     }
+
+    /// Opens up to `count` connections to the host of `url` ahead of time, so
+    /// that the requests which follow do not pay for connection setup.
+    ///
+    /// The connections are made like those of any request, through the
+    /// configured proxies and TLS settings, and are kept in the pool as idle
+    /// connections. Fewer than `count` may be warmed: the pool keeps no more
+    /// than [`pool_max_idle_per_host`](ClientBuilder::pool_max_idle_per_host)
+    /// idle connections per host, an HTTP/2 host is served by a single
+    /// connection, and connects that fail are skipped.
+    ///
+    /// Returns how many connections were warmed.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if the supplied `Url` cannot be parsed or uses an
+    /// unsupported scheme. Connect failures are not errors.
+    pub async fn warm<U: IntoUrl>(&self, url: U, count: usize) -> crate::Result<usize> {
+        let url = url.into_url()?;
+
+        let scheme = url.scheme();
+        if scheme != "http" && scheme != "https" {
+            return Err(Error::url_bad_scheme(url));
+        }
+
+        if self.inner.https_only && scheme != "https" {
+            return Err(Error::url_bad_scheme(url));
+        }
+
+        let uri = match try_uri(&url) {
+            Some(uri) => uri,
+            None => return Err(Error::url_bad_uri(url)),
+        };
+
+        self.inner
+            .pool
+            .warm(uri, count)
+            .await
+            .map_err(|e| Error::request(e).with_url(url))
+    }
+
+    /// Opens a CONNECT tunnel through `proxy` to `authority`, a `host:port`
+    /// pair, and returns the tunneled stream.
+    ///
+    /// Nothing is assumed about what is spoken over the tunnel, so it may
+    /// carry any TCP protocol: there is no TLS with the destination, and no
+    /// HTTP. The proxy is reached like for any request, over TLS for an
+    /// `https` proxy, and with the client's connect timeout. Layers added
+    /// with [`connector_layer`](ClientBuilder::connector_layer) are skipped,
+    /// and the tunnel is never pooled.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if `authority` cannot be parsed, if `proxy` does not
+    /// apply to it, or if the tunnel cannot be opened.
+    pub async fn connect_tunnel(&self, proxy: Proxy, authority: &str) -> crate::Result<Upgraded> {
+        let mut req = http::Request::connect(authority)
+            .body(())
+            .map_err(Error::builder)?;
+        let dst = Dst::new(&mut req, true).map_err(Error::builder)?;
+        let proxy = proxy
+            .into_matcher()
+            .intercept(dst.uri())
+            .ok_or_else(|| Error::builder("proxy does not apply to the tunnel destination"))?;
+
+        let conn = self
+            .inner
+            .pool
+            .connector()
+            .service()
+            .connect_tunnel(dst, proxy)
+            .await
+            .map_err(Error::request)?;
+        Ok(crate::core::upgrade::Upgraded::new(conn, bytes::Bytes::new()).into())
+    }
+
+    /// Sets up a UDP relay through `proxy`, a SOCKSv5 proxy, with the
+    /// `UDP ASSOCIATE` command, and returns a socket sending datagrams to any
+    /// destination through it.
+    ///
+    /// The control connection to the proxy is made like a direct one, and
+    /// the handshake is bounded by the
+    /// [`socks_handshake_timeout`](ClientBuilder::socks_handshake_timeout).
+    /// The association lasts until the socket is dropped.
+    ///
+    /// # Errors
+    ///
+    /// This method fails if `proxy` is not a `socks5` or `socks5h` proxy
+    /// applying to any destination, as made with [`Proxy::all`], or if the
+    /// proxy refuses the association.
+    #[cfg(feature = "socks")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "socks")))]
+    pub async fn socks_udp_associate(&self, proxy: Proxy) -> crate::Result<crate::SocksUdpSocket> {
+        // Datagrams may go anywhere, so any destination picks the proxy.
+        let proxy = proxy
+            .into_matcher()
+            .intercept(&http::Uri::from_static("http://0.0.0.0/"))
+            .ok_or_else(|| Error::builder("proxy does not apply to UDP relaying"))?;
+
+        self.inner
+            .pool
+            .connector()
+            .service()
+            .socks_udp_associate(proxy)
+            .await
+            .map_err(Error::request)
+    }
+
+    /// Drops every cached TLS session, so that the next connections do full
+    /// handshakes rather than resume one.
+    ///
+    /// This helps once resumption breaks, such as after a server rotated its
+    /// session ticket keys. Sessions from handshakes still in flight are not
+    /// cached either. Connections already open, including idle ones in the
+    /// pool, are left alone.
+    pub fn clear_tls_session_cache(&self) {
+        self.inner.tls.clear_session_cache();
+    }
+
+    /// Drops the cached TLS sessions of `host`, on any port, so that the next
+    /// connections to it do full handshakes.
+    ///
+    /// Sessions from handshakes still in flight, to any host, are not cached
+    /// either.
+    pub fn clear_tls_session_for(&self, host: &str) {
+        self.inner.tls.clear_session_for(host);
+    }
+
+    /// Returns a hash of the TLS settings that shape the `ClientHello` of
+    /// this client.
+    ///
+    /// Clients with the same fingerprint send the same `ClientHello`, up to
+    /// its random parts, so connectors and pools built for one can serve the
+    /// other. The hash covers the TLS versions, the cipher, curve and
+    /// signature algorithm lists, ALPN and ALPS, GREASE and the extension
+    /// order, session resumption and early data, the other extensions the
+    /// [`TlsConfig`](crate::tls::TlsConfig) turns on, whether SNI is sent,
+    /// and custom extensions. Settings that only apply to the server's
+    /// answer, such as the identity or certificate verification, are not
+    /// part of it.
+    ///
+    /// The fingerprint is stable for a given build of this crate, but not
+    /// across versions, so it shouldn't be persisted.
+    pub fn tls_config_fingerprint(&self) -> u64 {
+        self.inner.tls_fingerprint
+    }
+
+    /// Connects to the host of `url` without sending a request, and returns
+    /// what is known about the connection.
+    #[cfg(feature = "blocking")]
+    pub(crate) async fn connect<U: IntoUrl>(
+        &self,
+        url: U,
+    ) -> crate::Result<crate::blocking::ConnectInfo> {
+        let url = url.into_url()?;
+
+        let scheme = url.scheme();
+        if scheme != "http" && scheme != "https" {
+            return Err(Error::url_bad_scheme(url));
+        }
+
+        if self.inner.https_only && scheme != "https" {
+            return Err(Error::url_bad_scheme(url));
+        }
+
+        let uri = match try_uri(&url) {
+            Some(uri) => uri,
+            None => return Err(Error::url_bad_uri(url)),
+        };
+
+        self.inner
+            .pool
+            .connect_with(uri, |connected| {
+                let mut extensions = http::Extensions::new();
+                connected.get_extras(&mut extensions);
+                crate::blocking::ConnectInfo {
+                    tls_info: extensions.remove(),
+                    proxied: connected.is_proxied(),
+                    negotiated_h2: connected.is_negotiated_h2(),
+                }
+            })
+            .await
+            .map_err(|e| Error::request(e).with_url(url))
+    }
 }
 
 impl tower_service::Service<Request> for Client {
@@ -8,6 +8,7 @@ use std::{
 
 use http::{Extensions, Request as HttpRequest, Version, request::Parts};
 use serde::Serialize;
+use tokio::io::{AsyncRead, AsyncWrite};
 
 #[cfg(feature = "multipart")]
 use super::multipart;
@@ -34,13 +35,18 @@ use crate::{
     config::{
         RequestReadTimeout, RequestRedirectPolicy, RequestSkipDefaultHeaders, RequestTotalTimeout,
     },
-    core::ext::{
-        RequestConfig, RequestHttpVersionPref, RequestIpv4Addr, RequestIpv6Addr,
-        RequestOriginalHeaders, RequestProxyMatcher,
+    core::{
+        client::ProvidedStream,
+        ext::{
+            RequestConfig, RequestConnectOver, RequestConnectTimeout, RequestHttpVersionPref,
+            RequestIdentity, RequestIpv4Addr, RequestIpv6Addr, RequestOriginalHeaders,
+            RequestProxyMatcher,
+        },
     },
     header::{CONTENT_TYPE, HeaderMap, HeaderName, HeaderValue},
     proxy::Matcher as ProxyMatcher,
     redirect,
+    tls::Identity,
 };
 #[cfg(any(
     feature = "gzip",
@@ -165,6 +171,12 @@ impl Request {
         RequestConfig::<RequestReadTimeout>::get_mut(&mut self.extensions)
     }
 
+    /// Get a mutable reference to the connect timeout.
+    #[inline(always)]
+    pub fn connect_timeout_mut(&mut self) -> &mut Option<Duration> {
+        RequestConfig::<RequestConnectTimeout>::get_mut(&mut self.extensions)
+    }
+
     /// Get a mutable reference to the local ipv4 address.
     #[inline(always)]
     pub fn local_ipv4_address_mut(&mut self) -> &mut Option<Ipv4Addr> {
@@ -195,6 +207,12 @@ impl Request {
         RequestConfig::<RequestInterface>::get_mut(&mut self.extensions)
     }
 
+    /// Get a mutable reference to the client identity.
+    #[inline(always)]
+    pub fn identity_mut(&mut self) -> &mut Option<Identity> {
+        RequestConfig::<RequestIdentity>::get_mut(&mut self.extensions)
+    }
+
     /// Get a mutable reference to the proxy matcher.
     #[inline(always)]
     pub(crate) fn proxy_matcher_mut(&mut self) -> &mut Option<ProxyMatcher> {
@@ -473,6 +491,42 @@ impl RequestBuilder {
         self
     }
 
+    /// Enables a connect timeout.
+    ///
+    /// It bounds establishing a new connection for this request, and overrides
+    /// the timeout configured using `ClientBuilder::connect_timeout()`. Like
+    /// that one, it is divided evenly across the addresses a domain resolves
+    /// to. A request that reuses a pooled connection doesn't connect, so the
+    /// timeout doesn't apply to it.
+    ///
+    /// With `ClientBuilder::connector_layer()`, the client's connect timeout
+    /// still wraps the layers, so this can only shorten it.
+    pub fn connect_timeout(mut self, timeout: Duration) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.connect_timeout_mut() = Some(timeout);
+        }
+        self
+    }
+
+    /// Sends the request over `stream`, a transport connected elsewhere, such
+    /// as a socket handed over by another process or a tunnel opened by hand.
+    ///
+    /// There is no DNS lookup, TCP connect or proxy: for an `https` URL, the
+    /// TLS handshake with its host runs over `stream`, and otherwise HTTP is
+    /// spoken over it in clear text. The rest of the client's configuration
+    /// applies as for any other connection. The connection serves this
+    /// request alone and is never pooled; a redirect connects anew.
+    pub fn connect_over<S>(mut self, stream: S) -> RequestBuilder
+    where
+        S: AsyncRead + AsyncWrite + Unpin + Send + Sync + fmt::Debug + 'static,
+    {
+        if let Ok(ref mut req) = self.request {
+            RequestConfig::<RequestConnectOver>::get_mut(req.extensions_mut())
+                .replace(ProvidedStream::new(stream));
+        }
+        self
+    }
+
     /// Sends a multipart/form-data body.
     ///
     /// ```
@@ -643,6 +697,9 @@ impl RequestBuilder {
     }
 
     /// Set the local address for this request.
+    ///
+    /// A proxy the request goes through, SOCKS included, is connected to from
+    /// this address as well.
     pub fn local_address<V>(mut self, local_address: V) -> RequestBuilder
     where
         V: Into<Option<IpAddr>>,
@@ -694,6 +751,18 @@ impl RequestBuilder {
         self
     }
 
+    /// Set the identity used for client certificate authentication on this
+    /// request, instead of the one configured on the client.
+    ///
+    /// Connections are pooled per identity, so a connection authenticated
+    /// with one certificate is never reused for a request with another.
+    pub fn identity(mut self, identity: Identity) -> RequestBuilder {
+        if let Ok(ref mut req) = self.request {
+            *req.identity_mut() = Some(identity);
+        }
+        self
+    }
+
     /// Send a form body.
     ///
     /// Sets the body to the url encoded serialization of the passed value,
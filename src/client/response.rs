@@ -1,4 +1,4 @@
-use std::{fmt, net::SocketAddr};
+use std::{fmt, net::SocketAddr, time::Duration};
 
 use bytes::Bytes;
 #[cfg(feature = "charset")]
@@ -13,7 +13,13 @@ use url::Url;
 use super::body::{Body, ResponseBody};
 #[cfg(feature = "cookies")]
 use crate::cookie;
-use crate::{Error, Upgraded, core::client::connect::HttpInfo};
+use crate::{
+    Error, Upgraded,
+    core::client::connect::{
+        ByteCounts, ConnectTiming, HttpInfo, PeerSettings, PingStats, SettingsRtt,
+    },
+    http2::Http2PeerSettings,
+};
 
 /// A Response to a submitted `Request`.
 pub struct Response {
@@ -92,6 +98,11 @@ impl Response {
     }
 
     /// Get the remote address used to get this `Response`.
+    ///
+    /// This is the address the TCP connection went to, picked among those the
+    /// host resolved to. Through a proxy, including a tunnel through an HTTP
+    /// proxy, it is the address of the proxy, as the client never learns
+    /// which address the proxy connected to.
     pub fn remote_addr(&self) -> Option<SocketAddr> {
         self.res
             .extensions()
@@ -99,6 +110,79 @@ impl Response {
             .map(|info| info.remote_addr())
     }
 
+    /// Get how long each step of establishing the connection of this
+    /// `Response` took.
+    ///
+    /// This is only collected with
+    /// [`collect_timings`](crate::ClientBuilder::collect_timings). A
+    /// connection reused from the pool reports the timing of when it was
+    /// first established.
+    pub fn connect_timing(&self) -> Option<ConnectTiming> {
+        self.res.extensions().get::<ConnectTiming>().copied()
+    }
+
+    /// Get the bytes read from and written to the connection of this
+    /// `Response` so far.
+    ///
+    /// This is only counted with
+    /// [`count_bytes`](crate::ClientBuilder::count_bytes). A connection
+    /// reused from the pool counts every request it carried.
+    pub fn byte_counts(&self) -> Option<&ByteCounts> {
+        self.res.extensions().get::<ByteCounts>()
+    }
+
+    /// Get the time from sending the initial HTTP/2 SETTINGS on the connection
+    /// of this `Response` to the server acknowledging them.
+    ///
+    /// This is a cheap estimate of the round trip time to the server, measured
+    /// once per connection. It is `None` for HTTP/1 responses, and if the
+    /// server has not acknowledged the SETTINGS yet.
+    pub fn settings_rtt(&self) -> Option<Duration> {
+        self.res
+            .extensions()
+            .get::<SettingsRtt>()
+            .and_then(SettingsRtt::get)
+    }
+
+    /// Get the settings in the first SETTINGS frame the server sent on the
+    /// connection of this `Response`.
+    ///
+    /// A setting the server left out is `None`. This is `None` for HTTP/1
+    /// responses.
+    pub fn http2_peer_settings(&self) -> Option<&Http2PeerSettings> {
+        self.res
+            .extensions()
+            .get::<PeerSettings>()
+            .and_then(PeerSettings::get)
+    }
+
+    /// Get the round trip of the last HTTP/2 PING the server answered on the
+    /// connection of this `Response`.
+    ///
+    /// PINGs are only sent with a [`keep_alive_interval`], adaptive flow
+    /// control, or checks on reuse. It is `None` without them, for HTTP/1
+    /// responses, and until the first answer arrives.
+    ///
+    /// [`keep_alive_interval`]: crate::http2::Http2ConfigBuilder::keep_alive_interval
+    pub fn ping_rtt(&self) -> Option<Duration> {
+        self.res
+            .extensions()
+            .get::<PingStats>()
+            .and_then(PingStats::rtt)
+    }
+
+    /// Get how many HTTP/2 PINGs were sent on the connection of this
+    /// `Response` since the server last answered one.
+    ///
+    /// A connection has a single PING in flight at a time, so this is `1`
+    /// while one waits for its answer, and `0` for HTTP/1 responses.
+    pub fn unanswered_pings(&self) -> u64 {
+        self.res
+            .extensions()
+            .get::<PingStats>()
+            .map_or(0, PingStats::unanswered)
+    }
+
     /// Returns a reference to the associated extensions.
     pub fn extensions(&self) -> &http::Extensions {
         self.res.extensions()
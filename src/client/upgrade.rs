@@ -8,7 +8,8 @@ use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
 
 use crate::core::rt::TokioIo;
 
-/// An upgraded HTTP connection.
+/// An upgraded HTTP connection, or a tunnel opened with
+/// [`Client::connect_tunnel`](crate::Client::connect_tunnel).
 pub struct Upgraded {
     inner: TokioIo<crate::core::upgrade::Upgraded>,
 }
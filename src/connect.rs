@@ -1,17 +1,27 @@
 use std::{
+    collections::HashMap,
+    error::Error as StdError,
+    fmt,
     future::Future,
     io::{self, IoSlice},
     net::{IpAddr, Ipv4Addr, Ipv6Addr},
     pin::Pin,
     sync::Arc,
-    task::{Context, Poll},
+    task::{Context, Poll, ready},
     time::Duration,
 };
 
-use http::uri::Scheme;
+use http::{
+    Version,
+    uri::{Authority, Scheme},
+};
 use pin_project_lite::pin_project;
 use sealed::{Conn, Unnameable};
+use sync_wrapper::SyncWrapper;
+use tokio::sync::{AcquireError, OwnedSemaphorePermit, Semaphore};
 use tokio_boring2::SslStream;
+#[cfg(feature = "cancellation")]
+use tokio_util::sync::CancellationToken;
 use tower::{
     ServiceBuilder,
     timeout::TimeoutLayer,
@@ -19,22 +29,43 @@ use tower::{
 };
 use tower_service::Service;
 
-use self::tls_conn::BoringTlsConn;
+pub use self::inspected::IoDirection;
+use self::{
+    buffered::BufferedRead,
+    coalesce::WriteCoalesce,
+    extra::WithExtra,
+    h2_fallback::{H2Fallback, ReportH2Errors},
+    idle::IdleTimeout,
+    inspected::Inspected,
+    metered::Metered,
+    observed::Observed,
+    prior_knowledge::PriorKnowledgeH2,
+    provided::Provided,
+    reuse::{PingOnReuse, ProbeOnReuse},
+    tls_conn::BoringTlsConn,
+};
+#[cfg(feature = "cancellation")]
+use crate::error::Cancelled;
 use crate::{
     Error,
     core::{
         client::{
             Dst,
-            connect::{Connected, Connection, proxy::Tunnel},
+            connect::{
+                ConnEvent, ConnectTimingRecorder, Connected, Connection, SocketHook,
+                event::EventSender,
+                proxy::{ClientInfo, ProxyProtocol, ProxyProtocolVersion, Tunnel, TunnelError},
+            },
         },
         rt::{Read, ReadBufCursor, TokioIo, Write},
     },
-    dns::DynResolver,
-    error::{BoxError, TimedOut, map_timeout_to_connector_error},
-    proxy::{Intercepted, Matcher as ProxyMatcher},
+    dns::{AddressOrder, DynResolver},
+    error::{BoxError, TimedOut, is_retryable_connect, map_timeout_to_connector_error},
+    proxy::{Intercepted, Matcher as ProxyMatcher, ProxyMatchPolicy},
     tls::{
-        CertStore, HttpsConnector, Identity, KeyLogPolicy, MaybeHttpsStream, TlsConfig,
-        TlsConnector, TlsConnectorBuilder, TlsVersion,
+        AlpnProtocol, CertStore, ClientHelloHook, HandshakeProgressHook, HttpsConnector, Identity,
+        KeyLogPolicy, MaybeHttpsStream, Nid, SniCallback, Sniffing, TlsConfig, TlsConnector,
+        TlsConnectorBuilder, TlsVersion,
     },
 };
 
@@ -45,6 +76,10 @@ pub(crate) type BoxedConnectorService = BoxCloneSyncService<Unnameable, Conn, Bo
 pub(crate) type BoxedConnectorLayer =
     BoxCloneSyncServiceLayer<BoxedConnectorService, Unnameable, Conn, BoxError>;
 
+pub(crate) type MapConnectError = Arc<dyn Fn(BoxError) -> BoxError + Send + Sync>;
+
+pub(crate) type IoInspector = Arc<dyn Fn(IoDirection, &[u8]) + Send + Sync>;
+
 pub(crate) struct ConnectorBuilder {
     http: HttpConnector,
     proxies: Arc<Vec<ProxyMatcher>>,
@@ -54,11 +89,38 @@ pub(crate) struct ConnectorBuilder {
     /// This lets us avoid an extra `Box::pin` indirection layer
     /// since `tokio::time::Timeout` is `Unpin`
     timeout: Option<Duration>,
+    #[cfg(feature = "cancellation")]
+    cancellation: Option<CancellationToken>,
+    map_connect_error: Option<MapConnectError>,
+    max_concurrent_connects: Option<usize>,
+    connect_retries: u32,
+    connect_retry_backoff: Option<Duration>,
+    read_idle_timeout: Option<Duration>,
+    write_stall_timeout: Option<Duration>,
+    write_coalesce: Option<usize>,
+    count_bytes: bool,
+    inspect_io: Option<IoInspector>,
+    check_connection_on_reuse: bool,
     nodelay: bool,
+    auto_nodelay_for_tls: bool,
+    handshake_nodelay_only: bool,
+    proxy_match_policy: ProxyMatchPolicy,
+    http2_prior_knowledge: bool,
+    h2_fallback: Option<H2Fallback>,
+    forced_alpn: HashMap<String, AlpnProtocol>,
+    race_protocols: bool,
+    events: Option<EventSender>,
+    collect_timings: bool,
+    enforce_http: bool,
     #[cfg(feature = "socks")]
     resolver: DynResolver,
+    #[cfg(feature = "socks")]
+    socks_handshake_timeout: Option<Duration>,
+    #[cfg(feature = "socks")]
+    socks_retries: u32,
 
     tls_info: bool,
+    tls_read_buffer_size: Option<usize>,
     tls_builder: TlsConnectorBuilder,
 }
 
@@ -87,6 +149,14 @@ impl ConnectorBuilder {
         self
     }
 
+    /// Sets the value of the `SO_LINGER` option on the socket, including the
+    /// one connecting to a proxy.
+    #[inline(always)]
+    pub(crate) fn linger(mut self, linger: Option<Duration>) -> ConnectorBuilder {
+        self.http.set_linger(linger);
+        self
+    }
+
     /// Sets the value of the TCP_USER_TIMEOUT option on the socket.
     #[inline(always)]
     pub(crate) fn tcp_user_timeout(
@@ -100,6 +170,36 @@ impl ConnectorBuilder {
         self
     }
 
+    /// Sets the value of the `TCP_MAXSEG` option on the socket, including the
+    /// one connecting to a proxy. Ignored on platforms other than Linux.
+    #[inline(always)]
+    pub(crate) fn tcp_maxseg(mut self, mss: Option<u32>) -> ConnectorBuilder {
+        self.http.set_tcp_maxseg(mss);
+        self
+    }
+
+    /// Sets whether sockets connect with TCP Fast Open.
+    #[inline(always)]
+    pub(crate) fn tcp_fastopen(
+        #[allow(unused_mut)] mut self,
+        #[cfg(target_os = "linux")] enabled: bool,
+    ) -> ConnectorBuilder {
+        #[cfg(target_os = "linux")]
+        self.http.set_tcp_fastopen(enabled);
+        self
+    }
+
+    /// Sets the network namespace to create sockets in.
+    #[inline(always)]
+    pub(crate) fn netns(
+        #[allow(unused_mut)] mut self,
+        #[cfg(target_os = "linux")] path: Option<std::path::PathBuf>,
+    ) -> ConnectorBuilder {
+        #[cfg(target_os = "linux")]
+        self.http.set_netns(path);
+        self
+    }
+
     /// Set the connect timeout.
     ///
     /// If a domain resolves to multiple IP addresses, the timeout will be
@@ -111,6 +211,152 @@ impl ConnectorBuilder {
         self
     }
 
+    /// Set a timeout for connecting to a SOCKS proxy and completing its
+    /// handshake.
+    ///
+    /// The overall connect timeout still applies, so this only matters when it
+    /// is shorter. Whatever is left of the overall timeout goes to the TLS
+    /// handshake with the origin.
+    #[inline(always)]
+    pub(crate) fn socks_handshake_timeout(
+        #[allow(unused_mut)] mut self,
+        #[cfg(feature = "socks")] timeout: Option<Duration>,
+    ) -> ConnectorBuilder {
+        #[cfg(feature = "socks")]
+        {
+            self.socks_handshake_timeout = timeout;
+        }
+        self
+    }
+
+    /// Set how many more addresses of a SOCKS proxy to try when its
+    /// handshake fails transiently.
+    #[inline(always)]
+    pub(crate) fn socks_retries(
+        #[allow(unused_mut)] mut self,
+        #[cfg(feature = "socks")] retries: u32,
+    ) -> ConnectorBuilder {
+        #[cfg(feature = "socks")]
+        {
+            self.socks_retries = retries;
+        }
+        self
+    }
+
+    /// Set a token which aborts any in-flight connect once cancelled.
+    #[inline(always)]
+    pub(crate) fn cancellation_token(
+        #[allow(unused_mut)] mut self,
+        #[cfg(feature = "cancellation")] token: Option<CancellationToken>,
+    ) -> ConnectorBuilder {
+        #[cfg(feature = "cancellation")]
+        {
+            self.cancellation = token;
+        }
+        self
+    }
+
+    /// Bound how many connects run at once, queuing the rest until one
+    /// finishes. `0` means unlimited.
+    #[inline(always)]
+    pub(crate) fn max_concurrent_connects(mut self, max: usize) -> ConnectorBuilder {
+        self.max_concurrent_connects = (max > 0).then_some(max);
+        self
+    }
+
+    /// Set how many more times a connect is attempted after it fails at the
+    /// connection level, such as by being refused or reset.
+    #[inline(always)]
+    pub(crate) fn connect_retries(mut self, retries: u32) -> ConnectorBuilder {
+        self.connect_retries = retries;
+        self
+    }
+
+    /// Set how long to wait before the first retry of a connect, doubled
+    /// before each further one. `None` retries right away.
+    #[inline(always)]
+    pub(crate) fn connect_retry_backoff(mut self, backoff: Option<Duration>) -> ConnectorBuilder {
+        self.connect_retry_backoff = backoff;
+        self
+    }
+
+    /// Set a function which every connect error is passed through last,
+    /// after timeouts have been mapped to their own error type.
+    #[inline(always)]
+    pub(crate) fn map_connect_error(mut self, f: Option<MapConnectError>) -> ConnectorBuilder {
+        self.map_connect_error = f;
+        self
+    }
+
+    /// Set how long a pending read may go without receiving any bytes before
+    /// the connection fails with a timeout. `None` never times out.
+    #[inline(always)]
+    pub(crate) fn read_idle_timeout(mut self, timeout: Option<Duration>) -> ConnectorBuilder {
+        self.read_idle_timeout = timeout;
+        self
+    }
+
+    /// Write a PROXY protocol header of `version` on behalf of `client` as the
+    /// very first bytes of every connection, before any TLS handshake.
+    #[inline(always)]
+    pub(crate) fn send_proxy_protocol(
+        mut self,
+        version: ProxyProtocolVersion,
+        client: ClientInfo,
+    ) -> ConnectorBuilder {
+        self.http
+            .set_proxy_protocol(Some(ProxyProtocol::new(version, client)));
+        self
+    }
+
+    /// Set a hook called with every socket right after it connects, before
+    /// any PROXY protocol header or TLS handshake.
+    #[inline(always)]
+    pub(crate) fn on_socket(mut self, hook: Option<SocketHook>) -> ConnectorBuilder {
+        self.http.set_on_socket(hook);
+        self
+    }
+
+    /// Set how long a pending write may go without making any progress before
+    /// the connection fails with a timeout. `None` never times out.
+    #[inline(always)]
+    pub(crate) fn write_stall_timeout(mut self, timeout: Option<Duration>) -> ConnectorBuilder {
+        self.write_stall_timeout = timeout;
+        self
+    }
+
+    /// Sets how many bytes of small HTTP/1.1 writes are gathered before they
+    /// are written to the connection, which happens at the latest on flush.
+    /// `None` writes them straight through.
+    #[inline(always)]
+    pub(crate) fn write_coalesce(mut self, size: Option<usize>) -> ConnectorBuilder {
+        self.write_coalesce = size;
+        self
+    }
+
+    /// Count the bytes read from and written to every connection, and report
+    /// them as an extra of its `Connected`.
+    #[inline(always)]
+    pub(crate) fn count_bytes(mut self, enabled: bool) -> ConnectorBuilder {
+        self.count_bytes = enabled;
+        self
+    }
+
+    /// Call `inspector` with every chunk of bytes read from or written to a
+    /// connection, whatever the features enabled.
+    #[inline(always)]
+    pub(crate) fn inspect_io(mut self, inspector: Option<IoInspector>) -> ConnectorBuilder {
+        self.inspect_io = inspector;
+        self
+    }
+
+    /// Check that pooled connections are alive before reusing them.
+    #[inline(always)]
+    pub(crate) fn check_connection_on_reuse(mut self, enabled: bool) -> ConnectorBuilder {
+        self.check_connection_on_reuse = enabled;
+        self
+    }
+
     /// Sets the name of the interface to bind sockets produced by this
     /// connector.
     #[inline(always)]
@@ -166,6 +412,23 @@ impl ConnectorBuilder {
         self
     }
 
+    /// Sets how long to wait on the preferred address family before racing
+    /// the other one, per RFC 8305. `None` tries the addresses one after the
+    /// other. The delay is clamped to the connect timeout.
+    #[inline(always)]
+    pub(crate) fn happy_eyeballs_timeout(mut self, timeout: Option<Duration>) -> ConnectorBuilder {
+        self.http.set_happy_eyeballs_timeout(timeout);
+        self
+    }
+
+    /// Sets the order in which the addresses of a host are tried, by both
+    /// the connect loop and Happy Eyeballs.
+    #[inline(always)]
+    pub(crate) fn address_order(mut self, order: AddressOrder) -> ConnectorBuilder {
+        self.http.set_address_order(order);
+        self
+    }
+
     /// Set the nodelay flag for the connector.
     #[inline(always)]
     pub(crate) fn nodelay(mut self, enabled: bool) -> ConnectorBuilder {
@@ -174,6 +437,96 @@ impl ConnectorBuilder {
         self
     }
 
+    /// Enable nodelay for the TLS handshake of HTTPS connections, even when
+    /// it is disabled otherwise.
+    #[inline(always)]
+    pub(crate) fn auto_nodelay_for_tls(mut self, enabled: bool) -> ConnectorBuilder {
+        self.auto_nodelay_for_tls = enabled;
+        self
+    }
+
+    /// Disable nodelay again once the TLS handshake enabled by
+    /// [`auto_nodelay_for_tls`](ConnectorBuilder::auto_nodelay_for_tls) is
+    /// done.
+    ///
+    /// The toggle lives in `ConnectorService::connect_with_maybe_proxy`, the
+    /// path of direct connections and of those through HTTP proxies. When
+    /// disabled, nodelay stays on for the whole connection, which saves the
+    /// `set_nodelay(false)` call after the handshake.
+    #[inline(always)]
+    pub(crate) fn handshake_nodelay_only(mut self, enabled: bool) -> ConnectorBuilder {
+        self.handshake_nodelay_only = enabled;
+        self
+    }
+
+    /// Sets what to do when several proxies match a destination.
+    #[inline(always)]
+    pub(crate) fn proxy_match_policy(mut self, policy: ProxyMatchPolicy) -> ConnectorBuilder {
+        self.proxy_match_policy = policy;
+        self
+    }
+
+    /// Start HTTP/2 directly on cleartext connections, skipping HTTP/1.1 and
+    /// the `Upgrade: h2c` handshake. TLS connections still use ALPN.
+    #[inline(always)]
+    pub(crate) fn http2_prior_knowledge(mut self, enabled: bool) -> ConnectorBuilder {
+        self.http2_prior_knowledge = enabled;
+        self
+    }
+
+    /// Offer only HTTP/1.1 to hosts whose HTTP/2 connections recently failed
+    /// with a protocol error, until a cooldown has passed.
+    #[inline(always)]
+    pub(crate) fn h2_to_h1_fallback(mut self, enabled: bool) -> ConnectorBuilder {
+        self.h2_fallback = enabled.then(H2Fallback::new);
+        self
+    }
+
+    /// Offer only the ALPN protocol of `version` in TLS handshakes with
+    /// `host`, regardless of the protocols offered to other hosts.
+    ///
+    /// `HTTP/1.0` and `HTTP/1.1` offer `http/1.1`, `HTTP/2` offers `h2`. Other
+    /// versions are ignored.
+    #[inline(always)]
+    pub(crate) fn force_http_version(mut self, host: &str, version: Version) -> ConnectorBuilder {
+        let alpn = match version {
+            Version::HTTP_10 | Version::HTTP_11 => AlpnProtocol::HTTP1,
+            Version::HTTP_2 => AlpnProtocol::HTTP2,
+            _ => return self,
+        };
+        self.forced_alpn.insert(host.to_ascii_lowercase(), alpn);
+        self
+    }
+
+    /// Open two TLS connections to direct destinations, one offering only
+    /// `h2` and one only `http/1.1`, and keep whichever completes its
+    /// handshake first.
+    #[inline(always)]
+    pub(crate) fn race_protocols(mut self, enabled: bool) -> ConnectorBuilder {
+        self.race_protocols = enabled;
+        self
+    }
+
+    /// Report the steps of every connect, and the closing of every
+    /// connection, to `events`.
+    #[inline(always)]
+    pub(crate) fn events(
+        mut self,
+        events: Option<tokio::sync::mpsc::Sender<ConnEvent>>,
+    ) -> ConnectorBuilder {
+        self.events = events.map(EventSender::new);
+        self.http.set_events(self.events.clone());
+        self
+    }
+
+    /// Reject destinations that are not `http` or `https`, and proxies with
+    /// an unsupported scheme, before connecting.
+    #[inline(always)]
+    pub(crate) fn enforce_http(mut self, enabled: bool) -> ConnectorBuilder {
+        self.enforce_http = enabled;
+        self
+    }
+
     /// Set connecting verbose mode.
     #[inline(always)]
     pub(crate) fn verbose(mut self, enabled: bool) -> ConnectorBuilder {
@@ -211,10 +564,67 @@ impl ConnectorBuilder {
         self
     }
 
+    /// Sets the hook invoked with the `ClientHello` of each TLS connection.
+    #[inline(always)]
+    pub(crate) fn tls_client_hello_hook(
+        mut self,
+        hook: Option<ClientHelloHook>,
+    ) -> ConnectorBuilder {
+        self.tls_builder = self.tls_builder.on_client_hello(hook);
+        self
+    }
+
+    /// Sets the hook invoked as the handshake of each TLS connection
+    /// progresses.
+    #[inline(always)]
+    pub(crate) fn tls_handshake_progress(
+        mut self,
+        hook: Option<HandshakeProgressHook>,
+    ) -> ConnectorBuilder {
+        self.tls_builder = self.tls_builder.on_handshake_progress(hook);
+        self
+    }
+
+    /// Sets the callback computing the server name sent to each TLS
+    /// destination.
+    #[inline(always)]
+    pub(crate) fn tls_sni_callback(mut self, callback: Option<SniCallback>) -> ConnectorBuilder {
+        self.tls_builder = self.tls_builder.sni_callback(callback);
+        self
+    }
+
+    /// Sets whether to compute the JA3 and JA4 fingerprints of each TLS
+    /// connection.
+    #[inline(always)]
+    pub(crate) fn tls_compute_ja4(mut self, enabled: bool) -> ConnectorBuilder {
+        self.tls_builder = self.tls_builder.compute_ja4(enabled);
+        self
+    }
+
+    /// Sets whether to time the steps of establishing each connection, the
+    /// TLS handshake included.
+    #[inline(always)]
+    pub(crate) fn collect_timings(mut self, enabled: bool) -> ConnectorBuilder {
+        self.collect_timings = enabled;
+        self.tls_builder = self.tls_builder.collect_timings(enabled);
+        self
+    }
+
     /// Sets the TLS info flag.
+    ///
+    /// This also records the verified certificate chain of each handshake.
     #[inline(always)]
     pub(crate) fn tls_info(mut self, enabled: bool) -> ConnectorBuilder {
         self.tls_info = enabled;
+        self.tls_builder = self.tls_builder.verified_chain(enabled);
+        self
+    }
+
+    /// Sets the size of the buffer that reads from TLS connections are made
+    /// through. `None` hands reads straight to the TLS stream.
+    #[inline(always)]
+    pub(crate) fn tls_read_buffer_size(mut self, size: Option<usize>) -> ConnectorBuilder {
+        self.tls_read_buffer_size = size;
         self
     }
 
@@ -225,6 +635,14 @@ impl ConnectorBuilder {
         self
     }
 
+    /// Sets the server name sent and verified in place of the host of every
+    /// TLS destination, which is still the one connected to.
+    #[inline(always)]
+    pub(crate) fn tls_server_name(mut self, name: Option<String>) -> ConnectorBuilder {
+        self.tls_builder = self.tls_builder.server_name(name);
+        self
+    }
+
     /// Sets the hostname verification flag.
     #[inline(always)]
     pub(crate) fn tls_verify_hostname(mut self, enabled: bool) -> ConnectorBuilder {
@@ -253,28 +671,190 @@ impl ConnectorBuilder {
         self
     }
 
-    /// Builds the connector with the provided TLS configuration and optional layers.
+    /// Sets whether TLS False Start is allowed.
+    #[inline(always)]
+    pub(crate) fn tls_false_start(mut self, enabled: bool) -> ConnectorBuilder {
+        self.tls_builder = self.tls_builder.false_start(enabled);
+        self
+    }
+
+    /// Sets whether to send early data (0-RTT) on resumed TLS sessions.
+    #[inline(always)]
+    pub(crate) fn tls_early_data(mut self, enabled: bool) -> ConnectorBuilder {
+        self.tls_builder = self.tls_builder.early_data(enabled);
+        self
+    }
+
+    /// Sets how many TLS sessions are cached for each host and port.
+    #[inline(always)]
+    pub(crate) fn tls_session_cache_capacity(
+        mut self,
+        capacity: Option<usize>,
+    ) -> ConnectorBuilder {
+        if let Some(capacity) = capacity {
+            self.tls_builder = self.tls_builder.session_cache_capacity(capacity);
+        }
+        self
+    }
+
+    /// Sets whether the server must select one of the offered ALPN protocols.
+    #[inline(always)]
+    pub(crate) fn require_alpn(mut self, required: bool) -> ConnectorBuilder {
+        self.tls_builder = self.tls_builder.require_alpn(required);
+        self
+    }
+
+    /// Sets the ALPN protocols to offer in place of those of the TLS
+    /// configuration.
+    #[inline(always)]
+    pub(crate) fn tls_alpn_protocols(
+        mut self,
+        protocols: Option<Vec<Vec<u8>>>,
+    ) -> ConnectorBuilder {
+        self.tls_builder = self.tls_builder.alpn_protocols(protocols);
+        self
+    }
+
+    /// Sets the cipher suites to offer in place of those of the TLS
+    /// configuration.
+    #[inline(always)]
+    pub(crate) fn tls_cipher_list(
+        mut self,
+        ciphers: Option<std::borrow::Cow<'static, str>>,
+    ) -> ConnectorBuilder {
+        self.tls_builder = self.tls_builder.cipher_list(ciphers);
+        self
+    }
+
+    /// Sets the minimum size of an RSA key in the server's leaf certificate.
+    #[inline(always)]
+    pub(crate) fn min_rsa_bits(mut self, bits: Option<u16>) -> ConnectorBuilder {
+        if let Some(bits) = bits {
+            self.tls_builder = self.tls_builder.min_rsa_bits(bits);
+        }
+        self
+    }
+
+    /// Sets the curves an EC key in the server's leaf certificate may be on.
+    #[inline(always)]
+    pub(crate) fn allowed_curves_for_leaf(mut self, curves: Option<Vec<Nid>>) -> ConnectorBuilder {
+        if let Some(curves) = curves {
+            self.tls_builder = self.tls_builder.allowed_curves_for_leaf(curves);
+        }
+        self
+    }
+
+    /// Pins the key of the server's leaf certificate.
+    #[inline(always)]
+    pub(crate) fn tls_spki_pins(mut self, pins: Vec<[u8; 32]>) -> ConnectorBuilder {
+        if !pins.is_empty() {
+            self.tls_builder = self.tls_builder.spki_pins(pins);
+        }
+        self
+    }
+
+    /// Requires the server's leaf certificate to remain valid for at least
+    /// `min`.
+    #[inline(always)]
+    pub(crate) fn tls_min_cert_validity_remaining(
+        mut self,
+        min: Option<Duration>,
+    ) -> ConnectorBuilder {
+        self.tls_builder = self.tls_builder.min_cert_validity_remaining(min);
+        self
+    }
+
+    /// Requests a stapled OCSP response, and fails connections whose
+    /// response revokes the server's certificate.
+    #[inline(always)]
+    pub(crate) fn tls_ocsp_stapling(mut self, enabled: bool) -> ConnectorBuilder {
+        self.tls_builder = self.tls_builder.ocsp_stapling(enabled);
+        self
+    }
+
+    /// Appends custom extensions to the `ClientHello`.
+    #[inline(always)]
+    pub(crate) fn tls_custom_extensions(
+        mut self,
+        extensions: Vec<(u16, Vec<u8>)>,
+    ) -> ConnectorBuilder {
+        for (ext_type, data) in extensions {
+            self.tls_builder = self.tls_builder.add_custom_extension(ext_type, data);
+        }
+        self
+    }
+
+    /// Hashes the settings of `tls_config` and this builder that shape the
+    /// `ClientHello`.
+    pub(crate) fn tls_config_fingerprint(&self, tls_config: &TlsConfig) -> u64 {
+        self.tls_builder.config_fingerprint(tls_config)
+    }
+
+    /// Builds the TLS connector that [`build`](Self::build) takes, so that
+    /// the client can keep a handle to its session cache.
+    pub(crate) fn build_tls(&self, tls_config: TlsConfig) -> crate::Result<TlsConnector> {
+        self.tls_builder.clone().build(tls_config)
+    }
+
+    /// Builds the connector with the provided TLS connector and optional layers.
     pub(crate) fn build(
         self,
-        tls_config: TlsConfig,
+        tls: TlsConnector,
         layers: Option<Vec<BoxedConnectorLayer>>,
     ) -> crate::Result<Connector> {
         let mut service = ConnectorService {
             http: self.http,
-            tls: self.tls_builder.clone().build(tls_config)?,
+            tls,
             proxies: self.proxies,
             verbose: self.verbose,
             // The timeout is initially set to None and will be reassigned later
             // based on the presence or absence of user-provided layers.
             timeout: None,
+            #[cfg(feature = "cancellation")]
+            cancellation: self.cancellation,
+            map_connect_error: None,
+            connect_limit: self.max_concurrent_connects.map(ConnectLimit::new),
+            connect_permit: ConnectPermit(None),
+            connect_retries: self.connect_retries,
+            connect_retry_backoff: self.connect_retry_backoff,
+            read_idle_timeout: self.read_idle_timeout,
+            write_stall_timeout: self.write_stall_timeout,
+            write_coalesce: self.write_coalesce,
+            count_bytes: self.count_bytes,
+            inspect_io: self.inspect_io,
+            check_connection_on_reuse: self.check_connection_on_reuse,
             nodelay: self.nodelay,
+            auto_nodelay_for_tls: self.auto_nodelay_for_tls,
+            handshake_nodelay_only: self.handshake_nodelay_only,
+            proxy_match_policy: self.proxy_match_policy,
+            http2_prior_knowledge: self.http2_prior_knowledge,
+            h2_fallback: self.h2_fallback,
+            forced_alpn: Arc::new(self.forced_alpn),
+            race_protocols: self.race_protocols,
+            events: self.events,
+            collect_timings: self.collect_timings,
+            enforce_http: self.enforce_http,
             #[cfg(feature = "socks")]
             resolver: self.resolver,
+            #[cfg(feature = "socks")]
+            socks_handshake_timeout: self.socks_handshake_timeout,
+            #[cfg(feature = "socks")]
+            socks_retries: self.socks_retries,
             tls_info: self.tls_info,
+            tls_read_buffer_size: self.tls_read_buffer_size,
             tls_builder: Arc::new(self.tls_builder),
         };
 
+        service.timeout = self.timeout;
+        service.map_connect_error = self.map_connect_error.clone();
+
         if let Some(layers) = layers {
+            // The connections made outside of the pool skip the layers, but
+            // keep the timeout and error mapping.
+            let direct = service.clone();
+            service.timeout = None;
+            service.map_connect_error = None;
+
             // otherwise we have user provided layers
             // so we need type erasure all the way through
             // as well as mapping the unnameable type of the layers back to Dst for the inner
@@ -291,7 +871,7 @@ impl ConnectorBuilder {
             // now we handle the concrete stuff - any `connect_timeout`,
             // plus a final map_err layer we can use to cast default tower layer
             // errors to internal errors
-            match self.timeout {
+            let service = match self.timeout {
                 Some(timeout) => {
                     let service = ServiceBuilder::new()
                         .layer(TimeoutLayer::new(timeout))
@@ -299,8 +879,7 @@ impl ConnectorBuilder {
                     let service = ServiceBuilder::new()
                         .map_err(map_timeout_to_connector_error)
                         .service(service);
-                    let service = BoxCloneSyncService::new(service);
-                    Ok(Connector::WithLayers(service))
+                    BoxCloneSyncService::new(service)
                 }
                 None => {
                     // no timeout, but still map err
@@ -309,13 +888,25 @@ impl ConnectorBuilder {
                     let service = ServiceBuilder::new()
                         .map_err(map_timeout_to_connector_error)
                         .service(service);
-                    let service = BoxCloneSyncService::new(service);
-                    Ok(Connector::WithLayers(service))
+                    BoxCloneSyncService::new(service)
                 }
-            }
-        } else {
+            };
+
+            // the user's error mapping goes last, so it sees timeouts already typed
+            match self.map_connect_error {
+                Some(map_connect_error) => {
+                    let service = ServiceBuilder::new()
+                        .map_err(move |err| map_connect_error(err))
+                        .service(service);
+                    Ok(Connector::WithLayers(
+                        BoxCloneSyncService::new(service),
+                        direct,
+                    ))
+                }
+                None => Ok(Connector::WithLayers(service, direct)),
+            }
+        } else {
             // we have no user-provided layers, only use concrete types
-            service.timeout = self.timeout;
             Ok(Connector::Simple(service))
         }
     }
@@ -326,11 +917,19 @@ pub(crate) enum Connector {
     // base service, with or without an embedded timeout
     Simple(ConnectorService),
     // at least one custom layer along with maybe an outer timeout layer
-    // from `builder.connect_timeout()`
-    WithLayers(BoxedConnectorService),
+    // from `builder.connect_timeout()`, and the base service alone
+    WithLayers(BoxedConnectorService, ConnectorService),
 }
 
 impl Connector {
+    /// The base service, without the user's layers, for the connections
+    /// made outside of the pool.
+    pub(crate) fn service(&self) -> &ConnectorService {
+        match self {
+            Connector::Simple(service) | Connector::WithLayers(_, service) => service,
+        }
+    }
+
     pub(crate) fn builder(
         proxies: Arc<Vec<ProxyMatcher>>,
         resolver: DynResolver,
@@ -338,6 +937,10 @@ impl Connector {
         ConnectorBuilder {
             #[cfg(feature = "socks")]
             resolver: resolver.clone(),
+            #[cfg(feature = "socks")]
+            socks_handshake_timeout: None,
+            #[cfg(feature = "socks")]
+            socks_retries: 0,
             http: {
                 // Create a new HttpConnector with the provided resolver
                 let mut http = HttpConnector::new_with_resolver(resolver);
@@ -347,10 +950,33 @@ impl Connector {
             proxies,
             verbose: verbose::OFF,
             timeout: None,
+            #[cfg(feature = "cancellation")]
+            cancellation: None,
+            map_connect_error: None,
+            max_concurrent_connects: None,
+            connect_retries: 0,
+            connect_retry_backoff: None,
+            read_idle_timeout: None,
+            write_stall_timeout: None,
+            write_coalesce: None,
+            count_bytes: false,
+            inspect_io: None,
+            check_connection_on_reuse: false,
             nodelay: false,
+            auto_nodelay_for_tls: true,
+            handshake_nodelay_only: true,
+            proxy_match_policy: ProxyMatchPolicy::FirstWins,
+            http2_prior_knowledge: false,
+            h2_fallback: None,
+            forced_alpn: HashMap::new(),
+            race_protocols: false,
+            events: None,
+            collect_timings: false,
+            enforce_http: false,
 
             // TLS connector and its configuration
             tls_info: false,
+            tls_read_buffer_size: None,
             tls_builder: TlsConnector::builder(),
         }
     }
@@ -365,7 +991,7 @@ impl Service<Dst> for Connector {
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
         match self {
             Connector::Simple(service) => service.poll_ready(cx),
-            Connector::WithLayers(service) => service.poll_ready(cx),
+            Connector::WithLayers(service, _) => service.poll_ready(cx),
         }
     }
 
@@ -373,7 +999,7 @@ impl Service<Dst> for Connector {
     fn call(&mut self, dst: Dst) -> Self::Future {
         match self {
             Connector::Simple(service) => service.call(dst),
-            Connector::WithLayers(service) => service.call(Unnameable(dst)),
+            Connector::WithLayers(service, _) => service.call(Unnameable(dst)),
         }
     }
 }
@@ -389,61 +1015,361 @@ pub(crate) struct ConnectorService {
     /// This lets us avoid an extra `Box::pin` indirection layer
     /// since `tokio::time::Timeout` is `Unpin`
     timeout: Option<Duration>,
+    /// Races every connect, independently of any timeout layers.
+    #[cfg(feature = "cancellation")]
+    cancellation: Option<CancellationToken>,
+    /// Like `timeout`, only set here when there are no user-provided layers;
+    /// otherwise it is the outermost layer.
+    map_connect_error: Option<MapConnectError>,
+    /// Bounds how many connects run at once, if set.
+    connect_limit: Option<ConnectLimit>,
+    /// The slot taken by `poll_ready` for the next connect.
+    connect_permit: ConnectPermit,
+    /// How many more times to attempt a connect that failed at the
+    /// connection level.
+    connect_retries: u32,
+    /// The wait before the first retry, doubled before each further one.
+    connect_retry_backoff: Option<Duration>,
+    /// Fails reads that receive nothing for this long.
+    read_idle_timeout: Option<Duration>,
+    /// Fails writes that make no progress for this long.
+    write_stall_timeout: Option<Duration>,
+    /// Gathers small writes to HTTP/1.1 connections into this many bytes.
+    write_coalesce: Option<usize>,
+    /// Counts the bytes going through each connection.
+    count_bytes: bool,
+    /// Sees the bytes going through each connection.
+    inspect_io: Option<IoInspector>,
+    /// Lets the pool probe idle connections before reusing them.
+    check_connection_on_reuse: bool,
     nodelay: bool,
+    /// Enable nodelay during the TLS handshake when it is otherwise disabled.
+    auto_nodelay_for_tls: bool,
+    /// Disable nodelay again after that handshake.
+    handshake_nodelay_only: bool,
+    /// What to do when several proxies match a destination.
+    proxy_match_policy: ProxyMatchPolicy,
+    /// Speak HTTP/2 right away on cleartext connections.
+    http2_prior_knowledge: bool,
+    /// Hosts recently offered only HTTP/1.1 after breaking HTTP/2.
+    h2_fallback: Option<H2Fallback>,
+    /// The ALPN protocol offered to specific hosts.
+    forced_alpn: Arc<HashMap<String, AlpnProtocol>>,
+    /// Race an HTTP/2 and an HTTP/1.1 handshake on direct TLS connections.
+    race_protocols: bool,
+    /// Where connection events are reported, if anywhere.
+    events: Option<EventSender>,
+    /// Time the steps of establishing every connection.
+    collect_timings: bool,
+    /// Reject destinations and proxies with a scheme not spoken here.
+    enforce_http: bool,
     #[cfg(feature = "socks")]
     resolver: DynResolver,
+    /// Bounds the SOCKS handshake, within the overall connect timeout.
+    #[cfg(feature = "socks")]
+    socks_handshake_timeout: Option<Duration>,
+    /// How many more addresses of a SOCKS proxy to try after a transient
+    /// handshake failure.
+    #[cfg(feature = "socks")]
+    socks_retries: u32,
 
     // TLS configuration
     // Note: these are not used in the `TlsConnectorBuilder` but rather
     // in the `TlsConnector` that is built from it.
     tls_info: bool,
+    tls_read_buffer_size: Option<usize>,
     #[allow(unused)]
     tls_builder: Arc<TlsConnectorBuilder>,
 }
 
 impl ConnectorService {
+    /// Boxes an established TLS connection, applying the read buffer size.
+    fn wrap_tls<T: AsyncConnWithInfo>(&self, conn: T) -> BoxConn {
+        match self.tls_read_buffer_size {
+            Some(size) => self.verbose.wrap(BufferedRead::new(conn, size)),
+            None => self.verbose.wrap(conn),
+        }
+    }
+
+    /// Boxes a connect, applying the embedded timeout, the cancellation token,
+    /// and then the user's error mapping.
+    fn connecting<F>(&self, f: F) -> Connecting
+    where
+        F: Future<Output = Result<Conn, BoxError>> + Send + 'static,
+    {
+        let (read_idle, write_stall) = (self.read_idle_timeout, self.write_stall_timeout);
+        let check_on_reuse = self.check_connection_on_reuse;
+        let write_coalesce = self.write_coalesce;
+        let count_bytes = self.count_bytes;
+        let inspect_io = self.inspect_io.clone();
+        let events = self.events.clone();
+        let f = async move {
+            f.await.map(|conn| {
+                conn.with_byte_counts(count_bytes)
+                    .with_inspector(inspect_io)
+                    .with_write_coalesce(write_coalesce)
+                    .with_idle_timeouts(read_idle, write_stall)
+                    .with_reuse_check(check_on_reuse)
+                    .with_events(events)
+            })
+        };
+        let connecting = with_timeout(f, self.timeout);
+        #[cfg(feature = "cancellation")]
+        let connecting = with_cancellation(connecting, self.cancellation.clone());
+        match self.map_connect_error.clone() {
+            Some(map_connect_error) => {
+                Box::pin(async move { connecting.await.map_err(|err| map_connect_error(err)) })
+            }
+            None => Box::pin(connecting),
+        }
+    }
+
+    /// Holds a slot of the connect limit while `connecting` runs, waiting
+    /// for one unless `poll_ready` already took it.
+    fn limit_concurrency(&mut self, connecting: Connecting) -> Connecting {
+        let Some(ref limit) = self.connect_limit else {
+            return connecting;
+        };
+
+        let permit = self.connect_permit.0.take();
+        let semaphore = limit.semaphore.clone();
+        Box::pin(async move {
+            let _permit = match permit {
+                Some(permit) => permit,
+                None => semaphore
+                    .acquire_owned()
+                    .await
+                    .expect("connect limit is never closed"),
+            };
+            connecting.await
+        })
+    }
+
+    /// Applies the options specific to cleartext connections to the origin.
+    fn wrap_cleartext(&self, conn: BoxConn) -> BoxConn {
+        if self.http2_prior_knowledge {
+            Box::new(PriorKnowledgeH2::new(conn))
+        } else {
+            conn
+        }
+    }
+
+    /// Offers the protocol forced for the host of `dst`, unless the request
+    /// has an explicit HTTP version preference.
+    fn force_alpn(&self, dst: &mut Dst) {
+        if self.forced_alpn.is_empty() || dst.alpn_protos().is_some() {
+            return;
+        }
+
+        let forced = dst
+            .uri()
+            .host()
+            .and_then(|host| self.forced_alpn.get(&host.to_ascii_lowercase()))
+            .copied();
+        if let Some(alpn) = forced {
+            trace!("offering forced {:?} to {:?}", alpn, dst.uri().host());
+            dst.set_alpn_protos(alpn);
+        }
+    }
+
+    /// Offers only HTTP/1.1 to a host that recently broke HTTP/2.
+    ///
+    /// Otherwise returns the host to report HTTP/2 protocol errors for. An
+    /// explicit HTTP version preference on the request, or a protocol forced
+    /// for the host, is left alone.
+    fn h2_fallback(&self, dst: &mut Dst) -> Option<Authority> {
+        self.force_alpn(dst);
+
+        let fallback = self.h2_fallback.as_ref()?;
+        if dst.alpn_protos().is_some() {
+            return None;
+        }
+
+        let host = dst.uri().authority()?.clone();
+        if fallback.is_downgraded(&host) {
+            trace!("offering only HTTP/1.1 to {}", host);
+            dst.set_alpn_protos(AlpnProtocol::HTTP1);
+            None
+        } else {
+            Some(host)
+        }
+    }
+
+    /// Lets HTTP/2 protocol errors on the connection downgrade its host.
+    fn report_h2_errors(&self, conn: BoxConn, host: Option<Authority>) -> BoxConn {
+        match (self.h2_fallback.clone(), host) {
+            (Some(fallback), Some(host)) => Box::new(ReportH2Errors::new(conn, fallback, host)),
+            _ => conn,
+        }
+    }
+
+    /// Resolves the addresses of a SOCKS proxy, each as a proxy URI of its own.
+    #[cfg(feature = "socks")]
+    async fn socks_proxy_addrs(&self, proxy: &http::Uri) -> Result<Vec<http::Uri>, BoxError> {
+        use crate::core::client::connect::dns::Name;
+
+        let host = proxy.host().ok_or(Error::uri_bad_host())?;
+        let host = host.trim_start_matches('[').trim_end_matches(']');
+        if host.parse::<IpAddr>().is_ok() {
+            return Ok(vec![proxy.clone()]);
+        }
+
+        let scheme = proxy.scheme_str().unwrap_or("socks5");
+        let addrs = self.resolver.clone().call(host.parse::<Name>()?).await?;
+        addrs
+            .map(|mut addr| {
+                if let Some(port) = proxy.port_u16() {
+                    addr.set_port(port);
+                } else if addr.port() == 0 {
+                    addr.set_port(1080);
+                }
+                format!("{scheme}://{addr}")
+                    .parse::<http::Uri>()
+                    .map_err(Into::into)
+            })
+            .collect()
+    }
+
     #[cfg(feature = "socks")]
     async fn connect_socks(&self, mut dst: Dst, proxy: Intercepted) -> Result<Conn, BoxError> {
-        use crate::core::client::connect::proxy::Socks;
+        use crate::core::client::connect::proxy::{Socks, SocksError};
 
         let uri = dst.uri().clone();
 
-        let mut socks = Socks::new_with_resolver(
-            self.http.clone(),
-            self.resolver.clone(),
-            proxy.uri().clone(),
-            proxy.raw_auth(),
-        );
+        // The control connection is bound like a direct one, so it leaves
+        // through the same local address and interface.
+        let mut http = self.http.clone();
+        http.bind_to(&dst);
+        let timing = self.start_timing();
+        http.set_timing(timing.clone());
+
+        // Each retry goes to the next address of the proxy, so there is
+        // nothing to resolve when retries are disabled.
+        let proxies = if self.socks_retries == 0 {
+            vec![proxy.uri().clone()]
+        } else {
+            self.socks_proxy_addrs(proxy.uri())
+                .await
+                .map_err(ProxyUnreachable::wrap)?
+        };
+        let mut proxies = proxies
+            .into_iter()
+            .take(self.socks_retries as usize + 1)
+            .peekable();
+
+        let (conn, bound) = loop {
+            let Some(proxy_uri) = proxies.next() else {
+                return Err(ProxyUnreachable::wrap(
+                    io::Error::new(
+                        io::ErrorKind::NotFound,
+                        "SOCKS proxy resolved to no addresses",
+                    )
+                    .into(),
+                ));
+            };
+
+            let mut socks = Socks::new_with_resolver(
+                http.clone(),
+                self.resolver.clone(),
+                proxy_uri.clone(),
+                proxy.raw_auth(),
+            );
+
+            let handshake = socks.connect(uri.clone());
+            let res = match self.socks_handshake_timeout {
+                Some(timeout) => tokio::time::timeout(timeout, handshake)
+                    .await
+                    .map_err(|_| {
+                        io::Error::new(io::ErrorKind::TimedOut, "SOCKS handshake timed out")
+                    })?,
+                None => handshake.await,
+            };
+
+            match res {
+                Err(err) if err.is_transient() && proxies.peek().is_some() => {
+                    debug!("SOCKS proxy {:?} failed transiently: {}", proxy_uri, err);
+                }
+                Err(SocksError::Inner(err)) => {
+                    return Err(ProxyUnreachable::wrap(SocksError::Inner(err).into()));
+                }
+                res => break res?,
+            }
+        };
+        if let Some(ref timing) = timing {
+            timing.proxy_connected();
+        }
 
         if uri.scheme() == Some(&Scheme::HTTPS) {
             use crate::Error;
 
+            let report_for = self.h2_fallback(&mut dst);
             let http = HttpsConnector::new(self.http.clone(), self.tls.clone(), &mut dst);
 
             trace!("socks HTTPS over proxy");
-            let conn = socks.call(uri.clone()).await?;
-
             let host = uri.host().ok_or(Error::uri_bad_host())?;
             let io = http.connect(&uri, host, conn).await?;
+            if let Some(ref timing) = timing {
+                timing.tls_done();
+            }
 
-            return Ok(Conn {
-                inner: self.verbose.wrap(BoringTlsConn {
-                    inner: TokioIo::new(io),
+            let conn = Conn {
+                inner: Box::new(WithExtra {
+                    inner: self.report_h2_errors(
+                        self.wrap_tls(BoringTlsConn::new(TokioIo::new(io))),
+                        report_for,
+                    ),
+                    extra: bound,
                 }),
                 is_proxy: false,
                 tls_info: self.tls_info,
-            });
+            };
+            return Ok(conn.with_timing(timing));
         }
 
-        socks
-            .call(uri)
-            .await
-            .map(|tcp| Conn {
-                inner: self.verbose.wrap(tcp),
-                is_proxy: false,
-                tls_info: false,
-            })
-            .map_err(Into::into)
+        let conn = Conn {
+            inner: Box::new(WithExtra {
+                inner: self.wrap_cleartext(self.verbose.wrap(conn)),
+                extra: bound,
+            }),
+            is_proxy: false,
+            tls_info: false,
+        };
+        Ok(conn.with_timing(timing))
+    }
+
+    /// Sets up a UDP relay through the SOCKSv5 proxy `proxy`, for datagrams
+    /// to any destination.
+    ///
+    /// The control connection leaves like a direct one, and the handshake is
+    /// bounded by the SOCKS handshake timeout. A proxy of any other kind
+    /// fails.
+    #[cfg(feature = "socks")]
+    pub(crate) async fn socks_udp_associate(
+        &self,
+        proxy: Intercepted,
+    ) -> Result<crate::core::client::connect::proxy::SocksUdpSocket, BoxError> {
+        use crate::core::client::connect::proxy::Socks;
+
+        if !matches!(proxy.uri().scheme_str(), Some("socks5" | "socks5h")) {
+            return Err(format!("not a SOCKSv5 proxy: {:?}", proxy.uri()).into());
+        }
+
+        let mut socks = Socks::new_with_resolver(
+            self.http.clone(),
+            self.resolver.clone(),
+            proxy.uri().clone(),
+            proxy.raw_auth(),
+        );
+        let associate = socks.udp_associate();
+        let socket = match self.socks_handshake_timeout {
+            Some(timeout) => tokio::time::timeout(timeout, associate)
+                .await
+                .map_err(|_| {
+                    io::Error::new(io::ErrorKind::TimedOut, "SOCKS handshake timed out")
+                })??,
+            None => associate.await?,
+        };
+        Ok(socket)
     }
 
     async fn connect_with_maybe_proxy(
@@ -453,85 +1379,390 @@ impl ConnectorService {
     ) -> Result<Conn, BoxError> {
         let uri = dst.uri().clone();
         let mut http = self.http.clone();
+        let timing = self.start_timing();
+        http.set_timing(timing.clone());
 
         // Disable Nagle's algorithm for TLS handshake
         //
         // https://www.openssl.org/docs/man1.1.1/man3/SSL_connect.html#NOTES
-        if !self.nodelay && (uri.scheme() == Some(&Scheme::HTTPS)) {
+        let handshake_nodelay =
+            !self.nodelay && self.auto_nodelay_for_tls && uri.scheme() == Some(&Scheme::HTTPS);
+        if handshake_nodelay {
             http.set_nodelay(true);
         }
 
         trace!("connect with maybe proxy");
-        let mut http = HttpsConnector::new(http, self.tls, &mut dst);
-        let io = http.call(uri).await?;
+        let report_for = if is_proxy {
+            None
+        } else {
+            self.h2_fallback(&mut dst)
+        };
+        let io = if self.race_protocols
+            && !is_proxy
+            && uri.scheme() == Some(&Scheme::HTTPS)
+            && dst.alpn_protos().is_none()
+        {
+            trace!("racing HTTP/2 and HTTP/1.1 handshakes");
+            let attempts = [AlpnProtocol::HTTP2, AlpnProtocol::HTTP1].map(|alpn| {
+                let mut dst = dst.clone();
+                dst.set_alpn_protos(alpn);
+                HttpsConnector::new(http.clone(), self.tls.clone(), &mut dst).call(uri.clone())
+            });
+            // A failed attempt leaves the other running. The loser is not
+            // bound, so it is dropped, and cancelled, as soon as one wins.
+            let (io, _) = futures_util::future::select_ok(attempts).await?;
+            io
+        } else {
+            let mut http = HttpsConnector::new(http, self.tls.clone(), &mut dst);
+            http.call(uri).await?
+        };
 
-        if let MaybeHttpsStream::Https(stream) = io {
-            if !self.nodelay {
+        let conn = if let MaybeHttpsStream::Https(stream) = io {
+            if let Some(ref timing) = timing {
+                timing.tls_done();
+            }
+            // Application data may already be on its way, so keeping nodelay
+            // on is the only way to be sure none of it waits for Nagle.
+            if handshake_nodelay && self.handshake_nodelay_only {
                 stream
                     .inner()
                     .get_ref()
                     .inner()
                     .inner()
+                    .inner()
                     .set_nodelay(false)?;
             }
-            Ok(Conn {
-                inner: self.verbose.wrap(BoringTlsConn { inner: stream }),
+            Conn {
+                inner: self.report_h2_errors(self.wrap_tls(BoringTlsConn::new(stream)), report_for),
                 is_proxy,
                 tls_info: self.tls_info,
-            })
+            }
         } else {
-            Ok(Conn {
-                inner: self.verbose.wrap(io),
+            let inner = self.verbose.wrap(io);
+            Conn {
+                // A plain HTTP proxy forwards requests, it does not relay h2 frames.
+                inner: if is_proxy {
+                    inner
+                } else {
+                    self.wrap_cleartext(inner)
+                },
                 is_proxy,
                 tls_info: self.tls_info,
-            })
+            }
+        };
+        Ok(conn.with_timing(timing))
+    }
+
+    /// Starts timing a new connection, if timings are collected.
+    fn start_timing(&self) -> Option<ConnectTimingRecorder> {
+        self.collect_timings.then(ConnectTimingRecorder::default)
+    }
+
+    /// Connects to `dst`, through `proxy` if any, and starts over after a
+    /// connection level failure as many times as configured.
+    ///
+    /// Every attempt resolves the host again, so a dead address isn't tried
+    /// over and over. The connect timeout bounds all attempts together.
+    async fn connect_with_retries(
+        self,
+        dst: Dst,
+        proxy: Option<Intercepted>,
+    ) -> Result<Conn, BoxError> {
+        let mut retries = 0;
+        loop {
+            let res = match proxy.clone() {
+                Some(proxy) => self.clone().connect_via_proxy(dst.clone(), proxy).await,
+                None => {
+                    self.clone()
+                        .connect_with_maybe_proxy(dst.clone(), false)
+                        .await
+                }
+            };
+
+            match res {
+                Err(err) if retries < self.connect_retries && is_retryable_connect(&*err) => {
+                    retries += 1;
+                    debug!(
+                        "connect to {:?} failed, retry {} of {}: {}",
+                        dst.uri(),
+                        retries,
+                        self.connect_retries,
+                        err
+                    );
+                    if let Some(backoff) = self.connect_retry_backoff {
+                        let factor = 1u32 << (retries - 1).min(16);
+                        tokio::time::sleep(backoff.saturating_mul(factor)).await;
+                    }
+                }
+                res => return res,
+            }
         }
     }
 
-    async fn connect_via_proxy(self, mut dst: Dst, proxy: Intercepted) -> Result<Conn, BoxError> {
+    /// Finds the proxy for `uri`, the first one that intercepts it, and
+    /// handles any other that does per the match policy.
+    fn intercept(&self, uri: &http::Uri) -> Result<Option<Intercepted>, BoxError> {
+        let mut matched = self.proxies.iter().filter_map(|prox| prox.intercept(uri));
+        let Some(first) = matched.next() else {
+            return Ok(None);
+        };
+        let others: Vec<_> = matched.collect();
+        if others.is_empty() {
+            return Ok(Some(first));
+        }
+
+        let names = std::iter::once(&first)
+            .chain(&others)
+            .map(|proxy| proxy.info().uri().to_string())
+            .collect::<Vec<_>>()
+            .join(", ");
+        match self.proxy_match_policy {
+            ProxyMatchPolicy::FirstWins => {
+                warn!(
+                    "{} proxies match {}: {}; using the first",
+                    others.len() + 1,
+                    uri,
+                    names
+                );
+                Ok(Some(first))
+            }
+            ProxyMatchPolicy::Strict => {
+                Err(format!("ambiguous proxy configuration, {uri} is matched by {names}").into())
+            }
+        }
+    }
+
+    /// Connects through `proxy`, or through its fallbacks in order while the
+    /// proxies tried can't be reached.
+    async fn connect_via_proxy(self, dst: Dst, mut proxy: Intercepted) -> Result<Conn, BoxError> {
+        let (fallbacks, direct) = proxy.take_fallbacks();
+
+        // A forwarded request was prepared for its proxy before connecting,
+        // with its credentials, so it can't move to another.
+        let uri = dst.uri().clone();
+        if is_forwarded(&uri, &proxy) {
+            return self.connect_through_proxy(dst, proxy).await;
+        }
+        let mut fallbacks = fallbacks
+            .into_iter()
+            .filter(|fallback| !is_forwarded(&uri, fallback));
+
+        loop {
+            let err = match self.clone().connect_through_proxy(dst.clone(), proxy).await {
+                Err(err) => match err.downcast::<ProxyUnreachable>() {
+                    Ok(unreachable) => unreachable.0,
+                    Err(err) => return Err(err),
+                },
+                res => return res,
+            };
+
+            match fallbacks.next() {
+                Some(fallback) => {
+                    debug!("proxy unreachable ({}), trying {:?}", err, fallback);
+                    proxy = fallback;
+                }
+                None if direct => {
+                    debug!("no proxy reachable ({}), connecting directly", err);
+                    return self.connect_with_maybe_proxy(dst, false).await;
+                }
+                None => return Err(err),
+            }
+        }
+    }
+
+    async fn connect_through_proxy(
+        self,
+        mut dst: Dst,
+        proxy: Intercepted,
+    ) -> Result<Conn, BoxError> {
         let uri = dst.uri().clone();
         debug!("proxy({:?}) intercepts '{:?}'", proxy, dst);
+        let info = proxy.info();
+        if let Some(ref events) = self.events {
+            events.emit(ConnEvent::ProxyConnecting {
+                proxy: info.uri().clone(),
+            });
+        }
 
         #[cfg(feature = "socks")]
         if let Some("socks4" | "socks4a" | "socks5" | "socks5h") = proxy.uri().scheme_str() {
-            return self.connect_socks(dst, proxy).await;
+            return self
+                .connect_socks(dst, proxy)
+                .await
+                .map(|conn| conn.with_extra(info));
         }
 
-        let proxy_dst = proxy.uri().clone();
-        let auth = proxy.basic_auth().cloned();
-
         if uri.scheme() == Some(&Scheme::HTTPS) {
             trace!("tunneling HTTPS over proxy");
-            let http = HttpsConnector::new(self.http.clone(), self.tls, &mut dst);
-
-            let mut tunnel = Tunnel::new(proxy_dst, http.clone());
-            if let Some(auth) = auth {
-                tunnel = tunnel.with_auth(auth);
-            }
-
-            if let Some(headers) = proxy.custom_headers() {
-                tunnel = tunnel.with_headers(headers.clone());
-            }
+            let report_for = self.h2_fallback(&mut dst);
+            let timing = self.start_timing();
+            let mut http = self.http.clone();
+            http.set_timing(timing.clone());
+            let http = HttpsConnector::new(http, self.tls.clone(), &mut dst);
 
             let host = uri.host().ok_or(Error::uri_bad_host())?;
 
             // We don't wrap this again in an HttpsConnector since that uses Maybe,
             // and we know this is definitely HTTPS.
-            let tunneled = tunnel.call(uri.clone()).await?;
+            let tunneled = tunnel_through(&proxy, http.clone())
+                .call(uri.clone())
+                .await
+                .map_err(tunnel_error)?;
+            if let Some(ref timing) = timing {
+                timing.proxy_connected();
+            }
             let io = http.connect(&uri, host, tunneled).await?;
+            if let Some(ref timing) = timing {
+                timing.tls_done();
+            }
+
+            let conn = Conn {
+                inner: self.report_h2_errors(
+                    self.wrap_tls(BoringTlsConn::new(TokioIo::new(io))),
+                    report_for,
+                ),
+                is_proxy: false,
+                tls_info: self.tls_info,
+            };
+            return Ok(conn.with_timing(timing).with_extra(info));
+        }
+
+        dst.set_uri(proxy.uri().clone());
+
+        self.connect_with_maybe_proxy(dst, true)
+            .await
+            .map(|conn| conn.with_extra(info))
+    }
+
+    /// Opens a CONNECT tunnel through `proxy` to the host and port of `dst`,
+    /// and returns the tunneled stream as is.
+    ///
+    /// Nothing is assumed about what is spoken over the tunnel: there is no
+    /// TLS with the destination, and none of the wrapping for HTTP. Without a
+    /// port in `dst`, the tunnel goes to port 443.
+    pub(crate) fn connect_tunnel(&self, dst: Dst, proxy: Intercepted) -> Connecting {
+        self.connecting(self.clone().connect_raw_tunnel(dst, proxy))
+    }
+
+    async fn connect_raw_tunnel(self, mut dst: Dst, proxy: Intercepted) -> Result<Conn, BoxError> {
+        let uri = dst.uri().clone();
+        debug!("raw tunnel through proxy({:?}) to '{:?}'", proxy, uri);
+        let info = proxy.info();
+        if let Some(ref events) = self.events {
+            events.emit(ConnEvent::ProxyConnecting {
+                proxy: info.uri().clone(),
+            });
+        }
+
+        // The proxy itself may still be reached over TLS.
+        let http = HttpsConnector::new(self.http.clone(), self.tls.clone(), &mut dst);
+        let tunneled = tunnel_through(&proxy, http).call(uri).await?;
+
+        let conn = Conn {
+            inner: self.verbose.wrap(tunneled),
+            is_proxy: false,
+            tls_info: false,
+        };
+        Ok(conn.with_extra(info))
+    }
+}
+
+impl ConnectorService {
+    /// Sets up a connection to `dst` over `stream`, a transport connected
+    /// elsewhere, such as a socket handed over by another process.
+    ///
+    /// There is no DNS lookup, TCP connect or proxy: for an `https` `dst`,
+    /// the TLS handshake with its host runs over `stream`, and otherwise
+    /// `stream` is spoken to in clear text. The rest of the connector's
+    /// configuration applies as for any other connection.
+    pub(crate) fn connect_over<S>(&self, stream: S, dst: Dst) -> Connecting
+    where
+        S: Read + Write + Unpin + Send + Sync + fmt::Debug + 'static,
+    {
+        self.connecting(self.clone().connect_provided(Provided(stream), dst))
+    }
+
+    async fn connect_provided<S>(self, stream: Provided<S>, mut dst: Dst) -> Result<Conn, BoxError>
+    where
+        S: Read + Write + Unpin + Send + Sync + fmt::Debug + 'static,
+    {
+        let uri = dst.uri().clone();
+        debug!("connecting over a provided stream to {:?}", uri);
+
+        if uri.scheme() == Some(&Scheme::HTTPS) {
+            let report_for = self.h2_fallback(&mut dst);
+            let http = HttpsConnector::new(self.http.clone(), self.tls.clone(), &mut dst);
+
+            let host = uri.host().ok_or(Error::uri_bad_host())?;
+            let io = http.connect(&uri, host, stream).await?;
 
             return Ok(Conn {
-                inner: self.verbose.wrap(BoringTlsConn {
-                    inner: TokioIo::new(io),
-                }),
+                inner: self.report_h2_errors(
+                    self.wrap_tls(BoringTlsConn::new(TokioIo::new(io))),
+                    report_for,
+                ),
                 is_proxy: false,
                 tls_info: self.tls_info,
             });
         }
 
-        dst.set_uri(proxy_dst);
+        Ok(Conn {
+            inner: self.wrap_cleartext(self.verbose.wrap(stream)),
+            is_proxy: false,
+            tls_info: false,
+        })
+    }
+}
+
+/// Whether a request to `uri` is forwarded by `proxy`, rather than tunneled.
+fn is_forwarded(uri: &http::Uri, proxy: &Intercepted) -> bool {
+    uri.scheme() == Some(&Scheme::HTTP)
+        && !matches!(
+            proxy.uri().scheme_str(),
+            Some("socks4" | "socks4a" | "socks5" | "socks5h")
+        )
+}
+
+/// A failure to reach a proxy, rather than of the proxy to reach the origin,
+/// after which the next fallback proxy is tried.
+///
+/// It never leaves `connect_via_proxy`, which unwraps it.
+#[derive(Debug)]
+struct ProxyUnreachable(BoxError);
+
+impl ProxyUnreachable {
+    fn wrap(err: BoxError) -> BoxError {
+        Box::new(ProxyUnreachable(err))
+    }
+}
+
+impl fmt::Display for ProxyUnreachable {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+impl StdError for ProxyUnreachable {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        self.0.source()
+    }
+}
+
+fn tunnel_error(err: TunnelError) -> BoxError {
+    match err {
+        TunnelError::ConnectFailed(_) => ProxyUnreachable::wrap(err.into()),
+        err => err.into(),
+    }
+}
 
-        self.connect_with_maybe_proxy(dst, true).await
+/// Sets up a CONNECT tunnel through `proxy`, reaching it with `connector`.
+fn tunnel_through<C>(proxy: &Intercepted, connector: C) -> Tunnel<C> {
+    let tunnel = Tunnel::new(proxy.uri().clone(), connector);
+    let headers = proxy.tunnel_headers();
+    if headers.is_empty() {
+        tunnel
+    } else {
+        tunnel.with_headers(headers)
     }
 }
 
@@ -550,78 +1781,210 @@ where
     }
 }
 
+#[cfg(feature = "cancellation")]
+async fn with_cancellation<T, F>(
+    f: F,
+    cancellation: Option<CancellationToken>,
+) -> Result<T, BoxError>
+where
+    F: Future<Output = Result<T, BoxError>>,
+{
+    match cancellation {
+        Some(token) => token
+            .run_until_cancelled(f)
+            .await
+            .unwrap_or_else(|| Err(Box::new(Cancelled) as BoxError)),
+        None => f.await,
+    }
+}
+
 impl Service<Dst> for ConnectorService {
     type Response = Conn;
     type Error = BoxError;
     type Future = Connecting;
 
-    #[inline(always)]
-    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        if let Some(ref mut limit) = self.connect_limit {
+            if self.connect_permit.0.is_none() {
+                self.connect_permit.0 = Some(ready!(limit.poll_acquire(cx)));
+            }
+        }
         Poll::Ready(Ok(()))
     }
 
     fn call(&mut self, mut dst: Dst) -> Self::Future {
         debug!("starting new connection: {:?}", dst.uri());
 
-        if let Some(proxy_scheme) = dst.take_proxy_intercepted() {
-            return Box::pin(with_timeout(
-                self.clone().connect_via_proxy(dst, proxy_scheme),
-                self.timeout,
-            ));
-        } else {
-            for prox in self.proxies.iter() {
-                if let Some(intercepted) = prox.intercept(dst.uri()) {
-                    return Box::pin(with_timeout(
-                        self.clone().connect_via_proxy(dst, intercepted),
-                        self.timeout,
-                    ));
-                }
+        // A connect timeout of the request takes precedence over the
+        // connector's, both as the overall bound and as what is divided
+        // across the resolved addresses.
+        let mut service = self.clone();
+        if let Some(timeout) = dst.connect_timeout() {
+            service.timeout = Some(timeout);
+            service.http.set_connect_timeout(Some(timeout));
+        }
+
+        if let Some(stream) = dst.take_provided_stream() {
+            return service.connect_over(stream, dst);
+        }
+
+        let proxy = match dst.take_proxy_intercepted() {
+            Some(proxy) => Some(proxy),
+            None => match self.intercept(dst.uri()) {
+                Ok(proxy) => proxy,
+                Err(err) => return self.connecting(std::future::ready(Err(err))),
+            },
+        };
+
+        if self.enforce_http {
+            let checked = check_schemes(dst.uri(), proxy.as_ref().map(Intercepted::uri));
+            if let Err(err) = checked {
+                return self.connecting(std::future::ready(Err(err)));
             }
         }
 
-        Box::pin(with_timeout(
-            self.clone().connect_with_maybe_proxy(dst, false),
-            self.timeout,
-        ))
+        let connecting = service.connecting(service.clone().connect_with_retries(dst, proxy));
+        self.limit_concurrency(connecting)
     }
 }
 
-trait TlsInfoFactory {
-    fn tls_info(&self) -> Option<crate::tls::TlsInfo>;
+/// Bounds how many connects run at once.
+///
+/// Clones share the semaphore, but not a slot being waited for.
+struct ConnectLimit {
+    semaphore: Arc<Semaphore>,
+    acquiring: Option<SyncWrapper<Pin<Box<AcquireFuture>>>>,
 }
 
-impl TlsInfoFactory for tokio::net::TcpStream {
-    fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
-        None
+type AcquireFuture = dyn Future<Output = Result<OwnedSemaphorePermit, AcquireError>> + Send;
+
+impl ConnectLimit {
+    fn new(max: usize) -> ConnectLimit {
+        ConnectLimit {
+            semaphore: Arc::new(Semaphore::new(max)),
+            acquiring: None,
+        }
     }
-}
 
-impl<T: TlsInfoFactory> TlsInfoFactory for TokioIo<T> {
-    fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
-        self.inner().tls_info()
+    /// Waits for a slot, keeping its place in the queue across polls.
+    fn poll_acquire(&mut self, cx: &mut Context<'_>) -> Poll<OwnedSemaphorePermit> {
+        let acquiring = self.acquiring.get_or_insert_with(|| {
+            SyncWrapper::new(Box::pin(self.semaphore.clone().acquire_owned()))
+        });
+        let permit = ready!(acquiring.get_mut().as_mut().poll(cx));
+        self.acquiring = None;
+        Poll::Ready(permit.expect("connect limit is never closed"))
     }
 }
 
-impl TlsInfoFactory for SslStream<TokioIo<TokioIo<tokio::net::TcpStream>>> {
-    fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
-        self.ssl()
-            .peer_certificate()
-            .and_then(|c| c.to_der().ok())
-            .map(|c| crate::tls::TlsInfo {
-                peer_certificate: Some(c),
-            })
+impl Clone for ConnectLimit {
+    fn clone(&self) -> Self {
+        ConnectLimit {
+            semaphore: self.semaphore.clone(),
+            acquiring: None,
+        }
     }
 }
 
-impl TlsInfoFactory for SslStream<TokioIo<MaybeHttpsStream<TokioIo<tokio::net::TcpStream>>>> {
-    fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
-        self.get_ref().inner().tls_info()
+/// A slot of the connect limit, which clones of the service do not share.
+struct ConnectPermit(Option<OwnedSemaphorePermit>);
+
+impl Clone for ConnectPermit {
+    fn clone(&self) -> Self {
+        ConnectPermit(None)
     }
 }
 
-impl TlsInfoFactory for MaybeHttpsStream<TokioIo<tokio::net::TcpStream>> {
-    fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
-        match self {
+/// Rejects a destination that is not `http` or `https`, and a proxy the
+/// connector cannot speak to.
+fn check_schemes(dst: &http::Uri, proxy: Option<&http::Uri>) -> Result<(), BoxError> {
+    if !matches!(dst.scheme_str(), Some("http" | "https")) {
+        debug!("rejecting destination scheme {:?}", dst.scheme_str());
+        return Err(Error::uri_bad_scheme().into());
+    }
+
+    let Some(proxy) = proxy else {
+        return Ok(());
+    };
+    match proxy.scheme_str() {
+        Some("http" | "https") => Ok(()),
+        #[cfg(feature = "socks")]
+        Some("socks4" | "socks4a" | "socks5" | "socks5h") => Ok(()),
+        scheme => {
+            debug!("rejecting proxy scheme {:?}", scheme);
+            Err(Error::uri_bad_scheme().into())
+        }
+    }
+}
+
+trait TlsInfoFactory {
+    fn tls_info(&self) -> Option<crate::tls::TlsInfo>;
+}
+
+impl TlsInfoFactory for tokio::net::TcpStream {
+    fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+        None
+    }
+}
+
+impl<T: TlsInfoFactory> TlsInfoFactory for TokioIo<T> {
+    fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+        self.inner().tls_info()
+    }
+}
+
+impl TlsInfoFactory for SslStream<Sniffing<TokioIo<TokioIo<tokio::net::TcpStream>>>> {
+    fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+        ssl_tls_info(self)
+    }
+}
+
+impl<S> TlsInfoFactory for SslStream<Sniffing<TokioIo<Provided<S>>>> {
+    fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+        ssl_tls_info(self)
+    }
+}
+
+/// The TLS info of a handshake made by this connector.
+fn ssl_tls_info<S>(stream: &SslStream<S>) -> Option<crate::tls::TlsInfo> {
+    let ssl = stream.ssl();
+    let cert = ssl.peer_certificate()?;
+    cert.to_der().ok().map(|c| {
+        let fingerprint = crate::tls::client_hello_fingerprint(ssl);
+        crate::tls::TlsInfo {
+            peer_certificate: Some(c),
+            peer_cert_chain: ssl.peer_cert_chain().map(|chain| {
+                chain
+                    .into_iter()
+                    .filter_map(|cert| cert.to_der().ok())
+                    .collect()
+            }),
+            peer_validity: crate::tls::validity(&cert),
+            early_data: None,
+            verified_chain: crate::tls::verified_chain(ssl),
+            session_reused: ssl.session_reused(),
+            ja3: fingerprint.as_ref().map(|f| f.ja3.clone()),
+            ja4: fingerprint.map(|f| f.ja4),
+            alpn_offered: crate::tls::alpn_offered(ssl),
+            alpn_selected: ssl.selected_alpn_protocol().map(<[u8]>::to_vec),
+            alps_negotiated: crate::tls::alps_negotiated(ssl),
+            cert_compression: crate::tls::cert_compression_used(ssl),
+            ocsp_response: ssl.ocsp_status().map(<[u8]>::to_vec),
+        }
+    })
+}
+
+impl TlsInfoFactory
+    for SslStream<Sniffing<TokioIo<MaybeHttpsStream<TokioIo<tokio::net::TcpStream>>>>>
+{
+    fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+        self.get_ref().inner().inner().tls_info()
+    }
+}
+
+impl TlsInfoFactory for MaybeHttpsStream<TokioIo<tokio::net::TcpStream>> {
+    fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+        match self {
             MaybeHttpsStream::Https(tls) => tls.inner().tls_info(),
             MaybeHttpsStream::Http(_) => None,
         }
@@ -641,200 +2004,2314 @@ impl<T: AsyncConn + TlsInfoFactory> AsyncConnWithInfo for T {}
 
 type BoxConn = Box<dyn AsyncConnWithInfo>;
 
-pub(crate) mod sealed {
-    use super::*;
+impl TlsInfoFactory for BoxConn {
+    fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+        (**self).tls_info()
+    }
+}
+
+impl Connection for BoxConn {
+    fn connected(&self) -> Connected {
+        (**self).connected()
+    }
+}
+
+pub(crate) mod sealed {
+    use super::*;
+
+    #[derive(Debug)]
+    pub struct Unnameable(pub(super) Dst);
+
+    pin_project! {
+        /// Note: the `is_proxy` member means *is plain text HTTP proxy*.
+        /// This tells hyper whether the URI should be written in
+        /// * origin-form (`GET /just/a/path HTTP/1.1`), when `is_proxy == false`, or
+        /// * absolute-form (`GET http://foo.bar/and/a/path HTTP/1.1`), otherwise.
+        pub struct Conn {
+            #[pin]
+            pub(super) inner: BoxConn,
+            pub(super) is_proxy: bool,
+            // Only needed for __tls, but #[cfg()] on fields breaks pin_project!
+            pub(super) tls_info: bool,
+        }
+    }
+
+    impl Conn {
+        /// Counts the bytes read and written, beneath the write buffer, so
+        /// that gathered writes count once they are written out.
+        pub(super) fn with_byte_counts(mut self, enabled: bool) -> Conn {
+            if enabled {
+                self.inner = Box::new(Metered::new(self.inner));
+            }
+            self
+        }
+
+        /// Hands every chunk read and written to `inspector`, beneath the
+        /// write buffer as well.
+        pub(super) fn with_inspector(mut self, inspector: Option<IoInspector>) -> Conn {
+            if let Some(inspector) = inspector {
+                self.inner = Box::new(Inspected::new(self.inner, inspector));
+            }
+            self
+        }
+
+        /// Gathers small writes, unless the connection speaks HTTP/2, whose
+        /// frames are already buffered by the h2 crate.
+        pub(super) fn with_write_coalesce(mut self, size: Option<usize>) -> Conn {
+            if let Some(size) = size {
+                if !self.connected().is_negotiated_h2() {
+                    self.inner = Box::new(WriteCoalesce::new(self.inner, size));
+                }
+            }
+            self
+        }
+
+        pub(super) fn with_idle_timeouts(
+            mut self,
+            read: Option<Duration>,
+            write: Option<Duration>,
+        ) -> Conn {
+            if read.is_some() || write.is_some() {
+                self.inner = Box::new(IdleTimeout::new(self.inner, read, write));
+            }
+            self
+        }
+
+        pub(super) fn with_extra<T>(mut self, extra: T) -> Conn
+        where
+            T: Clone + Send + Sync + Unpin + 'static,
+        {
+            self.inner = Box::new(WithExtra {
+                inner: self.inner,
+                extra,
+            });
+            self
+        }
+
+        /// Attaches the timing `timing` recorded, if any.
+        pub(super) fn with_timing(self, timing: Option<ConnectTimingRecorder>) -> Conn {
+            match timing {
+                Some(timing) => self.with_extra(timing.finish()),
+                None => self,
+            }
+        }
+
+        /// HTTP/2 connections are checked with a PING, which leaves their
+        /// I/O alone; others are probed with a read.
+        pub(super) fn with_reuse_check(mut self, enabled: bool) -> Conn {
+            if enabled {
+                self.inner = if self.connected().is_negotiated_h2() {
+                    Box::new(PingOnReuse::new(self.inner))
+                } else {
+                    Box::new(ProbeOnReuse::new(self.inner))
+                };
+            }
+            self
+        }
+
+        pub(super) fn with_events(mut self, events: Option<EventSender>) -> Conn {
+            if let Some(events) = events {
+                let connected = self.connected();
+                self.inner = Box::new(Observed::new(self.inner, events, &connected));
+            }
+            self
+        }
+    }
+
+    impl Connection for Conn {
+        fn connected(&self) -> Connected {
+            let connected = self.inner.connected().proxy(self.is_proxy);
+
+            if self.tls_info {
+                if let Some(tls_info) = self.inner.tls_info() {
+                    connected.extra(tls_info)
+                } else {
+                    connected
+                }
+            } else {
+                connected
+            }
+        }
+    }
+
+    impl Read for Conn {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: ReadBufCursor<'_>,
+        ) -> Poll<io::Result<()>> {
+            let this = self.project();
+            Read::poll_read(this.inner, cx, buf)
+        }
+    }
+
+    impl Write for Conn {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            let this = self.project();
+            Write::poll_write(this.inner, cx, buf)
+        }
+
+        fn poll_write_vectored(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<Result<usize, io::Error>> {
+            let this = self.project();
+            Write::poll_write_vectored(this.inner, cx, bufs)
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.inner.is_write_vectored()
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+            let this = self.project();
+            Write::poll_flush(this.inner, cx)
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+            let this = self.project();
+            Write::poll_shutdown(this.inner, cx)
+        }
+    }
+}
+
+pub(crate) type Connecting = Pin<Box<dyn Future<Output = Result<Conn, BoxError>> + Send>>;
+
+mod tls_conn {
+    use std::{
+        io::{self, IoSlice},
+        pin::Pin,
+        task::{Context, Poll, ready},
+    };
+
+    use pin_project_lite::pin_project;
+    use tokio::io::{AsyncRead, AsyncWrite};
+    use tokio_boring2::SslStream;
+
+    use super::TlsInfoFactory;
+    use crate::{
+        core::{
+            client::connect::{Connected, Connection},
+            rt::{Read, ReadBufCursor, TokioIo, Write},
+        },
+        tls::{self, EarlyDataStatus},
+    };
+
+    pin_project! {
+        pub(super) struct BoringTlsConn<T> {
+            #[pin] pub(super) inner: TokioIo<SslStream<T>>,
+            pub(super) early_data: Option<EarlyDataStatus>,
+            // What was written as early data, until the server answers.
+            sent_early: Vec<u8>,
+            // Whether `sent_early` must be written again, the server having
+            // rejected it.
+            replay: bool,
+        }
+    }
+
+    impl<T> BoringTlsConn<T> {
+        pub(super) fn new(inner: TokioIo<SslStream<T>>) -> Self {
+            // With early data the handshake returns before the server has answered;
+            // its answer arrives with the first read.
+            let early_data = inner
+                .inner()
+                .ssl()
+                .in_early_data()
+                .then(EarlyDataStatus::pending);
+
+            BoringTlsConn {
+                inner,
+                early_data,
+                sent_early: Vec::new(),
+                replay: false,
+            }
+        }
+
+        fn in_early_data(&self) -> bool {
+            self.early_data
+                .as_ref()
+                .is_some_and(EarlyDataStatus::is_pending)
+        }
+    }
+
+    impl<T: AsyncRead + AsyncWrite + Unpin> BoringTlsConn<T> {
+        /// Writes again what was sent as early data, if the server rejected it.
+        fn poll_replay(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let mut this = self.project();
+            if !*this.replay {
+                return Poll::Ready(Ok(()));
+            }
+            while !this.sent_early.is_empty() {
+                let n = ready!(Write::poll_write(this.inner.as_mut(), cx, this.sent_early))?;
+                if n == 0 {
+                    return Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                }
+                this.sent_early.drain(..n);
+            }
+            ready!(Write::poll_flush(this.inner, cx))?;
+            *this.replay = false;
+            *this.sent_early = Vec::new();
+            Poll::Ready(Ok(()))
+        }
+    }
+
+    /// Records whether the server accepted our early data, once it has answered.
+    fn update_early_data<T>(
+        early_data: &Option<EarlyDataStatus>,
+        inner: &TokioIo<SslStream<T>>,
+        sent_early: &mut Vec<u8>,
+    ) {
+        if let Some(status) = early_data {
+            let ssl = inner.inner().ssl();
+            if status.is_pending() && !ssl.in_early_data() {
+                status.set(ssl.early_data_accepted());
+                *sent_early = Vec::new();
+            }
+        }
+    }
+
+    /// Returns whether `err` is the server rejecting our early data, and if
+    /// so, readies the connection to write it again.
+    ///
+    /// The server discarded the early data without acting on it, and the
+    /// handshake is now complete, so it is safe to send it again.
+    fn early_data_rejected<T>(
+        early_data: &Option<EarlyDataStatus>,
+        inner: &TokioIo<SslStream<T>>,
+        replay: &mut bool,
+        err: &io::Error,
+    ) -> bool {
+        match early_data {
+            Some(status) if tls::early_data_rejected(err) => {
+                debug!("server rejected the early data, sending it again");
+                tls::reset_early_data(inner.inner().ssl());
+                status.set(false);
+                *replay = true;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    impl<T: Connection> Connection for BoringTlsConn<T> {
+        fn connected(&self) -> Connected {
+            let connected = self.inner.inner().get_ref().connected();
+            let connected = if self.in_early_data() {
+                connected.early_data()
+            } else {
+                connected
+            };
+            if self.inner.inner().ssl().selected_alpn_protocol() == Some(b"h2") {
+                connected.negotiated_h2()
+            } else {
+                connected
+            }
+        }
+    }
+
+    impl<T: AsyncRead + AsyncWrite + Unpin> Read for BoringTlsConn<T> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: ReadBufCursor<'_>,
+        ) -> Poll<tokio::io::Result<()>> {
+            ready!(self.as_mut().poll_replay(cx))?;
+            let this = self.project();
+            let res = Read::poll_read(this.inner.as_mut(), cx, buf);
+            if let Poll::Ready(Err(ref err)) = res {
+                if early_data_rejected(this.early_data, &this.inner, this.replay, err) {
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+            }
+            if res.is_ready() {
+                update_early_data(this.early_data, &this.inner, this.sent_early);
+            }
+            res
+        }
+    }
+
+    impl<T: AsyncRead + AsyncWrite + Unpin> Write for BoringTlsConn<T> {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, tokio::io::Error>> {
+            ready!(self.as_mut().poll_replay(cx))?;
+            let in_early_data = self.in_early_data();
+            let this = self.project();
+            let res = Write::poll_write(this.inner.as_mut(), cx, buf);
+            match res {
+                // Kept in case the server rejects it.
+                Poll::Ready(Ok(n)) if in_early_data => {
+                    this.sent_early.extend_from_slice(&buf[..n]);
+                }
+                Poll::Ready(Err(ref err)) => {
+                    if early_data_rejected(this.early_data, &this.inner, this.replay, err) {
+                        cx.waker().wake_by_ref();
+                        return Poll::Pending;
+                    }
+                }
+                _ => {}
+            }
+            if res.is_ready() {
+                update_early_data(this.early_data, &this.inner, this.sent_early);
+            }
+            res
+        }
+
+        fn poll_write_vectored(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<Result<usize, io::Error>> {
+            // Early data goes through `poll_write`, which keeps a copy of it.
+            if self.in_early_data() || self.replay {
+                let buf = bufs
+                    .iter()
+                    .find(|buf| !buf.is_empty())
+                    .map_or(&[][..], |buf| &**buf);
+                return self.poll_write(cx, buf);
+            }
+            let this = self.project();
+            Write::poll_write_vectored(this.inner, cx, bufs)
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.inner.is_write_vectored()
+        }
+
+        fn poll_flush(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+        ) -> Poll<Result<(), tokio::io::Error>> {
+            let this = self.project();
+            Write::poll_flush(this.inner, cx)
+        }
+
+        fn poll_shutdown(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+        ) -> Poll<Result<(), tokio::io::Error>> {
+            let this = self.project();
+            Write::poll_shutdown(this.inner, cx)
+        }
+    }
+
+    impl<T> TlsInfoFactory for BoringTlsConn<T>
+    where
+        TokioIo<SslStream<T>>: TlsInfoFactory,
+    {
+        fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+            self.inner.tls_info().map(|mut info| {
+                info.early_data = self.early_data.clone();
+                info
+            })
+        }
+    }
+}
+
+mod extra {
+    use std::{
+        io::{self, IoSlice},
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use super::{BoxConn, TlsInfoFactory};
+    use crate::core::{
+        client::connect::{Connected, Connection},
+        rt::{Read, ReadBufCursor, Write},
+    };
+
+    /// Attaches an additional value to the [`Connected`] metadata of a connection.
+    pub(super) struct WithExtra<T> {
+        pub(super) inner: BoxConn,
+        pub(super) extra: T,
+    }
+
+    impl<T: Clone + Send + Sync + 'static> Connection for WithExtra<T> {
+        fn connected(&self) -> Connected {
+            self.inner.connected().extra(self.extra.clone())
+        }
+    }
+
+    impl<T: Unpin> Read for WithExtra<T> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: ReadBufCursor<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    impl<T: Unpin> Write for WithExtra<T> {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            Pin::new(&mut self.inner).poll_write(cx, buf)
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<Result<usize, io::Error>> {
+            Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.inner.is_write_vectored()
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+        ) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    impl<T> TlsInfoFactory for WithExtra<T> {
+        fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+            self.inner.tls_info()
+        }
+    }
+}
+
+mod h2_fallback {
+    use std::{
+        collections::HashMap,
+        io::{self, IoSlice},
+        pin::Pin,
+        sync::Arc,
+        task::{Context, Poll},
+        time::{Duration, Instant},
+    };
+
+    use antidote::Mutex;
+    use http::uri::Authority;
+
+    use super::{BoxConn, TlsInfoFactory};
+    use crate::core::{
+        client::connect::{Connected, Connection},
+        rt::{Read, ReadBufCursor, Write},
+    };
+
+    /// How long a host is offered only HTTP/1.1 after breaking HTTP/2.
+    const COOLDOWN: Duration = Duration::from_secs(5 * 60);
+
+    /// Hosts whose HTTP/2 connections recently failed with a protocol error,
+    /// such as frames corrupted by a broken middlebox.
+    #[derive(Clone)]
+    pub(super) struct H2Fallback {
+        downgraded: Arc<Mutex<HashMap<Authority, Instant>>>,
+        cooldown: Duration,
+    }
+
+    impl H2Fallback {
+        pub(super) fn new() -> Self {
+            Self::with_cooldown(COOLDOWN)
+        }
+
+        fn with_cooldown(cooldown: Duration) -> Self {
+            H2Fallback {
+                downgraded: Arc::new(Mutex::new(HashMap::new())),
+                cooldown,
+            }
+        }
+
+        /// Whether only HTTP/1.1 should be offered to `host` right now.
+        pub(super) fn is_downgraded(&self, host: &Authority) -> bool {
+            let mut downgraded = self.downgraded.lock();
+            match downgraded.get(host) {
+                Some(until) if Instant::now() < *until => true,
+                Some(_) => {
+                    downgraded.remove(host);
+                    false
+                }
+                None => false,
+            }
+        }
+
+        fn downgrade(&self, host: Authority) {
+            debug!(
+                "HTTP/2 to {} failed, offering only HTTP/1.1 for a while",
+                host
+            );
+            let now = Instant::now();
+            let mut downgraded = self.downgraded.lock();
+            // Don't let hosts that are never connected to again pile up.
+            downgraded.retain(|_, until| now < *until);
+            downgraded.insert(host, now + self.cooldown);
+        }
+    }
+
+    /// Downgrades the host of a connection once HTTP/2 on it fails with
+    /// a protocol error.
+    pub(super) struct ReportH2Errors {
+        inner: BoxConn,
+        fallback: H2Fallback,
+        host: Authority,
+    }
+
+    impl ReportH2Errors {
+        pub(super) fn new(inner: BoxConn, fallback: H2Fallback, host: Authority) -> Self {
+            ReportH2Errors {
+                inner,
+                fallback,
+                host,
+            }
+        }
+    }
+
+    impl Connection for ReportH2Errors {
+        fn connected(&self) -> Connected {
+            let fallback = self.fallback.clone();
+            let host = self.host.clone();
+            self.inner
+                .connected()
+                .on_h2_protocol_error(move || fallback.downgrade(host.clone()))
+        }
+    }
+
+    impl Read for ReportH2Errors {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: ReadBufCursor<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
+        }
+    }
+
+    impl Write for ReportH2Errors {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            Pin::new(&mut self.inner).poll_write(cx, buf)
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<Result<usize, io::Error>> {
+            Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.inner.is_write_vectored()
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+        ) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    impl TlsInfoFactory for ReportH2Errors {
+        fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+            self.inner.tls_info()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn downgrade_is_per_host() {
+            let fallback = H2Fallback::new();
+            let broken = Authority::from_static("broken.example:443");
+            let fine = Authority::from_static("fine.example:443");
+
+            fallback.downgrade(broken.clone());
+            assert!(fallback.is_downgraded(&broken));
+            assert!(!fallback.is_downgraded(&fine));
+        }
+
+        #[test]
+        fn downgrade_expires() {
+            let fallback = H2Fallback::with_cooldown(Duration::ZERO);
+            let host = Authority::from_static("broken.example:443");
+
+            fallback.downgrade(host.clone());
+            assert!(!fallback.is_downgraded(&host));
+            assert!(fallback.downgraded.lock().is_empty());
+        }
+    }
+}
+
+mod provided {
+    use std::{
+        io::{self, IoSlice},
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use super::TlsInfoFactory;
+    use crate::core::{
+        client::connect::{Connected, Connection},
+        rt::{Read, ReadBufCursor, Write},
+    };
+
+    /// A stream connected by the caller rather than by the connector.
+    ///
+    /// Nothing is known about its transport, so it reports no addresses.
+    #[derive(Debug)]
+    pub(super) struct Provided<S>(pub(super) S);
+
+    impl<S> Connection for Provided<S> {
+        fn connected(&self) -> Connected {
+            Connected::new()
+        }
+    }
+
+    impl<S> TlsInfoFactory for Provided<S> {
+        fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+            None
+        }
+    }
+
+    impl<S: Read + Unpin> Read for Provided<S> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: ReadBufCursor<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.0).poll_read(cx, buf)
+        }
+    }
+
+    impl<S: Write + Unpin> Write for Provided<S> {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            Pin::new(&mut self.0).poll_write(cx, buf)
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<Result<usize, io::Error>> {
+            Pin::new(&mut self.0).poll_write_vectored(cx, bufs)
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.0.is_write_vectored()
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.0).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+        ) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.0).poll_shutdown(cx)
+        }
+    }
+}
+
+mod reuse {
+    use std::{
+        io::{self, IoSlice},
+        pin::Pin,
+        sync::{Arc, Weak},
+        task::{Context, Poll, Waker},
+    };
+
+    use antidote::Mutex;
+
+    use super::{BoxConn, TlsInfoFactory};
+    use crate::core::{
+        client::connect::{Connected, Connection},
+        rt::{Read, ReadBuf, ReadBufCursor, Write},
+    };
+
+    /// Lets the pool check that the peer has not closed a connection before
+    /// handing it out again.
+    ///
+    /// The check attempts a one-byte read without waiting: a closed or
+    /// failed socket fails the check, while a byte that did arrive is kept
+    /// for the next read of the connection.
+    ///
+    /// The pool probes from outside the task driving the connection, so every
+    /// read and write goes through a lock. It is only contended while a probe
+    /// runs, when the connection sits idle in the pool.
+    pub(super) struct ProbeOnReuse(Arc<Mutex<Probed>>);
+
+    /// Lets the pool check an HTTP/2 connection with a PING before handing it
+    /// out again, passing its I/O straight through.
+    pub(super) struct PingOnReuse(BoxConn);
+
+    struct Probed {
+        conn: BoxConn,
+        stash: Option<u8>,
+        reader: Option<Waker>,
+    }
+
+    impl ProbeOnReuse {
+        pub(super) fn new(conn: BoxConn) -> Self {
+            ProbeOnReuse(Arc::new(Mutex::new(Probed {
+                conn,
+                stash: None,
+                reader: None,
+            })))
+        }
+    }
+
+    /// Returns whether the connection behind `probed` can still be used.
+    fn probe(probed: &Weak<Mutex<Probed>>) -> bool {
+        let Some(probed) = probed.upgrade() else {
+            return false;
+        };
+        let mut probed = probed.lock();
+        if probed.stash.is_some() {
+            return true;
+        }
+
+        let mut byte = [0u8; 1];
+        let mut buf = ReadBuf::new(&mut byte);
+        let mut cx = Context::from_waker(Waker::noop());
+        let alive = match Pin::new(&mut probed.conn).poll_read(&mut cx, buf.unfilled()) {
+            Poll::Pending => true,
+            Poll::Ready(Ok(())) => match buf.filled() {
+                [] => false,
+                [b] => {
+                    probed.stash = Some(*b);
+                    true
+                }
+                _ => unreachable!("read more than the buffer holds"),
+            },
+            Poll::Ready(Err(_)) => false,
+        };
+
+        // polling replaced the waker of a pending read, its task has to
+        // poll again to register anew
+        if let Some(reader) = probed.reader.take() {
+            reader.wake();
+        }
+        alive
+    }
+
+    impl Connection for ProbeOnReuse {
+        fn connected(&self) -> Connected {
+            let probed = Arc::downgrade(&self.0);
+            self.0
+                .lock()
+                .conn
+                .connected()
+                .check_on_reuse(move || probe(&probed))
+        }
+    }
+
+    impl Read for ProbeOnReuse {
+        fn poll_read(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            mut buf: ReadBufCursor<'_>,
+        ) -> Poll<io::Result<()>> {
+            let mut probed = self.0.lock();
+            if let Some(b) = probed.stash.take() {
+                buf.put_slice(&[b]);
+                return Poll::Ready(Ok(()));
+            }
+
+            let res = Pin::new(&mut probed.conn).poll_read(cx, buf);
+            if res.is_pending() {
+                probed.reader = Some(cx.waker().clone());
+            }
+            res
+        }
+    }
+
+    impl Write for ProbeOnReuse {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            Pin::new(&mut self.0.lock().conn).poll_write(cx, buf)
+        }
+
+        fn poll_write_vectored(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<Result<usize, io::Error>> {
+            Pin::new(&mut self.0.lock().conn).poll_write_vectored(cx, bufs)
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.0.lock().conn.is_write_vectored()
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.0.lock().conn).poll_flush(cx)
+        }
+
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.0.lock().conn).poll_shutdown(cx)
+        }
+    }
+
+    impl TlsInfoFactory for ProbeOnReuse {
+        fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+            self.0.lock().conn.tls_info()
+        }
+    }
+
+    impl PingOnReuse {
+        pub(super) fn new(conn: BoxConn) -> Self {
+            PingOnReuse(conn)
+        }
+    }
+
+    impl Connection for PingOnReuse {
+        fn connected(&self) -> Connected {
+            // The pool pings HTTP/2 connections itself, there is nothing to
+            // probe here.
+            self.0.connected().check_on_reuse(|| true)
+        }
+    }
+
+    impl Read for PingOnReuse {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: ReadBufCursor<'_>,
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.0).poll_read(cx, buf)
+        }
+    }
+
+    impl Write for PingOnReuse {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            Pin::new(&mut self.0).poll_write(cx, buf)
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<Result<usize, io::Error>> {
+            Pin::new(&mut self.0).poll_write_vectored(cx, bufs)
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.0.is_write_vectored()
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.0).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+        ) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.0).poll_shutdown(cx)
+        }
+    }
+
+    impl TlsInfoFactory for PingOnReuse {
+        fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+            self.0.tls_info()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::future::poll_fn;
+
+        use tokio::io::{AsyncWriteExt, duplex};
+
+        use super::*;
+        use crate::core::rt::TokioIo;
+
+        async fn read(conn: &mut ProbeOnReuse) -> Vec<u8> {
+            let mut buf = [0u8; 16];
+            let mut buf = ReadBuf::new(&mut buf);
+            poll_fn(|cx| Pin::new(&mut *conn).poll_read(cx, buf.unfilled()))
+                .await
+                .unwrap();
+            buf.filled().to_vec()
+        }
+
+        #[tokio::test]
+        async fn closed_connection_fails_the_check() {
+            let (client, server) = duplex(64);
+            let conn = ProbeOnReuse::new(Box::new(TokioIo::new(client)));
+            let connected = conn.connected();
+
+            assert!(connected.probe());
+            drop(server);
+            assert!(!connected.probe());
+        }
+
+        #[tokio::test]
+        async fn probed_byte_is_read_again() {
+            let (client, mut server) = duplex(64);
+            let mut conn = ProbeOnReuse::new(Box::new(TokioIo::new(client)));
+            let connected = conn.connected();
+
+            server.write_all(b"hi").await.unwrap();
+            assert!(connected.probe());
+            assert_eq!(read(&mut conn).await, b"h");
+            assert_eq!(read(&mut conn).await, b"i");
+        }
+
+        #[tokio::test]
+        async fn dropped_connection_fails_the_check() {
+            let (client, _server) = duplex(64);
+            let conn = ProbeOnReuse::new(Box::new(TokioIo::new(client)));
+            let connected = conn.connected();
+
+            drop(conn);
+            assert!(!connected.probe());
+        }
+    }
+}
+
+mod idle {
+    use std::{
+        io::{self, IoSlice},
+        pin::Pin,
+        task::{Context, Poll, ready},
+        time::Duration,
+    };
+
+    use tokio::time::{Instant, Sleep};
+
+    use super::TlsInfoFactory;
+    use crate::{
+        core::{
+            client::connect::{Connected, Connection},
+            rt::{Read, ReadBufCursor, Write},
+        },
+        error::TimedOut,
+    };
+
+    /// Fails an operation that has been pending for a set duration without
+    /// making progress.
+    ///
+    /// The timer starts when the operation first returns `Pending` and is
+    /// disarmed as soon as it completes.
+    struct Stall {
+        timeout: Duration,
+        sleep: Pin<Box<Sleep>>,
+        armed: bool,
+    }
+
+    impl Stall {
+        fn new(timeout: Duration) -> Self {
+            Stall {
+                timeout,
+                sleep: Box::pin(tokio::time::sleep(timeout)),
+                armed: false,
+            }
+        }
+
+        /// Passes on the result of the operation, or an error once it has
+        /// been pending for too long.
+        fn poll<T>(
+            &mut self,
+            cx: &mut Context<'_>,
+            res: Poll<io::Result<T>>,
+        ) -> Poll<io::Result<T>> {
+            if res.is_ready() {
+                self.armed = false;
+                return res;
+            }
+
+            if !self.armed {
+                self.armed = true;
+                let deadline = Instant::now() + self.timeout;
+                self.sleep.as_mut().reset(deadline);
+            }
+
+            ready!(self.sleep.as_mut().poll(cx));
+            trace!("no progress for {:?}, closing", self.timeout);
+            self.armed = false;
+            Poll::Ready(Err(io::Error::new(io::ErrorKind::TimedOut, TimedOut)))
+        }
+    }
+
+    /// Fails reads that receive no bytes, and writes that make no progress,
+    /// within their configured durations.
+    pub(super) struct IdleTimeout<T> {
+        inner: T,
+        read: Option<Stall>,
+        write: Option<Stall>,
+    }
+
+    impl<T> IdleTimeout<T> {
+        pub(super) fn new(inner: T, read: Option<Duration>, write: Option<Duration>) -> Self {
+            IdleTimeout {
+                inner,
+                read: read.map(Stall::new),
+                write: write.map(Stall::new),
+            }
+        }
+    }
+
+    impl<T: Connection> Connection for IdleTimeout<T> {
+        fn connected(&self) -> Connected {
+            self.inner.connected()
+        }
+    }
+
+    impl<T: Read + Unpin> Read for IdleTimeout<T> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: ReadBufCursor<'_>,
+        ) -> Poll<io::Result<()>> {
+            let this = &mut *self;
+            let res = Pin::new(&mut this.inner).poll_read(cx, buf);
+            match this.read {
+                Some(ref mut stall) => stall.poll(cx, res),
+                None => res,
+            }
+        }
+    }
+
+    impl<T: Write + Unpin> Write for IdleTimeout<T> {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            let this = &mut *self;
+            let res = Pin::new(&mut this.inner).poll_write(cx, buf);
+            match this.write {
+                Some(ref mut stall) => stall.poll(cx, res),
+                None => res,
+            }
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<Result<usize, io::Error>> {
+            let this = &mut *self;
+            let res = Pin::new(&mut this.inner).poll_write_vectored(cx, bufs);
+            match this.write {
+                Some(ref mut stall) => stall.poll(cx, res),
+                None => res,
+            }
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.inner.is_write_vectored()
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+            let this = &mut *self;
+            let res = Pin::new(&mut this.inner).poll_flush(cx);
+            match this.write {
+                Some(ref mut stall) => stall.poll(cx, res),
+                None => res,
+            }
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+        ) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    impl<T: TlsInfoFactory> TlsInfoFactory for IdleTimeout<T> {
+        fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+            self.inner.tls_info()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::future::poll_fn;
+
+        use tokio::io::{AsyncReadExt, AsyncWriteExt, duplex};
+
+        use super::*;
+        use crate::core::rt::{ReadBuf, TokioIo};
+
+        async fn read<T: Read + Unpin>(conn: &mut IdleTimeout<T>) -> io::Result<usize> {
+            let mut buf = [0u8; 16];
+            let mut buf = ReadBuf::new(&mut buf);
+            poll_fn(|cx| Pin::new(&mut *conn).poll_read(cx, buf.unfilled())).await?;
+            Ok(buf.filled().len())
+        }
+
+        async fn write<T: Write + Unpin>(
+            conn: &mut IdleTimeout<T>,
+            buf: &[u8],
+        ) -> io::Result<usize> {
+            poll_fn(|cx| Pin::new(&mut *conn).poll_write(cx, buf)).await
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn idle_read_times_out() {
+            let (client, mut server) = duplex(64);
+            let mut conn =
+                IdleTimeout::new(TokioIo::new(client), Some(Duration::from_secs(5)), None);
+
+            server.write_all(b"hello").await.unwrap();
+            assert_eq!(read(&mut conn).await.unwrap(), 5);
+
+            let err = read(&mut conn).await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+            assert!(err.get_ref().is_some_and(|e| e.is::<TimedOut>()));
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn bytes_reset_the_timer() {
+            let (client, mut server) = duplex(64);
+            let mut conn =
+                IdleTimeout::new(TokioIo::new(client), Some(Duration::from_secs(5)), None);
+
+            tokio::spawn(async move {
+                for _ in 0..3 {
+                    tokio::time::sleep(Duration::from_secs(3)).await;
+                    server.write_all(b"x").await.unwrap();
+                }
+                std::future::pending::<()>().await;
+            });
+
+            for _ in 0..3 {
+                assert_eq!(read(&mut conn).await.unwrap(), 1);
+            }
+            assert!(read(&mut conn).await.is_err());
+        }
+
+        #[tokio::test(start_paused = true)]
+        async fn stalled_write_times_out() {
+            let (client, mut server) = duplex(8);
+            let mut conn =
+                IdleTimeout::new(TokioIo::new(client), None, Some(Duration::from_secs(5)));
+
+            // fills the pipe, the peer never reads
+            assert_eq!(write(&mut conn, &[0; 8]).await.unwrap(), 8);
+
+            let err = write(&mut conn, b"more").await.unwrap_err();
+            assert_eq!(err.kind(), io::ErrorKind::TimedOut);
+
+            // once the peer drains the pipe, writes go through again
+            let mut drained = [0; 8];
+            server.read_exact(&mut drained).await.unwrap();
+            assert_eq!(write(&mut conn, b"more").await.unwrap(), 4);
+        }
+    }
+}
+
+mod observed {
+    use std::{
+        io::{self, IoSlice},
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use http::Extensions;
+
+    use super::TlsInfoFactory;
+    use crate::core::{
+        client::connect::{
+            CloseReason, ConnStats, Connected, Connection, HttpInfo,
+            event::{ConnEvent, EventSender},
+        },
+        rt::{Read, ReadBufCursor, Write},
+    };
+
+    /// Reports a connection as established when created, and as closed when
+    /// dropped along with why it closed.
+    pub(super) struct Observed<T> {
+        inner: T,
+        events: EventSender,
+        id: u64,
+        stats: ConnStats,
+    }
+
+    impl<T> Observed<T> {
+        pub(super) fn new(inner: T, events: EventSender, connected: &Connected) -> Self {
+            let mut extensions = Extensions::new();
+            connected.get_extras(&mut extensions);
+
+            let id = EventSender::next_id();
+            events.emit(ConnEvent::Established {
+                id,
+                remote_addr: extensions.get::<HttpInfo>().map(HttpInfo::remote_addr),
+                proxied: connected.is_proxied(),
+                http2: connected.is_negotiated_h2(),
+            });
+
+            Observed {
+                inner,
+                events,
+                id,
+                stats: ConnStats::new(),
+            }
+        }
+
+        fn record<R>(&mut self, res: Poll<io::Result<R>>) -> Poll<io::Result<R>> {
+            if let Poll::Ready(Err(ref err)) = res {
+                self.stats.close(CloseReason::IoError(err.kind()));
+            }
+            res
+        }
+    }
+
+    impl<T> Drop for Observed<T> {
+        fn drop(&mut self) {
+            self.events.emit(ConnEvent::Closed {
+                id: self.id,
+                requests: self.stats.request_count(),
+                lifetime: self.stats.established_at().elapsed(),
+                reason: self.stats.close_reason().unwrap_or(CloseReason::Other),
+            });
+        }
+    }
+
+    impl<T: Connection> Connection for Observed<T> {
+        fn connected(&self) -> Connected {
+            let events = self.events.clone();
+            let id = self.id;
+            self.inner
+                .connected()
+                .stats(self.stats.clone())
+                .on_h2_drain(move || events.emit(ConnEvent::Draining { id }))
+        }
+    }
+
+    impl<T: Read + Unpin> Read for Observed<T> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: ReadBufCursor<'_>,
+        ) -> Poll<io::Result<()>> {
+            let res = Pin::new(&mut self.inner).poll_read(cx, buf);
+            self.record(res)
+        }
+    }
+
+    impl<T: Write + Unpin> Write for Observed<T> {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            let res = Pin::new(&mut self.inner).poll_write(cx, buf);
+            self.record(res)
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<Result<usize, io::Error>> {
+            let res = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+            self.record(res)
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.inner.is_write_vectored()
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+            let res = Pin::new(&mut self.inner).poll_flush(cx);
+            self.record(res)
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+        ) -> Poll<Result<(), io::Error>> {
+            let res = Pin::new(&mut self.inner).poll_shutdown(cx);
+            self.record(res)
+        }
+    }
+
+    impl<T: TlsInfoFactory> TlsInfoFactory for Observed<T> {
+        fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+            self.inner.tls_info()
+        }
+    }
+}
+
+mod metered {
+    use std::{
+        io::{self, IoSlice},
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use super::TlsInfoFactory;
+    use crate::core::{
+        client::connect::{ByteCounts, Connected, Connection},
+        rt::{Read, ReadBuf, ReadBufCursor, Write},
+    };
+
+    /// Counts the bytes read from and written to a connection into a
+    /// [`ByteCounts`], which its `Connected` carries as an extra.
+    pub(super) struct Metered<T> {
+        inner: T,
+        counts: ByteCounts,
+    }
+
+    impl<T> Metered<T> {
+        pub(super) fn new(inner: T) -> Self {
+            Metered {
+                inner,
+                counts: ByteCounts::default(),
+            }
+        }
+    }
+
+    impl<T: Connection> Connection for Metered<T> {
+        fn connected(&self) -> Connected {
+            self.inner.connected().extra(self.counts.clone())
+        }
+    }
+
+    impl<T: Read + Unpin> Read for Metered<T> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            mut buf: ReadBufCursor<'_>,
+        ) -> Poll<io::Result<()>> {
+            let init = buf.init_remaining();
+            // SAFETY: Passing to a ReadBuf will never de-initialize any bytes.
+            let mut mbuf = ReadBuf::uninit(unsafe { buf.as_mut() });
+            // SAFETY: These bytes are initialized in the outer buffer already.
+            unsafe { mbuf.set_init(init) };
+            let res = Pin::new(&mut self.inner).poll_read(cx, mbuf.unfilled());
+            if let Poll::Ready(Ok(())) = res {
+                let len = mbuf.filled().len();
+                self.counts.add_read(len);
+                // SAFETY: The two cursors were for the same buffer. What was
+                // filled in one is safe in the other.
+                unsafe { buf.advance(len) };
+            }
+            res
+        }
+    }
+
+    impl<T: Write + Unpin> Write for Metered<T> {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            let res = Pin::new(&mut self.inner).poll_write(cx, buf);
+            if let Poll::Ready(Ok(n)) = res {
+                self.counts.add_written(n);
+            }
+            res
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<Result<usize, io::Error>> {
+            let res = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+            if let Poll::Ready(Ok(n)) = res {
+                self.counts.add_written(n);
+            }
+            res
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.inner.is_write_vectored()
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+        ) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    impl<T: TlsInfoFactory> TlsInfoFactory for Metered<T> {
+        fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+            self.inner.tls_info()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::{
+            io::{self, IoSlice},
+            pin::Pin,
+            task::{Context, Poll, Waker},
+        };
+
+        use super::Metered;
+        use crate::core::rt::{Read, ReadBuf, ReadBufCursor, Write};
+
+        /// Reads `data` in pieces of at most `max` bytes, and takes at most
+        /// `max` bytes of each write.
+        struct Io {
+            data: &'static [u8],
+            max: usize,
+        }
+
+        impl Read for Io {
+            fn poll_read(
+                mut self: Pin<&mut Self>,
+                _cx: &mut Context,
+                mut buf: ReadBufCursor<'_>,
+            ) -> Poll<io::Result<()>> {
+                let n = self.max.min(self.data.len()).min(buf.remaining());
+                buf.put_slice(&self.data[..n]);
+                self.data = &self.data[n..];
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        impl Write for Io {
+            fn poll_write(
+                self: Pin<&mut Self>,
+                _cx: &mut Context,
+                buf: &[u8],
+            ) -> Poll<Result<usize, io::Error>> {
+                Poll::Ready(Ok(self.max.min(buf.len())))
+            }
+
+            fn poll_write_vectored(
+                self: Pin<&mut Self>,
+                _cx: &mut Context<'_>,
+                bufs: &[IoSlice<'_>],
+            ) -> Poll<Result<usize, io::Error>> {
+                let len = bufs.iter().map(|buf| buf.len()).sum::<usize>();
+                Poll::Ready(Ok(self.max.min(len)))
+            }
+
+            fn poll_flush(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+
+            fn poll_shutdown(self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        #[test]
+        fn counts_what_went_through() {
+            let mut cx = Context::from_waker(Waker::noop());
+            let mut io = Metered::new(Io {
+                data: b"HTTP/1.1 200 OK\r\n",
+                max: 8,
+            });
+
+            let mut raw = [0; 64];
+            let mut buf = ReadBuf::new(&mut raw);
+            for _ in 0..3 {
+                assert!(
+                    Pin::new(&mut io)
+                        .poll_read(&mut cx, buf.unfilled())
+                        .is_ready()
+                );
+            }
+            assert_eq!(buf.filled(), b"HTTP/1.1 200 OK\r\n");
+            assert_eq!(io.counts.read(), 17);
+
+            // Only what the connection took counts, vectored or not.
+            let _ = Pin::new(&mut io).poll_write(&mut cx, b"GET / HTTP/1.1\r\n");
+            let bufs = [IoSlice::new(b"abc"), IoSlice::new(b"defgh")];
+            let _ = Pin::new(&mut io).poll_write_vectored(&mut cx, &bufs);
+            let _ = Pin::new(&mut io).poll_write_vectored(&mut cx, &bufs[..1]);
+            assert_eq!(io.counts.written(), 8 + 8 + 3);
+        }
+    }
+}
+
+mod inspected {
+    use std::{
+        io::{self, IoSlice},
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use super::{IoInspector, TlsInfoFactory};
+    use crate::core::{
+        client::connect::{Connected, Connection},
+        rt::{Read, ReadBuf, ReadBufCursor, Write},
+    };
+
+    /// Which way bytes went through a connection, as seen by
+    /// [`ClientBuilder::inspect_io`].
+    ///
+    /// [`ClientBuilder::inspect_io`]: crate::ClientBuilder::inspect_io
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+    pub enum IoDirection {
+        /// The bytes were read from the connection.
+        Read,
+        /// The bytes were written to the connection.
+        Write,
+    }
+
+    /// Hands every chunk read from and written to a connection to an
+    /// inspector.
+    pub(super) struct Inspected<T> {
+        inner: T,
+        inspector: IoInspector,
+    }
+
+    impl<T> Inspected<T> {
+        pub(super) fn new(inner: T, inspector: IoInspector) -> Self {
+            Inspected { inner, inspector }
+        }
+    }
+
+    impl<T: Connection> Connection for Inspected<T> {
+        fn connected(&self) -> Connected {
+            self.inner.connected()
+        }
+    }
+
+    impl<T: Read + Unpin> Read for Inspected<T> {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            mut buf: ReadBufCursor<'_>,
+        ) -> Poll<io::Result<()>> {
+            let init = buf.init_remaining();
+            // SAFETY: Passing to a ReadBuf will never de-initialize any bytes.
+            let mut ibuf = ReadBuf::uninit(unsafe { buf.as_mut() });
+            // SAFETY: These bytes are initialized in the outer buffer already.
+            unsafe { ibuf.set_init(init) };
+            let res = Pin::new(&mut self.inner).poll_read(cx, ibuf.unfilled());
+            if let Poll::Ready(Ok(())) = res {
+                let len = ibuf.filled().len();
+                if len > 0 {
+                    (self.inspector)(IoDirection::Read, ibuf.filled());
+                }
+                // SAFETY: The two cursors were for the same buffer. What was
+                // filled in one is safe in the other.
+                unsafe { buf.advance(len) };
+            }
+            res
+        }
+    }
+
+    impl<T: Write + Unpin> Write for Inspected<T> {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            let res = Pin::new(&mut self.inner).poll_write(cx, buf);
+            if let Poll::Ready(Ok(n)) = res {
+                if n > 0 {
+                    (self.inspector)(IoDirection::Write, &buf[..n]);
+                }
+            }
+            res
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<Result<usize, io::Error>> {
+            let res = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+            if let Poll::Ready(Ok(n)) = res {
+                // Only the start of the slices was written.
+                let mut left = n;
+                for buf in bufs {
+                    if left == 0 {
+                        break;
+                    }
+                    let len = buf.len().min(left);
+                    if len > 0 {
+                        (self.inspector)(IoDirection::Write, &buf[..len]);
+                    }
+                    left -= len;
+                }
+            }
+            res
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.inner.is_write_vectored()
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+        ) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    impl<T: TlsInfoFactory> TlsInfoFactory for Inspected<T> {
+        fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+            self.inner.tls_info()
+        }
+    }
+}
+
+mod prior_knowledge {
+    use std::{
+        io::{self, IoSlice},
+        pin::Pin,
+        task::{Context, Poll},
+    };
+
+    use super::{BoxConn, TlsInfoFactory};
+    use crate::core::{
+        client::connect::{Connected, Connection},
+        rt::{Read, ReadBuf, ReadBufCursor, Write},
+    };
+
+    /// What an HTTP/1.x server sends first, where an HTTP/2 server sends SETTINGS.
+    const HTTP1_STATUS_LINE: &[u8] = b"HTTP/1.";
+
+    /// A cleartext connection that speaks HTTP/2 from the first byte, without
+    /// ALPN or an `Upgrade: h2c` round trip.
+    ///
+    /// The start of the server's reply is checked, so that an HTTP/1.x server
+    /// fails the connection with a clear error rather than an h2 framing error.
+    pub(super) struct PriorKnowledgeH2 {
+        inner: BoxConn,
+        /// How many bytes of `HTTP1_STATUS_LINE` the server has sent so far,
+        /// or `None` once the reply can no longer be HTTP/1.x.
+        matched: Option<usize>,
+    }
+
+    impl PriorKnowledgeH2 {
+        pub(super) fn new(inner: BoxConn) -> Self {
+            PriorKnowledgeH2 {
+                inner,
+                matched: Some(0),
+            }
+        }
+    }
+
+    impl Connection for PriorKnowledgeH2 {
+        fn connected(&self) -> Connected {
+            self.inner.connected().negotiated_h2()
+        }
+    }
+
+    impl Read for PriorKnowledgeH2 {
+        fn poll_read(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            mut buf: ReadBufCursor<'_>,
+        ) -> Poll<io::Result<()>> {
+            let Some(matched) = self.matched else {
+                return Pin::new(&mut self.inner).poll_read(cx, buf);
+            };
+
+            let init = buf.init_remaining();
+            // SAFETY: Passing to a ReadBuf will never de-initialize any bytes.
+            let mut sbuf = ReadBuf::uninit(unsafe { buf.as_mut() });
+            // SAFETY: These bytes are initialized in the outer buffer already.
+            unsafe { sbuf.set_init(init) };
+            match Pin::new(&mut self.inner).poll_read(cx, sbuf.unfilled()) {
+                Poll::Ready(Ok(())) => {
+                    let filled = sbuf.filled();
+                    let expected = &HTTP1_STATUS_LINE[matched..];
+                    let n = filled.len().min(expected.len());
+                    if filled[..n] != expected[..n] {
+                        self.matched = None;
+                    } else if n == expected.len() {
+                        return Poll::Ready(Err(io::Error::new(
+                            io::ErrorKind::InvalidData,
+                            "server responded with HTTP/1.x to an HTTP/2 prior knowledge connection",
+                        )));
+                    } else {
+                        self.matched = Some(matched + n);
+                    }
+
+                    let len = filled.len();
+                    // SAFETY: The two cursors were for the same buffer. What was
+                    // filled in one is safe in the other.
+                    unsafe {
+                        buf.advance(len);
+                    }
+                    Poll::Ready(Ok(()))
+                }
+                Poll::Ready(Err(e)) => Poll::Ready(Err(e)),
+                Poll::Pending => Poll::Pending,
+            }
+        }
+    }
+
+    impl Write for PriorKnowledgeH2 {
+        fn poll_write(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+            buf: &[u8],
+        ) -> Poll<Result<usize, io::Error>> {
+            Pin::new(&mut self.inner).poll_write(cx, buf)
+        }
+
+        fn poll_write_vectored(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[IoSlice<'_>],
+        ) -> Poll<Result<usize, io::Error>> {
+            Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.inner.is_write_vectored()
+        }
+
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
+        }
+
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+        ) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
+        }
+    }
+
+    impl TlsInfoFactory for PriorKnowledgeH2 {
+        fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+            self.inner.tls_info()
+        }
+    }
+}
+
+mod buffered {
+    use std::{
+        io::{self, IoSlice},
+        pin::Pin,
+        task::{Context, Poll, ready},
+    };
+
+    use super::TlsInfoFactory;
+    use crate::core::{
+        client::connect::{Connected, Connection},
+        rt::{Read, ReadBuf, ReadBufCursor, Write},
+    };
 
-    #[derive(Debug)]
-    pub struct Unnameable(pub(super) Dst);
+    /// Reads from a TLS connection in chunks of at least a configured size.
+    ///
+    /// A TLS stream hands out no more than one record per read, so a caller
+    /// with a large buffer would otherwise see many small reads. Each read
+    /// here keeps going until the caller's buffer is full or the stream has
+    /// nothing ready. Callers with smaller buffers are served from an
+    /// internal buffer of the configured size, filled the same way.
+    pub(super) struct BufferedRead<T> {
+        inner: T,
+        buf: Box<[u8]>,
+        pos: usize,
+        end: usize,
+        /// An error hit after some bytes were already read, returned once
+        /// those bytes have been handed out.
+        err: Option<io::Error>,
+    }
 
-    pin_project! {
-        /// Note: the `is_proxy` member means *is plain text HTTP proxy*.
-        /// This tells hyper whether the URI should be written in
-        /// * origin-form (`GET /just/a/path HTTP/1.1`), when `is_proxy == false`, or
-        /// * absolute-form (`GET http://foo.bar/and/a/path HTTP/1.1`), otherwise.
-        pub struct Conn {
-            #[pin]
-            pub(super) inner: BoxConn,
-            pub(super) is_proxy: bool,
-            // Only needed for __tls, but #[cfg()] on fields breaks pin_project!
-            pub(super) tls_info: bool,
+    impl<T> BufferedRead<T> {
+        pub(super) fn new(inner: T, size: usize) -> Self {
+            BufferedRead {
+                inner,
+                buf: vec![0; size].into_boxed_slice(),
+                pos: 0,
+                end: 0,
+                err: None,
+            }
         }
     }
 
-    impl Connection for Conn {
-        fn connected(&self) -> Connected {
-            let connected = self.inner.connected().proxy(self.is_proxy);
-
-            if self.tls_info {
-                if let Some(tls_info) = self.inner.tls_info() {
-                    connected.extra(tls_info)
-                } else {
-                    connected
+    /// Reads into `buf` until it is full, the stream ends, or nothing more is
+    /// ready. Errors after a partial read are left in `err`.
+    fn fill<T: Read + Unpin>(
+        inner: &mut T,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+        err: &mut Option<io::Error>,
+    ) -> Poll<io::Result<()>> {
+        loop {
+            let before = buf.len();
+            let res = Pin::new(&mut *inner).poll_read(cx, buf.unfilled());
+            let progress = buf.len() > before;
+            match res {
+                Poll::Ready(Ok(())) if progress && buf.len() < buf.capacity() => continue,
+                Poll::Ready(Ok(())) => return Poll::Ready(Ok(())),
+                Poll::Ready(Err(e)) if buf.len() > 0 => {
+                    *err = Some(e);
+                    return Poll::Ready(Ok(()));
                 }
-            } else {
-                connected
+                Poll::Ready(Err(e)) => return Poll::Ready(Err(e)),
+                Poll::Pending if buf.len() > 0 => return Poll::Ready(Ok(())),
+                Poll::Pending => return Poll::Pending,
             }
         }
     }
 
-    impl Read for Conn {
+    impl<T: Read + Unpin> Read for BufferedRead<T> {
         fn poll_read(
-            self: Pin<&mut Self>,
+            mut self: Pin<&mut Self>,
             cx: &mut Context,
-            buf: ReadBufCursor<'_>,
+            mut buf: ReadBufCursor<'_>,
         ) -> Poll<io::Result<()>> {
-            let this = self.project();
-            Read::poll_read(this.inner, cx, buf)
+            let this = &mut *self;
+
+            if this.pos == this.end {
+                if let Some(e) = this.err.take() {
+                    return Poll::Ready(Err(e));
+                }
+
+                if buf.remaining() >= this.buf.len() {
+                    let init = buf.init_remaining();
+                    // SAFETY: Passing to a ReadBuf will never de-initialize any bytes.
+                    let mut rbuf = ReadBuf::uninit(unsafe { buf.as_mut() });
+                    // SAFETY: These bytes are initialized in the outer buffer already.
+                    unsafe { rbuf.set_init(init) };
+                    ready!(fill(&mut this.inner, cx, &mut rbuf, &mut this.err))?;
+                    let len = rbuf.len();
+                    // SAFETY: The two cursors were for the same buffer. What was
+                    // filled in one is safe in the other.
+                    unsafe {
+                        buf.advance(len);
+                    }
+                    return Poll::Ready(Ok(()));
+                }
+
+                let mut rbuf = ReadBuf::new(&mut this.buf);
+                ready!(fill(&mut this.inner, cx, &mut rbuf, &mut this.err))?;
+                this.pos = 0;
+                this.end = rbuf.len();
+            }
+
+            let n = buf.remaining().min(this.end - this.pos);
+            buf.put_slice(&this.buf[this.pos..this.pos + n]);
+            this.pos += n;
+            Poll::Ready(Ok(()))
         }
     }
 
-    impl Write for Conn {
+    impl<T: Write + Unpin> Write for BufferedRead<T> {
         fn poll_write(
-            self: Pin<&mut Self>,
+            mut self: Pin<&mut Self>,
             cx: &mut Context,
             buf: &[u8],
         ) -> Poll<Result<usize, io::Error>> {
-            let this = self.project();
-            Write::poll_write(this.inner, cx, buf)
+            Pin::new(&mut self.inner).poll_write(cx, buf)
         }
 
         fn poll_write_vectored(
-            self: Pin<&mut Self>,
+            mut self: Pin<&mut Self>,
             cx: &mut Context<'_>,
             bufs: &[IoSlice<'_>],
         ) -> Poll<Result<usize, io::Error>> {
-            let this = self.project();
-            Write::poll_write_vectored(this.inner, cx, bufs)
+            Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
         }
 
         fn is_write_vectored(&self) -> bool {
             self.inner.is_write_vectored()
         }
 
-        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
-            let this = self.project();
-            Write::poll_flush(this.inner, cx)
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.inner).poll_flush(cx)
         }
 
-        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
-            let this = self.project();
-            Write::poll_shutdown(this.inner, cx)
+        fn poll_shutdown(
+            mut self: Pin<&mut Self>,
+            cx: &mut Context,
+        ) -> Poll<Result<(), io::Error>> {
+            Pin::new(&mut self.inner).poll_shutdown(cx)
         }
     }
-}
 
-pub(crate) type Connecting = Pin<Box<dyn Future<Output = Result<Conn, BoxError>> + Send>>;
+    impl<T: Connection> Connection for BufferedRead<T> {
+        fn connected(&self) -> Connected {
+            self.inner.connected()
+        }
+    }
 
-mod tls_conn {
+    impl<T: TlsInfoFactory> TlsInfoFactory for BufferedRead<T> {
+        fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
+            self.inner.tls_info()
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use std::{
+            io,
+            pin::Pin,
+            task::{Context, Poll, Waker},
+        };
+
+        use super::BufferedRead;
+        use crate::core::rt::{Read, ReadBuf, ReadBufCursor};
+
+        /// Hands out `data` a few bytes at a time, like TLS records.
+        struct Records {
+            data: Vec<u8>,
+            pos: usize,
+            record: usize,
+            polls: usize,
+        }
+
+        impl Read for Records {
+            fn poll_read(
+                mut self: Pin<&mut Self>,
+                _cx: &mut Context,
+                mut buf: ReadBufCursor<'_>,
+            ) -> Poll<io::Result<()>> {
+                self.polls += 1;
+                let n = self
+                    .record
+                    .min(buf.remaining())
+                    .min(self.data.len() - self.pos);
+                buf.put_slice(&self.data[self.pos..self.pos + n]);
+                self.pos += n;
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        /// Reads `io` to the end with `chunk` sized reads, returning the bytes
+        /// and how many reads it took.
+        fn read_to_end<T: Read + Unpin>(mut io: T, chunk: usize) -> (Vec<u8>, usize) {
+            let mut cx = Context::from_waker(Waker::noop());
+            let mut out = Vec::new();
+            let mut reads = 0;
+            loop {
+                let mut raw = vec![0; chunk];
+                let mut buf = ReadBuf::new(&mut raw);
+                match Pin::new(&mut io).poll_read(&mut cx, buf.unfilled()) {
+                    Poll::Ready(Ok(())) => {}
+                    other => panic!("unexpected read result: {other:?}"),
+                }
+                reads += 1;
+                if buf.filled().is_empty() {
+                    return (out, reads);
+                }
+                out.extend_from_slice(buf.filled());
+            }
+        }
+
+        fn records(len: usize) -> Records {
+            Records {
+                data: (0..len).map(|i| i as u8).collect(),
+                pos: 0,
+                record: 16,
+                polls: 0,
+            }
+        }
+
+        #[test]
+        fn large_reads_take_many_records() {
+            let (out, reads) = read_to_end(BufferedRead::new(records(4096), 1024), 1024);
+            assert_eq!(out, records(4096).data);
+            assert_eq!(reads, 4 + 1);
+
+            let (_, reads) = read_to_end(records(4096), 1024);
+            assert_eq!(reads, 256 + 1);
+        }
+
+        #[test]
+        fn small_reads_are_served_from_the_buffer() {
+            let mut io = BufferedRead::new(records(4096), 1024);
+            let (out, _) = read_to_end(&mut io, 100);
+            assert_eq!(out, records(4096).data);
+            // 4 full buffers, and one poll that sees the end of the stream.
+            assert_eq!(io.inner.polls, 4 * 64 + 1);
+        }
+    }
+}
+
+mod coalesce {
     use std::{
         io::{self, IoSlice},
         pin::Pin,
-        task::{Context, Poll},
-    };
-
-    use pin_project_lite::pin_project;
-    use tokio::{
-        io::{AsyncRead, AsyncWrite},
-        net::TcpStream,
+        task::{Context, Poll, ready},
     };
-    use tokio_boring2::SslStream;
 
     use super::TlsInfoFactory;
-    use crate::{
-        core::{
-            client::connect::{Connected, Connection},
-            rt::{Read, ReadBufCursor, TokioIo, Write},
-        },
-        tls::MaybeHttpsStream,
+    use crate::core::{
+        client::connect::{Connected, Connection},
+        rt::{Read, ReadBufCursor, Write},
     };
 
-    pin_project! {
-        pub(super) struct BoringTlsConn<T> {
-            #[pin] pub(super) inner: TokioIo<SslStream<T>>,
-        }
+    /// Gathers small writes into a buffer of a configured size, which is
+    /// written to the connection once full, or when flushed or shut down.
+    ///
+    /// HTTP/1.1 may write a message in several small pieces, each of which
+    /// would otherwise be its own syscall, and its own packet with Nagle's
+    /// algorithm off. Writes that don't fit in the buffer go straight through,
+    /// after what is already buffered.
+    pub(super) struct WriteCoalesce<T> {
+        inner: T,
+        buf: Vec<u8>,
+        size: usize,
     }
 
-    impl Connection for BoringTlsConn<TokioIo<TokioIo<TcpStream>>> {
-        fn connected(&self) -> Connected {
-            let connected = self.inner.inner().get_ref().connected();
-            if self.inner.inner().ssl().selected_alpn_protocol() == Some(b"h2") {
-                connected.negotiated_h2()
-            } else {
-                connected
+    impl<T> WriteCoalesce<T> {
+        pub(super) fn new(inner: T, size: usize) -> Self {
+            WriteCoalesce {
+                inner,
+                buf: Vec::with_capacity(size),
+                size,
             }
         }
     }
 
-    impl Connection for BoringTlsConn<TokioIo<MaybeHttpsStream<TokioIo<TcpStream>>>> {
-        fn connected(&self) -> Connected {
-            let connected = self.inner.inner().get_ref().connected();
-            if self.inner.inner().ssl().selected_alpn_protocol() == Some(b"h2") {
-                connected.negotiated_h2()
-            } else {
-                connected
-            }
+    impl<T: Write + Unpin> WriteCoalesce<T> {
+        /// Writes out everything buffered.
+        fn poll_write_buf(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let mut written = 0;
+            let res = loop {
+                if written == self.buf.len() {
+                    break Poll::Ready(Ok(()));
+                }
+                match Pin::new(&mut self.inner).poll_write(cx, &self.buf[written..]) {
+                    Poll::Ready(Ok(0)) => {
+                        break Poll::Ready(Err(io::ErrorKind::WriteZero.into()));
+                    }
+                    Poll::Ready(Ok(n)) => written += n,
+                    Poll::Ready(Err(e)) => break Poll::Ready(Err(e)),
+                    Poll::Pending => break Poll::Pending,
+                }
+            };
+            self.buf.drain(..written);
+            res
         }
     }
 
-    impl<T: AsyncRead + AsyncWrite + Unpin> Read for BoringTlsConn<T> {
+    impl<T: Read + Unpin> Read for WriteCoalesce<T> {
         fn poll_read(
-            self: Pin<&mut Self>,
+            mut self: Pin<&mut Self>,
             cx: &mut Context,
             buf: ReadBufCursor<'_>,
-        ) -> Poll<tokio::io::Result<()>> {
-            let this = self.project();
-            Read::poll_read(this.inner, cx, buf)
+        ) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.inner).poll_read(cx, buf)
         }
     }
 
-    impl<T: AsyncRead + AsyncWrite + Unpin> Write for BoringTlsConn<T> {
+    impl<T: Write + Unpin> Write for WriteCoalesce<T> {
         fn poll_write(
-            self: Pin<&mut Self>,
+            mut self: Pin<&mut Self>,
             cx: &mut Context,
             buf: &[u8],
-        ) -> Poll<Result<usize, tokio::io::Error>> {
-            let this = self.project();
-            Write::poll_write(this.inner, cx, buf)
+        ) -> Poll<Result<usize, io::Error>> {
+            let this = &mut *self;
+            if this.buf.len() + buf.len() > this.size {
+                ready!(this.poll_write_buf(cx))?;
+            }
+            if buf.len() >= this.size {
+                return Pin::new(&mut this.inner).poll_write(cx, buf);
+            }
+            this.buf.extend_from_slice(buf);
+            Poll::Ready(Ok(buf.len()))
         }
 
         fn poll_write_vectored(
-            self: Pin<&mut Self>,
+            mut self: Pin<&mut Self>,
             cx: &mut Context<'_>,
             bufs: &[IoSlice<'_>],
         ) -> Poll<Result<usize, io::Error>> {
-            let this = self.project();
-            Write::poll_write_vectored(this.inner, cx, bufs)
+            let this = &mut *self;
+            let len = bufs.iter().map(|b| b.len()).sum::<usize>();
+            if this.buf.len() + len > this.size {
+                ready!(this.poll_write_buf(cx))?;
+            }
+            if len >= this.size {
+                return Pin::new(&mut this.inner).poll_write_vectored(cx, bufs);
+            }
+            for buf in bufs {
+                this.buf.extend_from_slice(buf);
+            }
+            Poll::Ready(Ok(len))
         }
 
         fn is_write_vectored(&self) -> bool {
-            self.inner.is_write_vectored()
+            true
         }
 
-        fn poll_flush(
-            self: Pin<&mut Self>,
-            cx: &mut Context,
-        ) -> Poll<Result<(), tokio::io::Error>> {
-            let this = self.project();
-            Write::poll_flush(this.inner, cx)
+        fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context) -> Poll<Result<(), io::Error>> {
+            ready!(self.poll_write_buf(cx))?;
+            Pin::new(&mut self.inner).poll_flush(cx)
         }
 
         fn poll_shutdown(
-            self: Pin<&mut Self>,
+            mut self: Pin<&mut Self>,
             cx: &mut Context,
-        ) -> Poll<Result<(), tokio::io::Error>> {
-            let this = self.project();
-            Write::poll_shutdown(this.inner, cx)
+        ) -> Poll<Result<(), io::Error>> {
+            ready!(self.poll_write_buf(cx))?;
+            Pin::new(&mut self.inner).poll_shutdown(cx)
         }
     }
 
-    impl<T> TlsInfoFactory for BoringTlsConn<T>
-    where
-        TokioIo<SslStream<T>>: TlsInfoFactory,
-    {
+    impl<T: Connection> Connection for WriteCoalesce<T> {
+        fn connected(&self) -> Connected {
+            self.inner.connected()
+        }
+    }
+
+    impl<T: TlsInfoFactory> TlsInfoFactory for WriteCoalesce<T> {
         fn tls_info(&self) -> Option<crate::tls::TlsInfo> {
             self.inner.tls_info()
         }
     }
+
+    #[cfg(test)]
+    mod tests {
+        use std::{
+            io::{self, IoSlice},
+            pin::Pin,
+            task::{Context, Poll, Waker},
+        };
+
+        use super::WriteCoalesce;
+        use crate::core::rt::Write;
+
+        /// Records every write it takes, accepting at most `max` bytes each.
+        #[derive(Default)]
+        struct Writes {
+            writes: Vec<Vec<u8>>,
+            max: Option<usize>,
+            flushed: bool,
+            shutdown: bool,
+        }
+
+        impl Write for Writes {
+            fn poll_write(
+                mut self: Pin<&mut Self>,
+                _cx: &mut Context,
+                buf: &[u8],
+            ) -> Poll<Result<usize, io::Error>> {
+                let n = self.max.unwrap_or(buf.len()).min(buf.len());
+                self.writes.push(buf[..n].to_vec());
+                Poll::Ready(Ok(n))
+            }
+
+            fn poll_flush(mut self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+                self.flushed = true;
+                Poll::Ready(Ok(()))
+            }
+
+            fn poll_shutdown(mut self: Pin<&mut Self>, _cx: &mut Context) -> Poll<io::Result<()>> {
+                self.shutdown = true;
+                Poll::Ready(Ok(()))
+            }
+        }
+
+        fn write(io: &mut WriteCoalesce<Writes>, buf: &[u8]) -> usize {
+            let mut cx = Context::from_waker(Waker::noop());
+            match Pin::new(io).poll_write(&mut cx, buf) {
+                Poll::Ready(Ok(n)) => n,
+                other => panic!("unexpected write result: {other:?}"),
+            }
+        }
+
+        #[test]
+        fn small_writes_are_written_on_flush() {
+            let mut cx = Context::from_waker(Waker::noop());
+            let mut io = WriteCoalesce::new(Writes::default(), 64);
+            assert_eq!(write(&mut io, b"GET / HTTP/1.1\r\n"), 16);
+            assert_eq!(write(&mut io, b"host: a\r\n"), 9);
+            assert_eq!(write(&mut io, b"\r\n"), 2);
+            assert!(io.inner.writes.is_empty());
+
+            assert!(Pin::new(&mut io).poll_flush(&mut cx).is_ready());
+            assert_eq!(
+                io.inner.writes,
+                [b"GET / HTTP/1.1\r\nhost: a\r\n\r\n".to_vec()]
+            );
+            assert!(io.inner.flushed);
+        }
+
+        #[test]
+        fn writes_that_overflow_go_after_the_buffer() {
+            let mut io = WriteCoalesce::new(Writes::default(), 8);
+            write(&mut io, b"abc");
+            write(&mut io, b"defgh");
+            assert!(io.inner.writes.is_empty());
+
+            // A full buffer is written before taking more.
+            write(&mut io, b"ij");
+            assert_eq!(io.inner.writes, [b"abcdefgh".to_vec()]);
+
+            // Large writes go straight through, once the buffer is out.
+            assert_eq!(write(&mut io, &[0; 16]), 16);
+            assert_eq!(io.inner.writes[1..], [b"ij".to_vec(), vec![0; 16]]);
+        }
+
+        #[test]
+        fn vectored_writes_are_gathered() {
+            let mut cx = Context::from_waker(Waker::noop());
+            let mut io = WriteCoalesce::new(Writes::default(), 64);
+            let bufs = [IoSlice::new(b"head"), IoSlice::new(b"body")];
+            match Pin::new(&mut io).poll_write_vectored(&mut cx, &bufs) {
+                Poll::Ready(Ok(8)) => {}
+                other => panic!("unexpected write result: {other:?}"),
+            }
+            assert!(Pin::new(&mut io).poll_shutdown(&mut cx).is_ready());
+            assert_eq!(io.inner.writes, [b"headbody".to_vec()]);
+            assert!(io.inner.shutdown);
+        }
+
+        #[test]
+        fn partial_writes_of_the_buffer_are_continued() {
+            let mut cx = Context::from_waker(Waker::noop());
+            let mut io = WriteCoalesce::new(
+                Writes {
+                    max: Some(3),
+                    ..Writes::default()
+                },
+                64,
+            );
+            write(&mut io, b"abcdefg");
+            assert!(Pin::new(&mut io).poll_flush(&mut cx).is_ready());
+            assert_eq!(
+                io.inner.writes,
+                [b"abc".to_vec(), b"def".to_vec(), b"g".to_vec()]
+            );
+        }
+    }
 }
 
 mod verbose {
@@ -898,10 +4375,11 @@ mod verbose {
                 cx: &mut Context,
                 mut buf: ReadBufCursor<'_>,
             ) -> Poll<std::io::Result<()>> {
-                // TODO: This _does_ forget the `init` len, so it could result in
-                // re-initializing twice. Needs upstream support, perhaps.
+                let init = buf.init_remaining();
                 // SAFETY: Passing to a ReadBuf will never de-initialize any bytes.
                 let mut vbuf = crate::core::rt::ReadBuf::uninit(unsafe { buf.as_mut() });
+                // SAFETY: These bytes are initialized in the outer buffer already.
+                unsafe { vbuf.set_init(init) };
                 match Pin::new(&mut self.inner).poll_read(cx, vbuf.unfilled()) {
                     Poll::Ready(Ok(())) => {
                         trace!("{:08x} read: {:?}", self.id, Escape(vbuf.filled()));
@@ -1029,3 +4507,211 @@ mod verbose {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use boring2::{
+        pkey::PKey,
+        ssl::{SslAcceptor, SslMethod},
+        x509::X509,
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpListener,
+    };
+
+    use super::{Connector, ConnectorBuilder, ConnectorService, Dst, check_schemes};
+    use crate::{
+        Proxy,
+        core::{
+            client::connect::{Connection, HttpInfo},
+            rt::{TokioIo, read, write_all},
+        },
+        dns::{DynResolver, gai::GaiResolver},
+        tls::{AlpnProtocol, TlsConfig, TlsInfo},
+    };
+
+    /// Builds a connector service without layers, configured by `f`.
+    fn service(f: impl FnOnce(ConnectorBuilder) -> ConnectorBuilder) -> ConnectorService {
+        let builder = f(Connector::builder(
+            Arc::new(Vec::new()),
+            DynResolver::new(Arc::new(GaiResolver::new())),
+        ));
+        let tls = builder.build_tls(TlsConfig::default()).unwrap();
+        let Connector::Simple(service) = builder.build(tls, None).unwrap() else {
+            unreachable!("no layers were given");
+        };
+        service
+    }
+
+    #[test]
+    fn check_schemes_rejects_unsupported() {
+        let uri = |s: &str| s.parse::<http::Uri>().unwrap();
+
+        assert!(check_schemes(&uri("http://example.com"), None).is_ok());
+        assert!(check_schemes(&uri("https://example.com"), None).is_ok());
+        assert!(check_schemes(&uri("ftp://example.com"), None).is_err());
+        assert!(check_schemes(&uri("/relative"), None).is_err());
+
+        let dst = uri("https://example.com");
+        assert!(check_schemes(&dst, Some(&uri("http://proxy:8080"))).is_ok());
+        assert!(check_schemes(&dst, Some(&uri("ftp://proxy:21"))).is_err());
+        #[cfg(feature = "socks")]
+        assert!(check_schemes(&dst, Some(&uri("socks5h://proxy:1080"))).is_ok());
+    }
+
+    #[tokio::test]
+    async fn raw_tunnel_relays_bytes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        let server = tokio::spawn(async move {
+            let (mut sock, _) = listener.accept().await.unwrap();
+            let mut buf = [0; 1024];
+            let n = sock.read(&mut buf).await.unwrap();
+            let req = String::from_utf8_lossy(&buf[..n]).into_owned();
+            sock.write_all(b"HTTP/1.1 200 OK\r\n\r\n").await.unwrap();
+
+            // Whatever goes through the tunnel comes back as is.
+            let n = sock.read(&mut buf).await.unwrap();
+            sock.write_all(&buf[..n]).await.unwrap();
+            req
+        });
+
+        let service = service(|builder| builder);
+
+        let mut req = http::Request::connect("db.internal:5432").body(()).unwrap();
+        let dst = Dst::new(&mut req, true).unwrap();
+        let proxy = Proxy::all(format!("http://{addr}"))
+            .unwrap()
+            .into_matcher()
+            .intercept(dst.uri())
+            .unwrap();
+
+        let mut conn = service.connect_tunnel(dst, proxy).await.unwrap();
+        write_all(&mut conn, b"\x00raw").await.unwrap();
+        let mut buf = [0; 4];
+        let n = read(&mut conn, &mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"\x00raw");
+
+        let req = server.await.unwrap();
+        assert!(req.starts_with("CONNECT db.internal:5432 HTTP/1.1\r\n"));
+    }
+
+    #[tokio::test]
+    async fn connects_over_provided_stream() {
+        let (client, mut server) = tokio::io::duplex(64);
+        let service = service(|builder| builder);
+
+        let mut req = http::Request::get("http://example.com/").body(()).unwrap();
+        let dst = Dst::new(&mut req, true).unwrap();
+        let mut conn = service
+            .connect_over(TokioIo::new(client), dst)
+            .await
+            .unwrap();
+        let mut extras = http::Extensions::new();
+        conn.connected().get_extras(&mut extras);
+        assert!(extras.get::<HttpInfo>().is_none());
+
+        write_all(&mut conn, b"ping").await.unwrap();
+        let mut buf = [0; 4];
+        server.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+
+    #[tokio::test]
+    async fn tls_over_provided_stream() {
+        let cert = X509::from_der(include_bytes!("../tests/support/server.cert")).unwrap();
+        let key =
+            PKey::private_key_from_der(include_bytes!("../tests/support/server.key")).unwrap();
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_certificate(&cert).unwrap();
+        acceptor.set_private_key(&key).unwrap();
+        let acceptor = acceptor.build();
+
+        let (client, server) = tokio::io::duplex(16 * 1024);
+        let server = tokio::spawn(async move {
+            let mut tls = tokio_boring2::accept(&acceptor, server).await.unwrap();
+            let mut buf = [0; 4];
+            tls.read_exact(&mut buf).await.unwrap();
+            tls.write_all(&buf).await.unwrap();
+        });
+
+        let service = service(|builder| builder.cert_verification(false).tls_info(true));
+        let mut req = http::Request::get("https://localhost/").body(()).unwrap();
+        let dst = Dst::new(&mut req, true).unwrap();
+        let mut conn = service
+            .connect_over(TokioIo::new(client), dst)
+            .await
+            .unwrap();
+        let mut extras = http::Extensions::new();
+        conn.connected().get_extras(&mut extras);
+        assert!(extras.get::<TlsInfo>().is_some());
+
+        write_all(&mut conn, b"ping").await.unwrap();
+        let mut buf = [0; 4];
+        let n = read(&mut conn, &mut buf).await.unwrap();
+        assert_eq!(&buf[..n], b"ping");
+        server.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn early_data_only_for_idempotent_requests() {
+        let cert = X509::from_der(include_bytes!("../tests/support/server.cert")).unwrap();
+        let key =
+            PKey::private_key_from_der(include_bytes!("../tests/support/server.key")).unwrap();
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_certificate(&cert).unwrap();
+        acceptor.set_private_key(&key).unwrap();
+        acceptor.set_early_data_enabled(true);
+        let acceptor = acceptor.build();
+
+        let service = service(|builder| {
+            builder
+                .cert_verification(false)
+                .tls_info(true)
+                .tls_early_data(true)
+        });
+
+        // Echoes one message on a new connection, and returns whether the
+        // server accepted early data on it.
+        let exchange = |mut req: http::Request<()>| {
+            let (client, server) = tokio::io::duplex(16 * 1024);
+            let acceptor = acceptor.clone();
+            let server = tokio::spawn(async move {
+                let mut tls = tokio_boring2::accept(&acceptor, server).await.unwrap();
+                let mut buf = [0; 4];
+                tls.read_exact(&mut buf).await.unwrap();
+                tls.write_all(&buf).await.unwrap();
+            });
+            let service = service.clone();
+            async move {
+                let mut dst = Dst::new(&mut req, false).unwrap();
+                dst.set_alpn_protos(AlpnProtocol::HTTP1);
+                let mut conn = service
+                    .connect_over(TokioIo::new(client), dst)
+                    .await
+                    .unwrap();
+                let mut extras = http::Extensions::new();
+                conn.connected().get_extras(&mut extras);
+
+                write_all(&mut conn, b"ping").await.unwrap();
+                let mut buf = [0; 4];
+                let n = read(&mut conn, &mut buf).await.unwrap();
+                assert_eq!(&buf[..n], b"ping");
+                server.await.unwrap();
+                extras.get::<TlsInfo>().unwrap().early_data_accepted()
+            }
+        };
+
+        // Nothing to resume yet.
+        let get = || http::Request::get("https://localhost/").body(()).unwrap();
+        assert_eq!(exchange(get()).await, None);
+        assert_eq!(exchange(get()).await, Some(true));
+
+        let post = http::Request::post("https://localhost/").body(()).unwrap();
+        assert_eq!(exchange(post).await, None);
+    }
+}
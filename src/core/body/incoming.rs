@@ -12,7 +12,10 @@ use http::HeaderMap;
 use http_body::{Body, Frame, SizeHint};
 
 use super::DecodedLength;
-use crate::core::{common::watch, proto::h2::ping};
+use crate::core::{
+    common::watch,
+    proto::h2::{client::ActiveStream, ping},
+};
 
 type BodySender = mpsc::Sender<Result<Bytes, crate::core::Error>>;
 type TrailersSender = oneshot::Sender<HeaderMap>;
@@ -50,6 +53,7 @@ enum Kind {
         data_done: bool,
         ping: ping::Recorder,
         recv: http2::RecvStream,
+        stream: Option<ActiveStream>,
     },
 }
 
@@ -124,6 +128,7 @@ impl Incoming {
         recv: http2::RecvStream,
         mut content_length: DecodedLength,
         ping: ping::Recorder,
+        mut stream: Option<ActiveStream>,
     ) -> Self {
         // If the stream is already EOS, then the "unknown length" is clearly
         // actually ZERO.
@@ -131,11 +136,17 @@ impl Incoming {
             content_length = DecodedLength::ZERO;
         }
 
+        // A finished stream no longer counts against the connection.
+        if recv.is_end_stream() {
+            stream = None;
+        }
+
         Incoming::new(Kind::H2 {
             data_done: false,
             ping,
             content_length,
             recv,
+            stream,
         })
     }
 }
@@ -176,6 +187,7 @@ impl Body for Incoming {
                 ref ping,
                 recv: ref mut h2,
                 content_length: ref mut len,
+                ref mut stream,
             } => {
                 if !*data_done {
                     match ready!(h2.poll_data(cx)) {
@@ -186,6 +198,7 @@ impl Body for Incoming {
                             return Poll::Ready(Some(Ok(Frame::data(bytes))));
                         }
                         Some(Err(e)) => {
+                            stream.take();
                             return match e.reason() {
                                 // These reasons should cause the body reading to stop, but not fail
                                 // it. The same logic as for `Read
@@ -204,7 +217,9 @@ impl Body for Incoming {
                 }
 
                 // after data, check trailers
-                match ready!(h2.poll_trailers(cx)) {
+                let trailers = ready!(h2.poll_trailers(cx));
+                stream.take();
+                match trailers {
                     Ok(t) => {
                         ping.record_non_data();
                         Poll::Ready(Ok(t.map(Frame::trailers)).transpose())
@@ -26,6 +26,8 @@ pub struct Http1Config {
     pub(crate) h1_max_headers: Option<usize>,
     pub(crate) h1_read_buf_exact_size: Option<usize>,
     pub(crate) h1_max_buf_size: Option<usize>,
+    pub(crate) h1_pipeline_depth: Option<usize>,
+    pub(crate) h1_pipeline_non_idempotent: bool,
 }
 
 impl Http1ConfigBuilder {
@@ -127,6 +129,40 @@ impl Http1ConfigBuilder {
         self
     }
 
+    /// Set how many requests an HTTP/1 connection may have written ahead of
+    /// their responses.
+    ///
+    /// With a depth above 1, a connection sends its next request as soon as
+    /// the previous one is written, rather than once its response is read.
+    /// Responses are matched to requests in the order the requests were
+    /// sent. Only requests with idempotent methods are pipelined, unless
+    /// [`pipeline_non_idempotent`] is set; any other request waits for the
+    /// responses ahead of it.
+    ///
+    /// Many servers and intermediaries handle pipelining poorly, so this is
+    /// meant for benchmarking and interoperability testing. A `depth` of 1 or
+    /// less disables it.
+    ///
+    /// Default is no pipelining.
+    ///
+    /// [`pipeline_non_idempotent`]: Http1ConfigBuilder::pipeline_non_idempotent
+    pub fn pipelining(mut self, depth: usize) -> Self {
+        self.config.h1_pipeline_depth = Some(depth.max(1));
+        self
+    }
+
+    /// Set whether requests with non-idempotent methods, such as `POST`, may
+    /// be pipelined too.
+    ///
+    /// Such a request cannot be retried safely if the connection fails
+    /// before its response arrives.
+    ///
+    /// Default is false.
+    pub fn pipeline_non_idempotent(mut self, enabled: bool) -> Self {
+        self.config.h1_pipeline_non_idempotent = enabled;
+        self
+    }
+
     /// Set whether HTTP/1 connections will accept spaces between header names
     /// and the colon that follow them in responses.
     ///
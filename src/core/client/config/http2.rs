@@ -1,14 +1,21 @@
 //! Re-export the `http2` module for HTTP/2 frame types and utilities.
 
+use std::time::Duration;
+
+pub use http2::Reason;
 use http2::frame::ExperimentalSettings;
 pub use http2::frame::{
     Priorities, PrioritiesBuilder, Priority, PseudoId, PseudoOrder, Setting, SettingId,
     SettingsOrder, SettingsOrderBuilder, StreamDependency, StreamId,
 };
 
-use crate::core::proto::{
-    h2::client::Config,
-    {self},
+pub use crate::core::proto::h2::watch::Http2PeerSettings;
+use crate::{
+    Error,
+    core::proto::{
+        h2::client::Config,
+        {self},
+    },
 };
 
 /// Builder for `Http2Config`.
@@ -57,6 +64,33 @@ impl Http2ConfigBuilder {
         self
     }
 
+    /// Sets the increment of the WINDOW_UPDATE sent for the connection in the
+    /// [connection preface].
+    ///
+    /// This is another way to set `initial_connection_window_size`, in the
+    /// terms a capture of the preface shows: the window is the default of
+    /// 65,535 bytes plus `increment`, and an increment of 0 sends no
+    /// WINDOW_UPDATE. Like `initial_connection_window_size`, a `Some` value
+    /// disables the adaptive window. Building a `Client` with a window above
+    /// 2^31-1 bytes fails with a builder error.
+    ///
+    /// Passing `None` will do nothing.
+    ///
+    /// The WINDOW_UPDATE always directly follows the SETTINGS frame, which
+    /// has to be the first frame a client sends.
+    ///
+    /// [connection preface]: https://httpwg.org/specs/rfc9113.html#preface
+    pub fn preface_window_update(mut self, increment: impl Into<Option<u32>>) -> Self {
+        use proto::h2::SPEC_WINDOW_SIZE;
+
+        if let Some(increment) = increment.into() {
+            self.config.h2_builder.adaptive_window = false;
+            self.config.h2_builder.initial_conn_window_size =
+                SPEC_WINDOW_SIZE.saturating_add(increment);
+        }
+        self
+    }
+
     /// Sets the initial maximum of locally initiated (send) streams.
     ///
     /// This value will be overwritten by the value included in the initial
@@ -75,6 +109,13 @@ impl Http2ConfigBuilder {
     }
 
     /// Sets the initial stream id for the connection.
+    ///
+    /// Client-initiated streams must use odd identifiers, so `id` must be odd
+    /// and non-zero. Each following stream on the connection increments the id
+    /// by 2. Building a `Client` with an even or zero id fails with a builder
+    /// error.
+    ///
+    /// Passing `None` uses the default of `1`.
     pub fn initial_stream_id(mut self, id: impl Into<Option<u32>>) -> Self {
         self.config.h2_builder.initial_stream_id = id.into();
         self
@@ -96,6 +137,51 @@ impl Http2ConfigBuilder {
         self
     }
 
+    /// Sets an interval for HTTP2 Ping frames should be sent to keep a
+    /// connection alive.
+    ///
+    /// Pass `None` to disable HTTP2 keep-alive.
+    ///
+    /// The round trip of the last answered PING is reported by
+    /// `Response::ping_rtt`.
+    ///
+    /// Unlike TCP keepalive, set with `ClientBuilder::tcp_keepalive`, which
+    /// only probes the socket and is often answered by NATs and proxies on the
+    /// way, a PING has to be acknowledged by the server itself. It detects a
+    /// dead connection behind a middlebox that silently stopped forwarding,
+    /// and it keeps such middleboxes from expiring an idle connection. Both
+    /// can be enabled together.
+    ///
+    /// Default is currently disabled.
+    pub fn keep_alive_interval(mut self, interval: impl Into<Option<Duration>>) -> Self {
+        self.config.h2_builder.keep_alive_interval = interval.into();
+        self
+    }
+
+    /// Sets a timeout for receiving an acknowledgement of the keep-alive ping.
+    ///
+    /// If the ping is not acknowledged within the timeout, the connection will
+    /// be closed, which fails the requests in flight on it and takes it out
+    /// of the pool. Does nothing if `keep_alive_interval` is disabled.
+    ///
+    /// Default is 20 seconds.
+    pub fn keep_alive_timeout(mut self, timeout: Duration) -> Self {
+        self.config.h2_builder.keep_alive_timeout = timeout;
+        self
+    }
+
+    /// Sets whether HTTP2 keep-alive should apply while the connection is idle.
+    ///
+    /// If disabled, keep-alive pings are only sent while there are open
+    /// request/responses streams. If enabled, pings are also sent when no
+    /// streams are active. Does nothing if `keep_alive_interval` is disabled.
+    ///
+    /// Default is `false`.
+    pub fn keep_alive_while_idle(mut self, enabled: bool) -> Self {
+        self.config.h2_builder.keep_alive_while_idle = enabled;
+        self
+    }
+
     /// Sets the maximum frame size to use for HTTP2.
     ///
     /// Default is currently 16KB, but can change.
@@ -152,6 +238,42 @@ impl Http2ConfigBuilder {
         self
     }
 
+    /// Sets the maximum number of HTTP/2 connections kept open to a single host.
+    ///
+    /// Requests are spread across these connections, each one going to the
+    /// connection with the fewest streams in flight. A new connection is only
+    /// opened once every existing one has reached the server's
+    /// `SETTINGS_MAX_CONCURRENT_STREAMS`. When the limit is reached as well,
+    /// requests queue on the least busy connection until a stream frees up.
+    ///
+    /// HTTP/2 is designed around a single connection per origin, and [RFC 9113]
+    /// asks clients not to open more than one. Only raise this for servers known
+    /// to cap concurrent streams below what a workload needs. Additional
+    /// connections don't share flow control windows or header compression state.
+    ///
+    /// Default is 1. A value of 0 is treated as 1.
+    ///
+    /// [RFC 9113]: https://httpwg.org/specs/rfc9113.html#rfc.section.9.1
+    pub fn max_connections_per_host(mut self, max: usize) -> Self {
+        self.config.h2_builder.max_connections_per_host = max;
+        self
+    }
+
+    /// Sets whether a graceful GOAWAY from the server drains the connection.
+    ///
+    /// A server shutting a connection down gracefully sends a GOAWAY with
+    /// `NO_ERROR`: the streams already in flight go on, but new ones would be
+    /// refused. When enabled, the connection is taken out of the pool as soon
+    /// as the GOAWAY arrives, so new requests go to a fresh connection while
+    /// the ones in flight finish on the old one. When disabled, the connection
+    /// stays in the pool until it closes.
+    ///
+    /// Default is `true`.
+    pub fn graceful_goaway(mut self, enabled: bool) -> Self {
+        self.config.h2_builder.graceful_goaway = enabled;
+        self
+    }
+
     /// Enables and disables the push feature for HTTP2.
     ///
     /// Passing `None` will do nothing.
@@ -212,6 +334,10 @@ impl Http2ConfigBuilder {
     /// This configures the priority of the stream by specifying its dependency and weight,
     /// as defined by the HTTP/2 priority mechanism. This can be used to influence how the
     /// server allocates resources to this stream relative to others.
+    ///
+    /// The same dependency is sent on every request of the connection. The h2 client builds
+    /// HEADERS frames from its connection settings alone, so it can't be overridden for a
+    /// single request.
     pub fn headers_stream_dependency<T>(mut self, stream_dependency: T) -> Self
     where
         T: Into<Option<StreamDependency>>,
@@ -267,6 +393,9 @@ impl Http2ConfigBuilder {
     ///
     /// Each `Priority` in the list must have a valid (non-zero) stream ID. Any priority with a
     /// stream ID of zero will be ignored.
+    ///
+    /// [`PriorityTree`] builds the list from a dependency tree that is checked to be
+    /// well-formed, and has presets for the trees browsers send.
     pub fn priorities<T>(mut self, priorities: T) -> Self
     where
         T: Into<Option<Priorities>>,
@@ -288,4 +417,367 @@ impl Http2Config {
             config: Http2Config::default(),
         }
     }
+
+    /// The HTTP/2 configuration of Chrome `version`: its SETTINGS and their
+    /// order, its window sizes, and the priority and pseudo-header order of
+    /// its HEADERS.
+    ///
+    /// Covers Chrome 100 and later. Before 106, Chrome advertised
+    /// `SETTINGS_MAX_CONCURRENT_STREAMS`; since, it disables server push
+    /// instead. Older versions get the configuration of Chrome 100.
+    pub fn chrome(version: u16) -> Http2Config {
+        let builder = Http2Config::builder()
+            .header_table_size(65536)
+            .initial_stream_window_size(6291456)
+            .initial_connection_window_size(15663105 + 65535)
+            .max_header_list_size(262144)
+            .headers_stream_dependency(StreamDependency::new(StreamId::zero(), 255, true))
+            .headers_pseudo_order(pseudo_order([
+                PseudoId::Method,
+                PseudoId::Authority,
+                PseudoId::Scheme,
+                PseudoId::Path,
+            ]))
+            .settings_order(settings_order([
+                SettingId::HeaderTableSize,
+                SettingId::EnablePush,
+                SettingId::MaxConcurrentStreams,
+                SettingId::InitialWindowSize,
+                SettingId::MaxFrameSize,
+                SettingId::MaxHeaderListSize,
+            ]));
+
+        if version < 106 {
+            builder.max_concurrent_streams(1000).build()
+        } else {
+            builder.enable_push(false).build()
+        }
+    }
+
+    /// The HTTP/2 configuration of Firefox 100 and later: its SETTINGS and
+    /// their order, its window sizes, the [`PriorityTree::firefox`] tree it
+    /// sends in its preface, and the priority and pseudo-header order of its
+    /// HEADERS.
+    ///
+    /// These Firefox versions all share this configuration, so unlike
+    /// [`chrome`](Http2Config::chrome) and [`safari`](Http2Config::safari) it
+    /// takes no version.
+    pub fn firefox() -> Http2Config {
+        let priorities = PriorityTree::firefox()
+            .build()
+            .expect("the firefox priority tree is well-formed");
+
+        Http2Config::builder()
+            .initial_stream_id(15)
+            .header_table_size(65536)
+            .initial_stream_window_size(131072)
+            .max_frame_size(16384)
+            .initial_connection_window_size(12517377 + 65535)
+            .headers_stream_dependency(StreamDependency::new(StreamId::from(13), 41, false))
+            .headers_pseudo_order(pseudo_order([
+                PseudoId::Method,
+                PseudoId::Path,
+                PseudoId::Authority,
+                PseudoId::Scheme,
+            ]))
+            .settings_order(settings_order([
+                SettingId::HeaderTableSize,
+                SettingId::EnablePush,
+                SettingId::MaxConcurrentStreams,
+                SettingId::InitialWindowSize,
+                SettingId::MaxFrameSize,
+                SettingId::MaxHeaderListSize,
+            ]))
+            .priorities(priorities)
+            .build()
+    }
+
+    /// The HTTP/2 configuration of Safari `version`: its SETTINGS and their
+    /// order, its window sizes, and the pseudo-header order of its HEADERS.
+    ///
+    /// Covers Safari 15 and later. Since 17, Safari disables server push and
+    /// RFC 7540 priorities, and uses smaller windows. Older versions get the
+    /// configuration of Safari 15.
+    pub fn safari(version: u16) -> Http2Config {
+        if version < 17 {
+            return Http2Config::builder()
+                .initial_stream_window_size(4194304)
+                .max_concurrent_streams(100)
+                .initial_connection_window_size(10485760 + 65535)
+                .headers_pseudo_order(pseudo_order([
+                    PseudoId::Method,
+                    PseudoId::Scheme,
+                    PseudoId::Path,
+                    PseudoId::Authority,
+                ]))
+                .settings_order(settings_order([
+                    SettingId::InitialWindowSize,
+                    SettingId::MaxConcurrentStreams,
+                ]))
+                .build();
+        }
+
+        Http2Config::builder()
+            .enable_push(false)
+            .max_concurrent_streams(100)
+            .initial_stream_window_size(2097152)
+            .no_rfc7540_priorities(true)
+            .initial_connection_window_size(10420225 + 65535)
+            .headers_pseudo_order(pseudo_order([
+                PseudoId::Method,
+                PseudoId::Scheme,
+                PseudoId::Authority,
+                PseudoId::Path,
+            ]))
+            .settings_order(settings_order([
+                SettingId::EnablePush,
+                SettingId::MaxConcurrentStreams,
+                SettingId::InitialWindowSize,
+                SettingId::NoRfc7540Priorities,
+            ]))
+            .build()
+    }
+}
+
+fn pseudo_order(ids: [PseudoId; 4]) -> PseudoOrder {
+    PseudoOrder::builder().extend(ids).build()
+}
+
+/// Orders `ids` first, and the settings a browser doesn't send after them.
+fn settings_order<const N: usize>(ids: [SettingId; N]) -> SettingsOrder {
+    let rest = [
+        SettingId::HeaderTableSize,
+        SettingId::EnablePush,
+        SettingId::MaxConcurrentStreams,
+        SettingId::InitialWindowSize,
+        SettingId::MaxFrameSize,
+        SettingId::MaxHeaderListSize,
+        SettingId::EnableConnectProtocol,
+        SettingId::NoRfc7540Priorities,
+    ]
+    .into_iter()
+    .filter(|id| !ids.contains(id));
+    SettingsOrder::builder().extend(ids).extend(rest).build()
+}
+
+/// A dependency tree of streams, sent as the PRIORITY frames of the connection
+/// preface.
+///
+/// Unlike a raw [`Priorities`] list, the tree is checked when it is built:
+/// every stream is non-zero and appears once, and depends on the root (stream
+/// `0`) or on another stream of the tree, without cycles.
+///
+/// Weights are given as sent on the wire, one less than the weight they stand
+/// for, like [`StreamDependency::new`]. The streams of the tree are never
+/// opened, so their ids should be below the
+/// [`initial_stream_id`](Http2ConfigBuilder::initial_stream_id) of the
+/// connection.
+///
+/// # Example
+///
+/// ```
+/// use wreq::http2::{Http2Config, PriorityTree};
+///
+/// let config = Http2Config::builder()
+///     .initial_stream_id(15)
+///     .priorities(PriorityTree::firefox().build()?)
+///     .build();
+/// # Ok::<(), wreq::Error>(())
+/// ```
+#[must_use]
+#[derive(Debug, Clone, Default)]
+pub struct PriorityTree {
+    streams: Vec<(u32, u32, u8, bool)>,
+}
+
+impl PriorityTree {
+    /// Creates an empty tree.
+    pub fn new() -> PriorityTree {
+        PriorityTree::default()
+    }
+
+    /// The tree Chrome sends, which is empty.
+    ///
+    /// Chrome sends no PRIORITY frames in its preface. It sets the priority on
+    /// the HEADERS frame of each request instead, see
+    /// [`headers_stream_dependency`](Http2ConfigBuilder::headers_stream_dependency).
+    pub fn chrome() -> PriorityTree {
+        PriorityTree::new()
+    }
+
+    /// The tree of idle streams that Firefox sends, and hangs its requests
+    /// from.
+    ///
+    /// Requests go on stream `15` and above, so this is meant to go with an
+    /// [`initial_stream_id`](Http2ConfigBuilder::initial_stream_id) of `15`.
+    pub fn firefox() -> PriorityTree {
+        PriorityTree::new()
+            .stream(3, 0, 200, false)
+            .stream(5, 0, 100, false)
+            .stream(7, 0, 0, false)
+            .stream(9, 7, 0, false)
+            .stream(11, 3, 0, false)
+            .stream(13, 0, 240, false)
+    }
+
+    /// Adds `id` to the tree, depending on `parent` with `weight`, and
+    /// exclusively if `exclusive` is set.
+    ///
+    /// Streams are sent in the order they are added. A parent may be added
+    /// after the streams that depend on it.
+    pub fn stream(mut self, id: u32, parent: u32, weight: u8, exclusive: bool) -> PriorityTree {
+        self.streams.push((id, parent, weight, exclusive));
+        self
+    }
+
+    /// Checks the tree, and builds the list of PRIORITY frames to pass to
+    /// [`priorities`](Http2ConfigBuilder::priorities).
+    ///
+    /// # Errors
+    ///
+    /// This fails with a builder error if the tree is not well-formed.
+    pub fn build(self) -> crate::Result<Priorities> {
+        self.check()?;
+        Ok(Priorities::builder()
+            .extend(
+                self.streams
+                    .into_iter()
+                    .map(|(id, parent, weight, exclusive)| {
+                        Priority::new(
+                            StreamId::from(id),
+                            StreamDependency::new(StreamId::from(parent), weight, exclusive),
+                        )
+                    }),
+            )
+            .build())
+    }
+
+    fn check(&self) -> crate::Result<()> {
+        const MAX_STREAM_ID: u32 = (1 << 31) - 1;
+
+        let parent_of = |id: u32| {
+            self.streams
+                .iter()
+                .find(|&&(stream, ..)| stream == id)
+                .map(|&(_, parent, ..)| parent)
+        };
+
+        for (i, &(id, parent, ..)) in self.streams.iter().enumerate() {
+            if id == 0 || id > MAX_STREAM_ID {
+                return Err(Error::builder(format!(
+                    "http2 priority stream id {id} is out of range"
+                )));
+            }
+            if self.streams[..i].iter().any(|&(stream, ..)| stream == id) {
+                return Err(Error::builder(format!(
+                    "http2 priority stream {id} appears more than once"
+                )));
+            }
+            if parent != 0 && parent_of(parent).is_none() {
+                return Err(Error::builder(format!(
+                    "http2 priority stream {id} depends on unknown stream {parent}"
+                )));
+            }
+
+            // Every stream is unique, so a walk to the root that takes more
+            // steps than there are streams has gone around a cycle.
+            let mut ancestor = parent;
+            for _ in 0..self.streams.len() {
+                if ancestor == 0 {
+                    break;
+                }
+                if ancestor == id {
+                    return Err(Error::builder(format!(
+                        "http2 priority stream {id} depends on itself"
+                    )));
+                }
+                ancestor = parent_of(ancestor).unwrap_or(0);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn browser_presets_by_version() {
+        let chrome = Http2Config::chrome(105).h2_builder;
+        assert_eq!(chrome.max_concurrent_streams, Some(1000));
+        assert_eq!(chrome.enable_push, None);
+        let chrome = Http2Config::chrome(131).h2_builder;
+        assert_eq!(chrome.max_concurrent_streams, None);
+        assert_eq!(chrome.enable_push, Some(false));
+        assert!(!chrome.adaptive_window);
+
+        let firefox = Http2Config::firefox().h2_builder;
+        assert_eq!(firefox.initial_stream_id, Some(15));
+        assert!(firefox.priorities.is_some());
+
+        let safari = Http2Config::safari(16).h2_builder;
+        assert_eq!(safari.initial_stream_window_size, 4194304);
+        assert_eq!(safari.no_rfc7540_priorities, None);
+        let safari = Http2Config::safari(18).h2_builder;
+        assert_eq!(safari.initial_stream_window_size, 2097152);
+        assert_eq!(safari.no_rfc7540_priorities, Some(true));
+    }
+
+    #[test]
+    fn preface_window_update_none_does_nothing() {
+        let h2 = Http2Config::builder()
+            .initial_connection_window_size(1 << 20)
+            .preface_window_update(None)
+            .build()
+            .h2_builder;
+        assert_eq!(h2.initial_conn_window_size, 1 << 20);
+
+        let h2 = Http2Config::builder()
+            .adaptive_window(true)
+            .preface_window_update(None)
+            .build()
+            .h2_builder;
+        assert!(h2.adaptive_window);
+
+        let h2 = Http2Config::builder()
+            .preface_window_update(1 << 20)
+            .build()
+            .h2_builder;
+        assert_eq!(h2.initial_conn_window_size, 65535 + (1 << 20));
+        assert!(!h2.adaptive_window);
+    }
+
+    #[test]
+    fn priority_tree_presets_are_well_formed() {
+        assert!(PriorityTree::chrome().check().is_ok());
+        assert!(PriorityTree::firefox().check().is_ok());
+    }
+
+    #[test]
+    fn priority_tree_rejects_malformed() {
+        let malformed = [
+            PriorityTree::new().stream(0, 0, 0, false),
+            PriorityTree::new().stream(1 << 31, 0, 0, false),
+            PriorityTree::new()
+                .stream(3, 0, 0, false)
+                .stream(3, 0, 0, false),
+            PriorityTree::new().stream(3, 5, 0, false),
+            PriorityTree::new().stream(3, 3, 0, false),
+            PriorityTree::new()
+                .stream(3, 7, 0, false)
+                .stream(5, 3, 0, false)
+                .stream(7, 5, 0, false),
+        ];
+        for tree in malformed {
+            let err = tree.clone().check().unwrap_err();
+            assert!(err.is_builder(), "{tree:?}");
+        }
+
+        // A parent may come after its children.
+        let tree = PriorityTree::new()
+            .stream(5, 3, 0, false)
+            .stream(3, 0, 0, true);
+        assert!(tree.check().is_ok());
+    }
 }
@@ -28,6 +28,7 @@ type Dispatcher<T, B> =
 /// The sender side of an established connection.
 pub struct SendRequest<B> {
     dispatch: dispatch::Sender<Request<B>, Response<IncomingBody>>,
+    pipelining: bool,
 }
 
 /// Deconstructed parts of a `Connection`.
@@ -118,6 +119,12 @@ impl<B> SendRequest<B> {
     pub fn is_ready(&self) -> bool {
         self.dispatch.is_ready()
     }
+
+    /// Returns whether the connection may take a request before the
+    /// response to the previous one has arrived.
+    pub(crate) fn is_pipelining(&self) -> bool {
+        self.pipelining
+    }
 }
 
 impl<B> SendRequest<B>
@@ -135,7 +142,8 @@ where
     pub fn try_send_request(
         &mut self,
         req: Request<B>,
-    ) -> impl Future<Output = Result<Response<IncomingBody>, TrySendError<Request<B>>>> {
+    ) -> impl Future<Output = Result<Response<IncomingBody>, TrySendError<Request<B>>>> + use<B>
+    {
         let sent = self.dispatch.try_send(req);
         async move {
             match sent {
@@ -285,10 +293,21 @@ impl Builder {
             if let Some(max) = opts.h1_max_buf_size {
                 conn.set_max_buf_size(max);
             }
-            let cd = proto::h1::dispatch::Client::new(rx);
+            let mut cd = proto::h1::dispatch::Client::new(rx);
+            let pipelining = opts.h1_pipeline_depth.is_some_and(|depth| depth > 1);
+            if let Some(depth) = opts.h1_pipeline_depth {
+                conn.set_pipelining(depth, opts.h1_pipeline_non_idempotent);
+                cd.set_pipelining(depth, opts.h1_pipeline_non_idempotent);
+            }
             let proto = proto::h1::Dispatcher::new(cd, conn);
 
-            Ok((SendRequest { dispatch: tx }, Connection { inner: proto }))
+            Ok((
+                SendRequest {
+                    dispatch: tx,
+                    pipelining,
+                },
+                Connection { inner: proto },
+            ))
         }
     }
 }
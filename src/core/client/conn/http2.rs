@@ -18,7 +18,14 @@ use crate::{
         body::Incoming as IncomingBody,
         client::dispatch::{self, TrySendError},
         common::time::Time,
-        proto,
+        proto::{
+            self,
+            h2::{
+                client::{ActiveStream, StreamLoad},
+                ping::{PingStats, Pinger},
+                watch::{Draining, PeerSettings, SettingsRtt, Watch},
+            },
+        },
         rt::{Read, Timer, Write, bounds::Http2ClientConnExec},
     },
     http2::Http2Config,
@@ -27,12 +34,18 @@ use crate::{
 /// The sender side of an established connection.
 pub struct SendRequest<B> {
     dispatch: dispatch::UnboundedSender<Request<B>, Response<IncomingBody>>,
+    load: StreamLoad,
+    pinger: Pinger,
+    draining: Draining,
 }
 
 impl<B> Clone for SendRequest<B> {
     fn clone(&self) -> SendRequest<B> {
         SendRequest {
             dispatch: self.dispatch.clone(),
+            load: self.load.clone(),
+            pinger: self.pinger.clone(),
+            draining: self.draining.clone(),
         }
     }
 }
@@ -65,6 +78,10 @@ pub struct Builder<Ex> {
     pub(super) exec: Ex,
     pub(super) timer: Time,
     config: Http2Config,
+    settings_rtt: Option<SettingsRtt>,
+    peer_settings: Option<PeerSettings>,
+    ping_stats: Option<PingStats>,
+    draining: Option<Draining>,
 }
 
 // ===== impl SendRequest
@@ -103,6 +120,31 @@ impl<B> SendRequest<B> {
     pub fn is_closed(&self) -> bool {
         self.dispatch.is_closed()
     }
+
+    /// Returns the number of requests in flight on this connection.
+    pub(crate) fn active_streams(&self) -> usize {
+        self.load.active()
+    }
+
+    /// Checks if the server's concurrent stream limit has been reached.
+    pub(crate) fn is_saturated(&self) -> bool {
+        self.load.is_saturated()
+    }
+
+    /// Checks if the server sent a graceful GOAWAY, so that the connection
+    /// only serves the requests already in flight.
+    pub(crate) fn is_draining(&self) -> bool {
+        self.draining.is_draining()
+    }
+
+    /// Sends a PING and resolves to `true` once it is acknowledged.
+    ///
+    /// Resolves to `false` right away unless on demand pings were enabled
+    /// with [`Builder::ping_on_demand`]. Otherwise it waits for as long as
+    /// the pong takes, so should be bounded with a timeout.
+    pub(crate) fn ping(&self) -> impl Future<Output = bool> + Send + 'static {
+        self.pinger.ping()
+    }
 }
 
 impl<B> SendRequest<B>
@@ -119,18 +161,26 @@ where
     /// connection, the message will be returned as part of this error.
     pub fn try_send_request(
         &mut self,
-        req: Request<B>,
-    ) -> impl Future<Output = Result<Response<IncomingBody>, TrySendError<Request<B>>>> {
+        mut req: Request<B>,
+    ) -> impl Future<Output = Result<Response<IncomingBody>, TrySendError<Request<B>>>> + use<B>
+    {
+        req.extensions_mut().insert(self.load.open());
         let sent = self.dispatch.try_send(req);
         async move {
             match sent {
                 Ok(rx) => match rx.await {
                     Ok(Ok(res)) => Ok(res),
-                    Ok(Err(err)) => Err(err),
+                    Ok(Err(mut err)) => {
+                        if let Some(req) = err.message.as_mut() {
+                            req.extensions_mut().remove::<ActiveStream>();
+                        }
+                        Err(err)
+                    }
                     // this is definite bug if it happens, but it shouldn't happen!
                     Err(_) => panic!("dispatch dropped without returning error"),
                 },
-                Err(req) => {
+                Err(mut req) => {
+                    req.extensions_mut().remove::<ActiveStream>();
                     debug!("connection was not ready");
                     let error = crate::core::Error::new_canceled().with("connection was not ready");
                     Err(TrySendError {
@@ -195,6 +245,10 @@ where
             exec,
             timer: Time::Empty,
             config: Default::default(),
+            settings_rtt: None,
+            peer_settings: None,
+            ping_stats: None,
+            draining: None,
         }
     }
 
@@ -213,6 +267,39 @@ where
         self
     }
 
+    /// Enables sending pings on demand through [`SendRequest::ping`].
+    pub(crate) fn ping_on_demand(&mut self, enabled: bool) -> &mut Builder<Ex> {
+        self.config.h2_builder.ping_on_demand = enabled;
+        self
+    }
+
+    /// Records the round trip of the initial SETTINGS of the next connection
+    /// into `rtt`.
+    pub(crate) fn settings_rtt(&mut self, rtt: SettingsRtt) -> &mut Builder<Ex> {
+        self.settings_rtt = Some(rtt);
+        self
+    }
+
+    /// Records into `settings` the first SETTINGS the server of the next
+    /// connection sends.
+    pub(crate) fn peer_settings(&mut self, settings: PeerSettings) -> &mut Builder<Ex> {
+        self.peer_settings = Some(settings);
+        self
+    }
+
+    /// Records the PINGs of the next connection into `stats`.
+    pub(crate) fn ping_stats(&mut self, stats: PingStats) -> &mut Builder<Ex> {
+        self.ping_stats = Some(stats);
+        self
+    }
+
+    /// Records into `draining` whether the next connection was sent a
+    /// graceful GOAWAY.
+    pub(crate) fn draining(&mut self, draining: Draining) -> &mut Builder<Ex> {
+        self.draining = Some(draining);
+        self
+    }
+
     /// Constructs a connection with the configured options and IO.
     /// See [`client::conn`](crate::core::client::conn) for more.
     ///
@@ -235,17 +322,32 @@ where
             trace!("client handshake HTTP/2");
 
             let (tx, rx) = dispatch::channel();
+            let load = StreamLoad::new(&opts.config.h2_builder);
+            let watch = Watch {
+                settings_rtt: opts.settings_rtt.unwrap_or_default(),
+                peer_settings: opts.peer_settings.unwrap_or_default(),
+                draining: opts.draining.unwrap_or_default(),
+                ping: opts.ping_stats.unwrap_or_default(),
+                ignore_graceful_goaway: !opts.config.h2_builder.graceful_goaway,
+            };
+            let draining = watch.draining.clone();
             let h2 = proto::h2::client::handshake(
                 io,
                 rx,
                 &opts.config.h2_builder,
+                load.clone(),
+                watch,
                 opts.exec,
                 opts.timer,
             )
             .await?;
+            let pinger = h2.pinger();
             Ok((
                 SendRequest {
                     dispatch: tx.unbound(),
+                    load,
+                    pinger,
+                    draining,
                 },
                 Connection {
                     inner: (PhantomData, h2),
@@ -0,0 +1,43 @@
+//! Counting the bytes that go through a connection.
+
+use std::sync::{
+    Arc,
+    atomic::{AtomicU64, Ordering},
+};
+
+/// How many bytes were read from and written to a connection.
+///
+/// Made available on responses when [`ClientBuilder::count_bytes`] is set.
+/// The counts are shared with the connection, and keep growing while it
+/// carries more requests. Over TLS, they are the bytes of HTTP, after
+/// decryption, without the overhead of TLS records.
+///
+/// [`ClientBuilder::count_bytes`]: crate::ClientBuilder::count_bytes
+#[derive(Debug, Clone, Default)]
+pub struct ByteCounts(Arc<Counts>);
+
+#[derive(Debug, Default)]
+struct Counts {
+    read: AtomicU64,
+    written: AtomicU64,
+}
+
+impl ByteCounts {
+    /// Get how many bytes were read from the connection so far.
+    pub fn read(&self) -> u64 {
+        self.0.read.load(Ordering::Relaxed)
+    }
+
+    /// Get how many bytes were written to the connection so far.
+    pub fn written(&self) -> u64 {
+        self.0.written.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn add_read(&self, n: usize) {
+        self.0.read.fetch_add(n as u64, Ordering::Relaxed);
+    }
+
+    pub(crate) fn add_written(&self, n: usize) {
+        self.0.written.fetch_add(n as u64, Ordering::Relaxed);
+    }
+}
@@ -25,7 +25,7 @@ use std::{
     fmt,
     future::{self, Future},
     io,
-    net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6, ToSocketAddrs},
     pin::Pin,
     str::FromStr,
     task::{self, Poll},
@@ -37,6 +37,27 @@ use tower_service::Service;
 
 pub(super) use self::sealed::Resolve;
 
+/// The order in which the addresses a host resolves to are tried.
+///
+/// With Happy Eyeballs, the family of the first address in this order is
+/// the preferred one, and each family keeps this order among its own
+/// addresses.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum AddressOrder {
+    /// The order the resolver returned them in.
+    #[default]
+    AsResolved,
+    /// Sorted by the destination address selection rules of [RFC 6724]
+    /// that only depend on the destination: higher precedence first, then
+    /// smaller scope, keeping the resolver's order otherwise.
+    ///
+    /// [RFC 6724]: https://www.rfc-editor.org/rfc/rfc6724#section-6
+    Rfc6724,
+    /// Shuffled anew on every connect, which spreads connects across all
+    /// the addresses of a host instead of pinning them to the first one.
+    Shuffle,
+}
+
 /// A domain name to resolve into IP addresses.
 #[derive(Clone, Hash, Eq, PartialEq)]
 pub struct Name {
@@ -214,9 +235,25 @@ impl SocketAddrs {
         None
     }
 
-    #[inline]
-    fn filter(self, predicate: impl FnMut(&SocketAddr) -> bool) -> SocketAddrs {
-        SocketAddrs::new(self.iter.filter(predicate).collect())
+    /// Puts the addresses in `order`.
+    pub(super) fn ordered(self, order: AddressOrder) -> SocketAddrs {
+        let mut addrs = self.iter.collect::<Vec<_>>();
+        match order {
+            AddressOrder::AsResolved => {}
+            AddressOrder::Rfc6724 => {
+                addrs.sort_by_key(|addr| {
+                    let ip = to_canonical(addr.ip());
+                    (std::cmp::Reverse(precedence(ip)), scope(ip))
+                });
+            }
+            AddressOrder::Shuffle => {
+                for i in (1..addrs.len()).rev() {
+                    let j = (crate::util::fast_random() % (i as u64 + 1)) as usize;
+                    addrs.swap(i, j);
+                }
+            }
+        }
+        SocketAddrs::new(addrs)
     }
 
     pub(super) fn split_by_preference(
@@ -225,8 +262,26 @@ impl SocketAddrs {
         local_addr_ipv6: Option<Ipv6Addr>,
     ) -> (SocketAddrs, SocketAddrs) {
         match (local_addr_ipv4, local_addr_ipv6) {
-            (Some(_), None) => (self.filter(SocketAddr::is_ipv4), SocketAddrs::new(vec![])),
-            (None, Some(_)) => (self.filter(SocketAddr::is_ipv6), SocketAddrs::new(vec![])),
+            (Some(_), None) | (None, Some(_)) => {
+                let bound_v6 = local_addr_ipv6.is_some();
+                let (bindable, other) = self
+                    .iter
+                    .partition::<Vec<_>, _>(|addr| addr.is_ipv6() == bound_v6);
+
+                // Connecting unbound beats failing when the host has no
+                // address of the family of the local address.
+                if bindable.is_empty() {
+                    debug!(
+                        "no {} address to connect from {:?}, connecting unbound",
+                        if bound_v6 { "IPv6" } else { "IPv4" },
+                        local_addr_ipv4
+                            .map(IpAddr::from)
+                            .or(local_addr_ipv6.map(IpAddr::from))
+                    );
+                    return (SocketAddrs::new(other), SocketAddrs::new(vec![]));
+                }
+                (SocketAddrs::new(bindable), SocketAddrs::new(vec![]))
+            }
             _ => {
                 let preferring_v6 = self
                     .iter
@@ -301,6 +356,56 @@ mod sealed {
     }
 }
 
+/// Maps IPv4-mapped IPv6 addresses to IPv4, as RFC 6724 treats them alike.
+fn to_canonical(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => match v6.to_ipv4_mapped() {
+            Some(v4) => IpAddr::V4(v4),
+            None => IpAddr::V6(v6),
+        },
+        v4 => v4,
+    }
+}
+
+/// The precedence of `ip` in the default policy table of RFC 6724.
+fn precedence(ip: IpAddr) -> u8 {
+    let v6 = match ip {
+        // ::ffff:0:0/96
+        IpAddr::V4(_) => return 35,
+        IpAddr::V6(v6) => v6,
+    };
+    let segments = v6.segments();
+    if v6.is_loopback() {
+        50
+    } else if segments[0] == 0x2002 {
+        30
+    } else if segments[0] == 0x2001 && segments[1] == 0 {
+        5
+    } else if segments[0] & 0xfe00 == 0xfc00 {
+        3
+    } else if segments[..6] == [0; 6] || segments[0] & 0xffc0 == 0xfec0 || segments[0] == 0x3ffe {
+        1
+    } else {
+        40
+    }
+}
+
+/// The scope of `ip`, as RFC 6724 defines it.
+fn scope(ip: IpAddr) -> u8 {
+    const LINK_LOCAL: u8 = 0x2;
+    const SITE_LOCAL: u8 = 0x5;
+    const GLOBAL: u8 = 0xe;
+
+    match ip {
+        IpAddr::V4(v4) if v4.is_loopback() || v4.is_link_local() => LINK_LOCAL,
+        IpAddr::V4(_) => GLOBAL,
+        IpAddr::V6(v6) if v6.is_multicast() => v6.segments()[0] as u8 & 0xf,
+        IpAddr::V6(v6) if v6.is_loopback() || v6.segments()[0] & 0xffc0 == 0xfe80 => LINK_LOCAL,
+        IpAddr::V6(v6) if v6.segments()[0] & 0xffc0 == 0xfec0 => SITE_LOCAL,
+        IpAddr::V6(_) => GLOBAL,
+    }
+}
+
 pub(super) async fn resolve<R>(resolver: &mut R, name: Name) -> Result<R::Addrs, R::Error>
 where
     R: Resolve,
@@ -363,6 +468,71 @@ mod tests {
         .split_by_preference(None, Some(ip_v6));
         assert!(preferred.next().unwrap().is_ipv6());
         assert!(fallback.is_empty());
+
+        // Without an address of the family bound to, the others are kept.
+        let (mut preferred, fallback) = SocketAddrs {
+            iter: vec![v4_addr].into_iter(),
+        }
+        .split_by_preference(None, Some(ip_v6));
+        assert!(preferred.next().unwrap().is_ipv4());
+        assert!(fallback.is_empty());
+    }
+
+    #[test]
+    fn test_ip_addrs_ordered() {
+        let addrs = |addrs: &[&str]| {
+            SocketAddrs::new(
+                addrs
+                    .iter()
+                    .map(|ip| SocketAddr::new(ip.parse().unwrap(), 443))
+                    .collect(),
+            )
+        };
+        let ips = |addrs: SocketAddrs| addrs.map(|addr| addr.ip().to_string()).collect::<Vec<_>>();
+
+        let resolved = [
+            "fd00::1",
+            "93.184.216.34",
+            "2001:0:4136:e378::1",
+            "2606:2800:220:1::1",
+            "::1",
+            "169.254.0.1",
+            "2002:c000:204::1",
+        ];
+        assert_eq!(
+            ips(addrs(&resolved).ordered(AddressOrder::AsResolved)),
+            resolved
+        );
+        assert_eq!(
+            ips(addrs(&resolved).ordered(AddressOrder::Rfc6724)),
+            [
+                "::1",
+                "2606:2800:220:1::1",
+                "169.254.0.1",
+                "93.184.216.34",
+                "2002:c000:204::1",
+                "2001:0:4136:e378::1",
+                "fd00::1",
+            ]
+        );
+
+        let mut shuffled = ips(addrs(&resolved).ordered(AddressOrder::Shuffle));
+        shuffled.sort();
+        let mut expected = resolved.map(String::from).to_vec();
+        expected.sort();
+        assert_eq!(shuffled, expected);
+
+        // Every address comes first now and then.
+        let mut first = std::collections::HashSet::new();
+        for _ in 0..1000 {
+            first.insert(
+                addrs(&resolved)
+                    .ordered(AddressOrder::Shuffle)
+                    .next()
+                    .unwrap(),
+            );
+        }
+        assert_eq!(first.len(), resolved.len());
     }
 
     #[test]
@@ -0,0 +1,116 @@
+use std::{
+    io,
+    net::SocketAddr,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use http::Uri;
+use tokio::sync::mpsc;
+
+/// A step in the life of a connection, reported to the channel given to
+/// [`ClientBuilder::connection_events`].
+///
+/// Events of one connection share its `id`, from `Established` on. Events
+/// are dropped rather than awaited when the channel is full or closed, so a
+/// slow or dropped receiver never holds up a connect.
+///
+/// [`ClientBuilder::connection_events`]: crate::ClientBuilder::connection_events
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum ConnEvent {
+    /// Resolving the host name of a destination.
+    Resolving {
+        /// The host being resolved.
+        host: String,
+    },
+    /// Opening a TCP connection to one of the resolved addresses.
+    Connecting {
+        /// The address being connected to.
+        addr: SocketAddr,
+    },
+    /// Establishing a tunnel or SOCKS session through a proxy.
+    ProxyConnecting {
+        /// The proxy being connected through.
+        proxy: Uri,
+    },
+    /// Performing the TLS handshake with a host.
+    TlsHandshaking {
+        /// The host being authenticated.
+        host: String,
+    },
+    /// A connection was established and is ready for requests.
+    Established {
+        /// Identifies the connection in later events.
+        id: u64,
+        /// The address of the peer, if known.
+        remote_addr: Option<SocketAddr>,
+        /// Whether the connection goes through a proxy.
+        proxied: bool,
+        /// Whether HTTP/2 was negotiated.
+        http2: bool,
+    },
+    /// The server sent a graceful GOAWAY on an HTTP/2 connection. The
+    /// requests in flight on it go on, but new ones go to another connection.
+    Draining {
+        /// The id the connection was established with.
+        id: u64,
+    },
+    /// A connection was closed.
+    Closed {
+        /// The id the connection was established with.
+        id: u64,
+        /// How many requests were sent on the connection, counting each
+        /// HTTP/2 stream.
+        requests: u64,
+        /// How long the connection was open.
+        lifetime: Duration,
+        /// Why the connection was closed.
+        reason: CloseReason,
+    },
+}
+
+/// Why a connection was closed, as reported by [`ConnEvent::Closed`].
+///
+/// The first reason recorded for a connection is the one reported, even if
+/// others followed before it closed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum CloseReason {
+    /// It stayed idle in the pool for longer than the pool idle timeout.
+    IdleTimeout,
+    /// The server sent an HTTP/2 GOAWAY with an error.
+    ServerGoAway,
+    /// The server sent a graceful HTTP/2 GOAWAY, and the connection closed
+    /// once the requests in flight on it were done.
+    Drained,
+    /// Reading from or writing to the connection failed.
+    IoError(io::ErrorKind),
+    /// The client stopped using it, because it was poisoned or the pool had
+    /// no room left for it.
+    Retired,
+    /// None of the above, such as when the server closed it, or the client
+    /// was dropped.
+    Other,
+}
+
+/// The sending half of a connection event channel.
+#[derive(Debug, Clone)]
+pub(crate) struct EventSender(mpsc::Sender<ConnEvent>);
+
+impl EventSender {
+    pub(crate) fn new(tx: mpsc::Sender<ConnEvent>) -> EventSender {
+        EventSender(tx)
+    }
+
+    /// Sends `event` if there is room for it, and drops it otherwise.
+    pub(crate) fn emit(&self, event: ConnEvent) {
+        let _ = self.0.try_send(event);
+    }
+
+    /// Returns a new connection id.
+    pub(crate) fn next_id() -> u64 {
+        static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+        NEXT_ID.fetch_add(1, Ordering::Relaxed)
+    }
+}
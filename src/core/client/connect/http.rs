@@ -8,7 +8,7 @@ use std::{
     pin::Pin,
     sync::Arc,
     task::{self, Poll, ready},
-    time::Duration,
+    time::{Duration, Instant},
 };
 
 use futures_util::future::Either;
@@ -16,15 +16,23 @@ use http::uri::{Scheme, Uri};
 use pin_project_lite::pin_project;
 use socket2::TcpKeepalive;
 use tokio::{
+    io::AsyncWriteExt,
     net::{TcpSocket, TcpStream},
     time::Sleep,
 };
 
 use super::{
     Connected, Connection,
-    dns::{self, GaiResolver, Resolve, resolve},
+    dns::{self, AddressOrder, GaiResolver, Resolve, resolve},
+    event::{ConnEvent, EventSender},
+    proxy::ProxyProtocol,
+    timing::ConnectTimingRecorder,
 };
-use crate::core::{error::BoxError, rt::TokioIo};
+use crate::core::{client::Dst, error::BoxError, rt::TokioIo};
+
+/// A hook called with every socket once it connected, to set options the
+/// connector doesn't.
+pub(crate) type SocketHook = Arc<dyn Fn(&TcpStream) -> io::Result<()> + Send + Sync>;
 
 /// A connector for the `http` scheme.
 ///
@@ -71,10 +79,12 @@ struct Config {
     connect_timeout: Option<Duration>,
     enforce_http: bool,
     happy_eyeballs_timeout: Option<Duration>,
+    address_order: AddressOrder,
     tcp_keepalive_config: TcpKeepaliveConfig,
     local_address_ipv4: Option<Ipv4Addr>,
     local_address_ipv6: Option<Ipv6Addr>,
     nodelay: bool,
+    linger: Option<Duration>,
     reuse_address: bool,
     send_buffer_size: Option<usize>,
     recv_buffer_size: Option<usize>,
@@ -92,6 +102,15 @@ struct Config {
     interface: Option<std::ffi::CString>,
     #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
     tcp_user_timeout: Option<Duration>,
+    tcp_maxseg: Option<u32>,
+    #[cfg(target_os = "linux")]
+    tcp_fastopen: bool,
+    #[cfg(target_os = "linux")]
+    netns: Option<std::path::PathBuf>,
+    proxy_protocol: Option<ProxyProtocol>,
+    on_socket: Option<SocketHook>,
+    timing: Option<ConnectTimingRecorder>,
+    events: Option<EventSender>,
 }
 
 #[derive(Default, Debug, Clone, Copy)]
@@ -234,10 +253,12 @@ impl<R> HttpConnector<R> {
                 connect_timeout: None,
                 enforce_http: true,
                 happy_eyeballs_timeout: Some(Duration::from_millis(300)),
+                address_order: AddressOrder::AsResolved,
                 tcp_keepalive_config: TcpKeepaliveConfig::default(),
                 local_address_ipv4: None,
                 local_address_ipv6: None,
                 nodelay: false,
+                linger: None,
                 reuse_address: false,
                 send_buffer_size: None,
                 recv_buffer_size: None,
@@ -256,6 +277,15 @@ impl<R> HttpConnector<R> {
                 interface: None,
                 #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
                 tcp_user_timeout: None,
+                tcp_maxseg: None,
+                #[cfg(target_os = "linux")]
+                tcp_fastopen: false,
+                #[cfg(target_os = "linux")]
+                netns: None,
+                proxy_protocol: None,
+                on_socket: None,
+                timing: None,
+                events: None,
             }),
             resolver,
         }
@@ -302,6 +332,15 @@ impl<R> HttpConnector<R> {
         self.config_mut().nodelay = nodelay;
     }
 
+    /// Sets the value of the `SO_LINGER` option on the socket.
+    ///
+    /// `Some(Duration::ZERO)` makes closing the socket send a RST instead of
+    /// a FIN, discarding any data not yet sent. `None` keeps the OS default.
+    #[inline]
+    pub fn set_linger(&mut self, linger: Option<Duration>) {
+        self.config_mut().linger = linger;
+    }
+
     /// Sets the value of the SO_SNDBUF option on the socket.
     #[inline]
     pub fn set_send_buffer_size(&mut self, size: Option<usize>) {
@@ -343,6 +382,33 @@ impl<R> HttpConnector<R> {
         cfg.local_address_ipv6 = Some(addr_ipv6);
     }
 
+    /// Binds sockets to the local addresses and interface requested for
+    /// `dst`, keeping the configured ones where the request sets none.
+    pub(crate) fn bind_to(&mut self, dst: &Dst) {
+        match dst.addresses() {
+            (Some(a), Some(b)) => self.set_local_addresses(a, b),
+            (Some(a), None) => self.config_mut().local_address_ipv4 = Some(a),
+            (None, Some(b)) => self.config_mut().local_address_ipv6 = Some(b),
+            _ => (),
+        }
+
+        #[cfg(any(
+            target_os = "android",
+            target_os = "fuchsia",
+            target_os = "illumos",
+            target_os = "ios",
+            target_os = "linux",
+            target_os = "macos",
+            target_os = "solaris",
+            target_os = "tvos",
+            target_os = "visionos",
+            target_os = "watchos",
+        ))]
+        if let Some(interface) = dst.interface() {
+            self.set_interface(interface.clone());
+        }
+    }
+
     /// Set the connect timeout.
     ///
     /// If a domain resolves to multiple IP addresses, the timeout will be
@@ -361,7 +427,8 @@ impl<R> HttpConnector<R> {
     /// elapses, then connector will in parallel attempt connection using other
     /// address family.
     ///
-    /// If `None`, parallel connection attempts are disabled.
+    /// If `None`, parallel connection attempts are disabled. The timeout never
+    /// exceeds the connect timeout, if one is set.
     ///
     /// Default is 300 milliseconds.
     ///
@@ -371,6 +438,15 @@ impl<R> HttpConnector<R> {
         self.config_mut().happy_eyeballs_timeout = dur;
     }
 
+    /// Set the order in which the addresses a host resolves to are tried,
+    /// which also decides the preferred family for Happy Eyeballs.
+    ///
+    /// Default is [`AddressOrder::AsResolved`].
+    #[inline]
+    pub fn set_address_order(&mut self, order: AddressOrder) {
+        self.config_mut().address_order = order;
+    }
+
     /// Set that all socket have `SO_REUSEADDR` set to the supplied value `reuse_address`.
     ///
     /// Default is `false`.
@@ -441,6 +517,77 @@ impl<R> HttpConnector<R> {
         self.config_mut().tcp_user_timeout = time;
     }
 
+    /// Sets the value of the `TCP_MAXSEG` option on the socket, before it
+    /// connects, which caps the MSS it advertises and sends with.
+    ///
+    /// Only Linux supports this; elsewhere it is ignored.
+    #[inline]
+    pub fn set_tcp_maxseg(&mut self, mss: Option<u32>) {
+        self.config_mut().tcp_maxseg = mss;
+    }
+
+    /// Sets whether sockets connect with TCP Fast Open, through the
+    /// `TCP_FASTOPEN_CONNECT` option.
+    ///
+    /// With a cookie from an earlier connection to the same server, the first
+    /// write goes out in the SYN. A connect that fails with `EOPNOTSUPP` is
+    /// tried again without it.
+    #[cfg(target_os = "linux")]
+    #[inline]
+    pub fn set_tcp_fastopen(&mut self, enabled: bool) {
+        self.config_mut().tcp_fastopen = enabled;
+    }
+
+    /// Sets the network namespace that sockets produced by this connector are
+    /// created in, given as a path such as `/var/run/netns/<name>` or
+    /// `/proc/<pid>/ns/net`.
+    ///
+    /// Creating a socket switches the calling thread into the namespace with
+    /// `setns(2)`, creates the socket, and switches straight back, with no
+    /// await point in between; the socket then keeps using the namespace it
+    /// was created in. This requires `CAP_SYS_ADMIN`, and the switch back
+    /// failing panics rather than leave a runtime thread in the wrong
+    /// namespace. Name resolution is not affected and still happens in the
+    /// process' own namespace.
+    #[cfg(target_os = "linux")]
+    #[inline]
+    pub fn set_netns(&mut self, path: Option<std::path::PathBuf>) {
+        self.config_mut().netns = path;
+    }
+
+    /// Sets a PROXY protocol header to write on every connection, right after
+    /// the TCP connect and before anything else is sent.
+    #[inline]
+    pub fn set_proxy_protocol(&mut self, header: Option<ProxyProtocol>) {
+        self.config_mut().proxy_protocol = header;
+    }
+
+    /// Sets a hook called with every socket right after it connects, before
+    /// anything is sent on it. An error from the hook fails the connect.
+    #[inline]
+    pub(crate) fn set_on_socket(&mut self, hook: Option<SocketHook>) {
+        self.config_mut().on_socket = hook;
+    }
+
+    /// Records how long name resolution and the TCP connect take into
+    /// `timing`.
+    #[inline]
+    pub(crate) fn set_timing(&mut self, timing: Option<ConnectTimingRecorder>) {
+        self.config_mut().timing = timing;
+    }
+
+    /// Reports name resolution and TCP connect attempts to `events`.
+    #[inline]
+    pub(crate) fn set_events(&mut self, events: Option<EventSender>) {
+        self.config_mut().events = events;
+    }
+
+    /// Returns the sender connection events are reported to, if any.
+    #[inline]
+    pub(crate) fn events(&self) -> Option<&EventSender> {
+        self.config.events.as_ref()
+    }
+
     // private
 
     fn config_mut(&mut self) -> &mut Config {
@@ -548,9 +695,16 @@ where
         let addrs = if let Some(addrs) = dns::SocketAddrs::try_parse(host, port) {
             addrs
         } else {
+            if let Some(ref events) = config.events {
+                events.emit(ConnEvent::Resolving { host: host.into() });
+            }
+            let start = Instant::now();
             let addrs = resolve(&mut self.resolver, dns::Name::new(host.into()))
                 .await
                 .map_err(ConnectError::dns)?;
+            if let Some(ref timing) = config.timing {
+                timing.resolved(start.elapsed());
+            }
             let addrs = addrs
                 .map(|mut addr| {
                     set_port(&mut addr, port, dst.port().is_some());
@@ -561,18 +715,49 @@ where
             dns::SocketAddrs::new(addrs)
         };
 
-        let c = ConnectingTcp::new(addrs, config);
+        let c = ConnectingTcp::new(addrs.ordered(config.address_order), config);
 
-        let sock = c.connect().await?;
+        let start = Instant::now();
+        let mut sock = c.connect().await?;
+        if let Some(ref timing) = config.timing {
+            timing.tcp_connected(start.elapsed());
+        }
 
         if let Err(e) = sock.set_nodelay(config.nodelay) {
             warn!("tcp set_nodelay error: {}", e);
         }
 
+        if let Some(ref on_socket) = config.on_socket {
+            on_socket(&sock).map_err(|e| ConnectError {
+                msg: "socket hook error",
+                addr: sock.peer_addr().ok(),
+                cause: Some(e.into()),
+            })?;
+        }
+
+        if let Some(ref proxy_protocol) = config.proxy_protocol {
+            send_proxy_protocol(&mut sock, proxy_protocol).await?;
+        }
+
         Ok(TokioIo::new(sock))
     }
 }
 
+async fn send_proxy_protocol(
+    sock: &mut TcpStream,
+    proxy_protocol: &ProxyProtocol,
+) -> Result<(), ConnectError> {
+    const MSG: &str = "error sending PROXY protocol header";
+
+    let peer = sock.peer_addr().map_err(ConnectError::m(MSG))?;
+    let header = proxy_protocol.encode(peer).map_err(ConnectError::m(MSG))?;
+    sock.write_all(&header).await.map_err(|e| ConnectError {
+        msg: MSG,
+        addr: Some(peer),
+        cause: Some(e.into()),
+    })
+}
+
 impl Connection for TcpStream {
     fn connected(&self) -> Connected {
         let connected = Connected::new();
@@ -668,6 +853,16 @@ impl ConnectError {
     {
         move |cause| ConnectError::new(msg, cause)
     }
+
+    /// Returns whether the socket refused the operation as unsupported.
+    #[cfg(target_os = "linux")]
+    fn is_unsupported(&self) -> bool {
+        self.cause
+            .as_ref()
+            .and_then(|cause| cause.downcast_ref::<io::Error>())
+            .and_then(io::Error::raw_os_error)
+            == Some(libc::EOPNOTSUPP)
+    }
 }
 
 impl fmt::Debug for ConnectError {
@@ -705,6 +900,10 @@ struct ConnectingTcp<'a> {
 impl<'a> ConnectingTcp<'a> {
     fn new(remote_addrs: dns::SocketAddrs, config: &'a Config) -> Self {
         if let Some(fallback_timeout) = config.happy_eyeballs_timeout {
+            // The fallback has to start within the connect budget to help.
+            let fallback_timeout = config
+                .connect_timeout
+                .map_or(fallback_timeout, |budget| fallback_timeout.min(budget));
             let (preferred_addrs, fallback_addrs) = remote_addrs
                 .split_by_preference(config.local_address_ipv4, config.local_address_ipv6);
             if fallback_addrs.is_empty() {
@@ -759,7 +958,26 @@ impl ConnectingTcpRemote {
         let mut err = None;
         for addr in &mut self.addrs {
             debug!("connecting to {}", addr);
-            match connect(&addr, config, self.connect_timeout)?.await {
+            if let Some(ref events) = config.events {
+                events.emit(ConnEvent::Connecting { addr });
+            }
+            let result = connect(&addr, config, self.connect_timeout)?.await;
+            #[cfg(target_os = "linux")]
+            let result = match result {
+                Err(e) if config.tcp_fastopen && e.is_unsupported() => {
+                    debug!(
+                        "TCP Fast Open not supported, connecting to {} without it",
+                        addr
+                    );
+                    let config = Config {
+                        tcp_fastopen: false,
+                        ..config.clone()
+                    };
+                    connect(&addr, &config, self.connect_timeout)?.await
+                }
+                result => result,
+            };
+            match result {
                 Ok(tcp) => {
                     debug!("connected to {}", addr);
                     return Ok(tcp);
@@ -823,6 +1041,17 @@ fn connect(
     use socket2::{Domain, Protocol, Socket, Type};
 
     let domain = Domain::for_address(*addr);
+
+    #[cfg(target_os = "linux")]
+    let socket = match config.netns {
+        Some(ref netns) => netns::socket_in(netns, || {
+            Socket::new(domain, Type::STREAM, Some(Protocol::TCP))
+        })
+        .map_err(ConnectError::m("tcp open in network namespace error"))?,
+        None => Socket::new(domain, Type::STREAM, Some(Protocol::TCP))
+            .map_err(ConnectError::m("tcp open error"))?,
+    };
+    #[cfg(not(target_os = "linux"))]
     let socket = Socket::new(domain, Type::STREAM, Some(Protocol::TCP))
         .map_err(ConnectError::m("tcp open error"))?;
 
@@ -891,6 +1120,12 @@ fn connect(
         }
     }
 
+    if let Some(linger) = config.linger {
+        if let Err(e) = socket.set_linger(Some(linger)) {
+            warn!("tcp set_linger error: {}", e);
+        }
+    }
+
     #[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
     if let Some(tcp_user_timeout) = &config.tcp_user_timeout {
         if let Err(e) = socket.set_tcp_user_timeout(Some(*tcp_user_timeout)) {
@@ -898,6 +1133,25 @@ fn connect(
         }
     }
 
+    if let Some(mss) = config.tcp_maxseg {
+        #[cfg(target_os = "linux")]
+        if let Err(e) = socket.set_mss(mss) {
+            warn!("tcp set_mss error: {}", e);
+        }
+        #[cfg(not(target_os = "linux"))]
+        debug!(
+            "TCP_MAXSEG is not supported on this platform, ignoring {}",
+            mss
+        );
+    }
+
+    #[cfg(target_os = "linux")]
+    if config.tcp_fastopen {
+        if let Err(e) = set_tcp_fastopen_connect(&socket) {
+            warn!("tcp set_tcp_fastopen_connect error: {}", e);
+        }
+    }
+
     bind_local_address(
         &socket,
         addr,
@@ -957,6 +1211,29 @@ fn connect(
     })
 }
 
+/// Makes the connect of `socket` wait for its first write, which goes out in
+/// the SYN when the server handed out a Fast Open cookie before.
+#[cfg(target_os = "linux")]
+fn set_tcp_fastopen_connect(socket: &socket2::Socket) -> io::Result<()> {
+    use std::os::fd::AsRawFd;
+
+    let enabled: libc::c_int = 1;
+    // SAFETY: `enabled` outlives the call, and its size is passed along.
+    let ret = unsafe {
+        libc::setsockopt(
+            socket.as_raw_fd(),
+            libc::IPPROTO_TCP,
+            libc::TCP_FASTOPEN_CONNECT,
+            &enabled as *const libc::c_int as *const libc::c_void,
+            std::mem::size_of_val(&enabled) as libc::socklen_t,
+        )
+    };
+    if ret == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
 impl ConnectingTcp<'_> {
     async fn connect(mut self) -> Result<TcpStream, ConnectError> {
         match self.fallback {
@@ -1004,3 +1281,34 @@ fn set_port(addr: &mut SocketAddr, host_port: u16, explicit: bool) {
         addr.set_port(host_port)
     };
 }
+
+#[cfg(target_os = "linux")]
+mod netns {
+    use std::{fs::File, io, os::fd::AsRawFd, path::Path};
+
+    /// Runs `f` with the calling thread switched into the network namespace
+    /// at `path`, then switches back.
+    ///
+    /// `setns(2)` only affects the calling thread, so `f` must not yield.
+    pub(super) fn socket_in<T>(path: &Path, f: impl FnOnce() -> io::Result<T>) -> io::Result<T> {
+        let current = File::open("/proc/thread-self/ns/net")?;
+        let target = File::open(path)?;
+
+        setns(&target)?;
+        let res = f();
+        if let Err(e) = setns(&current) {
+            // Every later socket made on this thread would end up in the
+            // wrong namespace.
+            panic!("failed to restore the network namespace: {e}");
+        }
+        res
+    }
+
+    fn setns(ns: &File) -> io::Result<()> {
+        // SAFETY: `ns` is an open file, and `setns` does not take ownership of it.
+        if unsafe { libc::setns(ns.as_raw_fd(), libc::CLONE_NEWNET) } == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
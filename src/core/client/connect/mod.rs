@@ -71,19 +71,33 @@
 use std::{
     fmt::{self, Formatter},
     sync::{
-        Arc,
-        atomic::{AtomicBool, Ordering},
+        Arc, OnceLock,
+        atomic::{AtomicBool, AtomicU64, Ordering},
     },
+    time::{Duration, Instant},
 };
 
 use ::http::Extensions;
 
-pub use self::http::{HttpConnector, HttpInfo};
-use crate::core::error::BoxError;
+pub use self::{
+    byte_counts::ByteCounts,
+    event::{CloseReason, ConnEvent},
+    http::{HttpConnector, HttpInfo},
+    timing::ConnectTiming,
+};
+pub(crate) use self::{http::SocketHook, timing::ConnectTimingRecorder};
+pub(crate) use crate::core::proto::h2::{
+    ping::PingStats,
+    watch::{PeerSettings, SettingsRtt},
+};
+use crate::{core::error::BoxError, http2::Http2PeerSettings};
 
+mod byte_counts;
 pub mod dns;
+pub(crate) mod event;
 mod http;
 pub mod proxy;
+mod timing;
 
 pub(crate) mod capture;
 #[allow(unused)]
@@ -107,6 +121,11 @@ pub struct Connected {
     pub(super) is_proxied: bool,
     pub(super) extra: Option<Extra>,
     pub(super) poisoned: PoisonPill,
+    pub(super) h2_protocol_error: Option<ProtocolErrorHook>,
+    pub(super) h2_drain: Option<DrainHook>,
+    pub(super) reuse_check: Option<ReuseCheck>,
+    pub(super) stats: ConnStats,
+    pub(super) early_data: bool,
 }
 
 #[derive(Clone)]
@@ -141,6 +160,100 @@ impl PoisonPill {
     }
 }
 
+/// When a connection was established, how many requests it carried, the
+/// round trips of its HTTP/2 SETTINGS and PINGs, and the server's SETTINGS,
+/// shared by every copy of its `Connected`.
+#[derive(Clone, Debug)]
+pub(crate) struct ConnStats(Arc<Stats>);
+
+#[derive(Debug)]
+struct Stats {
+    established_at: Instant,
+    request_count: AtomicU64,
+    settings_rtt: SettingsRtt,
+    peer_settings: PeerSettings,
+    ping: PingStats,
+    close_reason: OnceLock<CloseReason>,
+}
+
+impl ConnStats {
+    /// Starts the stats of a connection established now.
+    pub(crate) fn new() -> ConnStats {
+        ConnStats(Arc::new(Stats {
+            established_at: Instant::now(),
+            request_count: AtomicU64::new(0),
+            settings_rtt: SettingsRtt::default(),
+            peer_settings: PeerSettings::default(),
+            ping: PingStats::default(),
+            close_reason: OnceLock::new(),
+        }))
+    }
+
+    pub(crate) fn established_at(&self) -> Instant {
+        self.0.established_at
+    }
+
+    pub(crate) fn request_count(&self) -> u64 {
+        self.0.request_count.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn settings_rtt(&self) -> &SettingsRtt {
+        &self.0.settings_rtt
+    }
+
+    pub(crate) fn peer_settings(&self) -> &PeerSettings {
+        &self.0.peer_settings
+    }
+
+    pub(crate) fn ping(&self) -> &PingStats {
+        &self.0.ping
+    }
+
+    /// Records why the connection is about to close, unless a reason was
+    /// recorded already.
+    pub(crate) fn close(&self, reason: CloseReason) {
+        let _ = self.0.close_reason.set(reason);
+    }
+
+    pub(crate) fn close_reason(&self) -> Option<CloseReason> {
+        self.0.close_reason.get().copied()
+    }
+
+    fn count_request(&self) {
+        self.0.request_count.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+/// Notified when an HTTP/2 connection fails with a protocol level error.
+#[derive(Clone)]
+pub(crate) struct ProtocolErrorHook(Arc<dyn Fn() + Send + Sync>);
+
+impl fmt::Debug for ProtocolErrorHook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProtocolErrorHook").finish()
+    }
+}
+
+/// Notified when the server sends an HTTP/2 connection a graceful GOAWAY.
+#[derive(Clone)]
+pub(crate) struct DrainHook(Arc<dyn Fn() + Send + Sync>);
+
+impl fmt::Debug for DrainHook {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DrainHook").finish()
+    }
+}
+
+/// Tells whether an idle HTTP/1 connection still looks alive.
+#[derive(Clone)]
+pub(crate) struct ReuseCheck(Arc<dyn Fn() -> bool + Send + Sync>);
+
+impl fmt::Debug for ReuseCheck {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ReuseCheck").finish()
+    }
+}
+
 pub(super) struct Extra(Box<dyn ExtraInner>);
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -163,6 +276,11 @@ impl Connected {
             is_proxied: false,
             extra: None,
             poisoned: PoisonPill::healthy(),
+            h2_protocol_error: None,
+            h2_drain: None,
+            reuse_check: None,
+            stats: ConnStats::new(),
+            early_data: false,
         }
     }
 
@@ -227,11 +345,137 @@ impl Connected {
     /// A poisoned connection will not be reused for subsequent requests by the pool
     pub fn poison(&self) {
         self.poisoned.poison();
+        self.stats.close(CloseReason::Retired);
         debug!(
             "connection was poisoned. this connection will not be reused for subsequent requests"
         );
     }
 
+    /// Returns when the connection was established.
+    pub fn established_at(&self) -> Instant {
+        self.stats.established_at()
+    }
+
+    /// Returns how many requests were sent on the connection, counting each
+    /// HTTP/2 stream.
+    pub fn request_count(&self) -> u64 {
+        self.stats.request_count()
+    }
+
+    /// Returns the time from sending the initial HTTP/2 SETTINGS to the
+    /// server acknowledging them.
+    ///
+    /// This is `None` for HTTP/1 connections, and until the acknowledgement
+    /// arrives.
+    pub fn settings_rtt(&self) -> Option<Duration> {
+        self.stats.settings_rtt().get()
+    }
+
+    /// Returns the settings in the first SETTINGS frame the server sent.
+    ///
+    /// This is `None` for HTTP/1 connections, and until the frame is read.
+    pub fn http2_peer_settings(&self) -> Option<&Http2PeerSettings> {
+        self.stats.peer_settings().get()
+    }
+
+    /// Returns the round trip of the last HTTP/2 PING that the server
+    /// answered.
+    ///
+    /// PINGs are only sent for keep-alive, adaptive flow control, or checks
+    /// on reuse, so this is `None` without them, for HTTP/1 connections, and
+    /// until the first answer arrives.
+    pub fn ping_rtt(&self) -> Option<Duration> {
+        self.stats.ping().rtt()
+    }
+
+    /// Returns how many HTTP/2 PINGs were sent since the server last answered
+    /// one.
+    ///
+    /// A connection has a single PING in flight at a time, so this is `1`
+    /// while one is waiting for its answer. Along with
+    /// [`ping_rtt`](Connected::ping_rtt), it tells a slow link, whose
+    /// answers arrive late, from a dead one, whose PING stays unanswered
+    /// until the keep-alive timeout closes the connection.
+    pub fn unanswered_pings(&self) -> u64 {
+        self.stats.ping().unanswered()
+    }
+
+    /// Share the stats of the connection with an observer of it, which
+    /// created them when the connection was established.
+    pub(crate) fn stats(mut self, stats: ConnStats) -> Connected {
+        self.stats = stats;
+        self
+    }
+
+    pub(super) fn count_request(&self) {
+        self.stats.count_request();
+    }
+
+    /// Set a callback for when HTTP/2 on this connection fails in a way that
+    /// suggests the peer, or something in between, doesn't speak it correctly.
+    pub(crate) fn on_h2_protocol_error<F>(mut self, f: F) -> Connected
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.h2_protocol_error = Some(ProtocolErrorHook(Arc::new(f)));
+        self
+    }
+
+    pub(super) fn h2_protocol_error(&self) {
+        if let Some(ProtocolErrorHook(ref f)) = self.h2_protocol_error {
+            f();
+        }
+    }
+
+    /// Set a callback for when the server sends a graceful GOAWAY on this
+    /// HTTP/2 connection, after which it only drains the requests in flight.
+    pub(crate) fn on_h2_drain<F>(mut self, f: F) -> Connected
+    where
+        F: Fn() + Send + Sync + 'static,
+    {
+        self.h2_drain = Some(DrainHook(Arc::new(f)));
+        self
+    }
+
+    pub(super) fn h2_drain(&self) {
+        self.stats.close(CloseReason::Drained);
+        if let Some(DrainHook(ref f)) = self.h2_drain {
+            f();
+        }
+    }
+
+    /// Ask for the connection to be checked before it is reused from the
+    /// pool: HTTP/2 connections with a PING, HTTP/1 ones with `f`, which
+    /// returns whether the connection still looks alive.
+    pub(crate) fn check_on_reuse<F>(mut self, f: F) -> Connected
+    where
+        F: Fn() -> bool + Send + Sync + 'static,
+    {
+        self.reuse_check = Some(ReuseCheck(Arc::new(f)));
+        self
+    }
+
+    /// Set that the connection was handed over with its TLS handshake still
+    /// in early data, before the server answered.
+    pub(crate) fn early_data(mut self) -> Connected {
+        self.early_data = true;
+        self
+    }
+
+    /// Whether the connection was handed over in early data.
+    pub(super) fn is_early_data(&self) -> bool {
+        self.early_data
+    }
+
+    pub(super) fn checks_on_reuse(&self) -> bool {
+        self.reuse_check.is_some()
+    }
+
+    /// Whether an idle HTTP/1 connection still looks alive.
+    pub(crate) fn probe(&self) -> bool {
+        self.reuse_check.as_ref().is_none_or(|ReuseCheck(f)| f())
+    }
+
     // Don't public expose that `Connected` is `Clone`, unsure if we want to
     // keep that contract...
     pub(super) fn clone(&self) -> Connected {
@@ -240,6 +484,11 @@ impl Connected {
             is_proxied: self.is_proxied,
             extra: self.extra.clone(),
             poisoned: self.poisoned.clone(),
+            h2_protocol_error: self.h2_protocol_error.clone(),
+            h2_drain: self.h2_drain.clone(),
+            reuse_check: self.reuse_check.clone(),
+            stats: self.stats.clone(),
+            early_data: self.early_data,
         }
     }
 }
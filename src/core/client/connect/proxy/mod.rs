@@ -1,8 +1,12 @@
 //! Proxy helpers
+mod protocol;
 #[cfg(feature = "socks")]
 mod socks;
 mod tunnel;
 
 #[cfg(feature = "socks")]
-pub use self::socks::Socks;
-pub use self::tunnel::Tunnel;
+pub use self::socks::{Socks, SocksBoundAddr, SocksError, SocksUdpSocket};
+pub use self::{
+    protocol::{ClientInfo, ProxyProtocol, ProxyProtocolVersion},
+    tunnel::{Tunnel, TunnelError},
+};
@@ -0,0 +1,235 @@
+//! PROXY protocol headers
+//!
+//! See <https://www.haproxy.org/download/2.9/doc/proxy-protocol.txt>.
+
+use std::{
+    fmt::Write as _,
+    io,
+    net::{IpAddr, SocketAddr},
+};
+
+use bytes::{BufMut, Bytes, BytesMut};
+
+/// The signature every PROXY protocol v2 header starts with.
+const V2_SIGNATURE: [u8; 12] = *b"\r\n\r\n\0\r\nQUIT\n";
+
+/// Version 2, `PROXY` command.
+const V2_PROXY: u8 = 0x21;
+
+/// `AF_INET` over `SOCK_STREAM`.
+const V2_TCP4: u8 = 0x11;
+
+/// `AF_INET6` over `SOCK_STREAM`.
+const V2_TCP6: u8 = 0x21;
+
+/// The version of the PROXY protocol header to send.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProxyProtocolVersion {
+    /// The human-readable text format.
+    V1,
+    /// The binary format, which can also carry TLVs.
+    V2,
+}
+
+/// The client a PROXY protocol header is sent on behalf of.
+#[derive(Debug, Clone)]
+pub struct ClientInfo {
+    source: SocketAddr,
+    destination: Option<SocketAddr>,
+    tlvs: Vec<(u8, Bytes)>,
+}
+
+impl ClientInfo {
+    /// Creates the info for a client connecting from `source`.
+    ///
+    /// The destination defaults to the address the connection was made to.
+    pub fn new(source: SocketAddr) -> ClientInfo {
+        ClientInfo {
+            source,
+            destination: None,
+            tlvs: Vec::new(),
+        }
+    }
+
+    /// Sets the destination address reported to the server.
+    pub fn destination(mut self, addr: SocketAddr) -> ClientInfo {
+        self.destination = Some(addr);
+        self
+    }
+
+    /// Appends a type-length-value field, such as `PP2_TYPE_AUTHORITY`
+    /// (`0x02`).
+    ///
+    /// TLVs are only sent with [`ProxyProtocolVersion::V2`].
+    pub fn tlv(mut self, kind: u8, value: impl Into<Bytes>) -> ClientInfo {
+        self.tlvs.push((kind, value.into()));
+        self
+    }
+}
+
+/// A PROXY protocol header to send at the start of every connection.
+#[derive(Debug, Clone)]
+pub struct ProxyProtocol {
+    version: ProxyProtocolVersion,
+    client: ClientInfo,
+}
+
+impl ProxyProtocol {
+    /// Creates a header of `version` for `client`.
+    pub fn new(version: ProxyProtocolVersion, client: ClientInfo) -> ProxyProtocol {
+        ProxyProtocol { version, client }
+    }
+
+    /// Encodes the header for a connection to `peer`.
+    pub(crate) fn encode(&self, peer: SocketAddr) -> io::Result<Bytes> {
+        let (source, destination) =
+            same_family(self.client.source, self.client.destination.unwrap_or(peer));
+
+        match self.version {
+            ProxyProtocolVersion::V1 => Ok(encode_v1(source, destination)),
+            ProxyProtocolVersion::V2 => encode_v2(source, destination, &self.client.tlvs),
+        }
+    }
+}
+
+/// Maps both addresses to IPv6 if only one of them is, as the header
+/// carries a single address family.
+fn same_family(source: SocketAddr, destination: SocketAddr) -> (SocketAddr, SocketAddr) {
+    fn to_v6(addr: SocketAddr) -> SocketAddr {
+        match addr.ip() {
+            IpAddr::V4(ip) => SocketAddr::new(IpAddr::V6(ip.to_ipv6_mapped()), addr.port()),
+            IpAddr::V6(_) => addr,
+        }
+    }
+
+    if source.is_ipv4() == destination.is_ipv4() {
+        (source, destination)
+    } else {
+        (to_v6(source), to_v6(destination))
+    }
+}
+
+fn encode_v1(source: SocketAddr, destination: SocketAddr) -> Bytes {
+    let family = if source.is_ipv4() { "TCP4" } else { "TCP6" };
+    let mut header = String::with_capacity(107);
+    let _ = write!(
+        header,
+        "PROXY {} {} {} {} {}\r\n",
+        family,
+        source.ip(),
+        destination.ip(),
+        source.port(),
+        destination.port()
+    );
+    Bytes::from(header)
+}
+
+fn encode_v2(
+    source: SocketAddr,
+    destination: SocketAddr,
+    tlvs: &[(u8, Bytes)],
+) -> io::Result<Bytes> {
+    let too_long = || io::Error::new(io::ErrorKind::InvalidInput, "PROXY header too long");
+
+    let mut body = BytesMut::new();
+    let family = match (source.ip(), destination.ip()) {
+        (IpAddr::V4(src), IpAddr::V4(dst)) => {
+            body.put_slice(&src.octets());
+            body.put_slice(&dst.octets());
+            V2_TCP4
+        }
+        (IpAddr::V6(src), IpAddr::V6(dst)) => {
+            body.put_slice(&src.octets());
+            body.put_slice(&dst.octets());
+            V2_TCP6
+        }
+        _ => unreachable!("addresses are mapped to the same family"),
+    };
+    body.put_u16(source.port());
+    body.put_u16(destination.port());
+
+    for (kind, value) in tlvs {
+        let len = u16::try_from(value.len()).map_err(|_| too_long())?;
+        body.put_u8(*kind);
+        body.put_u16(len);
+        body.put_slice(value);
+    }
+
+    let len = u16::try_from(body.len()).map_err(|_| too_long())?;
+    let mut header = BytesMut::with_capacity(16 + body.len());
+    header.put_slice(&V2_SIGNATURE);
+    header.put_u8(V2_PROXY);
+    header.put_u8(family);
+    header.put_u16(len);
+    header.put_slice(&body);
+    Ok(header.freeze())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn v1_tcp4() {
+        let client = ClientInfo::new(addr("192.0.2.1:56324"));
+        let header = ProxyProtocol::new(ProxyProtocolVersion::V1, client)
+            .encode(addr("198.51.100.7:443"))
+            .unwrap();
+        assert_eq!(header, "PROXY TCP4 192.0.2.1 198.51.100.7 56324 443\r\n");
+    }
+
+    #[test]
+    fn v1_mixed_families_use_tcp6() {
+        let client = ClientInfo::new(addr("192.0.2.1:56324")).destination(addr("[2001:db8::1]:80"));
+        let header = ProxyProtocol::new(ProxyProtocolVersion::V1, client)
+            .encode(addr("198.51.100.7:443"))
+            .unwrap();
+        assert_eq!(
+            header,
+            "PROXY TCP6 ::ffff:192.0.2.1 2001:db8::1 56324 80\r\n"
+        );
+    }
+
+    #[test]
+    fn v2_tcp4_with_tlv() {
+        let client = ClientInfo::new(addr("192.0.2.1:56324")).tlv(0x02, "example.com");
+        let header = ProxyProtocol::new(ProxyProtocolVersion::V2, client)
+            .encode(addr("198.51.100.7:443"))
+            .unwrap();
+
+        let mut expected = V2_SIGNATURE.to_vec();
+        expected.extend_from_slice(&[0x21, 0x11, 0x00, 26]);
+        expected.extend_from_slice(&[192, 0, 2, 1, 198, 51, 100, 7]);
+        expected.extend_from_slice(&56324u16.to_be_bytes());
+        expected.extend_from_slice(&443u16.to_be_bytes());
+        expected.extend_from_slice(&[0x02, 0x00, 11]);
+        expected.extend_from_slice(b"example.com");
+        assert_eq!(header, expected);
+    }
+
+    #[test]
+    fn v2_tcp6() {
+        let client = ClientInfo::new(addr("[2001:db8::2]:1000"));
+        let header = ProxyProtocol::new(ProxyProtocolVersion::V2, client)
+            .encode(addr("[2001:db8::1]:443"))
+            .unwrap();
+
+        assert_eq!(&header[..12], &V2_SIGNATURE);
+        assert_eq!(&header[12..16], &[0x21, 0x21, 0x00, 36]);
+        assert_eq!(header.len(), 16 + 36);
+        assert_eq!(&header[48..52], &[0x03, 0xe8, 0x01, 0xbb]);
+    }
+
+    #[test]
+    fn v2_rejects_oversized_tlv() {
+        let client = ClientInfo::new(addr("192.0.2.1:1")).tlv(0xe0, vec![0; 70_000]);
+        let err = ProxyProtocol::new(ProxyProtocolVersion::V2, client)
+            .encode(addr("198.51.100.7:443"))
+            .unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+}
@@ -4,6 +4,7 @@ mod v5;
 use std::{
     error::Error as StdError,
     marker::PhantomData,
+    net::SocketAddr,
     pin::Pin,
     task::{Context, Poll},
 };
@@ -13,6 +14,7 @@ use http::Uri;
 use pin_project_lite::pin_project;
 use tower_service::Service;
 use v4::{SocksV4, SocksV4Error};
+pub use v5::SocksUdpSocket;
 use v5::{SocksV5, SocksV5Error};
 
 use crate::core::{
@@ -20,6 +22,35 @@ use crate::core::{
     rt::{Read, Write},
 };
 
+/// The address a SOCKS proxy reported as bound for a tunnel (`BND.ADDR`/`BND.PORT`).
+///
+/// For a `CONNECT` this is informational, typically the proxy's outbound address
+/// towards the target. It is available in the response extensions of requests
+/// sent through a SOCKS proxy.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum SocksBoundAddr {
+    /// An IP address and port.
+    Socket(SocketAddr),
+    /// A domain name and port. Only SOCKSv5 proxies may reply with a domain.
+    Domain(String, u16),
+}
+
+impl SocksBoundAddr {
+    /// Returns the bound port.
+    pub fn port(&self) -> u16 {
+        match self {
+            Self::Socket(socket) => socket.port(),
+            Self::Domain(_, port) => *port,
+        }
+    }
+}
+
+impl From<SocketAddr> for SocksBoundAddr {
+    fn from(addr: SocketAddr) -> Self {
+        Self::Socket(addr)
+    }
+}
+
 #[derive(Debug)]
 pub enum SocksError<C> {
     Inner(C),
@@ -31,6 +62,7 @@ pub enum SocksError<C> {
 
     V4(SocksV4Error),
     V5(SocksV5Error),
+    UdpUnsupported,
 
     Parsing(ParsingError),
     Serialize(SerializeError),
@@ -48,6 +80,17 @@ pub enum SerializeError {
     WouldOverflow,
 }
 
+impl<C> SocksError<C> {
+    /// Whether the handshake failed in a way another server behind the
+    /// same proxy name may not, so it is worth retrying on another address.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::V5(err) => err.is_transient(),
+            _ => false,
+        }
+    }
+}
+
 async fn read_message<T, M, C>(mut conn: &mut T, buf: &mut BytesMut) -> Result<M, SocksError<C>>
 where
     T: Read + Unpin,
@@ -59,7 +102,10 @@ where
         let n = crate::core::rt::read(&mut conn, &mut tmp).await?;
         buf.extend_from_slice(&tmp[..n]);
 
-        match M::try_from(buf) {
+        // Parsing consumes what it reads, so an incomplete message is parsed
+        // from a copy, and parsed again once more of it has arrived.
+        let mut pending = buf.clone();
+        match M::try_from(&mut pending) {
             Err(ParsingError::Incomplete) => {
                 if n == 0 {
                     if buf.spare_capacity_mut().is_empty() {
@@ -74,7 +120,10 @@ where
                 }
             }
             Err(err) => return Err(err.into()),
-            Ok(res) => return Ok(res),
+            Ok(res) => {
+                *buf = pending;
+                return Ok(res);
+            }
         }
     }
 }
@@ -96,6 +145,7 @@ impl<C> std::fmt::Display for SocksError<C> {
 
             Self::V4(e) => e.fmt(f),
             Self::V5(e) => e.fmt(f),
+            Self::UdpUnsupported => f.write_str("SOCKSv4 does not relay UDP"),
         }
     }
 }
@@ -209,6 +259,33 @@ where
     }
 }
 
+impl<C, R> Socks<C, R>
+where
+    C: Service<Uri>,
+    C::Future: Send + 'static,
+    C::Response: Read + Write + Unpin + Send + 'static,
+    C::Error: Send + Sync + 'static,
+    R: Resolve + Clone + Send + 'static,
+    <R as Resolve>::Future: Send + 'static,
+{
+    /// Establish a tunnel to `dst`, also returning the address the proxy reported
+    /// as bound in its reply.
+    pub fn connect(&mut self, dst: Uri) -> BoxHandshaking<(C::Response, SocksBoundAddr), C::Error> {
+        match self {
+            Self::SocksV5(socks_v5) => socks_v5.connect(dst),
+            Self::SocksV4(socks_v4) => socks_v4.connect(dst),
+        }
+    }
+
+    /// Sets up a UDP relay through the proxy, which only SOCKSv5 supports.
+    pub fn udp_associate(&mut self) -> BoxHandshaking<SocksUdpSocket, C::Error> {
+        match self {
+            Self::SocksV5(socks_v5) => socks_v5.udp_associate(),
+            Self::SocksV4(_) => Box::pin(std::future::ready(Err(SocksError::UdpUnsupported))),
+        }
+    }
+}
+
 impl<C, R> Service<Uri> for Socks<C, R>
 where
     C: Service<Uri>,
@@ -239,14 +316,18 @@ where
 
 #[cfg(test)]
 mod tests {
+    use bytes::Bytes;
     use tokio::{
         io::{AsyncReadExt, AsyncWriteExt},
         net::{TcpListener, TcpStream},
     };
     use tower_service::Service;
 
-    use super::{SocksV4, SocksV5};
-    use crate::core::client::connect::HttpConnector;
+    use super::{
+        Socks, SocksBoundAddr, SocksError, SocksV4, SocksV5,
+        v5::{AuthError, SocksV5Error},
+    };
+    use crate::core::client::connect::{HttpConnector, dns::GaiResolver};
 
     #[cfg(not(miri))]
     #[tokio::test]
@@ -549,6 +630,78 @@ mod tests {
         t2.await.expect("task - proxy");
     }
 
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn test_socks_v5_address_types() {
+        let ipv6: std::net::Ipv6Addr = "2001:db8::1".parse().unwrap();
+        let mut v6 = vec![0x04];
+        v6.extend_from_slice(&ipv6.octets());
+        v6.extend_from_slice(&8080u16.to_be_bytes());
+
+        let mut domain = vec![0x03, 11];
+        domain.extend_from_slice(b"example.com");
+        domain.extend_from_slice(&80u16.to_be_bytes());
+
+        let cases = [
+            ("http://192.0.2.1:80", true, vec![0x01, 192, 0, 2, 1, 0, 80]),
+            (
+                "http://192.0.2.1:80",
+                false,
+                vec![0x01, 192, 0, 2, 1, 0, 80],
+            ),
+            ("http://[2001:db8::1]:8080", true, v6.clone()),
+            ("http://[2001:db8::1]:8080", false, v6),
+            ("http://example.com:80", false, domain),
+        ];
+
+        for (target, local_dns, address) in cases {
+            let proxy_tcp = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+            let proxy_addr = proxy_tcp.local_addr().expect("local_addr");
+            let proxy_dst = format!("http://{proxy_addr}").parse().expect("uri");
+
+            let mut connector = SocksV5::new(proxy_dst, HttpConnector::new()).local_dns(local_dns);
+
+            let t1 = tokio::spawn(async move {
+                let (_, bound) = connector
+                    .connect(target.parse().expect("uri"))
+                    .await
+                    .expect("tunnel");
+                let expected = std::net::SocketAddr::new("2001:db8::2".parse().unwrap(), 4321);
+                assert_eq!(bound, SocksBoundAddr::Socket(expected));
+            });
+
+            // Proxy
+            //
+            // Will check the address of the CONNECT command, and reply with an
+            // IPv6 `BND.ADDR` split across writes.
+            let t2 = tokio::spawn(async move {
+                let (mut to_client, _) = proxy_tcp.accept().await.expect("accept");
+                let mut buf = [0u8; 513];
+
+                let n = to_client.read(&mut buf).await.expect("read 1");
+                assert_eq!(&buf[..n], [0x05, 0x01, 0x00]);
+                to_client.write_all(&[0x05, 0x00]).await.expect("write 1");
+
+                let n = to_client.read(&mut buf).await.expect("read 2");
+                assert_eq!(&buf[..3], [0x05, 0x01, 0x00], "{target}");
+                assert_eq!(&buf[3..n], address, "{target}");
+
+                let bound: std::net::Ipv6Addr = "2001:db8::2".parse().unwrap();
+                let mut message = vec![0x05, 0x00, 0x00, 0x04];
+                message.extend_from_slice(&bound.octets());
+                message.extend_from_slice(&4321u16.to_be_bytes());
+                let (first, rest) = message.split_at(10);
+                to_client.write_all(first).await.expect("write 2");
+                to_client.flush().await.expect("flush");
+                tokio::time::sleep(std::time::Duration::from_millis(20)).await;
+                to_client.write_all(rest).await.expect("write 3");
+            });
+
+            t1.await.expect("task - client");
+            t2.await.expect("task - proxy");
+        }
+    }
+
     #[cfg(not(miri))]
     #[tokio::test]
     async fn test_socks_v4_works() {
@@ -685,4 +838,185 @@ mod tests {
         t1.await.expect("task - client");
         t2.await.expect("task - proxy");
     }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn test_socks_v5_reports_bound_addr() {
+        let proxy_tcp = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let proxy_addr = proxy_tcp.local_addr().expect("local_addr");
+        let proxy_dst = format!("http://{proxy_addr}").parse().expect("uri");
+
+        let target_dst = "http://127.0.0.1:1234".parse().expect("uri");
+
+        let mut connector = SocksV5::new(proxy_dst, HttpConnector::new());
+
+        let t1 = tokio::spawn(async move {
+            let (_, bound) = connector.connect(target_dst).await.expect("tunnel");
+            assert_eq!(bound, SocksBoundAddr::Domain("bnd.local".into(), 4321));
+        });
+
+        // Proxy
+        //
+        // Will reply with a domain `BND.ADDR` which the client should report.
+        let t2 = tokio::spawn(async move {
+            let (mut to_client, _) = proxy_tcp.accept().await.expect("accept");
+            let mut buf = [0u8; 513];
+
+            let n = to_client.read(&mut buf).await.expect("read 1");
+            assert_eq!(&buf[..n], [0x05, 0x01, 0x00]);
+            to_client.write_all(&[0x05, 0x00]).await.expect("write 1");
+
+            let _ = to_client.read(&mut buf).await.expect("read 2");
+
+            let [p1, p2] = 4321u16.to_be_bytes();
+            let mut message = vec![0x05, 0x00, 0x00, 0x03, 9];
+            message.extend_from_slice(b"bnd.local");
+            message.extend_from_slice(&[p1, p2]);
+            to_client.write_all(&message).await.expect("write 2");
+        });
+
+        t1.await.expect("task - client");
+        t2.await.expect("task - proxy");
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn test_socks_v4_reports_bound_addr() {
+        let proxy_tcp = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let proxy_addr = proxy_tcp.local_addr().expect("local_addr");
+        let proxy_dst = format!("http://{proxy_addr}").parse().expect("uri");
+
+        let target_dst = "http://127.0.0.1:1234".parse().expect("uri");
+
+        let mut connector = SocksV4::new(proxy_dst, HttpConnector::new());
+
+        let t1 = tokio::spawn(async move {
+            let (_, bound) = connector.connect(target_dst).await.expect("tunnel");
+            let expected = std::net::SocketAddr::new([10, 0, 0, 7].into(), 4321);
+            assert_eq!(bound, SocksBoundAddr::Socket(expected));
+        });
+
+        let t2 = tokio::spawn(async move {
+            let (mut to_client, _) = proxy_tcp.accept().await.expect("accept");
+            let mut buf = [0u8; 512];
+
+            let _ = to_client.read(&mut buf).await.expect("read");
+
+            let [p1, p2] = 4321u16.to_be_bytes();
+            let message = [0, 90, p1, p2, 10, 0, 0, 7];
+            to_client.write_all(&message).await.expect("write");
+        });
+
+        t1.await.expect("task - client");
+        t2.await.expect("task - proxy");
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn test_socks_v5_udp_associate_relays_datagrams() {
+        let proxy_tcp = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let proxy_addr = proxy_tcp.local_addr().expect("local_addr");
+        let proxy_dst = format!("http://{proxy_addr}").parse().expect("uri");
+
+        let relay = tokio::net::UdpSocket::bind("127.0.0.1:0")
+            .await
+            .expect("bind");
+        let relay_addr = relay.local_addr().expect("local_addr");
+
+        let mut connector = SocksV5::new(proxy_dst, HttpConnector::new());
+        let target = std::net::SocketAddr::new([10, 0, 0, 53].into(), 53);
+
+        // Client
+        //
+        // Will send "ping" to the target through the relay, and receive
+        // "pong" back.
+        let t1 = tokio::spawn(async move {
+            let socket = connector.udp_associate().await.expect("associate");
+            assert_eq!(socket.relay_addr(), relay_addr);
+
+            let n = socket.send_to(b"ping", target).await.expect("send");
+            assert_eq!(n, 4);
+
+            let mut buf = [0u8; 64];
+            let (n, from) = socket.recv_from(&mut buf).await.expect("recv");
+            assert_eq!(&buf[..n], b"pong");
+            assert_eq!(from, SocksBoundAddr::Socket(target));
+        });
+
+        // Proxy
+        //
+        // Will receive the UDP ASSOCIATE command, and reply with the relay
+        // address. Keeps the control connection open until the client is
+        // done.
+        let t2 = tokio::spawn(async move {
+            let (mut to_client, _) = proxy_tcp.accept().await.expect("accept");
+            let mut buf = [0u8; 513];
+
+            let n = to_client.read(&mut buf).await.expect("read 1");
+            assert_eq!(&buf[..n], [0x05, 0x01, 0x00]);
+            to_client.write_all(&[0x05, 0x00]).await.expect("write 1");
+
+            let n = to_client.read(&mut buf).await.expect("read 2");
+            assert_eq!(&buf[..n], [0x05, 0x03, 0x00, 0x01, 0, 0, 0, 0, 0, 0]);
+
+            let [p1, p2] = relay_addr.port().to_be_bytes();
+            let message = [0x05, 0x00, 0x00, 0x01, 127, 0, 0, 1, p1, p2];
+            to_client.write_all(&message).await.expect("write 2");
+
+            let _ = to_client.read(&mut buf).await;
+        });
+
+        // Relay
+        //
+        // Will receive the wrapped "ping", and answer with a wrapped "pong"
+        // from the target.
+        let mut buf = [0u8; 64];
+        let (n, client) = relay.recv_from(&mut buf).await.expect("recv");
+        let header = [0, 0, 0, 0x01, 10, 0, 0, 53, 0, 53];
+        assert_eq!(&buf[..n], [&header[..], b"ping"].concat());
+
+        let message = [&header[..], b"pong"].concat();
+        relay.send_to(&message, client).await.expect("send");
+
+        t1.await.expect("task - client");
+        t2.await.expect("task - proxy");
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn test_socks_v5_rejects_long_credentials() {
+        let proxy_tcp = TcpListener::bind("127.0.0.1:0").await.expect("bind");
+        let proxy_addr = proxy_tcp.local_addr().expect("local_addr");
+        let proxy_dst = format!("socks5h://{proxy_addr}").parse().expect("uri");
+
+        let auth = (Bytes::from_static(b"user"), Bytes::from(vec![b'p'; 300]));
+        let mut connector = Socks::new_with_resolver(
+            HttpConnector::new(),
+            GaiResolver::new(),
+            proxy_dst,
+            Some(auth),
+        );
+
+        let t1 = tokio::spawn(async move {
+            let err = connector
+                .connect("http://hyper.rs".parse().unwrap())
+                .await
+                .expect_err("credentials too long");
+            assert!(matches!(
+                err,
+                SocksError::V5(SocksV5Error::Auth(AuthError::CredentialsTooLong))
+            ));
+        });
+
+        // Nothing reaches the proxy.
+        let t2 = tokio::spawn(async move {
+            let (mut to_client, _) = proxy_tcp.accept().await.expect("accept");
+            let mut buf = [0u8; 513];
+            let n = to_client.read(&mut buf).await.expect("read");
+            assert_eq!(n, 0);
+        });
+
+        t1.await.expect("task - client");
+        t2.await.expect("task - proxy");
+    }
 }
@@ -20,9 +20,9 @@ pub struct Request<'a>(pub &'a Address);
 /// |  1  |  1  |    2    |         4         |
 /// +-----+-----+----+----+----+----+----+----+
 ///             ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^
-///              ignore: only for SOCKSv4 BIND
+///              only meaningful for SOCKSv4 BIND
 #[derive(Debug)]
-pub struct Response(pub Status);
+pub struct Response(pub Status, pub SocketAddrV4);
 
 #[derive(Debug)]
 pub enum Address {
@@ -94,7 +94,7 @@ impl TryFrom<&mut BytesMut> for Response {
         }
 
         let status = buf.get_u8().try_into()?;
-        let _addr = {
+        let addr = {
             let port = buf.get_u16();
             let mut ip = [0; 4];
             buf.copy_to_slice(&mut ip);
@@ -102,7 +102,7 @@ impl TryFrom<&mut BytesMut> for Response {
             SocketAddrV4::new(ip.into(), port)
         };
 
-        Ok(Self(status))
+        Ok(Self(status, addr))
     }
 }
 
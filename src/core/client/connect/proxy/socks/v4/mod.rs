@@ -15,7 +15,7 @@ use messages::*;
 use pin_project_lite::pin_project;
 use tower_service::Service;
 
-use super::{BoxHandshaking, Handshaking, SocksError};
+use super::{BoxHandshaking, Handshaking, SocksBoundAddr, SocksError};
 use crate::core::{
     client::connect::dns::{GaiResolver, Name, Resolve},
     rt::{Read, Write},
@@ -115,7 +115,7 @@ where
         mut conn: T,
         host: &str,
         port: u16,
-    ) -> Result<T, super::SocksError<E>>
+    ) -> Result<(T, SocksBoundAddr), super::SocksError<E>>
     where
         T: Read + Write + Unpin,
     {
@@ -163,14 +163,14 @@ where
         // Read Response
         let res: Response = super::read_message(&mut conn, &mut recv_buf).await?;
         if res.0 == Status::Success {
-            Ok(conn)
+            Ok((conn, SocksBoundAddr::Socket(res.1.into())))
         } else {
             Err(SocksV4Error::Command(res.0).into())
         }
     }
 }
 
-impl<C, R> Service<Uri> for SocksV4<C, R>
+impl<C, R> SocksV4<C, R>
 where
     C: Service<Uri>,
     C::Future: Send + 'static,
@@ -179,28 +179,47 @@ where
     R: Resolve + Clone + Send + 'static,
     <R as Resolve>::Future: Send + 'static,
 {
-    type Response = C::Response;
-    type Error = SocksError<C::Error>;
-    type Future = Handshaking<C::Future, C::Response, C::Error>;
-
-    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        self.inner.poll_ready(cx).map_err(SocksError::Inner)
-    }
-
-    fn call(&mut self, dst: Uri) -> Self::Future {
+    /// Establish a tunnel to `dst`, also returning the address the proxy reported
+    /// in the `DSTPORT`/`DSTIP` fields of its reply.
+    pub fn connect(&mut self, dst: Uri) -> BoxHandshaking<(C::Response, SocksBoundAddr), C::Error> {
         let config = self.config.clone();
         let connecting = self.inner.call(config.proxy.clone());
 
         let fut = async move {
             let port = dst.port().map(|p| p.as_u16()).unwrap_or(443);
             let host = dst.host().ok_or(SocksError::MissingHost)?;
+            let host = host.trim_start_matches('[').trim_end_matches(']');
 
             let conn = connecting.await.map_err(SocksError::Inner)?;
             config.execute(conn, host, port).await
         };
 
+        Box::pin(fut)
+    }
+}
+
+impl<C, R> Service<Uri> for SocksV4<C, R>
+where
+    C: Service<Uri>,
+    C::Future: Send + 'static,
+    C::Response: Read + Write + Unpin + Send + 'static,
+    C::Error: Send + 'static,
+    R: Resolve + Clone + Send + 'static,
+    <R as Resolve>::Future: Send + 'static,
+{
+    type Response = C::Response;
+    type Error = SocksError<C::Error>;
+    type Future = Handshaking<C::Future, C::Response, C::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(SocksError::Inner)
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        let connecting = self.connect(dst);
+
         Handshaking {
-            fut: Box::pin(fut),
+            fut: Box::pin(async move { connecting.await.map(|(conn, _)| conn) }),
             _marker: Default::default(),
         }
     }
@@ -13,6 +13,20 @@ pub enum AuthError {
     MethodMismatch,
     Failed,
     MissingCredentials,
+    CredentialsTooLong,
+}
+
+impl SocksV5Error {
+    /// Whether the proxy may accept the same request on another attempt.
+    ///
+    /// Only a general failure and an expired TTL are; the other replies
+    /// describe the request or the target rather than the proxy.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            Self::Command(Status::GeneralServerFailure | Status::TtlExpired)
+        )
+    }
 }
 
 impl From<Status> for SocksV5Error {
@@ -44,6 +58,7 @@ impl std::fmt::Display for AuthError {
             Self::MethodMismatch => "server implements authentication incorrectly",
             Self::Failed => "credentials not accepted",
             Self::MissingCredentials => "missing credentials",
+            Self::CredentialsTooLong => "username or password is more than 255 bytes",
         })
     }
 }
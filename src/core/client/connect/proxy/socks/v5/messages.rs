@@ -42,7 +42,7 @@ pub struct AuthenticationRes(pub bool);
 /// | 1  |  1  | X'00' |  1   | Variable |    2     |
 /// +----+-----+-------+------+----------+----------+
 #[derive(Debug)]
-pub struct ProxyReq<'a>(pub &'a Address);
+pub struct ProxyReq<'a>(pub Command, pub &'a Address);
 
 /// +----+-----+-------+------+----------+----------+
 /// |VER | REP |  RSV  | ATYP | BND.ADDR | BND.PORT |
@@ -50,7 +50,22 @@ pub struct ProxyReq<'a>(pub &'a Address);
 /// | 1  |  1  | X'00' |  1   | Variable |    2     |
 /// +----+-----+-------+------+----------+----------+
 #[derive(Debug)]
-pub struct ProxyRes(pub Status);
+pub struct ProxyRes(pub Status, pub Address);
+
+/// +----+------+------+----------+----------+----------+
+/// |RSV | FRAG | ATYP | DST.ADDR | DST.PORT |   DATA   |
+/// +----+------+------+----------+----------+----------+
+/// | 2  |  1   |  1   | Variable |    2     | Variable |
+/// +----+------+------+----------+----------+----------+
+#[derive(Debug)]
+pub struct UdpHeader(pub Address);
+
+#[repr(u8)]
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub enum Command {
+    Connect = 0x01,
+    UdpAssociate = 0x03,
+}
 
 #[repr(u8)]
 #[derive(Debug, Copy, Clone, PartialEq)]
@@ -150,20 +165,16 @@ impl TryFrom<&mut BytesMut> for AuthenticationRes {
 
 impl ProxyReq<'_> {
     pub fn write_to_buf(&self, buf: &mut BytesMut) -> Result<usize, SerializeError> {
-        let addr_len = match self.0 {
-            Address::Socket(SocketAddr::V4(_)) => 1 + 4 + 2,
-            Address::Socket(SocketAddr::V6(_)) => 1 + 16 + 2,
-            Address::Domain(domain, _) => 1 + 1 + domain.len() + 2,
-        };
+        let addr_len = self.1.len();
 
         if buf.capacity() - buf.len() < 3 + addr_len {
             return Err(SerializeError::WouldOverflow);
         }
 
         buf.put_u8(0x05); // Version
-        buf.put_u8(0x01); // TCP tunneling command
+        buf.put_u8(self.0 as u8); // Command
         buf.put_u8(0x00); // Reserved
-        let _ = self.0.write_to_buf(buf); // Address
+        let _ = self.1.write_to_buf(buf); // Address
 
         Ok(3 + addr_len)
     }
@@ -191,13 +202,59 @@ impl TryFrom<&mut BytesMut> for ProxyRes {
         }
 
         // ATYP + ADDR
-        Address::try_from(buf)?;
+        let addr = Address::try_from(buf)?;
+
+        Ok(Self(status, addr))
+    }
+}
+
+impl UdpHeader {
+    pub fn write_to_buf(&self, buf: &mut BytesMut) -> Result<usize, SerializeError> {
+        if buf.capacity() - buf.len() < 3 + self.0.len() {
+            return Err(SerializeError::WouldOverflow);
+        }
+
+        buf.put_u16(0x0000); // Reserved
+        buf.put_u8(0x00); // Fragment number, as datagrams are never fragmented
+        let _ = self.0.write_to_buf(buf); // Address
+
+        Ok(3 + self.0.len())
+    }
+}
+
+impl TryFrom<&mut BytesMut> for UdpHeader {
+    type Error = ParsingError;
+
+    fn try_from(buf: &mut BytesMut) -> Result<Self, ParsingError> {
+        if buf.remaining() < 3 {
+            return Err(ParsingError::Incomplete);
+        }
+
+        // RSV
+        buf.advance(2);
+
+        // FRAG, only a standalone datagram is supported
+        if buf.get_u8() != 0x00 {
+            return Err(ParsingError::Other);
+        }
+
+        // ATYP + ADDR
+        let addr = Address::try_from(buf)?;
 
-        Ok(Self(status))
+        Ok(Self(addr))
     }
 }
 
 impl Address {
+    /// The length of the address once written, with its type.
+    fn len(&self) -> usize {
+        match self {
+            Self::Socket(SocketAddr::V4(_)) => 1 + 4 + 2,
+            Self::Socket(SocketAddr::V6(_)) => 1 + 16 + 2,
+            Self::Domain(domain, _) => 1 + 1 + domain.len() + 2,
+        }
+    }
+
     pub fn write_to_buf(&self, buf: &mut BytesMut) -> Result<usize, SerializeError> {
         match self {
             Self::Socket(SocketAddr::V4(v4)) => {
@@ -274,6 +331,7 @@ impl TryFrom<&mut BytesMut> for Address {
                 let domain = std::str::from_utf8(&buf[..len as usize])
                     .map_err(|_| ParsingError::Other)?
                     .to_string();
+                buf.advance(len as usize);
 
                 let port = buf.get_u16();
 
@@ -283,7 +341,7 @@ impl TryFrom<&mut BytesMut> for Address {
             0x04 => {
                 let mut ip = [0; 16];
 
-                if buf.remaining() < 6 {
+                if buf.remaining() < 18 {
                     return Err(ParsingError::Incomplete);
                 }
                 buf.copy_to_slice(&mut ip);
@@ -2,6 +2,9 @@ mod errors;
 pub use errors::*;
 
 mod messages;
+mod udp;
+pub use udp::SocksUdpSocket;
+
 use std::{
     future::Future,
     net::{IpAddr, SocketAddr, ToSocketAddrs},
@@ -15,7 +18,7 @@ use messages::*;
 use pin_project_lite::pin_project;
 use tower_service::Service;
 
-use super::{BoxHandshaking, Handshaking, SocksError};
+use super::{BoxHandshaking, Handshaking, SocksBoundAddr, SocksError};
 use crate::core::{
     client::connect::dns::{GaiResolver, Name, Resolve},
     rt::{Read, Write},
@@ -92,7 +95,8 @@ where
 
     /// Use User/Pass authentication method during handshake.
     ///
-    /// Username and Password must be maximum of 255 characters each.
+    /// Username and Password must be maximum of 255 bytes each, or the
+    /// handshake fails before anything is sent.
     /// 0 length strings are allowed despite RFC prohibiting it. This is done so that
     /// for compatablity with server implementations that require it for IP authentication.
     pub fn with_auth<A>(mut self, user: A, pass: A) -> Self
@@ -158,15 +162,22 @@ where
         }
     }
 
-    async fn execute<T, E>(self, mut conn: T, host: &str, port: u16) -> Result<T, SocksError<E>>
+    async fn execute<T, E>(
+        self,
+        conn: T,
+        host: &str,
+        port: u16,
+    ) -> Result<(T, SocksBoundAddr), SocksError<E>>
     where
         T: Read + Write + Unpin,
     {
+        // An IP literal names no host to resolve, so it is sent as an address
+        // of its own family, whether names are resolved here or by the proxy.
         let address = match host.parse::<IpAddr>() {
             Ok(ip) => Address::Socket(SocketAddr::new(ip, port)),
             Err(_) if host.len() <= 255 => {
                 if self.local_dns {
-                    let socket = if let Some(mut resolver) = self.resolver {
+                    let socket = if let Some(mut resolver) = self.resolver.clone() {
                         let mut socket_addr = resolver
                             .resolve(Name::new(host.into()))
                             .await
@@ -190,6 +201,29 @@ where
             Err(_) => return Err(SocksV5Error::HostTooLong.into()),
         };
 
+        let (conn, bound) = self.handshake(conn, Command::Connect, &address).await?;
+        Ok((conn, bound.into()))
+    }
+
+    /// Negotiates authentication with the proxy over `conn`, then sends it
+    /// `command` for `address`, returning the address from its reply.
+    async fn handshake<T, E>(
+        &self,
+        mut conn: T,
+        command: Command,
+        address: &Address,
+    ) -> Result<(T, Address), SocksError<E>>
+    where
+        T: Read + Write + Unpin,
+    {
+        // The lengths are sent as single bytes, so longer credentials are
+        // rejected before anything is sent.
+        if let Some((ref user, ref pass)) = self.proxy_auth {
+            if user.len() > 255 || pass.len() > 255 {
+                return Err(SocksV5Error::Auth(AuthError::CredentialsTooLong).into());
+            }
+        }
+
         let method = if self.proxy_auth.is_some() {
             AuthMethod::UserPass
         } else {
@@ -280,7 +314,7 @@ where
                 }
 
                 State::SendingProxyReq => {
-                    let req = ProxyReq(&address);
+                    let req = ProxyReq(command, address);
 
                     let start = send_buf.len();
                     req.write_to_buf(&mut send_buf)?;
@@ -297,7 +331,7 @@ where
                     let res: ProxyRes = super::read_message(&mut conn, &mut recv_buf).await?;
 
                     if res.0 == Status::Success {
-                        return Ok(conn);
+                        return Ok((conn, res.1));
                     } else {
                         return Err(SocksV5Error::Command(res.0).into());
                     }
@@ -307,7 +341,16 @@ where
     }
 }
 
-impl<C, R> Service<Uri> for SocksV5<C, R>
+impl From<Address> for SocksBoundAddr {
+    fn from(addr: Address) -> Self {
+        match addr {
+            Address::Socket(socket) => Self::Socket(socket),
+            Address::Domain(domain, port) => Self::Domain(domain, port),
+        }
+    }
+}
+
+impl<C, R> SocksV5<C, R>
 where
     C: Service<Uri>,
     C::Future: Send + 'static,
@@ -316,15 +359,9 @@ where
     R: Resolve + Clone + Send + 'static,
     <R as Resolve>::Future: Send + 'static,
 {
-    type Response = C::Response;
-    type Error = SocksError<C::Error>;
-    type Future = Handshaking<C::Future, C::Response, C::Error>;
-
-    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
-        self.inner.poll_ready(cx).map_err(SocksError::Inner)
-    }
-
-    fn call(&mut self, dst: Uri) -> Self::Future {
+    /// Establish a tunnel to `dst`, also returning the address the proxy reported
+    /// binding (`BND.ADDR`/`BND.PORT`) in its reply.
+    pub fn connect(&mut self, dst: Uri) -> BoxHandshaking<(C::Response, SocksBoundAddr), C::Error> {
         let config = self.config.clone();
         let connecting = self.inner.call(config.proxy.clone());
 
@@ -337,8 +374,60 @@ where
             config.execute(conn, host, port).await
         };
 
+        Box::pin(fut)
+    }
+
+    /// Asks the proxy to relay UDP datagrams, returning a socket that sends
+    /// and receives them through the relay.
+    ///
+    /// The association lasts as long as the control connection the request
+    /// is made over, which the socket keeps open until it is dropped.
+    pub fn udp_associate(&mut self) -> BoxHandshaking<SocksUdpSocket, C::Error> {
+        let config = self.config.clone();
+        let connecting = self.inner.call(config.proxy.clone());
+
+        let fut = async move {
+            let conn = connecting.await.map_err(SocksError::Inner)?;
+
+            // The datagrams will come from an address not known yet, which
+            // RFC 1928 has the client send as all zeros.
+            let unknown = Address::Socket(SocketAddr::from(([0, 0, 0, 0], 0)));
+            let (conn, relay) = config
+                .handshake(conn, Command::UdpAssociate, &unknown)
+                .await?;
+
+            let relay = udp::relay_addr(&config.proxy, relay).await?;
+            SocksUdpSocket::bind(conn, relay)
+                .await
+                .map_err(SocksError::Io)
+        };
+
+        Box::pin(fut)
+    }
+}
+
+impl<C, R> Service<Uri> for SocksV5<C, R>
+where
+    C: Service<Uri>,
+    C::Future: Send + 'static,
+    C::Response: Read + Write + Unpin + Send + 'static,
+    C::Error: Send + 'static,
+    R: Resolve + Clone + Send + 'static,
+    <R as Resolve>::Future: Send + 'static,
+{
+    type Response = C::Response;
+    type Error = SocksError<C::Error>;
+    type Future = Handshaking<C::Future, C::Response, C::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(SocksError::Inner)
+    }
+
+    fn call(&mut self, dst: Uri) -> Self::Future {
+        let connecting = self.connect(dst);
+
         Handshaking {
-            fut: Box::pin(fut),
+            fut: Box::pin(async move { connecting.await.map(|(conn, _)| conn) }),
             _marker: Default::default(),
         }
     }
@@ -0,0 +1,159 @@
+use std::{
+    io,
+    net::{IpAddr, SocketAddr},
+};
+
+use bytes::{BufMut, BytesMut};
+use http::Uri;
+use tokio::{net::UdpSocket, task::JoinHandle};
+
+use super::{
+    super::SocksBoundAddr,
+    messages::{Address, UdpHeader},
+};
+use crate::core::rt::{Read, Write};
+
+/// The longest header of a relayed datagram, with a domain of 255 bytes.
+const MAX_HEADER_LEN: usize = 3 + 1 + 1 + 255 + 2;
+
+/// A UDP socket relaying datagrams through a SOCKSv5 proxy, set up with
+/// `UDP ASSOCIATE`.
+///
+/// Every datagram is sent to the relay of the proxy, wrapped in a header
+/// naming its destination, and received from it wrapped in a header naming
+/// its source. Fragmented datagrams are not supported, and are dropped.
+///
+/// The proxy ends the association when the control connection closes. It is
+/// kept open until the socket is dropped.
+#[derive(Debug)]
+pub struct SocksUdpSocket {
+    socket: UdpSocket,
+    relay: SocketAddr,
+    control: JoinHandle<()>,
+}
+
+impl SocksUdpSocket {
+    /// Binds a socket of the family of `relay`, and keeps `control` open
+    /// alongside it.
+    pub(super) async fn bind<T>(mut control: T, relay: SocketAddr) -> io::Result<SocksUdpSocket>
+    where
+        T: Read + Write + Unpin + Send + 'static,
+    {
+        let local = match relay {
+            SocketAddr::V4(_) => SocketAddr::from(([0, 0, 0, 0], 0)),
+            SocketAddr::V6(_) => SocketAddr::from(([0u16; 8], 0)),
+        };
+        let socket = UdpSocket::bind(local).await?;
+        // Only datagrams from the relay are accepted.
+        socket.connect(relay).await?;
+
+        // The proxy sends nothing more on the control connection, which is
+        // only read to notice that it closed.
+        let control = tokio::spawn(async move {
+            let mut buf = [0; 64];
+            loop {
+                match crate::core::rt::read(&mut control, &mut buf).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(_) => {}
+                }
+            }
+            debug!("SOCKS UDP association closed by the proxy");
+        });
+
+        Ok(SocksUdpSocket {
+            socket,
+            relay,
+            control,
+        })
+    }
+
+    /// Returns the address of the relay that datagrams go through.
+    pub fn relay_addr(&self) -> SocketAddr {
+        self.relay
+    }
+
+    /// Returns whether the proxy closed the control connection, which ends
+    /// the association.
+    pub fn is_closed(&self) -> bool {
+        self.control.is_finished()
+    }
+
+    /// Sends `buf` to `target` through the relay, returning how many bytes of
+    /// it were sent.
+    ///
+    /// A domain `target` is resolved by the proxy.
+    pub async fn send_to<A>(&self, buf: &[u8], target: A) -> io::Result<usize>
+    where
+        A: Into<SocksBoundAddr>,
+    {
+        let address = match target.into() {
+            SocksBoundAddr::Socket(socket) => Address::Socket(socket),
+            SocksBoundAddr::Domain(domain, _) if domain.len() > 255 => {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "domain is more than 255 characters",
+                ));
+            }
+            SocksBoundAddr::Domain(domain, port) => Address::Domain(domain, port),
+        };
+
+        let mut datagram = BytesMut::with_capacity(MAX_HEADER_LEN + buf.len());
+        UdpHeader(address)
+            .write_to_buf(&mut datagram)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "header too long"))?;
+        datagram.put_slice(buf);
+
+        self.socket.send(&datagram).await?;
+        Ok(buf.len())
+    }
+
+    /// Receives a datagram relayed by the proxy into `buf`, returning how
+    /// many bytes were read and where the datagram came from.
+    ///
+    /// As with a UDP socket, a datagram too long for `buf` is truncated.
+    pub async fn recv_from(&self, buf: &mut [u8]) -> io::Result<(usize, SocksBoundAddr)> {
+        let mut datagram = BytesMut::zeroed(MAX_HEADER_LEN + buf.len());
+        loop {
+            let n = self.socket.recv(&mut datagram).await?;
+            let mut received = BytesMut::from(&datagram[..n]);
+            match UdpHeader::try_from(&mut received) {
+                Ok(UdpHeader(source)) => {
+                    let len = received.len().min(buf.len());
+                    buf[..len].copy_from_slice(&received[..len]);
+                    return Ok((len, source.into()));
+                }
+                Err(err) => trace!("dropping datagram from SOCKS relay: {:?}", err),
+            }
+        }
+    }
+}
+
+impl Drop for SocksUdpSocket {
+    fn drop(&mut self) {
+        self.control.abort();
+    }
+}
+
+/// Returns where to send datagrams for the proxy at `proxy`, which replied
+/// with the relay address `bound`.
+///
+/// A proxy may reply with an unspecified address, meaning its own.
+pub(super) async fn relay_addr(proxy: &Uri, bound: Address) -> io::Result<SocketAddr> {
+    let (host, port) = match bound {
+        Address::Socket(socket) if !socket.ip().is_unspecified() => return Ok(socket),
+        Address::Socket(socket) => {
+            let host = proxy.host().unwrap_or_default();
+            let host = host.trim_start_matches('[').trim_end_matches(']');
+            (host.to_owned(), socket.port())
+        }
+        Address::Domain(domain, port) => (domain, port),
+    };
+
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(SocketAddr::new(ip, port));
+    }
+    tokio::net::lookup_host((host.as_str(), port))
+        .await?
+        .next()
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "SOCKS relay resolved to nothing"))
+}
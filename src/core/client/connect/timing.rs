@@ -0,0 +1,119 @@
+//! Timing the steps of establishing a connection.
+
+use std::{
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use antidote::Mutex;
+
+/// How long each step of establishing a connection took.
+///
+/// Made available on responses when
+/// [`ClientBuilder::collect_timings`] is set. Each step is timed from the end
+/// of the one before, and is `None` when the connection skipped it. Through a
+/// proxy, the name resolution and TCP connect are those of the proxy.
+///
+/// [`ClientBuilder::collect_timings`]: crate::ClientBuilder::collect_timings
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ConnectTiming {
+    dns: Option<Duration>,
+    tcp_connect: Option<Duration>,
+    proxy_connect: Option<Duration>,
+    tls: Option<Duration>,
+}
+
+impl ConnectTiming {
+    /// Get how long resolving the host took.
+    ///
+    /// Returns `None` if the host was an IP address.
+    pub fn dns(&self) -> Option<Duration> {
+        self.dns
+    }
+
+    /// Get how long the TCP connect took, across every address tried.
+    pub fn tcp_connect(&self) -> Option<Duration> {
+        self.tcp_connect
+    }
+
+    /// Get how long setting up the tunnel through a proxy took, with a
+    /// CONNECT request or a SOCKS handshake.
+    ///
+    /// Returns `None` when not tunneling, which includes plain HTTP requests
+    /// sent to a proxy as is.
+    pub fn proxy_connect(&self) -> Option<Duration> {
+        self.proxy_connect
+    }
+
+    /// Get how long the TLS handshake with the destination took.
+    pub fn tls(&self) -> Option<Duration> {
+        self.tls
+    }
+}
+
+/// Collects the timing of one connection as it is established.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ConnectTimingRecorder(Arc<Mutex<Recorded>>);
+
+#[derive(Debug, Default)]
+struct Recorded {
+    timing: ConnectTiming,
+    /// When the last step ended.
+    step_end: Option<Instant>,
+}
+
+impl ConnectTimingRecorder {
+    /// Records how long resolving the host took.
+    pub(crate) fn resolved(&self, elapsed: Duration) {
+        self.0.lock().timing.dns = Some(elapsed);
+    }
+
+    /// Records how long the TCP connect took, which just ended.
+    pub(crate) fn tcp_connected(&self, elapsed: Duration) {
+        let mut recorded = self.0.lock();
+        recorded.timing.tcp_connect = Some(elapsed);
+        recorded.step_end = Some(Instant::now());
+    }
+
+    /// Records that the tunnel through the proxy was just set up.
+    pub(crate) fn proxy_connected(&self) {
+        let mut recorded = self.0.lock();
+        recorded.timing.proxy_connect = recorded.step_end.map(|end| end.elapsed());
+        recorded.step_end = Some(Instant::now());
+    }
+
+    /// Records that the TLS handshake just ended.
+    pub(crate) fn tls_done(&self) {
+        let mut recorded = self.0.lock();
+        recorded.timing.tls = recorded.step_end.map(|end| end.elapsed());
+        recorded.step_end = Some(Instant::now());
+    }
+
+    /// Returns the timing recorded so far.
+    pub(crate) fn finish(&self) -> ConnectTiming {
+        self.0.lock().timing
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps_follow_each_other() {
+        let recorder = ConnectTimingRecorder::default();
+        // Without a TCP connect, later steps have nothing to start from.
+        recorder.tls_done();
+        assert_eq!(recorder.finish(), ConnectTiming::default());
+
+        recorder.resolved(Duration::from_millis(3));
+        recorder.tcp_connected(Duration::from_millis(7));
+        recorder.proxy_connected();
+        recorder.tls_done();
+        let timing = recorder.finish();
+        assert_eq!(timing.dns(), Some(Duration::from_millis(3)));
+        assert_eq!(timing.tcp_connect(), Some(Duration::from_millis(7)));
+        assert!(timing.proxy_connect().is_some());
+        assert!(timing.tls().is_some());
+    }
+}
@@ -1,4 +1,11 @@
-use std::net::{Ipv4Addr, Ipv6Addr};
+use std::{
+    fmt,
+    net::{Ipv4Addr, Ipv6Addr},
+    sync::Arc,
+    time::Duration,
+};
+
+use antidote::Mutex;
 
 use http::{
     Request, Uri, Version,
@@ -7,12 +14,16 @@ use http::{
 
 use super::{Error, ErrorKind, PoolKey, set_scheme};
 use crate::{
-    core::ext::{
-        RequestConfig, RequestHttpVersionPref, RequestInterface, RequestIpv4Addr, RequestIpv6Addr,
-        RequestProxyMatcher,
+    core::{
+        ext::{
+            RequestConfig, RequestConnectOver, RequestConnectTimeout, RequestHttpVersionPref,
+            RequestIdentity, RequestInterface, RequestIpv4Addr, RequestIpv6Addr,
+            RequestProxyMatcher,
+        },
+        rt::TokioIo,
     },
     proxy::Intercepted,
-    tls::AlpnProtocol,
+    tls::{AlpnProtocol, Identity},
 };
 
 /// Destination of the request.
@@ -20,8 +31,43 @@ use crate::{
 /// The `Dst` struct is used to store the destination of the request, including the HTTP version
 /// preference, network scheme, and the pool key. It provides methods to create and manipulate the
 /// destination.
+///
+/// A per-request connect timeout rides along, but outside the pool key, so it
+/// never keeps a request from reusing a connection, and so does whether the
+/// request may be sent as TLS early data. So does a stream to connect over,
+/// if the request brought one.
 #[derive(Debug, Clone)]
-pub struct Dst(PoolKey);
+pub struct Dst(PoolKey, Option<Duration>, bool, Option<ProvidedStream>);
+
+/// A stream connected elsewhere, for a request to be sent over instead of a
+/// connection of its own.
+///
+/// Clones share the stream, which the first connect for the request takes.
+#[derive(Clone)]
+pub(crate) struct ProvidedStream(Arc<Mutex<Option<Box<dyn ProvidedIo>>>>);
+
+/// The I/O of a [`ProvidedStream`].
+pub(crate) trait ProvidedIo:
+    tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + Sync + fmt::Debug + 'static
+{
+}
+
+impl<T> ProvidedIo for T where
+    T: tokio::io::AsyncRead + tokio::io::AsyncWrite + Unpin + Send + Sync + fmt::Debug + 'static
+{
+}
+
+impl ProvidedStream {
+    pub(crate) fn new<S: ProvidedIo>(stream: S) -> ProvidedStream {
+        ProvidedStream(Arc::new(Mutex::new(Some(Box::new(stream)))))
+    }
+}
+
+impl fmt::Debug for ProvidedStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProvidedStream").finish()
+    }
+}
 
 impl Dst {
     /// Creates a new `Dst`.
@@ -29,6 +75,8 @@ impl Dst {
     /// This method initializes a new `Dst` instance based on the provided URI, HTTP connect flag,
     /// network scheme, and HTTP version.
     pub(crate) fn new<B>(req: &mut Request<B>, is_http_connect: bool) -> Result<Dst, Error> {
+        // Early data can be replayed, which only idempotent methods survive.
+        let early_data = req.method().is_idempotent();
         let uri = req.uri_mut();
         let (scheme, auth) = match (uri.scheme().cloned(), uri.authority().cloned()) {
             (Some(scheme), Some(auth)) => (scheme, auth),
@@ -72,6 +120,9 @@ impl Dst {
         let local_ipv6_address = RequestConfig::<RequestIpv6Addr>::remove(extensions);
         let interface = RequestConfig::<RequestInterface>::remove(extensions);
         let proxy_scheme = RequestConfig::<RequestProxyMatcher>::remove(extensions);
+        let identity = RequestConfig::<RequestIdentity>::remove(extensions);
+        let connect_timeout = RequestConfig::<RequestConnectTimeout>::remove(extensions);
+        let provided = RequestConfig::<RequestConnectOver>::remove(extensions);
 
         // Convert the scheme and host to a URI
         Uri::builder()
@@ -81,21 +132,27 @@ impl Dst {
             .build()
             .map(|uri| {
                 let proxy_intercepted = proxy_scheme.and_then(|matcher| matcher.intercept(&uri));
-                Dst((
-                    uri,
-                    alpn,
-                    local_ipv4_address,
-                    local_ipv6_address,
-                    interface,
-                    proxy_intercepted,
-                ))
+                Dst(
+                    (
+                        uri,
+                        alpn,
+                        local_ipv4_address,
+                        local_ipv6_address,
+                        interface,
+                        proxy_intercepted,
+                        identity,
+                    ),
+                    connect_timeout,
+                    early_data,
+                    provided,
+                )
             })
             .map_err(Into::into)
     }
 
+    /// Returns the URI of the destination.
     #[inline(always)]
-    #[allow(dead_code)]
-    pub(crate) fn uri(&self) -> &Uri {
+    pub fn uri(&self) -> &Uri {
         &self.0.0
     }
 
@@ -109,6 +166,11 @@ impl Dst {
         self.0.1
     }
 
+    #[inline(always)]
+    pub(crate) fn set_alpn_protos(&mut self, alpn: AlpnProtocol) {
+        self.0.1 = Some(alpn);
+    }
+
     #[inline(always)]
     pub(crate) fn only_http2(&self) -> bool {
         self.0.1 == Some(AlpnProtocol::HTTP2)
@@ -132,8 +194,8 @@ impl Dst {
         target_os = "watchos",
     ))]
     #[inline(always)]
-    pub(crate) fn interface(&mut self) -> Option<std::borrow::Cow<'static, str>> {
-        self.0.4.take()
+    pub(crate) fn interface(&self) -> Option<&std::borrow::Cow<'static, str>> {
+        self.0.4.as_ref()
     }
 
     #[inline(always)]
@@ -141,6 +203,52 @@ impl Dst {
         self.0.5.take()
     }
 
+    /// The client identity to present instead of the connector's, if any.
+    ///
+    /// It is part of the pool key, so connections authenticated with one
+    /// identity are never reused for requests with another.
+    #[inline(always)]
+    pub(crate) fn identity(&self) -> Option<&Identity> {
+        self.0.6.as_ref()
+    }
+
+    /// The connect timeout of this request, which takes precedence over the
+    /// connector's, if any.
+    #[inline(always)]
+    pub(crate) fn connect_timeout(&self) -> Option<Duration> {
+        self.1
+    }
+
+    /// Whether the request may be sent as TLS early data, which an attacker
+    /// can replay.
+    #[inline(always)]
+    pub(crate) fn early_data(&self) -> bool {
+        self.2
+    }
+
+    /// Keeps the connect from offering TLS early data, for a connection
+    /// opened without a request to send.
+    #[inline(always)]
+    pub(crate) fn disable_early_data(&mut self) {
+        self.2 = false;
+    }
+
+    /// Whether the request brought a stream to connect over that no connect
+    /// took yet.
+    #[inline(always)]
+    pub(crate) fn has_provided_stream(&self) -> bool {
+        self.3
+            .as_ref()
+            .is_some_and(|provided| provided.0.lock().is_some())
+    }
+
+    /// Takes the stream to connect over, if the request brought one that no
+    /// connect took yet.
+    #[inline(always)]
+    pub(crate) fn take_provided_stream(&mut self) -> Option<TokioIo<Box<dyn ProvidedIo>>> {
+        self.3.take()?.0.lock().take().map(TokioIo::new)
+    }
+
     #[inline(always)]
     pub(super) fn pool_key(&self) -> &PoolKey {
         &self.0
@@ -27,8 +27,11 @@ use std::{
 };
 
 use common::{Exec, Lazy, lazy as hyper_lazy, timer};
-use connect::{Alpn, Connect, Connected, Connection, capture::CaptureConnectionExtension};
+use connect::{
+    Alpn, CloseReason, Connect, Connected, Connection, capture::CaptureConnectionExtension,
+};
 pub use dst::Dst;
+pub(crate) use dst::ProvidedStream;
 use futures_util::future::{self, Either, FutureExt, TryFutureExt};
 use http::{HeaderValue, Method, Request, Response, Uri, Version, header::HOST, uri::Scheme};
 use http_body::Body;
@@ -43,10 +46,11 @@ use crate::{
         },
         common,
         error::BoxError,
+        proto::h2::watch::Draining,
         rt::Timer,
     },
     proxy::Intercepted,
-    tls::AlpnProtocol,
+    tls::{AlpnProtocol, Identity},
 };
 
 type BoxSendFuture = Pin<Box<dyn Future<Output = ()> + Send>>;
@@ -83,6 +87,7 @@ struct Config {
     retry_canceled_requests: bool,
     set_host: bool,
     ver: Ver,
+    reuse_ping_timeout: Duration,
 }
 
 /// Client errors
@@ -140,8 +145,22 @@ type PoolKey = (
     Option<Ipv6Addr>,
     Option<Cow<'static, str>>,
     Option<Intercepted>,
+    Option<Identity>,
 );
 
+/// The destination of a connection opened ahead of any request, to `uri`.
+///
+/// No early data is offered, since there is nothing to send yet, and a
+/// connection still in early data would send the next request on it as early
+/// data, whatever its method.
+fn connect_only_dst(uri: Uri) -> Result<Dst, Error> {
+    let mut req = Request::new(());
+    *req.uri_mut() = uri;
+    let mut dst = Dst::new(&mut req, false)?;
+    dst.disable_early_data();
+    Ok(dst)
+}
+
 #[allow(clippy::large_enum_variant)]
 enum TrySendError<B> {
     Retryable {
@@ -258,6 +277,49 @@ where
         ResponseFuture::new(self.clone().send_request(req, dst))
     }
 
+    /// Opens up to `count` new connections to the destination of `uri`, which
+    /// then wait in the pool as idle connections.
+    ///
+    /// No more connections are opened than the pool would keep idle for the
+    /// host, and at most one when it ends up speaking HTTP/2. Failed connects
+    /// only lower the returned count of warmed connections.
+    pub(crate) async fn warm(&self, uri: Uri, count: usize) -> Result<usize, Error> {
+        let dst = connect_only_dst(uri)?;
+
+        let count = count.min(self.pool.idle_capacity(dst.pool_key()));
+        let connects = (0..count).map(|_| self.connect_to(dst.clone()));
+        let warmed = future::join_all(connects)
+            .await
+            .into_iter()
+            .filter(|res| match res {
+                Ok(_pooled) => true,
+                Err(_err) => {
+                    trace!("warm connect error: {}", _err);
+                    false
+                }
+            })
+            .count();
+
+        // Dropping the `Pooled` connections above has put them in the pool.
+        Ok(warmed)
+    }
+
+    /// Opens a new connection to the destination of `uri` without sending a
+    /// request, and returns what `f` makes of its metadata.
+    ///
+    /// The connection then waits in the pool as an idle connection.
+    #[cfg(feature = "blocking")]
+    pub(crate) async fn connect_with<T>(
+        &self,
+        uri: Uri,
+        f: impl FnOnce(&Connected) -> T,
+    ) -> Result<T, Error> {
+        let dst = connect_only_dst(uri)?;
+
+        let pooled = self.connect_to(dst).await?;
+        Ok(f(&pooled.conn_info))
+    }
+
     async fn send_request(
         self,
         mut req: Request<B>,
@@ -341,30 +403,67 @@ where
             authority_form(req.uri_mut());
         }
 
-        let mut res = match pooled.try_send_request(req).await {
+        pooled.conn_info.count_request();
+
+        let conn_info = pooled.conn_info.clone();
+        let is_http2 = pooled.is_http2();
+        let connection_reused = pooled.is_reused();
+        let sent = pooled.try_send_request(req);
+
+        // A pipelining connection takes its next request before this
+        // response is back, so it returns to the pool as soon as it is
+        // ready for one rather than once the response arrives. Not while
+        // still in early data though, where the next request would be sent
+        // as early data too, whatever its method.
+        let pooled = if pooled.is_pipelining()
+            && pooled.is_pool_enabled()
+            && !pooled.conn_info.is_early_data()
+        {
+            let on_idle = std::future::poll_fn(move |cx| pooled.poll_ready(cx)).map(|_| ());
+            self.exec.execute(on_idle);
+            None
+        } else {
+            Some(pooled)
+        };
+
+        let mut res = match sent.await {
             Ok(res) => res,
             Err(mut err) => {
+                if is_http2 && is_h2_protocol_error(&err.error) {
+                    conn_info.poison();
+                    conn_info.h2_protocol_error();
+                }
                 return if let Some(req) = err.take_message() {
                     Err(TrySendError::Retryable {
-                        connection_reused: pooled.is_reused(),
-                        error: e!(Canceled, err.into_error())
-                            .with_connect_info(pooled.conn_info.clone()),
+                        connection_reused,
+                        error: e!(Canceled, err.into_error()).with_connect_info(conn_info),
                         req,
                     })
                 } else {
                     Err(TrySendError::Nope(
-                        e!(SendRequest, err.into_error())
-                            .with_connect_info(pooled.conn_info.clone()),
+                        e!(SendRequest, err.into_error()).with_connect_info(conn_info),
                     ))
                 };
             }
         };
 
         // If the Connector included 'extra' info, add to Response...
-        if let Some(extra) = &pooled.conn_info.extra {
+        if let Some(extra) = &conn_info.extra {
             extra.set(res.extensions_mut());
         }
 
+        if is_http2 {
+            res.extensions_mut()
+                .insert(conn_info.stats.settings_rtt().clone());
+            res.extensions_mut()
+                .insert(conn_info.stats.peer_settings().clone());
+            res.extensions_mut().insert(conn_info.stats.ping().clone());
+        }
+
+        let Some(pooled) = pooled else {
+            return Ok(res);
+        };
+
         // If pooled is HTTP/2, we can toss this reference immediately.
         //
         // when pooled is dropped, it will try to insert back into the
@@ -412,6 +511,16 @@ where
         &self,
         dst: Dst,
     ) -> Result<pool::Pooled<PoolClient<B>, PoolKey>, ClientConnectError> {
+        // A stream the request brought serves that request alone.
+        if dst.has_provided_stream() {
+            let pooled = self
+                .connect_to(dst)
+                .await
+                .map_err(ClientConnectError::Normal)?;
+            pooled.conn_info.poison();
+            return Ok(pooled);
+        }
+
         // Return a single connection if pooling is not enabled
         if !self.pool.is_enabled() {
             return self
@@ -442,6 +551,19 @@ where
             // If it has, let it finish and insert back into the pool,
             // so as to not waste the socket...
             Either::Left((Ok(checked_out), connecting)) => {
+                // A connection that turns out dead is retired, and the
+                // connect future is waited on instead.
+                let timer = self.pool.timer();
+                if !checked_out
+                    .is_alive(timer, self.config.reuse_ping_timeout)
+                    .await
+                {
+                    debug!("pooled connection failed its reuse check, connecting anew");
+                    checked_out.conn_info.poison();
+                    drop(checked_out);
+                    return connecting.await.map_err(ClientConnectError::Normal);
+                }
+
                 // This depends on the `select` above having the correct
                 // order, such that if the checkout future were ready
                 // immediately, the connect future will never have been
@@ -454,7 +576,13 @@ where
                         .map_err(|_err| {
                             trace!("background connect error: {}", _err);
                         })
-                        .map(|_pooled| {
+                        .map(|pooled| {
+                            // A connection still in early data would send
+                            // the next request on it as early data too,
+                            // whatever its method, so it is not pooled.
+                            if pooled.conn_info.is_early_data() {
+                                pooled.conn_info.poison();
+                            }
                             // dropping here should just place it in
                             // the Pool for us...
                         });
@@ -555,12 +683,30 @@ where
                         };
 
                         let is_h2 = is_ver_h2 || connected.alpn == Alpn::H2;
+                        let ping_on_demand = connected.checks_on_reuse();
 
                         Either::Left(Box::pin(async move {
                             let tx = if is_h2 {
                                {
-                                    let (mut tx, conn) =
-                                        h2_builder.handshake(io).await.map_err(Error::tx)?;
+                                    let mut h2_builder = h2_builder;
+                                    let (mut tx, conn) = h2_builder
+                                        .ping_on_demand(ping_on_demand)
+                                        .settings_rtt(connected.stats.settings_rtt().clone())
+                                        .peer_settings(connected.stats.peer_settings().clone())
+                                        .ping_stats(connected.stats.ping().clone())
+                                        .draining(Draining::new(
+                                            {
+                                                let connected = connected.clone();
+                                                move || connected.h2_drain()
+                                            },
+                                            {
+                                                let stats = connected.stats.clone();
+                                                move || stats.close(CloseReason::ServerGoAway)
+                                            },
+                                        ))
+                                        .handshake(io)
+                                        .await
+                                        .map_err(Error::tx)?;
 
                                     trace!(
                                         "http2 handshake complete, spawning background dispatcher task"
@@ -678,6 +824,11 @@ where
         })
     }
 
+    #[inline]
+    pub(crate) fn connector(&self) -> &C {
+        &self.connector
+    }
+
     #[allow(dead_code)]
     #[inline]
     pub(crate) fn connector_mut(&mut self) -> &mut C {
@@ -832,6 +983,16 @@ impl<B> PoolClient<B> {
         self.conn_info.poisoned.poisoned()
     }
 
+    /// Whether the server sent a graceful GOAWAY on this HTTP/2 connection:
+    /// requests in flight go on, but new ones go to another connection.
+    fn is_draining(&self) -> bool {
+        match self.tx {
+            PoolTx::Http1(_) => false,
+
+            PoolTx::Http2(ref tx) => tx.is_draining(),
+        }
+    }
+
     fn is_ready(&self) -> bool {
         match self.tx {
             PoolTx::Http1(ref tx) => tx.is_ready(),
@@ -839,13 +1000,49 @@ impl<B> PoolClient<B> {
             PoolTx::Http2(ref tx) => tx.is_ready(),
         }
     }
+
+    fn is_pipelining(&self) -> bool {
+        match self.tx {
+            PoolTx::Http1(ref tx) => tx.is_pipelining(),
+
+            PoolTx::Http2(_) => false,
+        }
+    }
+
+    /// Checks that a connection taken from the pool is still alive, if its
+    /// connector asked for it: HTTP/2 connections must answer a PING within
+    /// `timeout`, HTTP/1 ones must not have been closed by the peer.
+    ///
+    /// Without a `timer` to bound the PING, HTTP/2 connections are not
+    /// checked.
+    async fn is_alive(&self, timer: Option<timer::Timer>, timeout: Duration) -> bool {
+        if !self.conn_info.checks_on_reuse() {
+            return true;
+        }
+        match self.tx {
+            PoolTx::Http1(_) => self.conn_info.probe(),
+
+            PoolTx::Http2(ref tx) => match timer {
+                Some(timer) => {
+                    let ping = std::pin::pin!(tx.ping());
+                    match future::select(ping, timer.sleep(timeout)).await {
+                        Either::Left((alive, _)) => alive,
+                        Either::Right(_) => false,
+                    }
+                }
+                None => true,
+            },
+        }
+    }
 }
 
 impl<B: Body + 'static> PoolClient<B> {
     fn try_send_request(
         &mut self,
         req: Request<B>,
-    ) -> impl Future<Output = Result<Response<crate::core::body::Incoming>, ConnTrySendError<Request<B>>>>
+    ) -> impl Future<
+        Output = Result<Response<crate::core::body::Incoming>, ConnTrySendError<Request<B>>>,
+    > + use<B>
     where
         B: Send,
     {
@@ -861,7 +1058,7 @@ where
     B: Send + 'static,
 {
     fn is_open(&self) -> bool {
-        !self.is_poisoned() && self.is_ready()
+        !self.is_poisoned() && self.is_ready() && !self.is_draining()
     }
 
     fn reserve(self) -> pool::Reservation<Self> {
@@ -885,9 +1082,46 @@ where
         }
     }
 
+    fn retire(&self, reason: CloseReason) {
+        self.conn_info.stats.close(reason);
+    }
+
     fn can_share(&self) -> bool {
         self.is_http2()
     }
+
+    fn load(&self) -> usize {
+        match self.tx {
+            PoolTx::Http1(_) => 0,
+
+            PoolTx::Http2(ref tx) => tx.active_streams(),
+        }
+    }
+
+    fn is_saturated(&self) -> bool {
+        match self.tx {
+            PoolTx::Http1(_) => false,
+
+            PoolTx::Http2(ref tx) => tx.is_saturated(),
+        }
+    }
+}
+
+/// Whether an HTTP/2 failure points at a peer, or something in between, that
+/// can't speak the protocol, rather than an ordinary refusal of one request.
+fn is_h2_protocol_error(err: &crate::core::Error) -> bool {
+    let Some(err) = err.find_source::<http2::Error>() else {
+        return false;
+    };
+    match err.reason() {
+        Some(http2::Reason::HTTP_1_1_REQUIRED) => true,
+        Some(
+            http2::Reason::PROTOCOL_ERROR
+            | http2::Reason::FRAME_SIZE_ERROR
+            | http2::Reason::COMPRESSION_ERROR,
+        ) => !err.is_reset(),
+        _ => false,
+    }
 }
 
 enum ClientConnectError {
@@ -1016,6 +1250,7 @@ impl Builder {
                 retry_canceled_requests: true,
                 set_host: true,
                 ver: Ver::Auto,
+                reuse_ping_timeout: Duration::from_secs(1),
             },
             exec: exec.clone(),
 
@@ -1025,6 +1260,7 @@ impl Builder {
                 idle_timeout: Some(Duration::from_secs(90)),
                 max_idle_per_host: usize::MAX,
                 max_pool_size: None,
+                max_http2_per_host: 1,
             },
             pool_timer: None,
         }
@@ -1067,6 +1303,16 @@ impl Builder {
         self
     }
 
+    /// Set how long a pooled HTTP/2 connection has to answer a PING, when its
+    /// connector asked for it to be checked before reuse.
+    /// A `Timer` is required for this to take effect. See `Builder::pool_timer`
+    ///
+    /// Default is 1 second.
+    pub fn reuse_ping_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.client_config.reuse_ping_timeout = timeout;
+        self
+    }
+
     /// Sets the maximum idle connection per host allowed in the pool.
     ///
     /// Default is `usize::MAX` (no limit).
@@ -1120,6 +1366,7 @@ impl Builder {
 
     /// Provide a configuration for HTTP/2.
     pub fn http2_config(&mut self, config: Http2Config) -> &mut Self {
+        self.pool_config.max_http2_per_host = config.h2_builder.max_connections_per_host;
         self.h2_builder.config(config);
         self
     }
@@ -18,6 +18,7 @@ use lru::LruCache;
 use tokio::sync::oneshot;
 
 use crate::core::{
+    client::connect::CloseReason,
     common::{exec, exec::Exec, timer::Timer},
     rt::{Sleep, Timer as _},
 };
@@ -41,6 +42,13 @@ pub trait Poolable: Unpin + Send + Sized + 'static {
     /// Allows for HTTP/2 to return a shared reservation.
     fn reserve(self) -> Reservation<Self>;
     fn can_share(&self) -> bool;
+    /// The number of requests in flight on a shared connection.
+    fn load(&self) -> usize;
+    /// Whether a shared connection can't take another request without
+    /// queueing it behind the ones in flight.
+    fn is_saturated(&self) -> bool;
+    /// Records why the pool is letting go of this connection.
+    fn retire(&self, _reason: CloseReason) {}
 }
 
 pub trait Key: Eq + Hash + Clone + Debug + Unpin + Send + 'static {}
@@ -75,13 +83,16 @@ pub enum Reservation<T> {
 // pub type Key = (http::uri::Scheme, http::uri::Authority); //Arc<String>;
 struct PoolInner<T, K: Eq + Hash> {
     // A flag that a connection is being established, and the connection
-    // should be shared. This prevents making multiple HTTP/2 connections
-    // to the same host.
+    // should be shared. This prevents racing to make multiple HTTP/2
+    // connections to the same host.
     connecting: HashSet<K>,
     // These are internal Conns sitting in the event loop in the KeepAlive
     // state, waiting to receive a new Request to send on the socket.
     idle: LruCache<K, Vec<Idle<T>>>,
     max_idle_per_host: usize,
+    // How many shared (HTTP/2) connections may be kept for a single host.
+    // Another is only opened once all of them are saturated.
+    max_http2_per_host: usize,
     // These are outstanding Checkouts that are waiting for a socket to be
     // able to send a Request one. This is used when "racing" for a new
     // connection.
@@ -109,6 +120,7 @@ pub struct Config {
     pub idle_timeout: Option<Duration>,
     pub max_idle_per_host: usize,
     pub max_pool_size: Option<NonZero<usize>>,
+    pub max_http2_per_host: usize,
 }
 
 impl Config {
@@ -135,6 +147,7 @@ impl<T, K: Key> Pool<T, K> {
                 idle,
                 idle_interval_ref: None,
                 max_idle_per_host: config.max_idle_per_host,
+                max_http2_per_host: config.max_http2_per_host.max(1),
                 waiters: HashMap::new(),
                 exec,
                 timer,
@@ -150,6 +163,23 @@ impl<T, K: Key> Pool<T, K> {
     pub(crate) fn is_enabled(&self) -> bool {
         self.inner.is_some()
     }
+
+    /// Returns the timer the pool was given, if any.
+    pub(crate) fn timer(&self) -> Option<Timer> {
+        self.inner.as_ref()?.lock().timer.clone()
+    }
+
+    /// Returns how many more idle connections the pool would keep for `key`.
+    pub(crate) fn idle_capacity(&self, key: &K) -> usize {
+        match self.inner {
+            Some(ref inner) => {
+                let inner = inner.lock();
+                let idle = inner.idle.peek(key).map_or(0, Vec::len);
+                inner.max_idle_per_host.saturating_sub(idle)
+            }
+            None => 0,
+        }
+    }
 }
 
 impl<T: Poolable, K: Key> Pool<T, K> {
@@ -265,7 +295,11 @@ struct IdlePopper<'a, T, K> {
 }
 
 impl<'a, T: Poolable + 'a, K: Debug> IdlePopper<'a, T, K> {
-    fn pop(self, expiration: &Expiration) -> Option<Idle<T>> {
+    fn pop(self, expiration: &Expiration, max_shared: usize) -> Option<Idle<T>> {
+        if max_shared > 1 && self.list.iter().any(|entry| entry.value.can_share()) {
+            return self.pop_shared(expiration, max_shared);
+        }
+
         while let Some(entry) = self.list.pop() {
             // If the connection has been closed, or is older than our idle
             // timeout, simply drop it and keep looking...
@@ -281,6 +315,7 @@ impl<'a, T: Poolable + 'a, K: Debug> IdlePopper<'a, T, K> {
             // whole list...
             if expiration.expires(entry.idle_at) {
                 trace!("removing expired connection for {:?}", self.key);
+                entry.value.retire(CloseReason::IdleTimeout);
                 continue;
             }
 
@@ -303,11 +338,70 @@ impl<'a, T: Poolable + 'a, K: Debug> IdlePopper<'a, T, K> {
 
         None
     }
+
+    /// With several HTTP/2 connections to the same host, take the one with
+    /// the fewest streams in flight instead of the most recently idled one.
+    ///
+    /// If all of them are saturated and there's still room for another,
+    /// nothing is returned so that the checkout waits on a new connection.
+    fn pop_shared(self, expiration: &Expiration, max_shared: usize) -> Option<Idle<T>> {
+        self.list.retain(|entry| {
+            if !entry.value.is_open() {
+                trace!("removing closed connection for {:?}", self.key);
+                return false;
+            }
+            if expiration.expires(entry.idle_at) {
+                trace!("removing expired connection for {:?}", self.key);
+                entry.value.retire(CloseReason::IdleTimeout);
+                return false;
+            }
+            true
+        });
+
+        let shared = self
+            .list
+            .iter()
+            .filter(|entry| entry.value.can_share())
+            .count();
+        let idx = match least_loaded(self.list, |value| !value.is_saturated()) {
+            Some(idx) => idx,
+            None if shared < max_shared => {
+                trace!("all HTTP/2 connections saturated for {:?}", self.key);
+                return None;
+            }
+            None => least_loaded(self.list, |_| true)?,
+        };
+
+        let entry = self.list.remove(idx);
+        let value = match entry.value.reserve() {
+            Reservation::Shared(to_reinsert, to_checkout) => {
+                self.list.push(Idle {
+                    idle_at: Instant::now(),
+                    value: to_reinsert,
+                });
+                to_checkout
+            }
+            Reservation::Unique(unique) => unique,
+        };
+
+        Some(Idle {
+            idle_at: entry.idle_at,
+            value,
+        })
+    }
+}
+
+fn least_loaded<T: Poolable>(list: &[Idle<T>], usable: impl Fn(&T) -> bool) -> Option<usize> {
+    list.iter()
+        .enumerate()
+        .filter(|(_, entry)| entry.value.can_share() && usable(&entry.value))
+        .min_by_key(|(_, entry)| entry.value.load())
+        .map(|(idx, _)| idx)
 }
 
 impl<T: Poolable, K: Key> PoolInner<T, K> {
     fn put(&mut self, key: K, value: T, __pool_ref: &Arc<Mutex<PoolInner<T, K>>>) {
-        if value.can_share() && self.idle.contains(&key) {
+        if value.can_share() && self.shared_len(&key) >= self.max_http2_per_host {
             trace!("put; existing idle HTTP/2 connection for {:?}", key);
             return;
         }
@@ -355,6 +449,7 @@ impl<T: Poolable, K: Key> PoolInner<T, K> {
                     .get_or_insert_mut(key.clone(), Vec::<Idle<T>>::default);
                 if self.max_idle_per_host <= idle_list.len() {
                     trace!("max idle per host for {:?}, dropping connection", key);
+                    value.retire(CloseReason::Retired);
                     return;
                 }
 
@@ -371,6 +466,12 @@ impl<T: Poolable, K: Key> PoolInner<T, K> {
         }
     }
 
+    fn shared_len(&self, key: &K) -> usize {
+        self.idle.peek(key).map_or(0, |list| {
+            list.iter().filter(|entry| entry.value.can_share()).count()
+        })
+    }
+
     /// A `Connecting` task is complete. Not necessarily successfully,
     /// but the lock is going away, so clean up.
     fn connected(&mut self, key: &K) {
@@ -448,6 +549,7 @@ impl<T: Poolable, K: Key> PoolInner<T, K> {
                 // Avoid `Instant::sub` to avoid issues like rust-lang/rust#86470.
                 if now.saturating_duration_since(entry.idle_at) > dur {
                     trace!("idle interval evicting expired for {:?}", key);
+                    entry.value.retire(CloseReason::IdleTimeout);
                     return false;
                 }
 
@@ -611,26 +713,25 @@ impl<T: Poolable, K: Key> Checkout<T, K> {
         let entry = {
             let mut inner = self.pool.inner.as_ref()?.lock();
             let expiration = Expiration::new(inner.timeout);
-            let maybe_entry = inner.idle.get_mut(&self.key).and_then(|list| {
+            let max_shared = inner.max_http2_per_host;
+            let maybe_entry = inner.idle.get_mut(&self.key).map(|list| {
                 trace!("take? {:?}: expiration = {:?}", self.key, expiration.0);
                 // A block to end the mutable borrow on list,
-                // so the map below can check is_empty()
-                {
+                // so that is_empty() can be checked below
+                let entry = {
                     let popper = IdlePopper {
                         key: &self.key,
                         list,
                     };
-                    popper.pop(&expiration)
-                }
-                .map(|e| (e, list.is_empty()))
+                    popper.pop(&expiration, max_shared)
+                };
+                (entry, list.is_empty())
             });
 
-            let (entry, empty) = if let Some((e, empty)) = maybe_entry {
-                (Some(e), empty)
-            } else {
-                // No entry found means nuke the list for sure.
-                (None, true)
-            };
+            // No list found means nuke the list for sure. Otherwise, an
+            // entry might not have been taken while others remain, such as
+            // saturated HTTP/2 connections waiting on a new one.
+            let (entry, empty) = maybe_entry.unwrap_or((None, true));
             if empty {
                 //TODO: This could be done with the HashMap::entry API instead.
                 inner.idle.pop(&self.key);
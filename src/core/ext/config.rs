@@ -149,6 +149,30 @@ impl RequestConfigValue for RequestInterface {
     type Value = std::borrow::Cow<'static, str>;
 }
 
+/// Request client identity configuration.
+#[derive(Clone, Copy)]
+pub(crate) struct RequestIdentity;
+
+impl RequestConfigValue for RequestIdentity {
+    type Value = crate::tls::Identity;
+}
+
+/// Request connect timeout configuration.
+#[derive(Clone, Copy)]
+pub(crate) struct RequestConnectTimeout;
+
+impl RequestConfigValue for RequestConnectTimeout {
+    type Value = std::time::Duration;
+}
+
+/// Request stream to connect over.
+#[derive(Clone, Copy)]
+pub(crate) struct RequestConnectOver;
+
+impl RequestConfigValue for RequestConnectOver {
+    type Value = crate::core::client::ProvidedStream;
+}
+
 #[derive(Clone, Copy)]
 pub(crate) struct RequestProxyMatcher;
 
@@ -7,8 +7,9 @@ mod header;
 use std::fmt;
 
 pub(crate) use config::{
-    RequestConfig, RequestConfigValue, RequestHttpVersionPref, RequestInterface, RequestIpv4Addr,
-    RequestIpv6Addr, RequestOriginalHeaders, RequestProxyMatcher,
+    RequestConfig, RequestConfigValue, RequestConnectOver, RequestConnectTimeout,
+    RequestHttpVersionPref, RequestIdentity, RequestInterface, RequestIpv4Addr, RequestIpv6Addr,
+    RequestOriginalHeaders, RequestProxyMatcher,
 };
 pub(crate) use h1_reason_phrase::ReasonPhrase;
 
@@ -1,4 +1,5 @@
 use std::{
+    collections::VecDeque,
     fmt, io,
     marker::{PhantomData, Unpin},
     pin::Pin,
@@ -58,6 +59,9 @@ where
                 title_case_headers: false,
                 h09_responses: false,
                 notify_read: false,
+                pipeline_depth: 1,
+                pipeline_non_idempotent: false,
+                pipelined: VecDeque::new(),
                 reading: Reading::Init,
                 writing: Writing::Init,
                 upgrade: None,
@@ -106,6 +110,17 @@ where
         self.state.h1_max_headers = Some(val);
     }
 
+    pub(crate) fn set_pipelining(&mut self, depth: usize, non_idempotent: bool) {
+        self.state.pipeline_depth = depth;
+        self.state.pipeline_non_idempotent = non_idempotent;
+    }
+
+    /// Returns whether requests were written whose responses have not been
+    /// read yet. Only tracked when pipelining.
+    pub(crate) fn has_responses_due(&self) -> bool {
+        !self.state.pipelined.is_empty()
+    }
+
     pub(crate) fn into_inner(self) -> (I, Bytes) {
         self.io.into_inner()
     }
@@ -131,6 +146,10 @@ where
             return true;
         }
 
+        if self.state.is_pipelining() {
+            return !self.state.pipelined.is_empty();
+        }
+
         !matches!(self.state.writing, Writing::Init)
     }
 
@@ -345,10 +364,11 @@ where
     }
 
     fn is_mid_message(&self) -> bool {
-        !matches!(
-            (&self.state.reading, &self.state.writing),
-            (&Reading::Init, &Writing::Init)
-        )
+        !self.state.pipelined.is_empty()
+            || !matches!(
+                (&self.state.reading, &self.state.writing),
+                (&Reading::Init, &Writing::Init)
+            )
     }
 
     // This will check to make sure the io object read is empty.
@@ -518,17 +538,30 @@ where
 
         self.enforce_version(&mut head);
 
+        // When pipelining, `method` stays that of the oldest response due,
+        // so the method of this request joins the queue instead.
+        let mut pipelined_method = None;
+        let req_method = if self.state.is_pipelining() {
+            &mut pipelined_method
+        } else {
+            &mut self.state.method
+        };
+
         let buf = self.io.headers_buf();
         match super::role::encode_headers::<T>(
             Encode {
                 head: &mut head,
                 body,
-                req_method: &mut self.state.method,
+                req_method,
                 title_case_headers: self.state.title_case_headers,
             },
             buf,
         ) {
             Ok(encoder) => {
+                if self.state.is_pipelining() {
+                    self.state.pipelined.push_back(pipelined_method);
+                    self.state.method = self.state.pipelined.front().cloned().flatten();
+                }
                 debug_assert!(self.state.cached_headers.is_none());
                 debug_assert!(head.headers.is_empty());
                 self.state.cached_headers = Some(head.headers);
@@ -811,6 +844,14 @@ struct State {
     /// Set to true when the Dispatcher should poll read operations
     /// again. See the `maybe_notify` method for more.
     notify_read: bool,
+    /// How many requests a client may write ahead of their responses. 1
+    /// means no pipelining.
+    pipeline_depth: usize,
+    /// Whether requests with non-idempotent methods may be pipelined.
+    pipeline_non_idempotent: bool,
+    /// When pipelining, the methods of the requests written whose
+    /// responses are not complete yet, oldest first.
+    pipelined: VecDeque<Option<Method>>,
     /// State of allowed reads
     reading: Reading,
     /// State of allowed writes
@@ -932,7 +973,15 @@ impl State {
         !matches!(self.keep_alive.status(), KA::Disabled)
     }
 
+    fn is_pipelining(&self) -> bool {
+        self.pipeline_depth > 1
+    }
+
     fn try_keep_alive<T: Http1Transaction>(&mut self) {
+        if self.is_pipelining() {
+            self.advance_pipeline();
+        }
+
         match (&self.reading, &self.writing) {
             (&Reading::KeepAlive, &Writing::KeepAlive) => {
                 if let KA::Busy = self.keep_alive.status() {
@@ -953,6 +1002,41 @@ impl State {
         }
     }
 
+    /// Lets reads and writes of a pipelining client move on independently:
+    /// once a response completes the next one due is read, and once a
+    /// request is written another may follow, up to the pipeline depth.
+    fn advance_pipeline(&mut self) {
+        if let Reading::KeepAlive = self.reading {
+            self.pipelined.pop_front();
+            if let Some(method) = self.pipelined.front() {
+                self.method = method.clone();
+                self.reading = Reading::Init;
+            } else if let Writing::Init = self.writing {
+                // Nothing was written after the last response due, so the
+                // connection can go idle.
+                self.writing = Writing::KeepAlive;
+            }
+        }
+
+        if let Writing::KeepAlive = self.writing {
+            let may_follow = self.pipelined.back().is_some_and(|method| {
+                self.pipeline_non_idempotent || method.as_ref().is_none_or(Method::is_idempotent)
+            });
+            let reading_due = matches!(
+                self.reading,
+                Reading::Init | Reading::Continue(..) | Reading::Body(..)
+            );
+            if may_follow
+                && reading_due
+                && self.wants_keep_alive()
+                && self.pipelined.len() < self.pipeline_depth
+            {
+                self.writing = Writing::Init;
+                self.notify_read = true;
+            }
+        }
+    }
+
     fn disable_keep_alive(&mut self) {
         self.keep_alive.disable()
     }
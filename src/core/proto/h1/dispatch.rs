@@ -1,4 +1,5 @@
 use std::{
+    collections::VecDeque,
     future::Future,
     marker::Unpin,
     pin::Pin,
@@ -35,9 +36,12 @@ pub(crate) trait Dispatch {
     type RecvItem;
 
     #[allow(clippy::type_complexity)]
+    /// Polls for the next message to write. `pipelined` is set when it
+    /// would go out ahead of responses still due on the connection.
     fn poll_msg(
         self: Pin<&mut Self>,
         cx: &mut Context<'_>,
+        pipelined: bool,
     ) -> Poll<Option<Result<(Self::PollItem, Self::PollBody), Self::PollError>>>;
     fn recv_msg(
         &mut self,
@@ -49,7 +53,14 @@ pub(crate) trait Dispatch {
 
 pin_project_lite::pin_project! {
     pub(crate) struct Client<B> {
-        callback: Option<crate::core::client::dispatch::Callback<Request<B>, http::Response<IncomingBody>>>,
+        // Callbacks of the requests written, oldest first. Holds at most one
+        // unless pipelining.
+        callbacks: VecDeque<ClientCallback<B>>,
+        // A request that may not be pipelined, waiting for the responses
+        // ahead of it.
+        held: Option<(Request<B>, ClientCallback<B>)>,
+        pipeline_depth: usize,
+        pipeline_non_idempotent: bool,
         #[pin]
         rx: ClientRx<B>,
         rx_closed: bool,
@@ -59,6 +70,9 @@ pin_project_lite::pin_project! {
 type ClientRx<B> =
     crate::core::client::dispatch::Receiver<Request<B>, http::Response<IncomingBody>>;
 
+type ClientCallback<B> =
+    crate::core::client::dispatch::Callback<Request<B>, http::Response<IncomingBody>>;
+
 impl<D, Bs, I, T> Dispatcher<D, Bs, I, T>
 where
     D: Dispatch<
@@ -300,7 +314,8 @@ where
                 && self.conn.can_write_head()
                 && self.dispatch.should_poll()
             {
-                if let Some(msg) = ready!(Pin::new(&mut self.dispatch).poll_msg(cx)) {
+                let pipelined = self.conn.has_responses_due();
+                if let Some(msg) = ready!(Pin::new(&mut self.dispatch).poll_msg(cx, pipelined)) {
                     let (head, body) = msg.map_err(crate::core::Error::new_user_service)?;
 
                     let body_type = if body.is_end_stream() {
@@ -470,11 +485,23 @@ use std::convert::Infallible;
 impl<B> Client<B> {
     pub(crate) fn new(rx: ClientRx<B>) -> Client<B> {
         Client {
-            callback: None,
+            callbacks: VecDeque::new(),
+            held: None,
+            pipeline_depth: 1,
+            pipeline_non_idempotent: false,
             rx,
             rx_closed: false,
         }
     }
+
+    pub(crate) fn set_pipelining(&mut self, depth: usize, non_idempotent: bool) {
+        self.pipeline_depth = depth;
+        self.pipeline_non_idempotent = non_idempotent;
+    }
+
+    fn may_pipeline(&self, req: &Request<B>) -> bool {
+        self.pipeline_non_idempotent || req.method().is_idempotent()
+    }
 }
 
 impl<B> Dispatch for Client<B>
@@ -489,10 +516,25 @@ where
     fn poll_msg(
         mut self: Pin<&mut Self>,
         cx: &mut Context<'_>,
+        pipelined: bool,
     ) -> Poll<Option<Result<(Self::PollItem, Self::PollBody), Infallible>>> {
         let mut this = self.as_mut();
-        debug_assert!(!this.rx_closed);
-        match this.rx.poll_recv(cx) {
+        if pipelined && this.held.is_some() {
+            return Poll::Pending;
+        }
+        let next = match this.held.take() {
+            Some(held) => Poll::Ready(Some(held)),
+            None => {
+                debug_assert!(!this.rx_closed);
+                this.rx.poll_recv(cx)
+            }
+        };
+        match next {
+            Poll::Ready(Some((req, cb))) if pipelined && !this.may_pipeline(&req) => {
+                trace!("holding request until the responses ahead of it complete");
+                this.held = Some((req, cb));
+                Poll::Pending
+            }
             Poll::Ready(Some((req, mut cb))) => {
                 // check that future hasn't been canceled already
                 match cb.poll_canceled(cx) {
@@ -508,7 +550,7 @@ where
                             headers: parts.headers,
                             extensions: parts.extensions,
                         };
-                        this.callback = Some(cb);
+                        this.callbacks.push_back(cb);
                         Poll::Ready(Some(Ok((head, body))))
                     }
                 }
@@ -529,7 +571,7 @@ where
     ) -> crate::core::Result<()> {
         match msg {
             Ok((msg, body)) => {
-                if let Some(cb) = self.callback.take() {
+                if let Some(cb) = self.callbacks.pop_front() {
                     let res = msg.into_response(body);
                     cb.send(Ok(res));
                     Ok(())
@@ -541,7 +583,23 @@ where
                 }
             }
             Err(err) => {
-                if let Some(cb) = self.callback.take() {
+                // Pipelined requests behind the first were written, but
+                // their responses will not arrive.
+                let rest = self.callbacks.len().min(1);
+                for cb in self.callbacks.drain(rest..) {
+                    cb.send(Err(TrySendError {
+                        error: crate::core::Error::new_incomplete(),
+                        message: None,
+                    }));
+                }
+                if let Some((req, cb)) = self.held.take() {
+                    cb.send(Err(TrySendError {
+                        error: crate::core::Error::new_canceled().with("connection closed"),
+                        message: Some(req),
+                    }));
+                }
+
+                if let Some(cb) = self.callbacks.pop_front() {
                     cb.send(Err(TrySendError {
                         error: err,
                         message: None,
@@ -569,8 +627,8 @@ where
     }
 
     fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), ()>> {
-        match self.callback {
-            Some(ref mut cb) => match cb.poll_canceled(cx) {
+        match self.callbacks.front_mut() {
+            Some(cb) => match cb.poll_canceled(cx) {
                 Poll::Ready(()) => {
                     trace!("callback receiver has dropped");
                     Poll::Ready(Err(()))
@@ -582,7 +640,7 @@ where
     }
 
     fn should_poll(&self) -> bool {
-        self.callback.is_none()
+        self.callbacks.len() < self.pipeline_depth
     }
 }
 
@@ -660,6 +718,78 @@ mod tests {
         assert!(!tx.is_ready());
     }
 
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn client_pipelines_idempotent_requests() {
+        let _ = pretty_env_logger::try_init();
+
+        // Both requests go out before either response comes back.
+        let io = tokio_test::io::Builder::new()
+            .write(b"GET /a HTTP/1.1\r\n\r\n")
+            .write(b"GET /b HTTP/1.1\r\n\r\n")
+            .read(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+            .read(b"HTTP/1.1 404 Not Found\r\ncontent-length: 0\r\n\r\n")
+            .wait(Duration::from_secs(2))
+            .build();
+
+        let (mut tx, rx) = crate::core::client::dispatch::channel();
+        let mut conn = Conn::<_, bytes::Bytes, ClientTransaction>::new(Compat::new(io));
+        conn.set_pipelining(2, false);
+        let mut client = Client::new(rx);
+        client.set_pipelining(2, false);
+        let _dispatcher = tokio::spawn(Dispatcher::new(client, conn));
+
+        let get = |path| {
+            http::Request::get(path)
+                .body(IncomingBody::empty())
+                .unwrap()
+        };
+        let res_a = tx.try_send(get("/a")).unwrap();
+        std::future::poll_fn(|cx| tx.poll_ready(cx)).await.unwrap();
+        let res_b = tx.try_send(get("/b")).unwrap();
+
+        let res_a = res_a.await.unwrap().expect("response a");
+        let res_b = res_b.await.unwrap().expect("response b");
+        assert_eq!(res_a.status(), http::StatusCode::OK);
+        assert_eq!(res_b.status(), http::StatusCode::NOT_FOUND);
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn client_does_not_pipeline_non_idempotent_requests() {
+        let _ = pretty_env_logger::try_init();
+
+        // The POST waits for the response to the GET ahead of it.
+        let io = tokio_test::io::Builder::new()
+            .write(b"GET / HTTP/1.1\r\n\r\n")
+            .read(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+            .write(b"POST / HTTP/1.1\r\n\r\n")
+            .read(b"HTTP/1.1 201 Created\r\ncontent-length: 0\r\n\r\n")
+            .wait(Duration::from_secs(2))
+            .build();
+
+        let (mut tx, rx) = crate::core::client::dispatch::channel();
+        let mut conn = Conn::<_, bytes::Bytes, ClientTransaction>::new(Compat::new(io));
+        conn.set_pipelining(2, false);
+        let mut client = Client::new(rx);
+        client.set_pipelining(2, false);
+        let _dispatcher = tokio::spawn(Dispatcher::new(client, conn));
+
+        let res_get = tx
+            .try_send(http::Request::new(IncomingBody::empty()))
+            .unwrap();
+        std::future::poll_fn(|cx| tx.poll_ready(cx)).await.unwrap();
+        let post = http::Request::post("/")
+            .body(IncomingBody::empty())
+            .unwrap();
+        let res_post = tx.try_send(post).unwrap();
+
+        let res_get = res_get.await.unwrap().expect("response to get");
+        let res_post = res_post.await.unwrap().expect("response to post");
+        assert_eq!(res_get.status(), http::StatusCode::OK);
+        assert_eq!(res_post.status(), http::StatusCode::CREATED);
+    }
+
     #[cfg(not(miri))]
     #[tokio::test]
     async fn body_empty_chunks_ignored() {
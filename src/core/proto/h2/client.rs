@@ -3,6 +3,10 @@ use std::{
     future::Future,
     marker::PhantomData,
     pin::Pin,
+    sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    },
     task::{Context, Poll, ready},
     time::Duration,
 };
@@ -26,6 +30,7 @@ use pin_project_lite::pin_project;
 use super::{
     H2Upgraded, PipeToSendStream, SendBuf, ping,
     ping::{Ponger, Recorder},
+    watch::{Watch, Watched},
 };
 use crate::core::{
     body::Incoming as IncomingBody,
@@ -89,6 +94,9 @@ pub(crate) struct Config {
     pub(crate) experimental_settings: Option<ExperimentalSettings>,
     pub(crate) settings_order: Option<SettingsOrder>,
     pub(crate) priorities: Option<Priorities>,
+    pub(crate) max_connections_per_host: usize,
+    pub(crate) ping_on_demand: bool,
+    pub(crate) graceful_goaway: bool,
 }
 
 impl Default for Config {
@@ -117,10 +125,75 @@ impl Default for Config {
             headers_pseudo_order: None,
             headers_stream_dependency: None,
             priorities: None,
+            max_connections_per_host: 1,
+            ping_on_demand: false,
+            graceful_goaway: true,
         }
     }
 }
 
+/// How busy a client connection is, shared between its `SendRequest`
+/// handles and the dispatch task.
+#[derive(Clone, Debug)]
+pub(crate) struct StreamLoad {
+    shared: Arc<LoadShared>,
+}
+
+#[derive(Debug)]
+struct LoadShared {
+    active: AtomicUsize,
+    max_streams: AtomicUsize,
+}
+
+/// Counts as one in-flight stream on its connection until dropped.
+#[derive(Debug)]
+pub(crate) struct ActiveStream(Arc<LoadShared>);
+
+impl StreamLoad {
+    pub(crate) fn new(config: &Config) -> StreamLoad {
+        StreamLoad {
+            shared: Arc::new(LoadShared {
+                active: AtomicUsize::new(0),
+                max_streams: AtomicUsize::new(config.initial_max_send_streams),
+            }),
+        }
+    }
+
+    /// The number of requests sent on this connection whose responses
+    /// haven't finished yet.
+    pub(crate) fn active(&self) -> usize {
+        self.shared.active.load(Ordering::Relaxed)
+    }
+
+    /// Whether the peer's `SETTINGS_MAX_CONCURRENT_STREAMS` has been reached,
+    /// so that another request would have to wait for a stream to close.
+    pub(crate) fn is_saturated(&self) -> bool {
+        self.active() >= self.shared.max_streams.load(Ordering::Relaxed)
+    }
+
+    pub(crate) fn open(&self) -> ActiveStream {
+        self.shared.active.fetch_add(1, Ordering::Relaxed);
+        ActiveStream(self.shared.clone())
+    }
+
+    fn set_max_streams(&self, max: usize) {
+        self.shared.max_streams.store(max, Ordering::Relaxed);
+    }
+}
+
+impl Clone for ActiveStream {
+    fn clone(&self) -> ActiveStream {
+        self.0.active.fetch_add(1, Ordering::Relaxed);
+        ActiveStream(self.0.clone())
+    }
+}
+
+impl Drop for ActiveStream {
+    fn drop(&mut self) {
+        self.0.active.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
 fn new_builder(config: &Config) -> Builder {
     let mut builder = Builder::default();
     builder
@@ -186,6 +259,7 @@ fn new_ping_config(config: &Config) -> ping::Config {
         keep_alive_interval: config.keep_alive_interval,
         keep_alive_timeout: config.keep_alive_timeout,
         keep_alive_while_idle: config.keep_alive_while_idle,
+        on_demand: config.ping_on_demand,
     }
 }
 
@@ -193,6 +267,8 @@ pub(crate) async fn handshake<T, B, E>(
     io: T,
     req_rx: ClientRx<B>,
     config: &Config,
+    load: StreamLoad,
+    watch: Watch,
     mut exec: E,
     timer: Time,
 ) -> crate::core::Result<ClientTask<B, E, T>>
@@ -203,8 +279,9 @@ where
     E: Http2ClientConnExec<B, T> + Unpin,
     B::Error: Into<BoxError>,
 {
+    let ping_stats = watch.ping.clone();
     let (h2_tx, mut conn) = new_builder(config)
-        .handshake::<_, SendBuf<B::Data>>(Compat::new(io))
+        .handshake::<_, SendBuf<B::Data>>(Watched::new(Compat::new(io), watch))
         .await
         .map_err(crate::core::Error::new_h2)?;
 
@@ -219,7 +296,7 @@ where
 
     let (conn, ping) = if ping_config.is_enabled() {
         let pp = conn.ping_pong().expect("conn.ping_pong");
-        let (recorder, ponger) = ping::channel(pp, ping_config, timer);
+        let (recorder, ponger) = ping::channel(pp, ping_config, ping_stats, timer);
 
         let conn: Conn<_, B> = Conn::new(ponger, conn);
         (Either::left(conn), recorder)
@@ -242,6 +319,7 @@ where
         executor: exec,
         h2_tx,
         req_rx,
+        load,
         fut_ctx: None,
         marker: PhantomData,
     })
@@ -255,7 +333,7 @@ pin_project! {
         #[pin]
         ponger: Ponger,
         #[pin]
-        conn: Connection<Compat<T>, SendBuf<<B as Body>::Data>>,
+        conn: Connection<Watched<Compat<T>>, SendBuf<<B as Body>::Data>>,
     }
 }
 
@@ -264,7 +342,10 @@ where
     B: Body,
     T: Read + Write + Unpin,
 {
-    fn new(ponger: Ponger, conn: Connection<Compat<T>, SendBuf<<B as Body>::Data>>) -> Self {
+    fn new(
+        ponger: Ponger,
+        conn: Connection<Watched<Compat<T>>, SendBuf<<B as Body>::Data>>,
+    ) -> Self {
         Conn { ponger, conn }
     }
 }
@@ -303,7 +384,7 @@ pin_project! {
         T: Unpin,
     {
         #[pin]
-        conn: Either<Conn<T, B>, Connection<Compat<T>, SendBuf<<B as Body>::Data>>>,
+        conn: Either<Conn<T, B>, Connection<Watched<Compat<T>>, SendBuf<<B as Body>::Data>>>,
         #[pin]
         is_terminated: bool,
     }
@@ -458,6 +539,7 @@ where
     fut: ResponseFuture,
     body_tx: SendStream<SendBuf<B::Data>>,
     body: B,
+    stream: Option<ActiveStream>,
     cb: Callback<Request<B>, Response<IncomingBody>>,
 }
 
@@ -474,6 +556,7 @@ where
     executor: E,
     h2_tx: SendRequest<SendBuf<B::Data>>,
     req_rx: ClientRx<B>,
+    load: StreamLoad,
     fut_ctx: Option<FutCtx<B>>,
     marker: PhantomData<T>,
 }
@@ -517,6 +600,17 @@ where
     }
 }
 
+impl<B, E, T> ClientTask<B, E, T>
+where
+    B: Body,
+    E: Unpin,
+{
+    /// Returns a handle sending pings on the connection on demand.
+    pub(crate) fn pinger(&self) -> ping::Pinger {
+        self.ping.pinger()
+    }
+}
+
 impl<B, E, T> ClientTask<B, E, T>
 where
     B: Body + 'static + Unpin,
@@ -566,6 +660,7 @@ where
                     fut: f.fut,
                     ping: Some(ping),
                     send_stream: Some(send_stream),
+                    stream: f.stream,
                 },
                 call_back: Some(f.cb),
             },
@@ -585,6 +680,7 @@ pin_project! {
         ping: Option<Recorder>,
         #[pin]
         send_stream: Option<Option<SendStream<SendBuf<<B as Body>::Data>>>>,
+        stream: Option<ActiveStream>,
     }
 }
 
@@ -636,9 +732,10 @@ where
 
                     Poll::Ready(Ok(res))
                 } else {
+                    let active = this.stream.take();
                     let res = res.map(|stream| {
                         let ping = ping.for_stream(&stream);
-                        IncomingBody::h2(stream, content_length.into(), ping)
+                        IncomingBody::h2(stream, content_length.into(), ping, active)
                     });
                     Poll::Ready(Ok(res))
                 }
@@ -666,7 +763,11 @@ where
     fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
         loop {
             match ready!(self.h2_tx.poll_ready(cx)) {
-                Ok(()) => (),
+                Ok(()) => {
+                    // Keep the pool's view of the peer's stream limit current.
+                    self.load
+                        .set_max_streams(self.h2_tx.current_max_send_streams());
+                }
                 Err(err) => {
                     self.ping.ensure_not_timed_out()?;
                     return if err.reason() == Some(::http2::Reason::NO_ERROR) {
@@ -692,7 +793,8 @@ where
                         trace!("request callback is canceled");
                         continue;
                     }
-                    let (head, body) = req.into_parts();
+                    let (mut head, body) = req.into_parts();
+                    let stream = head.extensions.remove::<ActiveStream>();
                     let mut req = ::http::Request::from_parts(head, ());
                     super::strip_connection_headers(req.headers_mut(), true);
                     if let Some(len) = body.size_hint().exact() {
@@ -745,6 +847,7 @@ where
                         fut,
                         body_tx,
                         body,
+                        stream,
                         cb,
                     };
 
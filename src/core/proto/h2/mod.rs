@@ -1,5 +1,6 @@
 pub(crate) mod client;
 pub(crate) mod ping;
+pub(crate) mod watch;
 
 use std::{
     future::Future,
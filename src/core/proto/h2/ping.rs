@@ -1,11 +1,12 @@
 //! HTTP2 Ping usage
 //!
-//! hyper uses HTTP2 pings for two purposes:
+//! hyper uses HTTP2 pings for three purposes:
 //!
 //! 1. Adaptive flow control using BDP
 //! 2. Connection keep-alive
+//! 3. Checking an idle connection is alive before reusing it
 //!
-//! Both cases are optional.
+//! All cases are optional.
 //!
 //! # BDP Algorithm
 //!
@@ -20,8 +21,11 @@ use std::{
     fmt,
     future::Future,
     pin::Pin,
-    sync::{Arc, Mutex},
-    task::{self, Poll},
+    sync::{
+        Arc, Mutex, Weak,
+        atomic::{AtomicU64, Ordering},
+    },
+    task::{self, Poll, Waker},
     time::{Duration, Instant},
 };
 
@@ -35,7 +39,12 @@ pub(super) fn disabled() -> Recorder {
     Recorder { shared: None }
 }
 
-pub(super) fn channel(ping_pong: PingPong, config: Config, __timer: Time) -> (Recorder, Ponger) {
+pub(super) fn channel(
+    ping_pong: PingPong,
+    config: Config,
+    stats: PingStats,
+    __timer: Time,
+) -> (Recorder, Ponger) {
     debug_assert!(
         config.is_enabled(),
         "ping channel requires bdp or keep-alive config",
@@ -72,7 +81,10 @@ pub(super) fn channel(ping_pong: PingPong, config: Config, __timer: Time) -> (Re
         is_keep_alive_timed_out: false,
         ping_pong,
         ping_sent_at: None,
+        stats,
         next_bdp_at,
+        pongs: 0,
+        pong_waiters: Vec::new(),
     }));
 
     (
@@ -97,6 +109,8 @@ pub(super) struct Config {
     pub(super) keep_alive_timeout: Duration,
     /// If true, sends pings even when there are no active streams.
     pub(super) keep_alive_while_idle: bool,
+    /// If true, pings can be sent on demand through a [`Pinger`].
+    pub(super) on_demand: bool,
 }
 
 #[derive(Clone)]
@@ -104,15 +118,37 @@ pub(crate) struct Recorder {
     shared: Option<Arc<Mutex<Shared>>>,
 }
 
+/// Sends pings on demand.
+///
+/// Only holds a weak reference, so that it doesn't count as an open stream
+/// for keep-alive.
+#[derive(Clone)]
+pub(crate) struct Pinger {
+    shared: Option<Weak<Mutex<Shared>>>,
+}
+
 pub(super) struct Ponger {
     bdp: Option<Bdp>,
     keep_alive: Option<KeepAlive>,
     shared: Arc<Mutex<Shared>>,
 }
 
+/// The round trip of the last PING a connection sent, and how many of its
+/// PINGs are unanswered, shared by every handle to the connection.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PingStats(Arc<PingState>);
+
+#[derive(Debug, Default)]
+struct PingState {
+    /// The last round trip in nanoseconds, or `0` before the first pong.
+    rtt: AtomicU64,
+    unanswered: AtomicU64,
+}
+
 struct Shared {
     ping_pong: PingPong,
     ping_sent_at: Option<Instant>,
+    stats: PingStats,
 
     // bdp
     /// If `Some`, bdp is enabled, and this tracks how many bytes have been
@@ -128,6 +164,12 @@ struct Shared {
     last_read_at: Option<Instant>,
 
     is_keep_alive_timed_out: bool,
+
+    // on demand
+    /// How many pongs have been received.
+    pongs: u64,
+    /// Tasks waiting on the next pong.
+    pong_waiters: Vec<Waker>,
 }
 
 struct Bdp {
@@ -176,7 +218,7 @@ pub(super) struct KeepAliveTimedOut;
 
 impl Config {
     pub(super) fn is_enabled(&self) -> bool {
-        self.bdp_initial_window.is_some() || self.keep_alive_interval.is_some()
+        self.bdp_initial_window.is_some() || self.keep_alive_interval.is_some() || self.on_demand
     }
 }
 
@@ -239,6 +281,12 @@ impl Recorder {
         }
     }
 
+    pub(super) fn pinger(&self) -> Pinger {
+        Pinger {
+            shared: self.shared.as_ref().map(Arc::downgrade),
+        }
+    }
+
     pub(super) fn ensure_not_timed_out(&self) -> crate::core::Result<()> {
         if let Some(ref shared) = self.shared {
             let locked = shared.lock().unwrap();
@@ -252,6 +300,42 @@ impl Recorder {
     }
 }
 
+// ===== impl Pinger =====
+
+impl Pinger {
+    /// Sends a ping, unless one is already in flight, and waits for the pong.
+    ///
+    /// Resolves to `false` right away if on demand pings are not enabled or
+    /// the connection is gone. Otherwise it only resolves once a pong
+    /// arrives, so callers should bound it with a timeout.
+    pub(crate) fn ping(&self) -> impl Future<Output = bool> + Send + 'static {
+        let shared = self.shared.as_ref().and_then(Weak::upgrade);
+        let target = shared.as_ref().map(|shared| {
+            let mut locked = shared.lock().unwrap();
+            if !locked.is_ping_sent() {
+                locked.send_ping();
+            }
+            locked.pongs + 1
+        });
+        let shared = shared.map(|shared| Arc::downgrade(&shared));
+
+        std::future::poll_fn(move |cx| {
+            let (Some(shared), Some(target)) = (shared.as_ref().and_then(Weak::upgrade), target)
+            else {
+                return Poll::Ready(false);
+            };
+            let mut locked = shared.lock().unwrap();
+            if locked.pongs >= target {
+                return Poll::Ready(true);
+            }
+            if !locked.pong_waiters.iter().any(|w| w.will_wake(cx.waker())) {
+                locked.pong_waiters.push(cx.waker().clone());
+            }
+            Poll::Pending
+        })
+    }
+}
+
 // ===== impl Ponger =====
 
 impl Ponger {
@@ -278,6 +362,12 @@ impl Ponger {
                 locked.ping_sent_at = None;
                 let rtt = now - start;
                 trace!("recv pong");
+                locked.stats.on_pong(rtt);
+
+                locked.pongs += 1;
+                for waiter in locked.pong_waiters.drain(..) {
+                    waiter.wake();
+                }
 
                 if let Some(ref mut ka) = self.keep_alive {
                     locked.update_last_read_at();
@@ -320,6 +410,29 @@ impl Ponger {
     }
 }
 
+// ===== impl PingStats =====
+
+impl PingStats {
+    /// Returns the round trip of the last answered PING.
+    pub(crate) fn rtt(&self) -> Option<Duration> {
+        match self.0.rtt.load(Ordering::Relaxed) {
+            0 => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        }
+    }
+
+    /// Returns how many PINGs were sent since the last pong.
+    pub(crate) fn unanswered(&self) -> u64 {
+        self.0.unanswered.load(Ordering::Relaxed)
+    }
+
+    fn on_pong(&self, rtt: Duration) {
+        let nanos = u64::try_from(rtt.as_nanos()).unwrap_or(u64::MAX).max(1);
+        self.0.rtt.store(nanos, Ordering::Relaxed);
+        self.0.unanswered.store(0, Ordering::Relaxed);
+    }
+}
+
 // ===== impl Shared =====
 
 impl Shared {
@@ -327,6 +440,7 @@ impl Shared {
         match self.ping_pong.send_ping(Ping::opaque()) {
             Ok(()) => {
                 self.ping_sent_at = Some(Instant::now());
+                self.stats.0.unanswered.fetch_add(1, Ordering::Relaxed);
                 trace!("sent ping");
             }
             Err(_err) => {
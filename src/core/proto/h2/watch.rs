@@ -0,0 +1,511 @@
+//! Watching the frames a server sends, for what the h2 crate doesn't report:
+//! the server's initial SETTINGS and their round trip from the client's, and
+//! GOAWAY frames.
+
+use std::{
+    fmt,
+    io::{self, IoSlice},
+    pin::Pin,
+    sync::{
+        Arc, OnceLock,
+        atomic::{AtomicBool, Ordering},
+    },
+    task::{Context, Poll},
+    time::{Duration, Instant},
+};
+
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+
+use super::ping::PingStats;
+
+/// The length of an HTTP/2 frame header.
+const FRAME_HEADER_LEN: usize = 9;
+
+/// The type of a SETTINGS frame.
+const SETTINGS: u8 = 0x4;
+
+/// The type of a GOAWAY frame.
+const GOAWAY: u8 = 0x7;
+
+/// The flag set on a SETTINGS frame that acknowledges the peer's.
+const ACK: u8 = 0x1;
+
+/// The length of one setting in a SETTINGS payload: its id and its value.
+const SETTING_LEN: usize = 6;
+
+/// The length of the fixed part of a GOAWAY payload: the last stream id and
+/// the error code.
+const GOAWAY_LEN: usize = 8;
+
+/// The error code of a GOAWAY that shuts the connection down gracefully.
+const NO_ERROR: u32 = 0x0;
+
+/// The time from the client's initial SETTINGS to the server's SETTINGS ACK,
+/// shared by every handle to a connection.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct SettingsRtt(Arc<OnceLock<Duration>>);
+
+impl SettingsRtt {
+    /// Returns the round trip time, once the server has acknowledged the
+    /// client's SETTINGS.
+    pub(crate) fn get(&self) -> Option<Duration> {
+        self.0.get().copied()
+    }
+}
+
+/// The settings in the first SETTINGS frame a server sent on an HTTP/2
+/// connection.
+///
+/// Only the settings the server actually sent are reported: one it left out
+/// is `None`, rather than its default value from the specification.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Http2PeerSettings {
+    settings: Vec<(u16, u32)>,
+}
+
+impl Http2PeerSettings {
+    /// Returns `SETTINGS_HEADER_TABLE_SIZE`.
+    pub fn header_table_size(&self) -> Option<u32> {
+        self.get(0x1)
+    }
+
+    /// Returns `SETTINGS_ENABLE_PUSH`.
+    pub fn enable_push(&self) -> Option<bool> {
+        self.get(0x2).map(|value| value != 0)
+    }
+
+    /// Returns `SETTINGS_MAX_CONCURRENT_STREAMS`.
+    pub fn max_concurrent_streams(&self) -> Option<u32> {
+        self.get(0x3)
+    }
+
+    /// Returns `SETTINGS_INITIAL_WINDOW_SIZE`.
+    pub fn initial_window_size(&self) -> Option<u32> {
+        self.get(0x4)
+    }
+
+    /// Returns `SETTINGS_MAX_FRAME_SIZE`.
+    pub fn max_frame_size(&self) -> Option<u32> {
+        self.get(0x5)
+    }
+
+    /// Returns `SETTINGS_MAX_HEADER_LIST_SIZE`.
+    pub fn max_header_list_size(&self) -> Option<u32> {
+        self.get(0x6)
+    }
+
+    /// Returns `SETTINGS_ENABLE_CONNECT_PROTOCOL`, from RFC 8441.
+    pub fn enable_connect_protocol(&self) -> Option<bool> {
+        self.get(0x8).map(|value| value != 0)
+    }
+
+    /// Returns `SETTINGS_NO_RFC7540_PRIORITIES`, from RFC 9218.
+    pub fn no_rfc7540_priorities(&self) -> Option<bool> {
+        self.get(0x9).map(|value| value != 0)
+    }
+
+    /// Returns the value of the setting with identifier `id`, including
+    /// settings unknown to this crate.
+    ///
+    /// When the server sent a setting more than once, the last value is the
+    /// one in effect.
+    pub fn get(&self, id: u16) -> Option<u32> {
+        self.settings
+            .iter()
+            .rev()
+            .find(|(found, _)| *found == id)
+            .map(|(_, value)| *value)
+    }
+
+    /// Returns the identifiers and values of the settings, in the order the
+    /// server sent them.
+    pub fn iter(&self) -> impl Iterator<Item = (u16, u32)> + '_ {
+        self.settings.iter().copied()
+    }
+}
+
+/// The settings of a server, shared by every handle to a connection.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct PeerSettings(Arc<OnceLock<Http2PeerSettings>>);
+
+impl PeerSettings {
+    /// Returns the settings, once the server's first SETTINGS frame has been
+    /// read.
+    pub(crate) fn get(&self) -> Option<&Http2PeerSettings> {
+        self.0.get()
+    }
+}
+
+/// Whether the server sent a GOAWAY with `NO_ERROR`, shared by every handle
+/// to a connection.
+///
+/// Such a connection only drains: the streams in flight go on, but new ones
+/// would be refused.
+#[derive(Clone)]
+pub(crate) struct Draining(Arc<DrainState>);
+
+struct DrainState {
+    draining: AtomicBool,
+    on_drain: Box<dyn Fn() + Send + Sync>,
+    on_error: Box<dyn Fn() + Send + Sync>,
+}
+
+impl Draining {
+    /// Creates the state of a connection that isn't draining yet, calling
+    /// `on_drain` once it starts to, and `on_error` for every GOAWAY with an
+    /// error.
+    pub(crate) fn new<F, E>(on_drain: F, on_error: E) -> Draining
+    where
+        F: Fn() + Send + Sync + 'static,
+        E: Fn() + Send + Sync + 'static,
+    {
+        Draining(Arc::new(DrainState {
+            draining: AtomicBool::new(false),
+            on_drain: Box::new(on_drain),
+            on_error: Box::new(on_error),
+        }))
+    }
+
+    pub(crate) fn is_draining(&self) -> bool {
+        self.0.draining.load(Ordering::Relaxed)
+    }
+
+    fn start(&self) {
+        if !self.0.draining.swap(true, Ordering::Relaxed) {
+            debug!("server sent GOAWAY, connection is draining");
+            (self.0.on_drain)();
+        }
+    }
+
+    fn error(&self) {
+        debug!("server sent GOAWAY with an error");
+        (self.0.on_error)();
+    }
+}
+
+impl Default for Draining {
+    fn default() -> Draining {
+        Draining::new(|| (), || ())
+    }
+}
+
+impl fmt::Debug for Draining {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("Draining")
+            .field(&self.is_draining())
+            .finish()
+    }
+}
+
+/// What the frames of a connection are watched for.
+///
+/// PINGs are timed where they are sent, rather than here, but are reported
+/// along with the rest.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Watch {
+    pub(crate) settings_rtt: SettingsRtt,
+    pub(crate) peer_settings: PeerSettings,
+    pub(crate) draining: Draining,
+    pub(crate) ping: PingStats,
+    /// Whether a GOAWAY with `NO_ERROR` is left for the h2 crate to handle
+    /// when the connection closes, rather than draining it.
+    pub(crate) ignore_graceful_goaway: bool,
+}
+
+/// Watches the frames of a connection, and records what it sees into a
+/// [`Watch`].
+///
+/// The connection preface, which ends with the client's SETTINGS, is the
+/// first thing written, so the SETTINGS round trip is timed from the first
+/// write. Only frame headers are parsed, and the start of GOAWAY payloads.
+#[derive(Debug)]
+pub(crate) struct Watched<T> {
+    inner: T,
+    watch: Watch,
+    sent_at: Option<Instant>,
+    header: [u8; FRAME_HEADER_LEN],
+    header_len: usize,
+    payload_left: usize,
+    goaway: Option<([u8; GOAWAY_LEN], usize)>,
+    settings: Option<PendingSettings>,
+}
+
+/// The server's first SETTINGS, as its payload is read.
+#[derive(Debug, Default)]
+struct PendingSettings {
+    setting: [u8; SETTING_LEN],
+    len: usize,
+    settings: Vec<(u16, u32)>,
+}
+
+impl<T> Watched<T> {
+    pub(crate) fn new(inner: T, watch: Watch) -> Self {
+        Watched {
+            inner,
+            watch,
+            sent_at: None,
+            header: [0; FRAME_HEADER_LEN],
+            header_len: 0,
+            payload_left: 0,
+            goaway: None,
+            settings: None,
+        }
+    }
+
+    fn on_write(&mut self, res: &Poll<io::Result<usize>>) {
+        if matches!(res, Poll::Ready(Ok(n)) if *n > 0) && self.sent_at.is_none() {
+            self.sent_at = Some(Instant::now());
+        }
+    }
+
+    fn on_read(&mut self, mut buf: &[u8]) {
+        while !buf.is_empty() {
+            if self.payload_left > 0 {
+                let n = self.payload_left.min(buf.len());
+                if let Some((payload, len)) = self.goaway.as_mut() {
+                    let m = (GOAWAY_LEN - *len).min(n);
+                    payload[*len..*len + m].copy_from_slice(&buf[..m]);
+                    *len += m;
+                }
+                if let Some((payload, GOAWAY_LEN)) = self.goaway {
+                    self.goaway = None;
+                    let [.., e0, e1, e2, e3] = payload;
+                    if u32::from_be_bytes([e0, e1, e2, e3]) == NO_ERROR {
+                        if !self.watch.ignore_graceful_goaway {
+                            self.watch.draining.start();
+                        }
+                    } else {
+                        self.watch.draining.error();
+                    }
+                }
+                if let Some(pending) = self.settings.as_mut() {
+                    for &byte in &buf[..n] {
+                        pending.setting[pending.len] = byte;
+                        pending.len += 1;
+                        if pending.len == SETTING_LEN {
+                            pending.len = 0;
+                            let [i0, i1, v0, v1, v2, v3] = pending.setting;
+                            pending.settings.push((
+                                u16::from_be_bytes([i0, i1]),
+                                u32::from_be_bytes([v0, v1, v2, v3]),
+                            ));
+                        }
+                    }
+                    if n == self.payload_left {
+                        let settings = self.settings.take().unwrap_or_default().settings;
+                        self.on_peer_settings(settings);
+                    }
+                }
+                self.payload_left -= n;
+                buf = &buf[n..];
+                continue;
+            }
+
+            let n = (FRAME_HEADER_LEN - self.header_len).min(buf.len());
+            self.header[self.header_len..self.header_len + n].copy_from_slice(&buf[..n]);
+            self.header_len += n;
+            buf = &buf[n..];
+            if self.header_len < FRAME_HEADER_LEN {
+                return;
+            }
+
+            self.header_len = 0;
+            let [l0, l1, l2, kind, flags, ..] = self.header;
+            self.payload_left = u32::from_be_bytes([0, l0, l1, l2]) as usize;
+            match kind {
+                SETTINGS if flags & ACK == ACK => self.on_settings_ack(),
+                SETTINGS if self.watch.peer_settings.get().is_none() => {
+                    if self.payload_left == 0 {
+                        self.on_peer_settings(Vec::new());
+                    } else {
+                        self.settings = Some(PendingSettings::default());
+                    }
+                }
+                GOAWAY if self.payload_left >= GOAWAY_LEN => {
+                    self.goaway = Some(([0; GOAWAY_LEN], 0));
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn on_peer_settings(&self, settings: Vec<(u16, u32)>) {
+        trace!("server sent SETTINGS {:?}", settings);
+        let _ = self
+            .watch
+            .peer_settings
+            .0
+            .set(Http2PeerSettings { settings });
+    }
+
+    fn on_settings_ack(&self) {
+        if let (Some(sent_at), None) = (self.sent_at, self.watch.settings_rtt.get()) {
+            let rtt = sent_at.elapsed();
+            trace!("SETTINGS acknowledged after {:?}", rtt);
+            let _ = self.watch.settings_rtt.0.set(rtt);
+        }
+    }
+}
+
+impl<T: AsyncRead + Unpin> AsyncRead for Watched<T> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let res = Pin::new(&mut self.inner).poll_read(cx, buf);
+        if res.is_ready() {
+            self.on_read(&buf.filled()[filled..]);
+        }
+        res
+    }
+}
+
+impl<T: AsyncWrite + Unpin> AsyncWrite for Watched<T> {
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let res = Pin::new(&mut self.inner).poll_write(cx, buf);
+        self.on_write(&res);
+        res
+    }
+
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        let res = Pin::new(&mut self.inner).poll_write_vectored(cx, bufs);
+        self.on_write(&res);
+        res
+    }
+
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicUsize;
+
+    use super::*;
+
+    #[test]
+    fn records_first_settings_ack() {
+        let watch = Watch::default();
+        let mut watched = Watched::new((), watch.clone());
+        watched.sent_at = Some(Instant::now());
+
+        // The server's SETTINGS with one setting, then a WINDOW_UPDATE, split
+        // across reads.
+        watched.on_read(&[0, 0, 6, SETTINGS, 0, 0, 0]);
+        watched.on_read(&[0, 0, 0, 3, 0, 0, 0, 100]);
+        watched.on_read(&[0, 0, 4, 0x8, 0, 0, 0, 0, 0, 0, 1, 0, 0]);
+        assert_eq!(watch.settings_rtt.get(), None);
+
+        watched.on_read(&[0, 0, 0, SETTINGS, ACK, 0, 0, 0, 0]);
+        assert!(watch.settings_rtt.get().is_some());
+    }
+
+    #[test]
+    fn records_first_peer_settings() {
+        let watch = Watch::default();
+        let mut watched = Watched::new((), watch.clone());
+
+        // MAX_CONCURRENT_STREAMS and an unknown setting, split across reads,
+        // then a SETTINGS that is ignored.
+        watched.on_read(&[0, 0, 12, SETTINGS, 0, 0, 0, 0, 0, 0, 3, 0]);
+        watched.on_read(&[0, 0, 100, 0xfa]);
+        assert_eq!(watch.peer_settings.get(), None);
+        watched.on_read(&[0xce, 0, 0, 0, 1]);
+        watched.on_read(&[0, 0, 6, SETTINGS, 0, 0, 0, 0, 0, 0, 4, 0, 0, 0, 1]);
+
+        let settings = watch.peer_settings.get().unwrap();
+        assert_eq!(settings.max_concurrent_streams(), Some(100));
+        assert_eq!(settings.get(0xface), Some(1));
+        assert_eq!(settings.initial_window_size(), None);
+        assert_eq!(settings.enable_push(), None);
+        assert_eq!(
+            settings.iter().collect::<Vec<_>>(),
+            [(0x3, 100), (0xface, 1)]
+        );
+    }
+
+    #[test]
+    fn records_empty_peer_settings() {
+        let watch = Watch::default();
+        let mut watched = Watched::new((), watch.clone());
+
+        watched.on_read(&[0, 0, 0, SETTINGS, 0, 0, 0, 0, 0]);
+        assert_eq!(
+            watch.peer_settings.get(),
+            Some(&Http2PeerSettings::default())
+        );
+    }
+
+    #[test]
+    fn drains_on_graceful_goaway() {
+        let drained = Arc::new(AtomicUsize::new(0));
+        let errors = Arc::new(AtomicUsize::new(0));
+        let watch = Watch {
+            settings_rtt: SettingsRtt::default(),
+            peer_settings: PeerSettings::default(),
+            ping: PingStats::default(),
+            ignore_graceful_goaway: false,
+            draining: Draining::new(
+                {
+                    let drained = drained.clone();
+                    move || {
+                        drained.fetch_add(1, Ordering::Relaxed);
+                    }
+                },
+                {
+                    let errors = errors.clone();
+                    move || {
+                        errors.fetch_add(1, Ordering::Relaxed);
+                    }
+                },
+            ),
+        };
+        let mut watched = Watched::new((), watch.clone());
+
+        // A GOAWAY with an error, then two with `NO_ERROR` and debug data,
+        // the first split across reads.
+        watched.on_read(&[0, 0, 8, GOAWAY, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 2]);
+        assert!(!watch.draining.is_draining());
+        assert_eq!(errors.load(Ordering::Relaxed), 1);
+
+        watched.on_read(&[0, 0, 10, GOAWAY, 0, 0, 0, 0, 0, 0x7f, 0xff]);
+        watched.on_read(&[0xff, 0xff, 0, 0, 0]);
+        assert!(!watch.draining.is_draining());
+        watched.on_read(&[0, b'b', b'y']);
+        assert!(watch.draining.is_draining());
+
+        watched.on_read(&[0, 0, 8, GOAWAY, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0]);
+        assert_eq!(drained.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn ignores_graceful_goaway_when_asked() {
+        let watch = Watch {
+            ignore_graceful_goaway: true,
+            ..Watch::default()
+        };
+        let mut watched = Watched::new((), watch.clone());
+
+        watched.on_read(&[0, 0, 8, GOAWAY, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0, 0]);
+        assert!(!watch.draining.is_draining());
+    }
+}
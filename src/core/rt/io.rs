@@ -204,7 +204,7 @@ impl<'data> ReadBuf<'data> {
     }
 
     #[inline]
-    fn capacity(&self) -> usize {
+    pub(crate) fn capacity(&self) -> usize {
         self.raw.len()
     }
 }
@@ -251,6 +251,13 @@ impl ReadBufCursor<'_> {
         self.buf.remaining()
     }
 
+    /// Returns how many bytes at the start of the unfilled part are already
+    /// initialized.
+    #[inline]
+    pub(crate) fn init_remaining(&self) -> usize {
+        self.buf.init.saturating_sub(self.buf.filled)
+    }
+
     /// Transfer bytes into `self`` from `src` and advance the cursor
     /// by the number of bytes written.
     ///
@@ -110,7 +110,7 @@ pub(super) fn pending() -> (Pending, OnUpgrade) {
 // ===== impl Upgraded =====
 
 impl Upgraded {
-    pub(super) fn new<T>(io: T, read_buf: Bytes) -> Self
+    pub(crate) fn new<T>(io: T, read_buf: Bytes) -> Self
     where
         T: Read + Write + Unpin + Send + 'static,
     {
@@ -3,6 +3,8 @@
 #[cfg(feature = "hickory-dns")]
 pub use hickory::{HickoryDnsResolver, LookupIpStrategy};
 pub use resolve::{Addrs, Name, Resolve, Resolving};
+
+pub use crate::core::client::connect::dns::AddressOrder;
 pub(crate) use resolve::{DnsResolverWithOverrides, DynResolver};
 
 pub(crate) mod gai;
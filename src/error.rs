@@ -1,5 +1,7 @@
 use std::{error::Error as StdError, fmt, io};
 
+use bytes::Bytes;
+
 use crate::{StatusCode, Url};
 
 /// A `Result` alias where the `Err` case is `wreq::Error`.
@@ -76,6 +78,10 @@ impl Error {
     pub(crate) fn uri_bad_host() -> Error {
         Error::new(Kind::Builder, Some("no host in url"))
     }
+
+    pub(crate) fn uri_bad_scheme() -> Error {
+        Error::new(Kind::Builder, Some(BadScheme))
+    }
 }
 
 impl Error {
@@ -160,6 +166,69 @@ impl Error {
         false
     }
 
+    /// Returns true if the error is from a connect aborted by the client's
+    /// cancellation token.
+    pub fn is_cancelled(&self) -> bool {
+        self.source_is::<Cancelled>()
+    }
+
+    /// Returns true if the server did not select one of the ALPN protocols
+    /// offered, while ALPN was required.
+    pub fn is_alpn_mismatch(&self) -> bool {
+        self.source_is::<AlpnMismatch>()
+    }
+
+    /// Returns true if the server's certificate was rejected for its key
+    /// being weaker than required.
+    pub fn is_weak_key(&self) -> bool {
+        self.source_is::<WeakKey>()
+    }
+
+    /// Returns true if the key of the server's certificate matches none of
+    /// the pinned keys.
+    pub fn is_pin_mismatch(&self) -> bool {
+        self.source_is::<PinMismatch>()
+    }
+
+    /// Returns true if the server's certificate expires sooner than the
+    /// validity it was required to have left.
+    pub fn is_cert_expiring_soon(&self) -> bool {
+        self.source_is::<CertExpiringSoon>()
+    }
+
+    /// Returns true if the OCSP response stapled by the server revokes its
+    /// certificate.
+    pub fn is_cert_revoked(&self) -> bool {
+        self.source_is::<CertRevoked>()
+    }
+
+    /// Returns true if the server sent something other than TLS before the
+    /// handshake completed, such as a plaintext banner.
+    pub fn is_plaintext_before_tls(&self) -> bool {
+        self.source_is::<PlaintextBeforeTls>()
+    }
+
+    /// Returns true if the client failed to build because its TLS cipher list
+    /// names cipher suites that are not supported.
+    pub fn is_unsupported_cipher(&self) -> bool {
+        self.source_is::<UnsupportedCipher>()
+    }
+
+    /// Returns true if an error of type `T` is found in the chain of sources.
+    fn source_is<T: StdError + 'static>(&self) -> bool {
+        let mut source = self.source();
+
+        while let Some(err) = source {
+            if err.is::<T>() {
+                return true;
+            }
+
+            source = err.source();
+        }
+
+        false
+    }
+
     /// Returns true if the error is related to the request
     pub fn is_request(&self) -> bool {
         matches!(self.inner.kind, Kind::Request)
@@ -182,6 +251,17 @@ impl Error {
         false
     }
 
+    /// Returns true if the error is from a connect that failed at the
+    /// connection level, such as by being refused or reset during the TCP or
+    /// TLS handshake, and may succeed if attempted again.
+    ///
+    /// Timeouts and rejected certificates are not retryable. These are the
+    /// errors retried by
+    /// [`ClientBuilder::connect_retries`](crate::ClientBuilder::connect_retries).
+    pub fn is_connect_retryable(&self) -> bool {
+        self.is_connect() && self.source().is_some_and(is_retryable_connect)
+    }
+
     /// Returns true if the error is related to a connection reset.
     pub fn is_connection_reset(&self) -> bool {
         let mut source = self.source();
@@ -213,6 +293,28 @@ impl Error {
         matches!(self.inner.kind, Kind::Upgrade)
     }
 
+    /// Returns the error code of the `RST_STREAM` frame with which the server
+    /// reset the request's HTTP/2 stream, if that is why the request failed.
+    ///
+    /// A server answers with [`REFUSED_STREAM`](crate::http2::Reason::REFUSED_STREAM)
+    /// when it did not process the request at all, so the request can safely
+    /// be retried on a new connection, unlike after an `INTERNAL_ERROR`.
+    pub fn h2_reset_reason(&self) -> Option<crate::http2::Reason> {
+        let mut source = self.source();
+
+        while let Some(err) = source {
+            if let Some(h2) = err.downcast_ref::<http2::Error>() {
+                if h2.is_reset() && h2.is_remote() {
+                    return h2.reason();
+                }
+            }
+
+            source = err.source();
+        }
+
+        None
+    }
+
     /// Returns the status code, if the error was generated from a response.
     pub fn status(&self) -> Option<StatusCode> {
         match self.inner.kind {
@@ -222,6 +324,34 @@ impl Error {
     }
 }
 
+/// Whether a connect failed with `err` at the connection level, and may
+/// succeed if attempted again.
+pub(crate) fn is_retryable_connect(err: &(dyn StdError + 'static)) -> bool {
+    let mut source = Some(err);
+
+    while let Some(err) = source {
+        if err.is::<TimedOut>() || err.is::<Cancelled>() {
+            return false;
+        }
+
+        if let Some(io) = err.downcast_ref::<io::Error>() {
+            if matches!(
+                io.kind(),
+                io::ErrorKind::ConnectionRefused
+                    | io::ErrorKind::ConnectionReset
+                    | io::ErrorKind::ConnectionAborted
+                    | io::ErrorKind::BrokenPipe
+                    | io::ErrorKind::UnexpectedEof
+            ) {
+                return true;
+            }
+        }
+        source = err.source();
+    }
+
+    false
+}
+
 /// Maps external timeout errors (such as `tower::timeout::error::Elapsed`)
 /// to the internal `TimedOut` error type used for connector operations.
 /// Returns the original error if it is not a timeout.
@@ -363,6 +493,159 @@ impl fmt::Display for TimedOut {
 
 impl StdError for TimedOut {}
 
+#[derive(Debug)]
+#[cfg_attr(not(feature = "cancellation"), allow(dead_code))]
+pub(crate) struct Cancelled;
+
+impl fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("operation was cancelled")
+    }
+}
+
+impl StdError for Cancelled {}
+
+#[derive(Debug)]
+pub(crate) struct AlpnMismatch {
+    /// The wire-encoded list of protocols offered to the server.
+    pub(crate) offered: Bytes,
+    pub(crate) selected: Option<Vec<u8>>,
+}
+
+impl fmt::Display for AlpnMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("server did not select an offered ALPN protocol (offered: ")?;
+        for (i, proto) in crate::tls::decode_sequence(&self.offered).enumerate() {
+            if i > 0 {
+                f.write_str(", ")?;
+            }
+            f.write_str(&String::from_utf8_lossy(proto))?;
+        }
+        match self.selected {
+            Some(ref proto) => write!(f, "; selected: {})", String::from_utf8_lossy(proto)),
+            None => f.write_str("; selected: none)"),
+        }
+    }
+}
+
+impl StdError for AlpnMismatch {}
+
+#[derive(Debug)]
+pub(crate) enum WeakKey {
+    /// An RSA key with fewer bits than required.
+    Rsa { bits: u32, min: u16 },
+    /// An EC key on a curve that is not allowed, or on no named curve.
+    Curve(Option<boring2::nid::Nid>),
+}
+
+impl fmt::Display for WeakKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("server certificate key is too weak: ")?;
+        match *self {
+            WeakKey::Rsa { bits, min } => write!(f, "{bits}-bit RSA, at least {min} required"),
+            WeakKey::Curve(Some(curve)) => match curve.short_name() {
+                Ok(name) => write!(f, "curve {name} is not allowed"),
+                Err(_) => write!(f, "curve {} is not allowed", curve.as_raw()),
+            },
+            WeakKey::Curve(None) => f.write_str("unnamed curve is not allowed"),
+        }
+    }
+}
+
+impl StdError for WeakKey {}
+
+#[derive(Debug)]
+pub(crate) struct PinMismatch {
+    /// The SHA-256 digest of the server's `SubjectPublicKeyInfo`, if it
+    /// presented a certificate.
+    pub(crate) found: Option<[u8; 32]>,
+}
+
+impl fmt::Display for PinMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use base64::{Engine, prelude::BASE64_STANDARD};
+
+        match self.found {
+            Some(ref found) => write!(
+                f,
+                "server certificate key matches no pinned key: sha256/{}",
+                BASE64_STANDARD.encode(found)
+            ),
+            None => f.write_str("server presented no certificate key to check against the pins"),
+        }
+    }
+}
+
+impl StdError for PinMismatch {}
+
+#[derive(Debug)]
+pub(crate) struct CertExpiringSoon {
+    /// How long the server's certificate remains valid.
+    pub(crate) remaining: std::time::Duration,
+    /// How long it was required to remain valid.
+    pub(crate) min: std::time::Duration,
+}
+
+impl fmt::Display for CertExpiringSoon {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "server certificate expires in {}s, at least {}s of validity required",
+            self.remaining.as_secs(),
+            self.min.as_secs()
+        )
+    }
+}
+
+impl StdError for CertExpiringSoon {}
+
+#[derive(Debug)]
+pub(crate) struct CertRevoked;
+
+impl fmt::Display for CertRevoked {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("server certificate revoked by its stapled OCSP response")
+    }
+}
+
+impl StdError for CertRevoked {}
+
+#[derive(Debug)]
+pub(crate) struct PlaintextBeforeTls {
+    /// The first bytes the server sent, as far as they had arrived.
+    pub(crate) sniffed: Vec<u8>,
+}
+
+impl fmt::Display for PlaintextBeforeTls {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("received plaintext before TLS handshake")?;
+        if !self.sniffed.is_empty() {
+            write!(f, ": \"{}\"", self.sniffed.escape_ascii())?;
+        }
+        Ok(())
+    }
+}
+
+impl StdError for PlaintextBeforeTls {}
+
+#[derive(Debug)]
+pub(crate) struct UnsupportedCipher {
+    /// The names in the cipher list that select no cipher suite.
+    pub(crate) rejected: Vec<String>,
+}
+
+impl fmt::Display for UnsupportedCipher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "unsupported cipher suites in cipher list: {}",
+            self.rejected.join(", ")
+        )
+    }
+}
+
+impl StdError for UnsupportedCipher {}
+
 #[derive(Debug)]
 pub(crate) struct BadScheme;
 
@@ -449,6 +732,82 @@ mod tests {
         assert!(nested.is_timeout());
     }
 
+    #[test]
+    fn source_is() {
+        let err = Error::request(super::Cancelled);
+        assert!(err.source_is::<super::Cancelled>());
+        assert!(!err.source_is::<super::TimedOut>());
+
+        let io = io::Error::other(err);
+        let nested = Error::request(io);
+        assert!(nested.source_is::<super::Cancelled>());
+    }
+
+    #[test]
+    fn display_tls_rejections() {
+        let mismatch = super::AlpnMismatch {
+            offered: Bytes::from_static(b"\x02h2\x08http/1.1"),
+            selected: None,
+        };
+        assert_eq!(
+            mismatch.to_string(),
+            "server did not select an offered ALPN protocol (offered: h2, http/1.1; selected: none)"
+        );
+
+        let weak = super::WeakKey::Rsa {
+            bits: 1024,
+            min: 2048,
+        };
+        assert_eq!(
+            weak.to_string(),
+            "server certificate key is too weak: 1024-bit RSA, at least 2048 required"
+        );
+
+        let mismatch = super::PinMismatch {
+            found: Some([0; 32]),
+        };
+        assert_eq!(
+            mismatch.to_string(),
+            "server certificate key matches no pinned key: \
+             sha256/AAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAAA="
+        );
+
+        let expiring = super::CertExpiringSoon {
+            remaining: std::time::Duration::from_secs(3600),
+            min: std::time::Duration::from_secs(86400),
+        };
+        assert_eq!(
+            expiring.to_string(),
+            "server certificate expires in 3600s, at least 86400s of validity required"
+        );
+
+        let plaintext = super::PlaintextBeforeTls {
+            sniffed: b"SSH-2.0-OpenSSH_9.6\r\n".to_vec(),
+        };
+        assert_eq!(
+            plaintext.to_string(),
+            "received plaintext before TLS handshake: \"SSH-2.0-OpenSSH_9.6\\r\\n\""
+        );
+
+        let unsupported = super::UnsupportedCipher {
+            rejected: vec!["RC4-MD5".into(), "TLS_NOPE".into()],
+        };
+        assert_eq!(
+            unsupported.to_string(),
+            "unsupported cipher suites in cipher list: RC4-MD5, TLS_NOPE"
+        );
+    }
+
+    #[test]
+    fn h2_reset_reason() {
+        let err = Error::request(http2::Error::from(http2::Reason::REFUSED_STREAM));
+        // Not received from the peer.
+        assert_eq!(err.h2_reset_reason(), None);
+
+        let err = Error::request(io::Error::other("not h2"));
+        assert_eq!(err.h2_reset_reason(), None);
+    }
+
     #[test]
     fn is_connection_reset() {
         let err = Error::request(io::Error::new(
@@ -461,4 +820,18 @@ mod tests {
         let nested = Error::request(io);
         assert!(nested.is_connection_reset());
     }
+
+    #[test]
+    fn is_retryable_connect() {
+        let refused = io::Error::new(io::ErrorKind::ConnectionRefused, "refused");
+        assert!(super::is_retryable_connect(&refused));
+
+        let nested = io::Error::other(Error::request(refused));
+        assert!(super::is_retryable_connect(&nested));
+
+        assert!(!super::is_retryable_connect(&super::TimedOut));
+        assert!(!super::is_retryable_connect(&*BoxError::from(
+            "bad certificate"
+        )));
+    }
 }
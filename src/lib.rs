@@ -266,12 +266,16 @@
 //! - **multipart**: Provides functionality for multipart forms.
 //! - **charset** *(enabled by default)*: Improved support for decoding text.
 //! - **stream**: Adds support for `futures::Stream`.
+//! - **cancellation**: Aborts in-flight connects with a `tokio_util` `CancellationToken`.
 //! - **socks**: Provides SOCKS5 and SOCKS4 proxy support.
 //! - **hickory-dns**: Enables a hickory-dns async resolver instead of default threadpool using
 //!   `getaddrinfo`.
 //! - **native-roots**: Use the native system root certificate store.
 //! - **webpki-roots** *(enabled by default)*: Use the webpki-roots crate for root certificates.
 //! - **system-proxy** *(enabled by default)*: Enable system proxy support.
+//! - **system-keystore**: Load client identities and trust anchors from the keychains on macOS, or
+//!   the certificate stores on Windows.
+//! - **blocking**: Provides a blocking connect for tools without an async runtime.
 //! - **tracing**: Enable tracing logging support.
 //!
 //! [client]: ./struct.Client.html
@@ -331,21 +335,30 @@ doc_comment::doctest!("../README.md");
 pub use self::client::multipart;
 #[cfg(feature = "websocket")]
 pub use self::client::websocket;
+#[cfg(feature = "socks")]
+pub use self::core::client::connect::proxy::{SocksBoundAddr, SocksUdpSocket};
 pub use self::{
     client::{
         Body, Client, ClientBuilder, EmulationProvider, EmulationProviderFactory, Request,
         RequestBuilder, Response, Upgraded,
     },
+    connect::IoDirection,
     core::{
         client::{
             Dst,
             config::{http1, http2},
+            connect::{
+                ByteCounts, CloseReason, ConnEvent, ConnectTiming,
+                proxy::{ClientInfo, ProxyProtocolVersion},
+            },
         },
         header::OriginalHeaders,
     },
-    proxy::{NoProxy, Proxy},
+    proxy::{NoProxy, Proxy, ProxyInfo, ProxyMatchPolicy},
 };
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
 mod client;
 mod connect;
 #[cfg(feature = "cookies")]
@@ -1,8 +1,11 @@
-use std::{error::Error as StdError, fmt, sync::Arc};
+use std::{error::Error as StdError, fmt, hash::Hash, sync::Arc};
 
 #[cfg(feature = "socks")]
 use bytes::Bytes;
-use http::{HeaderMap, Uri, header::HeaderValue};
+use http::{
+    HeaderMap, Uri,
+    header::{HeaderName, HeaderValue, PROXY_AUTHORIZATION},
+};
 
 use crate::{
     Url,
@@ -59,6 +62,8 @@ pub struct Proxy {
     extra: Extra,
     intercept: Intercept,
     no_proxy: Option<NoProxy>,
+    fallbacks: Vec<Proxy>,
+    fallback_direct: bool,
 }
 
 /// A configuration for filtering out requests that shouldn't be proxied
@@ -71,6 +76,29 @@ pub struct NoProxy {
 struct Extra {
     auth: Option<HeaderValue>,
     misc: Option<HeaderMap>,
+    tunnel_filter: Option<HeaderFilter>,
+}
+
+/// Which headers a `CONNECT` request to the proxy keeps.
+#[derive(Clone, PartialEq, Eq, Hash)]
+enum HeaderFilter {
+    Allow(Vec<HeaderName>),
+    Deny(Vec<HeaderName>),
+}
+
+impl HeaderFilter {
+    fn apply(&self, headers: &mut HeaderMap) {
+        let names: Vec<HeaderName> = headers.keys().cloned().collect();
+        for name in names {
+            let keep = match self {
+                HeaderFilter::Allow(allowed) => allowed.contains(&name),
+                HeaderFilter::Deny(denied) => !denied.contains(&name),
+            };
+            if !keep {
+                headers.remove(&name);
+            }
+        }
+    }
 }
 
 impl std::hash::Hash for Extra {
@@ -93,6 +121,8 @@ impl std::hash::Hash for Extra {
         } else {
             state.write_u8(0);
         }
+
+        self.tunnel_filter.hash(state);
     }
 }
 
@@ -104,6 +134,8 @@ pub(crate) struct Matcher {
     extra: Extra,
     maybe_has_http_auth: bool,
     maybe_has_http_custom_headers: bool,
+    fallbacks: Arc<[Matcher]>,
+    fallback_direct: bool,
 }
 
 #[derive(Clone)]
@@ -120,6 +152,38 @@ pub(crate) struct Intercepted {
     /// This is because of `wreq::Proxy`'s design which allows configuring
     /// an explicit auth, besides what might have been in the URL (or Custom).
     extra: Extra,
+    /// The proxies to try in order when this one can't be reached.
+    fallbacks: Vec<Intercepted>,
+    /// Whether to connect directly when no proxy can be reached.
+    fallback_direct: bool,
+}
+
+/// The proxy a connection was made through.
+///
+/// It is available in the response extensions of requests sent through a
+/// proxy, whether tunneled, forwarded or over SOCKS, and missing for direct
+/// connections.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ProxyInfo(Uri);
+
+impl ProxyInfo {
+    /// Returns the scheme, host and port of the proxy, without credentials.
+    pub fn uri(&self) -> &Uri {
+        &self.0
+    }
+}
+
+/// What to do when more than one proxy of a `Client` matches a destination.
+///
+/// Proxies are matched in the order they were added, after which the
+/// system proxy, if enabled, comes last.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ProxyMatchPolicy {
+    /// Use the first proxy that matches, and log a warning naming the others.
+    #[default]
+    FirstWins,
+    /// Fail the connection with an error naming every proxy that matches.
+    Strict,
 }
 
 /// Trait used for converting into a proxy scheme. This trait supports
@@ -267,9 +331,12 @@ impl Proxy {
             extra: Extra {
                 auth: None,
                 misc: None,
+                tunnel_filter: None,
             },
             intercept,
             no_proxy: None,
+            fallbacks: Vec::new(),
+            fallback_direct: false,
         }
     }
 
@@ -343,6 +410,66 @@ impl Proxy {
         self
     }
 
+    /// Keeps only the named headers in the `CONNECT` request that opens a
+    /// tunnel through this proxy.
+    ///
+    /// This applies to the [custom headers](Proxy::custom_http_headers) and
+    /// the `Proxy-Authorization` header, which is dropped too unless named.
+    /// The `Host` header is always sent. Only the request establishing the
+    /// tunnel is affected, not the HTTP requests sent through it, nor
+    /// requests forwarded by an `http` proxy. Replaces any
+    /// [`tunnel_header_denylist`](Proxy::tunnel_header_denylist).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate wreq;
+    /// # use wreq::header::*;
+    /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let proxy = wreq::Proxy::https("http://localhost:1234")?
+    ///     .basic_auth("Aladdin", "open sesame")
+    ///     .tunnel_header_allowlist([PROXY_AUTHORIZATION]);
+    /// # Ok(())
+    /// # }
+    /// # fn main() {}
+    /// ```
+    pub fn tunnel_header_allowlist<I>(mut self, names: I) -> Proxy
+    where
+        I: IntoIterator<Item = HeaderName>,
+    {
+        self.extra.tunnel_filter = Some(HeaderFilter::Allow(names.into_iter().collect()));
+        self
+    }
+
+    /// Leaves the named headers out of the `CONNECT` request that opens a
+    /// tunnel through this proxy.
+    ///
+    /// This keeps headers such as a `User-Agent` among the
+    /// [custom headers](Proxy::custom_http_headers) from reaching the proxy.
+    /// Only the request establishing the tunnel is affected, not the HTTP
+    /// requests sent through it, nor requests forwarded by an `http` proxy.
+    /// Replaces any [`tunnel_header_allowlist`](Proxy::tunnel_header_allowlist).
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate wreq;
+    /// # use wreq::header::*;
+    /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let proxy = wreq::Proxy::https("http://localhost:1234")?
+    ///     .tunnel_header_denylist([USER_AGENT]);
+    /// # Ok(())
+    /// # }
+    /// # fn main() {}
+    /// ```
+    pub fn tunnel_header_denylist<I>(mut self, names: I) -> Proxy
+    where
+        I: IntoIterator<Item = HeaderName>,
+    {
+        self.extra.tunnel_filter = Some(HeaderFilter::Deny(names.into_iter().collect()));
+        self
+    }
+
     /// Adds a `No Proxy` exclusion list to this Proxy
     ///
     /// # Example
@@ -361,11 +488,57 @@ impl Proxy {
         self
     }
 
+    /// Try `proxy` when this proxy, and the fallbacks added before it, can't
+    /// be reached.
+    ///
+    /// Only a failure to reach a proxy moves on to the next one. Once a
+    /// proxy is connected, the request goes through it, whether or not the
+    /// proxy then reaches the origin. A fallback that doesn't intercept the
+    /// request, by its own rules, is skipped. The whole chain is tried within
+    /// a single connect timeout.
+    ///
+    /// Plain HTTP requests forwarded by an HTTP proxy, rather than tunneled,
+    /// are prepared for that proxy before connecting, so forwarding proxies
+    /// take no part in fallback.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// # extern crate wreq;
+    /// # fn run() -> Result<(), Box<dyn std::error::Error>> {
+    /// let proxy = wreq::Proxy::all("socks5://a.prox:1080")?
+    ///     .fallback(wreq::Proxy::all("socks5://b.prox:1080")?)
+    ///     .fallback(wreq::Proxy::all("socks5://c.prox:1080")?)
+    ///     .fallback_direct(true);
+    /// let client = wreq::Client::builder().proxy(proxy).build()?;
+    /// # Ok(())
+    /// # }
+    /// # fn main() {}
+    /// ```
+    pub fn fallback(mut self, mut proxy: Proxy) -> Proxy {
+        let nested = std::mem::take(&mut proxy.fallbacks);
+        self.fallback_direct |= proxy.fallback_direct;
+        self.fallbacks.push(proxy);
+        self.fallbacks.extend(nested);
+        self
+    }
+
+    /// Connect directly when neither this proxy nor any of its fallbacks can
+    /// be reached.
+    ///
+    /// Default is `false`.
+    pub fn fallback_direct(mut self, enabled: bool) -> Proxy {
+        self.fallback_direct = enabled;
+        self
+    }
+
     pub(crate) fn into_matcher(self) -> Matcher {
         let Proxy {
             intercept,
             extra,
             no_proxy,
+            fallbacks,
+            fallback_direct,
         } = self;
 
         let maybe_has_http_auth;
@@ -418,6 +591,8 @@ impl Proxy {
             extra,
             maybe_has_http_auth,
             maybe_has_http_custom_headers,
+            fallbacks: fallbacks.into_iter().map(Proxy::into_matcher).collect(),
+            fallback_direct,
         }
     }
 }
@@ -435,6 +610,7 @@ impl fmt::Debug for Proxy {
         f.debug_tuple("Proxy")
             .field(&self.intercept)
             .field(&self.no_proxy)
+            .field(&self.fallbacks)
             .finish()
     }
 }
@@ -488,10 +664,13 @@ impl Matcher {
             extra: Extra {
                 auth: None,
                 misc: None,
+                tunnel_filter: None,
             },
             // maybe env vars have auth!
             maybe_has_http_auth: true,
             maybe_has_http_custom_headers: true,
+            fallbacks: Arc::from([]),
+            fallback_direct: false,
         }
     }
 
@@ -504,6 +683,12 @@ impl Matcher {
         inner.map(|inner| Intercepted {
             inner,
             extra: self.extra.clone(),
+            fallbacks: self
+                .fallbacks
+                .iter()
+                .filter_map(|fallback| fallback.intercept(dst))
+                .collect(),
+            fallback_direct: self.fallback_direct,
         })
     }
 
@@ -559,6 +744,19 @@ impl Intercepted {
         self.inner.uri()
     }
 
+    /// Returns the proxy as reported to users, leaving out any credentials.
+    pub(crate) fn info(&self) -> ProxyInfo {
+        let uri = self.uri();
+        let stripped = match (uri.scheme_str(), uri.host()) {
+            (Some(scheme), Some(host)) => {
+                let port = uri.port().map(|p| format!(":{p}")).unwrap_or_default();
+                format!("{scheme}://{host}{port}").parse().ok()
+            }
+            _ => None,
+        };
+        ProxyInfo(stripped.unwrap_or_else(|| uri.clone()))
+    }
+
     pub(crate) fn basic_auth(&self) -> Option<&HeaderValue> {
         if let Some(ref val) = self.extra.auth {
             return Some(val);
@@ -573,10 +771,33 @@ impl Intercepted {
         None
     }
 
+    /// Returns the headers of the `CONNECT` request opening a tunnel, besides
+    /// `Host`, with the header filter of the proxy applied.
+    pub(crate) fn tunnel_headers(&self) -> HeaderMap {
+        let mut headers = self.custom_headers().cloned().unwrap_or_default();
+        if let Some(auth) = self.basic_auth() {
+            let mut auth = auth.clone();
+            auth.set_sensitive(true);
+            headers.entry(PROXY_AUTHORIZATION).or_insert(auth);
+        }
+
+        if let Some(ref filter) = self.extra.tunnel_filter {
+            filter.apply(&mut headers);
+        }
+        headers
+    }
+
     #[cfg(feature = "socks")]
     pub(crate) fn raw_auth(&self) -> Option<(Bytes, Bytes)> {
         self.inner.raw_auth()
     }
+
+    /// Splits off the proxies to try in order when this one can't be
+    /// reached, and whether to then connect directly.
+    pub(crate) fn take_fallbacks(&mut self) -> (Vec<Intercepted>, bool) {
+        let direct = std::mem::take(&mut self.fallback_direct);
+        (std::mem::take(&mut self.fallbacks), direct)
+    }
 }
 
 impl fmt::Debug for Intercepted {
@@ -739,6 +960,69 @@ mod tests {
         assert_eq!(auth, "testme");
     }
 
+    #[test]
+    fn test_tunnel_header_filters() {
+        use http::header::USER_AGENT;
+
+        let proxy = || {
+            Proxy::all("http://letme:in@example.domain/")
+                .unwrap()
+                .custom_http_headers({
+                    let mut headers = HeaderMap::new();
+                    headers.insert(USER_AGENT, HeaderValue::from_static("wreq"));
+                    headers
+                })
+        };
+        let tunnel_headers = |p: Proxy| {
+            p.into_matcher()
+                .intercept(&url("https://anywhere.local"))
+                .unwrap()
+                .tunnel_headers()
+        };
+
+        let headers = tunnel_headers(proxy());
+        assert!(headers.contains_key(USER_AGENT));
+        assert!(headers.contains_key(PROXY_AUTHORIZATION));
+
+        let headers = tunnel_headers(proxy().tunnel_header_denylist([USER_AGENT]));
+        assert!(!headers.contains_key(USER_AGENT));
+        assert!(headers.contains_key(PROXY_AUTHORIZATION));
+
+        let headers = tunnel_headers(proxy().tunnel_header_allowlist([USER_AGENT]));
+        assert!(headers.contains_key(USER_AGENT));
+        assert!(!headers.contains_key(PROXY_AUTHORIZATION));
+    }
+
+    #[test]
+    fn test_fallbacks() {
+        let p = Proxy::all("socks5://a.local")
+            .unwrap()
+            .fallback(
+                Proxy::https("http://b.local")
+                    .unwrap()
+                    .fallback(Proxy::all("socks5://c.local").unwrap())
+                    .fallback_direct(true),
+            )
+            .into_matcher();
+
+        let hosts = |dst: &str| {
+            let (fallbacks, direct) = p.intercept(&url(dst)).unwrap().take_fallbacks();
+            let hosts = fallbacks
+                .iter()
+                .map(|f| f.uri().host().unwrap().to_owned())
+                .collect::<Vec<_>>();
+            (hosts, direct)
+        };
+
+        // The nested fallback comes after the one it was added to.
+        assert_eq!(
+            hosts("https://hyper.rs"),
+            (vec!["b.local".to_owned(), "c.local".to_owned()], true)
+        );
+        // `b.local` only intercepts HTTPS.
+        assert_eq!(hosts("http://hyper.rs"), (vec!["c.local".to_owned()], true));
+    }
+
     #[test]
     fn test_maybe_has_http_auth() {
         let m = Proxy::all("https://letme:in@yo.local")
@@ -6,6 +6,29 @@ use bytes::Bytes;
 use super::{AlpnProtocol, AlpsProtocol, TlsVersion};
 use crate::tls::CertificateCompressionAlgorithm;
 
+/// The order BoringSSL sends `ClientHello` extensions in when they are not
+/// permuted.
+const DEFAULT_EXTENSION_ORDER: [ExtensionType; 18] = [
+    ExtensionType::SERVER_NAME,
+    ExtensionType::ENCRYPTED_CLIENT_HELLO,
+    ExtensionType::EXTENDED_MASTER_SECRET,
+    ExtensionType::RENEGOTIATE,
+    ExtensionType::SUPPORTED_GROUPS,
+    ExtensionType::EC_POINT_FORMATS,
+    ExtensionType::SESSION_TICKET,
+    ExtensionType::APPLICATION_LAYER_PROTOCOL_NEGOTIATION,
+    ExtensionType::STATUS_REQUEST,
+    ExtensionType::SIGNATURE_ALGORITHMS,
+    ExtensionType::CERTIFICATE_TIMESTAMP,
+    ExtensionType::KEY_SHARE,
+    ExtensionType::PSK_KEY_EXCHANGE_MODES,
+    ExtensionType::SUPPORTED_VERSIONS,
+    ExtensionType::CERT_COMPRESSION,
+    ExtensionType::DELEGATED_CREDENTIAL,
+    ExtensionType::APPLICATION_SETTINGS,
+    ExtensionType::RECORD_SIZE_LIMIT,
+];
+
 /// Builder for `[`TlsConfig`]`.
 #[must_use]
 #[derive(Debug, Clone)]
@@ -20,6 +43,7 @@ pub struct TlsConfigBuilder {
 pub struct TlsConfig {
     pub(crate) alpn_protos: Option<Bytes>,
     pub(crate) alps_protos: Option<Bytes>,
+    pub(crate) alps_h2_settings: Option<Bytes>,
     pub(crate) alps_use_new_codepoint: bool,
     pub(crate) session_ticket: bool,
     pub(crate) min_tls_version: Option<TlsVersion>,
@@ -34,6 +58,8 @@ pub struct TlsConfig {
     pub(crate) psk_skip_session_ticket: bool,
     pub(crate) key_shares_limit: Option<u8>,
     pub(crate) psk_dhe_ke: bool,
+    pub(crate) psk_key_exchange_modes: Option<Cow<'static, [u8]>>,
+    pub(crate) compression_methods: Option<Cow<'static, [u8]>>,
     pub(crate) renegotiation: bool,
     pub(crate) delegated_credentials: Option<Cow<'static, str>>,
     pub(crate) curves_list: Option<Cow<'static, str>>,
@@ -71,6 +97,19 @@ impl TlsConfigBuilder {
         self
     }
 
+    /// Sets the settings sent with ALPS for `h2`.
+    ///
+    /// The payload is sent as is, so it should be the HTTP/2 `SETTINGS`
+    /// the emulated client announces. Other protocols are sent with empty
+    /// settings. Defaults to empty.
+    pub fn alps_h2_settings<B>(mut self, settings: B) -> Self
+    where
+        B: Into<Bytes>,
+    {
+        self.config.alps_h2_settings = Some(settings.into());
+        self
+    }
+
     /// Sets whether to use a new codepoint for ALPS.
     pub fn alps_use_new_codepoint(mut self, enabled: bool) -> Self {
         self.config.alps_use_new_codepoint = enabled;
@@ -113,6 +152,12 @@ impl TlsConfigBuilder {
     }
 
     /// Sets whether to permute ClientHello extensions.
+    ///
+    /// When enabled, BoringSSL shuffles the extension order on every
+    /// connection, as Chrome does. When disabled, extensions are always sent
+    /// in the same order, so `ClientHello`s from one configuration only
+    /// differ in their random values, key shares and GREASE. Defaults to
+    /// BoringSSL's behavior, which is not to permute.
     pub fn permute_extensions<T>(mut self, permute: T) -> Self
     where
         T: Into<Option<bool>>,
@@ -169,6 +214,33 @@ impl TlsConfigBuilder {
         self
     }
 
+    /// Sets the values of the `psk_key_exchange_modes` extension, overriding
+    /// [`psk_dhe_ke`](Self::psk_dhe_ke).
+    ///
+    /// BoringSSL only implements `psk_dhe_ke` (`1`), so `[1]` and `[]` are
+    /// the only lists it can send. Building a client with any other mode,
+    /// such as `psk_ke` (`0`), fails.
+    pub fn psk_key_exchange_modes<T>(mut self, modes: T) -> Self
+    where
+        T: Into<Cow<'static, [u8]>>,
+    {
+        self.config.psk_key_exchange_modes = Some(modes.into());
+        self
+    }
+
+    /// Sets the legacy `compression_methods` of the `ClientHello`.
+    ///
+    /// BoringSSL always sends the single `null` method (`[0]`), which is also
+    /// what every current browser sends. Building a client with any other
+    /// list fails rather than sending something else.
+    pub fn compression_methods<T>(mut self, methods: T) -> Self
+    where
+        T: Into<Cow<'static, [u8]>>,
+    {
+        self.config.compression_methods = Some(methods.into());
+        self
+    }
+
     /// Sets the renegotiation flag.
     pub fn renegotiation(mut self, enabled: bool) -> Self {
         self.config.renegotiation = enabled;
@@ -212,6 +284,11 @@ impl TlsConfigBuilder {
     }
 
     /// Sets the certificate compression algorithms.
+    ///
+    /// They are advertised in the `compress_certificate` extension, in the
+    /// given order. The one the server picked, if any, is reported by
+    /// [`TlsInfo::cert_compression`](crate::tls::TlsInfo::cert_compression).
+    /// None are advertised by default.
     pub fn certificate_compression_algorithms<T>(mut self, algs: T) -> Self
     where
         T: Into<Cow<'static, [CertificateCompressionAlgorithm]>>,
@@ -229,6 +306,28 @@ impl TlsConfigBuilder {
         self
     }
 
+    /// Sets the order of `ClientHello` extensions by their type, e.g. `16`
+    /// for ALPN.
+    ///
+    /// Extensions that are not listed follow the listed ones, in BoringSSL's
+    /// default order. Only extensions that are actually sent appear in the
+    /// `ClientHello`. `pre_shared_key` is always sent last, as TLS requires.
+    /// This replaces any [`extension_permutation`](Self::extension_permutation).
+    pub fn extension_order<T>(mut self, order: T) -> Self
+    where
+        T: IntoIterator<Item = u16>,
+    {
+        let mut permutation = Vec::with_capacity(DEFAULT_EXTENSION_ORDER.len());
+        let listed = order.into_iter().map(ExtensionType::from);
+        for ext in listed.chain(DEFAULT_EXTENSION_ORDER) {
+            if !permutation.contains(&ext) {
+                permutation.push(ext);
+            }
+        }
+        self.config.extension_permutation = Some(permutation.into());
+        self
+    }
+
     /// Sets the AES hardware override flag.
     pub fn aes_hw_override<T>(mut self, enabled: T) -> Self
     where
@@ -275,6 +374,7 @@ impl Default for TlsConfig {
                 AlpnProtocol::HTTP1,
             ])),
             alps_protos: None,
+            alps_h2_settings: None,
             alps_use_new_codepoint: false,
             session_ticket: true,
             min_tls_version: None,
@@ -289,6 +389,8 @@ impl Default for TlsConfig {
             psk_skip_session_ticket: false,
             key_shares_limit: None,
             psk_dhe_ke: true,
+            psk_key_exchange_modes: None,
+            compression_methods: None,
             renegotiation: true,
             delegated_credentials: None,
             curves_list: None,
@@ -0,0 +1,58 @@
+//! Application-Layer Protocol Settings (ALPS).
+
+use std::ptr;
+
+use boring_sys2 as ffi;
+use boring2::{error::ErrorStack, ssl::SslRef};
+use foreign_types::ForeignTypeRef;
+
+/// The protocol whose settings can be configured.
+const H2: &[u8] = b"h2";
+
+/// Advertises ALPS on `ssl` for each protocol in the wire-format list
+/// `protos`.
+///
+/// The settings sent for `h2` are `h2_settings`; those of other protocols
+/// are empty. BoringSSL only sends ALPS in TLS 1.3 handshakes, for the
+/// protocols that are also offered with ALPN.
+pub(super) fn advertise(
+    ssl: &mut SslRef,
+    protos: &[u8],
+    h2_settings: &[u8],
+    use_new_codepoint: bool,
+) -> Result<(), ErrorStack> {
+    for proto in crate::tls::decode_sequence(protos) {
+        let settings = if proto == H2 { h2_settings } else { &[] };
+        // SAFETY: `ssl` is a valid handle, and BoringSSL copies the protocol
+        // and the settings.
+        let added = unsafe {
+            ffi::SSL_add_application_settings(
+                ssl.as_ptr(),
+                proto.as_ptr(),
+                proto.len(),
+                if settings.is_empty() {
+                    ptr::null()
+                } else {
+                    settings.as_ptr()
+                },
+                settings.len(),
+            )
+        };
+        if added != 1 {
+            return Err(ErrorStack::get());
+        }
+    }
+
+    // By default, the old codepoint is used. Avoid unnecessary FFI calls.
+    if use_new_codepoint {
+        // SAFETY: `ssl` is a valid handle.
+        unsafe { ffi::SSL_set_alps_use_new_codepoint(ssl.as_ptr(), 1) };
+    }
+    Ok(())
+}
+
+/// Returns whether the server accepted ALPS on `ssl`.
+pub(crate) fn negotiated(ssl: &SslRef) -> bool {
+    // SAFETY: `ssl` is a valid handle.
+    unsafe { ffi::SSL_has_application_settings(ssl.as_ptr()) == 1 }
+}
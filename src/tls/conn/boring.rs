@@ -1,43 +1,62 @@
 //! backport: <https://github.com/cloudflare/boring/blob/master/hyper-boring/src/lib.rs>
 
 use std::{
+    borrow::Cow,
     error::Error,
     fmt::Debug,
     future::Future,
-    net::{IpAddr, Ipv6Addr},
+    hash::{DefaultHasher, Hash, Hasher},
+    net::Ipv6Addr,
     pin::Pin,
     sync::Arc,
     task::{Context, Poll},
+    time::{Duration, Instant},
 };
 
 use antidote::Mutex;
 use boring2::{
     error::ErrorStack,
-    ssl::{SslConnector, SslMethod, SslOptions, SslRef, SslSessionCacheMode},
+    nid::Nid,
+    ssl::{SslConnector, SslContextBuilder, SslMethod, SslOptions, SslRef, SslSessionCacheMode},
 };
+use bytes::Bytes;
 use http::{Uri, uri::Scheme};
 use tokio_boring2::SslStream;
 use tower_service::Service;
 
 use super::{
-    HandshakeConfig, MaybeHttpsStream,
+    ClientHelloHook, HandshakeConfig, HandshakeProgressHook, MaybeHttpsStream, alpn_index, alps,
     cache::{SessionCache, SessionKey},
+    cert_compressor, chain_index, custom_ext,
     ext::{ConnectConfigurationExt, SslConnectorBuilderExt},
-    key_index,
+    fingerprint::FingerprintRecorder,
+    fingerprint_index, hello, key_index,
+    key_policy::{KeyCheck, KeyPolicy},
+    ocsp,
+    plaintext::{self, Sniffing},
+    progress,
+    timing::{self, TimingRecorder},
 };
 use crate::{
     Dst,
     connect::HttpConnector,
     core::{
-        client::connect::Connection,
+        client::connect::{ConnEvent, Connection, event::EventSender},
         rt::{Read, TokioIo, Write},
     },
-    error::BoxError,
-    tls::{CertStore, Identity, KeyLogPolicy, TlsConfig, TlsVersion},
+    error::{AlpnMismatch, BoxError, CertRevoked, UnsupportedCipher},
+    tls::{
+        CertStore, CertificateCompressionAlgorithm, Identity, KeyLogPolicy, TlsConfig, TlsVersion,
+        decode_sequence, x509::ChainRecorder,
+    },
 };
 
 type SslCallback = Arc<dyn Fn(&mut SslRef, &Uri) -> Result<(), ErrorStack> + Sync + Send>;
 
+/// A callback computing the server name sent to a destination, or `None` to
+/// send the host name.
+pub type SniCallback = Arc<dyn Fn(&Dst) -> Option<String> + Send + Sync>;
+
 /// A Connector using BoringSSL to support `http` and `https` schemes.
 #[derive(Clone)]
 pub struct HttpsConnector<T> {
@@ -52,32 +71,23 @@ impl HttpsConnector<HttpConnector> {
         connector: TlsConnector,
         dst: &mut Dst,
     ) -> HttpsConnector<HttpConnector> {
-        // Set the local address and interface
-        match dst.addresses() {
-            (Some(a), Some(b)) => http.set_local_addresses(a, b),
-            (Some(a), None) => http.set_local_address(Some(IpAddr::V4(a))),
-            (None, Some(b)) => http.set_local_address(Some(IpAddr::V6(b))),
-            _ => (),
-        }
-
-        // Set the interface
-        #[cfg(any(
-            target_os = "android",
-            target_os = "fuchsia",
-            target_os = "illumos",
-            target_os = "ios",
-            target_os = "linux",
-            target_os = "macos",
-            target_os = "solaris",
-            target_os = "tvos",
-            target_os = "visionos",
-            target_os = "watchos",
-        ))]
-        http.set_interface(dst.interface());
+        // Bind to the local address and interface of the request
+        http.bind_to(dst);
 
         // Get the ALPN protocols from the destination
         let alpn_protos = dst.alpn_protos();
         let mut connector = HttpsConnector::with_connector(http, connector);
+        connector.inner.identity = dst.identity().cloned();
+        connector.inner.early_data = dst.early_data();
+        connector.inner.events = connector.http.events().cloned();
+        connector.inner.sni = connector
+            .inner
+            .sni_callback
+            .as_ref()
+            .and_then(|callback| callback(dst));
+        if let Some(alpn) = alpn_protos {
+            connector.inner.config.alpn_protos = Some(alpn.encode());
+        }
         connector.set_ssl_callback(move |ssl, _| {
             if let Some(alpn) = alpn_protos {
                 ssl.set_alpn_protos(&alpn.encode())?;
@@ -125,7 +135,7 @@ where
         uri: &Uri,
         host: &str,
         conn: A,
-    ) -> Result<SslStream<TokioIo<A>>, BoxError>
+    ) -> Result<SslStream<Sniffing<TokioIo<A>>>, BoxError>
     where
         A: Read + Write + Unpin + Send + Sync + Debug + 'static,
     {
@@ -144,6 +154,25 @@ pub struct TlsConnectorBuilder {
     identity: Option<Identity>,
     cert_store: Option<CertStore>,
     cert_verification: bool,
+    false_start: bool,
+    early_data: bool,
+    session_cache_capacity: usize,
+    require_alpn: bool,
+    verified_chain: bool,
+    client_hello_hook: Option<ClientHelloHook>,
+    compute_ja4: bool,
+    handshake_progress: Option<HandshakeProgressHook>,
+    sni_callback: Option<SniCallback>,
+    server_name: Option<String>,
+    alpn_protocols: Option<Vec<Vec<u8>>>,
+    cipher_list: Option<Cow<'static, str>>,
+    collect_timings: bool,
+    key_policy: KeyPolicy,
+    custom_extensions: Vec<(u16, Vec<u8>)>,
+    alps_protos: Option<Bytes>,
+    alps_h2_settings: Option<Bytes>,
+    alps_use_new_codepoint: bool,
+    ocsp_stapling: bool,
 }
 
 /// A layer which wraps services in an `SslConnector`.
@@ -158,6 +187,12 @@ struct Inner {
     cache: Option<Arc<Mutex<SessionCache>>>,
     config: HandshakeConfig,
     ssl_callback: Option<SslCallback>,
+    identity: Option<Identity>,
+    events: Option<EventSender>,
+    sni_callback: Option<SniCallback>,
+    sni: Option<String>,
+    server_name: Option<String>,
+    early_data: bool,
 }
 
 impl TlsConnectorBuilder {
@@ -226,16 +261,451 @@ impl TlsConnectorBuilder {
         self
     }
 
+    /// Sets whether TLS False Start is allowed.
+    ///
+    /// False Start lets the client send application data right after its own
+    /// `Finished` message, saving a round trip on full TLS 1.2 handshakes.
+    /// BoringSSL only false starts when it is considered safe: TLS 1.2 was
+    /// negotiated, the cipher suite is a forward-secret (ECDHE) AEAD, and ALPN
+    /// selected a protocol. Otherwise the handshake completes as usual.
+    #[inline(always)]
+    pub fn false_start(mut self, enabled: bool) -> Self {
+        self.false_start = enabled;
+        self
+    }
+
+    /// Sets whether to send TLS 1.3 early data (0-RTT) on resumed sessions.
+    ///
+    /// When a cached session allows it, the handshake completes as soon as the
+    /// `ClientHello` is sent and the first request is written as early data,
+    /// saving a round trip. Enabling this also enables the client session cache.
+    ///
+    /// # Warning
+    ///
+    /// Early data can be replayed by an attacker, and the server may act on it
+    /// more than once. It is only offered when connecting for an idempotent
+    /// request, and when HTTP/2 is not among the ALPN protocols. Whether the
+    /// server accepted it is reported by
+    /// [`TlsInfo::early_data_accepted`](crate::tls::TlsInfo::early_data_accepted);
+    /// if it did not, the early data is written again after the handshake.
+    #[inline(always)]
+    pub fn early_data(mut self, enabled: bool) -> Self {
+        self.early_data = enabled;
+        self
+    }
+
+    /// Sets how many sessions the client session cache keeps for each host
+    /// and port, dropping the oldest beyond that.
+    ///
+    /// The cache is enabled by `pre_shared_key` in the [`TlsConfig`], or by
+    /// [`early_data`](Self::early_data). TLS 1.3 sessions are used once, so
+    /// a capacity above 1 lets concurrent connections to a host all resume.
+    /// A capacity of 0 is treated as 1.
+    ///
+    /// Default is 8.
+    #[inline(always)]
+    pub fn session_cache_capacity(mut self, capacity: usize) -> Self {
+        self.session_cache_capacity = capacity.max(1);
+        self
+    }
+
+    /// Sets whether the server must select one of the offered ALPN protocols.
+    ///
+    /// When enabled and ALPN protocols are offered, a handshake in which the
+    /// server selects no protocol, or one that was not offered, fails with an
+    /// ALPN mismatch error instead of silently falling back to HTTP/1.1.
+    #[inline(always)]
+    pub fn require_alpn(mut self, required: bool) -> Self {
+        self.require_alpn = required;
+        self
+    }
+
+    /// Sets the ALPN protocols to offer, in order of preference, in place of
+    /// those of the [`TlsConfig`].
+    ///
+    /// Any protocol can be offered, not only HTTP ones. A connection only
+    /// speaks HTTP/2 when `h2` is selected, and HTTP/1.1 otherwise. An empty
+    /// list, or a protocol that is empty or longer than 255 bytes, fails
+    /// building.
+    #[inline(always)]
+    pub fn alpn_protocols(mut self, protocols: Option<Vec<Vec<u8>>>) -> Self {
+        self.alpn_protocols = protocols;
+        self
+    }
+
+    /// Sets the cipher suites to offer, in order of preference, in place of
+    /// the cipher list of the [`TlsConfig`].
+    ///
+    /// The list is in BoringSSL's format, names separated by `:`, and holds
+    /// the TLS 1.3 suites along with the TLS 1.2 ones. Unlike the cipher list
+    /// of the [`TlsConfig`], where BoringSSL skips names it doesn't know,
+    /// every name must select a cipher suite, or building fails with an error
+    /// for which [`Error::is_unsupported_cipher`](crate::Error::is_unsupported_cipher)
+    /// returns true.
+    #[inline(always)]
+    pub fn cipher_list(mut self, ciphers: Option<Cow<'static, str>>) -> Self {
+        self.cipher_list = ciphers;
+        self
+    }
+
+    /// Sets the minimum size, in bits, of an RSA key in the server's leaf
+    /// certificate.
+    ///
+    /// A smaller key fails the handshake with an error for which
+    /// [`Error::is_weak_key`](crate::Error::is_weak_key) returns true, even if
+    /// the chain validates. Defaults to `None`, no requirement beyond what
+    /// BoringSSL itself enforces.
+    #[inline(always)]
+    pub fn min_rsa_bits(mut self, bits: u16) -> Self {
+        self.key_policy.min_rsa_bits = Some(bits);
+        self
+    }
+
+    /// Sets the curves an EC key in the server's leaf certificate may be on.
+    ///
+    /// A key on any other curve fails the handshake with an error for which
+    /// [`Error::is_weak_key`](crate::Error::is_weak_key) returns true, even if
+    /// the chain validates. Keys that are neither RSA nor EC are not
+    /// restricted. Defaults to `None`, any curve BoringSSL accepts.
+    ///
+    /// This restricts the certificate key, not the groups negotiated for the
+    /// key exchange.
+    #[inline(always)]
+    pub fn allowed_curves_for_leaf(mut self, curves: Vec<Nid>) -> Self {
+        self.key_policy.allowed_curves = Some(curves.into());
+        self
+    }
+
+    /// Pins the key of the server's leaf certificate to one of `pins`, the
+    /// SHA-256 digests of DER `SubjectPublicKeyInfo`s.
+    ///
+    /// Any other key fails the connection with an error for which
+    /// [`Error::is_pin_mismatch`](crate::Error::is_pin_mismatch) returns
+    /// true. Pins are checked even when certificate verification is
+    /// disabled. Defaults to `None`, any key.
+    #[inline(always)]
+    pub fn spki_pins(mut self, pins: Vec<[u8; 32]>) -> Self {
+        self.key_policy.spki_pins = Some(pins.into());
+        self
+    }
+
+    /// Requires the server's leaf certificate to remain valid for at least
+    /// `min` after the handshake.
+    ///
+    /// A certificate that expires sooner fails the connection with an error
+    /// for which
+    /// [`Error::is_cert_expiring_soon`](crate::Error::is_cert_expiring_soon)
+    /// returns true. This is checked even when certificate verification is
+    /// disabled. Defaults to `None`, no requirement.
+    #[inline(always)]
+    pub fn min_cert_validity_remaining(mut self, min: Option<Duration>) -> Self {
+        self.key_policy.min_validity_remaining = min;
+        self
+    }
+
+    /// Appends an extension of type `ext_type` with contents `data` to the
+    /// `ClientHello`.
+    ///
+    /// Meant for emulating clients that send vendor-specific or experimental
+    /// extensions. Extensions are sent after those BoringSSL adds itself, in
+    /// the order they were added, and whatever the server answers to them
+    /// is ignored. Building the connector fails for a type BoringSSL already
+    /// handles, or one added twice.
+    #[inline(always)]
+    pub fn add_custom_extension(mut self, ext_type: u16, data: Vec<u8>) -> Self {
+        self.custom_extensions.push((ext_type, data));
+        self
+    }
+
+    /// Sets the protocols to advertise Application-Layer Protocol Settings
+    /// (ALPS) for, e.g. `vec![b"h2".to_vec()]`.
+    ///
+    /// ALPS is only sent in TLS 1.3 handshakes, for protocols that are also
+    /// offered with ALPN. Whether the server accepted it is exposed by
+    /// [`TlsInfo::alps_negotiated`](crate::tls::TlsInfo::alps_negotiated).
+    /// Protocols set on the `TlsConfig` take precedence.
+    #[inline(always)]
+    pub fn alps_protocols(mut self, protocols: Vec<Vec<u8>>) -> Self {
+        self.alps_protos = Some(crate::tls::encode_sequence(&protocols));
+        self
+    }
+
+    /// Sets the settings sent with ALPS for `h2`.
+    ///
+    /// Settings set on the `TlsConfig` take precedence. Defaults to empty.
+    #[inline(always)]
+    pub fn alps_h2_settings(mut self, settings: Vec<u8>) -> Self {
+        self.alps_h2_settings = Some(settings.into());
+        self
+    }
+
+    /// Sets whether ALPS is sent with the new extension codepoint instead
+    /// of the old one.
+    #[inline(always)]
+    pub fn alps_use_new_codepoint(mut self, enabled: bool) -> Self {
+        self.alps_use_new_codepoint = enabled;
+        self
+    }
+
+    /// Sets whether to record the certificate chain built while verifying
+    /// the server.
+    ///
+    /// The chain is exposed by
+    /// [`TlsInfo::verified_chain`](crate::tls::TlsInfo::verified_chain).
+    /// Recording installs a verify callback on every handshake, so it is
+    /// only enabled together with `tls_info`.
+    #[inline(always)]
+    pub fn verified_chain(mut self, enabled: bool) -> Self {
+        self.verified_chain = enabled;
+        self
+    }
+
+    /// Sets a hook invoked with every `ClientHello` the connector sends.
+    ///
+    /// The hook receives the serialized handshake message, starting with its
+    /// 4-byte header, right before it is written. It is meant for checking
+    /// fingerprints such as JA3 in tests and is called from within BoringSSL,
+    /// so it must not panic or block. Without a hook, no callback is
+    /// installed.
+    #[inline(always)]
+    pub fn on_client_hello(mut self, hook: Option<ClientHelloHook>) -> Self {
+        self.client_hello_hook = hook;
+        self
+    }
+
+    /// Sets whether to compute the JA3 and JA4 fingerprints of the
+    /// `ClientHello` of each connection.
+    ///
+    /// The fingerprints are exposed by
+    /// [`TlsInfo::ja4`](crate::tls::TlsInfo::ja4) and
+    /// [`TlsInfo::ja3`](crate::tls::TlsInfo::ja3). When disabled, the
+    /// `ClientHello` is not inspected at all.
+    #[inline(always)]
+    pub fn compute_ja4(mut self, enabled: bool) -> Self {
+        self.compute_ja4 = enabled;
+        self
+    }
+
+    /// Sets a hook invoked as the handshake of each connection progresses.
+    ///
+    /// The hook is called from within BoringSSL with each
+    /// [`HandshakeState`](crate::tls::HandshakeState) the handshake reaches, so it must not panic or
+    /// block. Without a hook, no callback is installed.
+    #[inline(always)]
+    pub fn on_handshake_progress(mut self, hook: Option<HandshakeProgressHook>) -> Self {
+        self.handshake_progress = hook;
+        self
+    }
+
+    /// Sets a callback computing the server name to send in place of the
+    /// host name.
+    ///
+    /// The callback is invoked once per connection, before connecting, with
+    /// the destination of the connection. Returning `None` sends the host
+    /// name as usual. Only the name sent changes: the certificate is still
+    /// verified against the host name, unless hostname verification is
+    /// disabled. Nothing is sent when SNI is disabled.
+    #[inline(always)]
+    pub fn sni_callback(mut self, callback: Option<SniCallback>) -> Self {
+        self.sni_callback = callback;
+        self
+    }
+
+    /// Sets the server name to use in place of the host name of every
+    /// destination.
+    ///
+    /// Unlike with [`sni_callback`](Self::sni_callback), the certificate is
+    /// verified against this name too. It needs SNI to be enabled, or
+    /// building fails. A name from the callback is still sent in its place.
+    #[inline(always)]
+    pub fn server_name(mut self, name: Option<String>) -> Self {
+        self.server_name = name;
+        self
+    }
+
+    /// Sets whether to time the handshake of each connection.
+    ///
+    /// The timings, with certificate verification broken out of the rest of
+    /// the handshake, are attached to the connection as a
+    /// [`ConnectTimings`](crate::tls::ConnectTimings). Timing verification
+    /// installs a certificate verify callback on the connector, so it is
+    /// only done when enabled.
+    #[inline(always)]
+    pub fn collect_timings(mut self, enabled: bool) -> Self {
+        self.collect_timings = enabled;
+        self
+    }
+
+    /// Sets whether to request a stapled OCSP response, and check the
+    /// server's certificate against it.
+    ///
+    /// A response that revokes the certificate fails the connection with an
+    /// error for which
+    /// [`Error::is_cert_revoked`](crate::Error::is_cert_revoked) returns true.
+    /// Servers that staple nothing are still connected to. The response is
+    /// available from [`TlsInfo::ocsp_response`](crate::tls::TlsInfo::ocsp_response)
+    /// whether or not this is enabled, if the TLS config requests it.
+    #[inline(always)]
+    pub fn ocsp_stapling(mut self, enabled: bool) -> Self {
+        self.ocsp_stapling = enabled;
+        self
+    }
+
     /// Build the `TlsConnector` with the provided configuration.
+    /// Returns a hash of the settings that shape the `ClientHello` sent by a
+    /// connector built from this builder and `config`.
+    ///
+    /// Two connectors whose settings hash equal send the same `ClientHello`,
+    /// up to its random parts, so they can share connections. The hash
+    /// covers, once the settings of this builder are merged into `config`
+    /// as [`build`](Self::build) does:
+    ///
+    /// - the minimum and maximum TLS versions
+    /// - the cipher, curve and signature algorithm lists
+    /// - ALPN and ALPS, with the ALPS `h2` settings and codepoint
+    /// - GREASE, extension permutation and the extension order
+    /// - session tickets, PSK, early data and the PSK key exchange modes
+    /// - OCSP stapling, signed certificate timestamps, renegotiation, ECH
+    ///   GREASE, delegated credentials, certificate compression, the record
+    ///   size limit and the key shares limit
+    /// - the AES hardware overrides and the ChaCha20 preference
+    /// - whether SNI is sent, and the custom extensions
+    ///
+    /// Settings that only apply once the server answers, such as the
+    /// identity, certificate verification or key pins, are left out. The hash
+    /// is stable for a given build of this crate, but not across versions.
+    pub fn config_fingerprint(&self, config: &TlsConfig) -> u64 {
+        let mut h = DefaultHasher::new();
+
+        let versions = [
+            config.min_tls_version.or(self.min_version),
+            config.max_tls_version.or(self.max_version),
+        ];
+        versions.map(|v| v.map(version_id)).hash(&mut h);
+
+        self.cipher_list
+            .as_ref()
+            .or(config.cipher_list.as_ref())
+            .hash(&mut h);
+        config.curves_list.hash(&mut h);
+        config.sigalgs_list.hash(&mut h);
+
+        config.alpn_protos.hash(&mut h);
+        config
+            .alps_protos
+            .as_ref()
+            .or(self.alps_protos.as_ref())
+            .hash(&mut h);
+        config
+            .alps_h2_settings
+            .as_ref()
+            .or(self.alps_h2_settings.as_ref())
+            .hash(&mut h);
+        (config.alps_use_new_codepoint | self.alps_use_new_codepoint).hash(&mut h);
+
+        config.grease_enabled.hash(&mut h);
+        config.permute_extensions.hash(&mut h);
+        config.extension_permutation.hash(&mut h);
+
+        let psk_dhe_ke = match config.psk_key_exchange_modes.as_deref() {
+            Some([]) => false,
+            Some([1]) => true,
+            _ => config.psk_dhe_ke,
+        };
+        (
+            config.session_ticket,
+            config.pre_shared_key || self.early_data,
+            config.psk_skip_session_ticket,
+            self.early_data,
+            psk_dhe_ke,
+        )
+            .hash(&mut h);
+
+        (
+            config.enable_ocsp_stapling || self.ocsp_stapling,
+            config.enable_signed_cert_timestamps,
+            config.renegotiation,
+            config.enable_ech_grease,
+        )
+            .hash(&mut h);
+        config.delegated_credentials.hash(&mut h);
+        config
+            .certificate_compression_algorithms
+            .as_deref()
+            .map(|algs| algs.iter().map(compression_id).collect::<Vec<_>>())
+            .hash(&mut h);
+        config.record_size_limit.hash(&mut h);
+        config.key_shares_limit.hash(&mut h);
+
+        (
+            config.aes_hw_override,
+            config.random_aes_hw_override,
+            config.prefer_chacha20,
+        )
+            .hash(&mut h);
+
+        self.tls_sni.hash(&mut h);
+        self.server_name.hash(&mut h);
+        self.alpn_protocols.hash(&mut h);
+        self.custom_extensions.hash(&mut h);
+
+        h.finish()
+    }
+
     pub fn build(self, mut config: TlsConfig) -> crate::Result<TlsConnector> {
+        if self.server_name.is_some() && !self.tls_sni {
+            return Err(crate::Error::builder(
+                "a TLS server name override needs SNI to be enabled",
+            ));
+        }
+
+        if let Some(ref protocols) = self.alpn_protocols {
+            config.alpn_protos = Some(encode_alpn_protocols(protocols)?);
+        }
+
+        if let Some(ref ciphers) = self.cipher_list {
+            check_cipher_list(ciphers)?;
+            config.cipher_list = Some(ciphers.clone());
+        }
+
         // Replace the default configuration with the provided one
         config.max_tls_version = config.max_tls_version.or(self.max_version);
         config.min_tls_version = config.min_tls_version.or(self.min_version);
+        config.alps_protos = config.alps_protos.or(self.alps_protos);
+        config.alps_h2_settings = config.alps_h2_settings.or(self.alps_h2_settings);
+        config.alps_use_new_codepoint |= self.alps_use_new_codepoint;
+
+        // Only the fields BoringSSL can actually send are honored.
+        if let Some(ref modes) = config.psk_key_exchange_modes {
+            config.psk_dhe_ke = match modes.as_ref() {
+                [] => false,
+                [1] => true,
+                _ => {
+                    return Err(crate::Error::builder(
+                        "BoringSSL only supports the psk_dhe_ke key exchange mode",
+                    ));
+                }
+            };
+        }
+        if config
+            .compression_methods
+            .as_deref()
+            .is_some_and(|methods| methods != [0])
+        {
+            return Err(crate::Error::builder(
+                "BoringSSL only supports the null compression method",
+            ));
+        }
 
+        let cert_compression = config
+            .certificate_compression_algorithms
+            .as_deref()
+            .is_some_and(|algs| !algs.is_empty());
         let mut connector = SslConnector::no_default_verify_builder(SslMethod::tls_client())?
             .cert_store(self.cert_store)?
             .cert_verification(self.cert_verification)?
             .identity(self.identity)?
+            .false_start(self.false_start)?
             .certificate_compression_algorithms(config.certificate_compression_algorithms)?;
 
         // Set minimum TLS version
@@ -251,6 +721,9 @@ impl TlsConnectorBuilder {
             connector,
             enable_ocsp_stapling
         );
+        if self.ocsp_stapling {
+            connector.enable_ocsp_stapling();
+        }
 
         // Set Signed Certificate Timestamps (SCT)
         set_bool!(
@@ -346,17 +819,36 @@ impl TlsConnectorBuilder {
             });
         }
 
-        // Create the `HandshakeConfig` with the default session cache capacity.
+        // Set custom extensions
+        custom_ext::register(&mut connector, self.custom_extensions)?;
+
+        // Time verification around BoringSSL's own, for connections that ask.
+        if self.collect_timings {
+            timing::time_verification(&mut connector);
+        }
+
         let config = HandshakeConfig::builder()
-            .session_cache_capacity(8)
-            .session_cache(config.pre_shared_key)
+            .session_cache_capacity(self.session_cache_capacity)
+            .session_cache(config.pre_shared_key || self.early_data)
             .skip_session_ticket(config.psk_skip_session_ticket)
             .alps_protos(config.alps_protos)
+            .alps_h2_settings(config.alps_h2_settings)
             .alps_use_new_codepoint(config.alps_use_new_codepoint)
             .enable_ech_grease(config.enable_ech_grease)
             .tls_sni(self.tls_sni)
             .verify_hostname(self.verify_hostname)
             .random_aes_hw_override(config.random_aes_hw_override)
+            .early_data(self.early_data)
+            .alpn_protos(config.alpn_protos)
+            .require_alpn(self.require_alpn)
+            .verified_chain(self.verified_chain)
+            .client_hello_hook(self.client_hello_hook)
+            .compute_ja4(self.compute_ja4)
+            .handshake_progress(self.handshake_progress)
+            .collect_timings(self.collect_timings)
+            .key_policy(self.key_policy)
+            .cert_compression(cert_compression)
+            .ocsp_stapling(self.ocsp_stapling)
             .build();
 
         // If the session cache is disabled, we don't need to set up any callbacks.
@@ -369,8 +861,8 @@ impl TlsConnectorBuilder {
             connector.set_new_session_callback({
                 let cache = cache.clone();
                 move |ssl, session| {
-                    if let Ok(Some(key)) = key_index().map(|idx| ssl.ex_data(idx)) {
-                        cache.lock().insert(key.clone(), session);
+                    if let Ok(Some((key, generation))) = key_index().map(|idx| ssl.ex_data(idx)) {
+                        cache.lock().insert(key.clone(), *generation, session);
                     }
                 }
             });
@@ -384,12 +876,111 @@ impl TlsConnectorBuilder {
                 cache,
                 config,
                 ssl_callback: None,
+                identity: None,
+                events: None,
+                sni_callback: self.sni_callback,
+                sni: None,
+                server_name: self.server_name,
+                early_data: false,
             },
         })
     }
 }
 
+/// Encodes `protocols` as the length-prefixed list sent in the ALPN
+/// extension.
+fn encode_alpn_protocols(protocols: &[Vec<u8>]) -> crate::Result<Bytes> {
+    if protocols.is_empty() {
+        return Err(crate::Error::builder("ALPN protocol list is empty"));
+    }
+
+    let mut wire = Vec::new();
+    for protocol in protocols {
+        let len = u8::try_from(protocol.len())
+            .ok()
+            .filter(|&len| len > 0)
+            .ok_or_else(|| {
+                crate::Error::builder("ALPN protocols must be between 1 and 255 bytes long")
+            })?;
+        wire.push(len);
+        wire.extend_from_slice(protocol);
+    }
+    Ok(Bytes::from(wire))
+}
+
+/// Checks that every name in the cipher list `ciphers` selects a cipher
+/// suite, as BoringSSL silently skips those that don't.
+///
+/// Names that exclude suites, with `!` or `-`, and `@` commands are left
+/// alone: they don't have to match anything.
+fn check_cipher_list(ciphers: &str) -> crate::Result<()> {
+    let rejected: Vec<String> = ciphers
+        .split([':', ',', ';', ' '])
+        .filter(|rule| !rule.starts_with(['!', '-', '@']))
+        .flat_map(|rule| {
+            rule.trim_start_matches('+')
+                .trim_matches(['[', ']'])
+                .split(['|', '+'])
+        })
+        .filter(|name| !name.is_empty() && !selects_cipher(name))
+        .map(str::to_owned)
+        .collect();
+
+    if rejected.is_empty() {
+        Ok(())
+    } else {
+        Err(crate::Error::builder(UnsupportedCipher { rejected }))
+    }
+}
+
+/// Returns whether the cipher suite or alias `name` selects anything.
+fn selects_cipher(name: &str) -> bool {
+    SslContextBuilder::new(SslMethod::tls())
+        .and_then(|mut ctx| ctx.set_cipher_list(name))
+        .is_ok()
+}
+
+/// The code point of `version` in a `ClientHello`.
+fn version_id(version: TlsVersion) -> u16 {
+    match version {
+        v if v == TlsVersion::TLS_1_0 => 0x0301,
+        v if v == TlsVersion::TLS_1_1 => 0x0302,
+        v if v == TlsVersion::TLS_1_2 => 0x0303,
+        _ => 0x0304,
+    }
+}
+
+/// The code point of `alg` in the `compress_certificate` extension.
+fn compression_id(alg: &CertificateCompressionAlgorithm) -> u16 {
+    match *alg {
+        a if a == CertificateCompressionAlgorithm::ZLIB => 1,
+        a if a == CertificateCompressionAlgorithm::BROTLI => 2,
+        _ => 3,
+    }
+}
+
 impl TlsConnector {
+    /// Drops every cached session, so that the next connects do full
+    /// handshakes.
+    ///
+    /// Sessions from handshakes still in flight are not cached either.
+    pub fn clear_session_cache(&self) {
+        if let Some(ref cache) = self.inner.cache {
+            cache.lock().clear();
+        }
+    }
+
+    /// Drops the cached sessions of `host`, on any port, so that the next
+    /// connects to it do full handshakes.
+    ///
+    /// Sessions from handshakes still in flight, to any host, are not
+    /// cached either.
+    pub fn clear_session_for(&self, host: &str) {
+        if let Some(ref cache) = self.inner.cache {
+            cache.lock().clear_host(strip_ipv6_brackets(host));
+        }
+    }
+
     /// Creates a new `TlsConnectorBuilder` with the given configuration.
     pub fn builder() -> TlsConnectorBuilder {
         TlsConnectorBuilder {
@@ -401,11 +992,38 @@ impl TlsConnector {
             max_version: None,
             tls_sni: true,
             verify_hostname: true,
+            false_start: false,
+            early_data: false,
+            session_cache_capacity: 8,
+            require_alpn: false,
+            verified_chain: false,
+            client_hello_hook: None,
+            compute_ja4: false,
+            handshake_progress: None,
+            sni_callback: None,
+            server_name: None,
+            alpn_protocols: None,
+            cipher_list: None,
+            collect_timings: false,
+            key_policy: KeyPolicy::default(),
+            custom_extensions: Vec::new(),
+            alps_protos: None,
+            alps_h2_settings: None,
+            alps_use_new_codepoint: false,
+            ocsp_stapling: false,
         }
     }
 }
 
 impl Inner {
+    /// Returns whether HTTP/2 is among the ALPN protocols offered.
+    fn offers_h2(&self) -> bool {
+        self.config
+            .alpn_protos
+            .as_deref()
+            .is_some_and(|wire| decode_sequence(wire).any(|proto| proto == b"h2"))
+    }
+
     /// Connects to the given URI using the given connection.
     ///
     /// This function is used to connect to the given URI using the given connection.
@@ -414,10 +1032,22 @@ impl Inner {
         uri: &Uri,
         host: &str,
         conn: A,
-    ) -> Result<SslStream<TokioIo<A>>, BoxError>
+    ) -> Result<SslStream<Sniffing<TokioIo<A>>>, BoxError>
     where
         A: Read + Write + Unpin + Send + Sync + Debug + 'static,
     {
+        // The override is both sent and verified, the host only connected to.
+        let host = match self.server_name {
+            Some(ref name) => name.as_str(),
+            None => strip_ipv6_brackets(host),
+        };
+
+        if let Some(ref events) = self.events {
+            events.emit(ConnEvent::TlsHandshaking {
+                host: host.to_owned(),
+            });
+        }
+
         let mut cfg = self.ssl.configure()?;
 
         // Use server name indication
@@ -433,16 +1063,29 @@ impl Inner {
         cfg.set_random_aes_hw_override(self.config.random_aes_hw_override);
 
         // Set ALPS protos
-        cfg.alps_protos(
-            self.config.alps_protos.clone(),
-            self.config.alps_use_new_codepoint,
-        )?;
+        if let Some(ref protos) = self.config.alps_protos {
+            alps::advertise(
+                &mut cfg,
+                protos,
+                self.config.alps_h2_settings.as_deref().unwrap_or_default(),
+                self.config.alps_use_new_codepoint,
+            )?;
+        }
 
         if let Some(authority) = uri.authority() {
-            let key = SessionKey(authority.clone());
+            let key = SessionKey(
+                authority.clone(),
+                self.identity.as_ref().map(Identity::fingerprint),
+            );
 
+            let mut generation = 0;
             if let Some(ref cache) = self.cache {
-                if let Some(session) = cache.lock().get(&key) {
+                let session = {
+                    let mut cache = cache.lock();
+                    generation = cache.generation();
+                    cache.get(&key)
+                };
+                if let Some(session) = session {
                     unsafe {
                         cfg.set_session(&session)?;
                     }
@@ -450,26 +1093,153 @@ impl Inner {
                     if self.config.skip_session_ticket {
                         cfg.set_options(SslOptions::NO_TICKET)?;
                     }
+
+                    // Early data can only be offered when resuming a session,
+                    // and only carries idempotent requests. Over HTTP/2, other
+                    // requests could be multiplexed into it.
+                    if self.config.early_data && self.early_data && !self.offers_h2() {
+                        cfg.set_early_data_enabled(true);
+                    }
                 }
             }
 
             let idx = key_index()?;
-            cfg.set_ex_data(idx, key);
+            cfg.set_ex_data(idx, (key, generation));
+        }
+
+        // Record the chain BoringSSL builds and check the leaf key, keeping
+        // the verify mode as is.
+        let recorder = self.config.verified_chain.then(ChainRecorder::default);
+        let key_check = self
+            .config
+            .key_policy
+            .is_enabled()
+            .then(|| KeyCheck::new(self.config.key_policy.clone()));
+        if recorder.is_some() || key_check.is_some() {
+            let mode = cfg.verify_mode();
+            cfg.set_verify_callback(mode, {
+                let recorder = recorder.clone();
+                let key_check = key_check.clone();
+                move |preverify_ok, ctx| {
+                    if let Some(ref recorder) = recorder {
+                        recorder.record(preverify_ok, ctx);
+                    }
+                    match key_check {
+                        Some(ref key_check) => key_check.verify(preverify_ok, ctx),
+                        None => preverify_ok,
+                    }
+                }
+            });
+        }
+        if let Some(recorder) = recorder {
+            cfg.set_ex_data(chain_index()?, recorder);
+        }
+
+        // Fingerprinting observes the `ClientHello` alongside any user hook.
+        let hook = match self.config.client_hello_hook.clone() {
+            hook if self.config.compute_ja4 => {
+                let recorder = FingerprintRecorder::default();
+                cfg.set_ex_data(fingerprint_index()?, recorder.clone());
+                Some(Arc::new(move |hello: &[u8]| {
+                    recorder.record(hello);
+                    if let Some(ref hook) = hook {
+                        hook(hello);
+                    }
+                }) as ClientHelloHook)
+            }
+            hook => hook,
+        };
+        if let Some(hook) = hook {
+            hello::install(&mut cfg, hook)?;
+        }
+
+        if let Some(hook) = self.config.handshake_progress.clone() {
+            progress::install(&mut cfg, hook)?;
+        }
+
+        if self.config.cert_compression {
+            cert_compressor::observe(&mut cfg)?;
+        }
+
+        // Remember what was offered, for comparison with what was selected.
+        if let Some(ref offered) = self.config.alpn_protos {
+            cfg.set_ex_data(alpn_index()?, offered.clone());
         }
 
         let mut ssl = cfg.into_ssl(host)?;
 
+        // Only the name sent is overridden, `host` is still the one verified.
+        if let Some(sni) = self.sni.as_deref().filter(|_| self.config.tls_sni) {
+            ssl.set_hostname(sni)?;
+        }
+
+        if let Some(ref identity) = self.identity {
+            identity.add_to_ssl(&mut ssl)?;
+        }
+
         if let Some(ref ssl_callback) = self.ssl_callback {
             ssl_callback(&mut ssl, uri)?;
         }
 
-        tokio_boring2::SslStreamBuilder::new(ssl, TokioIo::new(conn))
+        let timings = self.config.collect_timings.then(TimingRecorder::default);
+        if let Some(ref timings) = timings {
+            timing::install(&mut ssl, timings.clone())?;
+        }
+
+        let start = Instant::now();
+        let stream = tokio_boring2::SslStreamBuilder::new(ssl, Sniffing::new(TokioIo::new(conn)))
             .connect()
-            .await
-            .map_err(Into::into)
+            .await;
+        if let Some(weak) = key_check.as_ref().and_then(KeyCheck::violation) {
+            return Err(Box::new(weak));
+        }
+        let mut stream = stream.map_err(plaintext::map_handshake_error)?;
+        stream.get_mut().stop();
+
+        // Pins hold even when the chain is not verified.
+        if let Err(mismatch) = self.config.key_policy.check_pins(stream.ssl()) {
+            return Err(Box::new(mismatch));
+        }
+        if let Err(expiring) = self.config.key_policy.check_validity(stream.ssl()) {
+            return Err(Box::new(expiring));
+        }
+        if self.config.ocsp_stapling && ocsp::is_revoked(stream.ssl()) {
+            return Err(Box::new(CertRevoked));
+        }
+
+        if let Some(timings) = timings {
+            timings.handshake_done(start.elapsed());
+        }
+
+        if self.config.require_alpn {
+            if let Some(ref offered) = self.config.alpn_protos {
+                let selected = stream.ssl().selected_alpn_protocol();
+                if !selected.is_some_and(|proto| decode_sequence(offered).any(|p| p == proto)) {
+                    return Err(Box::new(AlpnMismatch {
+                        offered: offered.clone(),
+                        selected: selected.map(<[u8]>::to_vec),
+                    }));
+                }
+            }
+        }
+
+        Ok(stream)
     }
 }
 
+/// Strips the square brackets around an IPv6 literal `host`, as found in
+/// URIs.
+///
+/// BoringSSL would otherwise fail to parse the host as an IP address: it
+/// would send it as SNI, and the handshake would fail hostname verification.
+/// Other hosts are returned as is.
+fn strip_ipv6_brackets(host: &str) -> &str {
+    host.strip_prefix('[')
+        .and_then(|host| host.strip_suffix(']'))
+        .filter(|host| host.parse::<Ipv6Addr>().is_ok())
+        .unwrap_or(host)
+}
+
 impl<T, S> Service<Uri> for HttpsConnector<S>
 where
     S: Service<Uri, Response = T> + Send,
@@ -504,21 +1274,7 @@ where
         let f = async move {
             let conn = connect.await.map_err(Into::into)?;
 
-            let mut host = uri.host().ok_or("URI missing host")?;
-
-            // If `host` is an IPv6 address, we must strip away the square brackets that surround
-            // it (otherwise, boring will fail to parse the host as an IP address, eventually
-            // causing the handshake to fail due a hostname verification error).
-            if !host.is_empty() {
-                let last = host.len() - 1;
-                let mut chars = host.chars();
-
-                if let (Some('['), Some(']')) = (chars.next(), chars.last()) {
-                    if host[1..last].parse::<Ipv6Addr>().is_ok() {
-                        host = &host[1..last];
-                    }
-                }
-            }
+            let host = uri.host().ok_or("URI missing host")?;
 
             inner
                 .connect(&uri, host, conn)
@@ -530,3 +1286,711 @@ where
         Box::pin(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        net::SocketAddr,
+        sync::{
+            Arc,
+            atomic::{AtomicBool, Ordering},
+        },
+        time::Duration,
+    };
+
+    use boring2::{
+        pkey::PKey,
+        ssl::{AlpnError, SslAcceptor, SslMethod, SslVersion, select_next_proto},
+        x509::X509,
+    };
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+    };
+
+    use super::*;
+    use crate::tls::AlpnProtocol;
+
+    fn tls12_acceptor() -> SslAcceptor {
+        acceptor_selecting(b"\x08http/1.1")
+    }
+
+    /// A TLS 1.2 acceptor that selects the first of the wire-encoded ALPN
+    /// `protocols` that the client offers.
+    fn acceptor_selecting(protocols: &'static [u8]) -> SslAcceptor {
+        let cert = X509::from_der(include_bytes!("../../../tests/support/server.cert")).unwrap();
+        let key = PKey::private_key_from_der(include_bytes!("../../../tests/support/server.key"))
+            .unwrap();
+
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_certificate(&cert).unwrap();
+        acceptor.set_private_key(&key).unwrap();
+        acceptor
+            .set_max_proto_version(Some(SslVersion::TLS1_2))
+            .unwrap();
+        acceptor.set_alpn_select_callback(move |_, client| {
+            select_next_proto(protocols, client).ok_or(AlpnError::NOACK)
+        });
+        acceptor.build()
+    }
+
+    /// Relays a single connection to `server`, but withholds everything the
+    /// server sends after the client's second flight (the server's
+    /// `ChangeCipherSpec`/`Finished`).
+    async fn withholding_relay(listener: TcpListener, server: SocketAddr) {
+        let (client, _) = listener.accept().await.unwrap();
+        let server = TcpStream::connect(server).await.unwrap();
+
+        let (mut client_rd, mut client_wr) = client.into_split();
+        let (mut server_rd, mut server_wr) = server.into_split();
+        let withhold = Arc::new(AtomicBool::new(false));
+
+        let upstream = {
+            let withhold = withhold.clone();
+            async move {
+                let mut buf = [0u8; 4096];
+                let mut flights = 0;
+                loop {
+                    let n = client_rd.read(&mut buf).await.unwrap_or(0);
+                    if n == 0 {
+                        break;
+                    }
+                    flights += 1;
+                    if flights >= 2 {
+                        withhold.store(true, Ordering::SeqCst);
+                    }
+                    server_wr.write_all(&buf[..n]).await.unwrap();
+                }
+            }
+        };
+
+        let downstream = async move {
+            let mut buf = [0u8; 4096];
+            loop {
+                let n = server_rd.read(&mut buf).await.unwrap_or(0);
+                if n == 0 {
+                    break;
+                }
+                if !withhold.load(Ordering::SeqCst) {
+                    client_wr.write_all(&buf[..n]).await.unwrap();
+                }
+            }
+        };
+
+        tokio::join!(upstream, downstream);
+    }
+
+    async fn connect_through_relay(false_start: bool) -> Option<Vec<u8>> {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = server.local_addr().unwrap();
+        let relay = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let relay_addr = relay.local_addr().unwrap();
+
+        let (tx, rx) = futures_channel::oneshot::channel();
+        tokio::spawn(async move {
+            let (tcp, _) = server.accept().await.unwrap();
+            let mut tls = tokio_boring2::accept(&tls12_acceptor(), tcp).await.unwrap();
+            let mut buf = [0u8; 5];
+            tls.read_exact(&mut buf).await.unwrap();
+            let _ = tx.send(buf.to_vec());
+        });
+        tokio::spawn(withholding_relay(relay, server_addr));
+
+        let config = TlsConfig::builder()
+            .alpn_protos(&[AlpnProtocol::HTTP1])
+            .build();
+        let connector = TlsConnector::builder()
+            .cert_verification(false)
+            .max_version(TlsVersion::TLS_1_2)
+            .false_start(false_start)
+            .build(config)
+            .unwrap();
+
+        let uri = format!("https://localhost:{}", relay_addr.port())
+            .parse::<Uri>()
+            .unwrap();
+        let tcp = TcpStream::connect(relay_addr).await.unwrap();
+        let handshake = connector
+            .inner
+            .connect(&uri, "localhost", TokioIo::new(tcp));
+
+        let mut tls = tokio::time::timeout(Duration::from_millis(500), handshake)
+            .await
+            .ok()?
+            .unwrap();
+        tls.write_all(b"hello").await.unwrap();
+
+        tokio::time::timeout(Duration::from_secs(5), rx)
+            .await
+            .ok()?
+            .ok()
+    }
+
+    /// Connects to a server that only speaks `http/1.1`, offering `alpn`.
+    async fn connect_offering(alpn: AlpnProtocol, require_alpn: bool) -> Result<(), BoxError> {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (tcp, _) = server.accept().await.unwrap();
+            let _ = tokio_boring2::accept(&tls12_acceptor(), tcp).await;
+        });
+
+        let config = TlsConfig::builder().alpn_protos(&[alpn]).build();
+        let connector = TlsConnector::builder()
+            .cert_verification(false)
+            .require_alpn(require_alpn)
+            .build(config)
+            .unwrap();
+
+        let uri = format!("https://{}:{}", host, addr.port())
+            .parse::<Uri>()
+            .unwrap();
+        let tcp = TcpStream::connect(addr).await.unwrap();
+        connector
+            .inner
+            .connect(&uri, host, TokioIo::new(tcp))
+            .await
+            .map(|_| ())
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn require_alpn_rejects_unselected_protocol() {
+        let err = connect_offering(AlpnProtocol::HTTP2, true)
+            .await
+            .unwrap_err();
+        let mismatch = err.downcast_ref::<AlpnMismatch>().unwrap();
+        assert_eq!(mismatch.selected, None);
+        assert_eq!(
+            err.to_string(),
+            "server did not select an offered ALPN protocol (offered: h2; selected: none)"
+        );
+
+        connect_offering(AlpnProtocol::HTTP1, true).await.unwrap();
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn custom_alpn_protocols_are_offered() {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (tcp, _) = server.accept().await.unwrap();
+            let _ = tokio_boring2::accept(&acceptor_selecting(b"\x08custom/1"), tcp).await;
+        });
+
+        let connector = TlsConnector::builder()
+            .cert_verification(false)
+            .alpn_protocols(Some(vec![b"h2".to_vec(), b"custom/1".to_vec()]))
+            .require_alpn(true)
+            .build(TlsConfig::default())
+            .unwrap();
+
+        let uri = format!("https://localhost:{}", addr.port())
+            .parse::<Uri>()
+            .unwrap();
+        let tcp = TcpStream::connect(addr).await.unwrap();
+        let stream = connector
+            .inner
+            .connect(&uri, "localhost", TokioIo::new(tcp))
+            .await
+            .unwrap();
+        assert_eq!(
+            stream.ssl().selected_alpn_protocol(),
+            Some(&b"custom/1"[..])
+        );
+
+        // Only `h2` makes the connection speak HTTP/2.
+        let stream = MaybeHttpsStream::Https(TokioIo::new(stream));
+        assert!(!stream.connected().is_negotiated_h2());
+    }
+
+    #[test]
+    fn invalid_alpn_protocols_fail_building() {
+        for protocols in [vec![], vec![Vec::new()], vec![vec![b'a'; 256]]] {
+            let built = TlsConnector::builder()
+                .alpn_protocols(Some(protocols))
+                .build(TlsConfig::default());
+            assert!(built.is_err());
+        }
+        assert_eq!(
+            encode_alpn_protocols(&[b"h2".to_vec(), b"custom/1".to_vec()]).unwrap(),
+            &b"\x02h2\x08custom/1"[..]
+        );
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn alpn_is_not_required_by_default() {
+        connect_offering(AlpnProtocol::HTTP2, false).await.unwrap();
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn false_start_writes_before_server_finished() {
+        let received = connect_through_relay(true).await;
+        assert_eq!(received.as_deref(), Some(&b"hello"[..]));
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn without_false_start_handshake_waits_for_server_finished() {
+        assert_eq!(connect_through_relay(false).await, None);
+    }
+
+    /// Connects to a server as `host`, with `sni` in place of it or a
+    /// connector built with `server_name`, returning the server name the
+    /// server received.
+    async fn server_name_received(
+        host: &str,
+        sni: Option<&str>,
+        server_name: Option<&str>,
+        tls_sni: bool,
+    ) -> Option<String> {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+
+        let accepted = tokio::spawn(async move {
+            let (tcp, _) = server.accept().await.unwrap();
+            let tls = tokio_boring2::accept(&tls12_acceptor(), tcp).await.unwrap();
+            tls.ssl()
+                .servername(boring2::ssl::NameType::HOST_NAME)
+                .map(str::to_owned)
+        });
+
+        let mut connector = TlsConnector::builder()
+            .cert_verification(false)
+            .server_name(server_name.map(str::to_owned))
+            .tls_sni(tls_sni)
+            .build(TlsConfig::default())
+            .unwrap();
+        connector.inner.sni = sni.map(str::to_owned);
+
+        let uri = format!("https://{}:{}", host, addr.port())
+            .parse::<Uri>()
+            .unwrap();
+        let tcp = TcpStream::connect(addr).await.unwrap();
+        connector
+            .inner
+            .connect(&uri, host, TokioIo::new(tcp))
+            .await
+            .unwrap();
+
+        accepted.await.unwrap()
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn sni_override_replaces_host() {
+        assert_eq!(
+            server_name_received("localhost", None, None, true)
+                .await
+                .as_deref(),
+            Some("localhost")
+        );
+        assert_eq!(
+            server_name_received("localhost", Some("edge.test"), None, true)
+                .await
+                .as_deref(),
+            Some("edge.test")
+        );
+        assert_eq!(
+            server_name_received("localhost", Some("edge.test"), None, false).await,
+            None
+        );
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn server_name_replaces_host() {
+        assert_eq!(
+            server_name_received("localhost", None, Some("front.test"), true)
+                .await
+                .as_deref(),
+            Some("front.test")
+        );
+    }
+
+    #[test]
+    fn server_name_needs_sni() {
+        let built = TlsConnector::builder()
+            .server_name(Some("front.test".to_owned()))
+            .tls_sni(false)
+            .build(TlsConfig::default());
+        assert!(built.is_err());
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn ip_literals_are_not_sent_as_sni() {
+        assert_eq!(server_name_received("[::1]", None, None, true).await, None);
+        assert_eq!(
+            server_name_received("127.0.0.1", None, None, true).await,
+            None
+        );
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn records_verified_chain() {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (tcp, _) = server.accept().await.unwrap();
+            let _ = tokio_boring2::accept(&tls12_acceptor(), tcp).await;
+        });
+
+        let connector = TlsConnector::builder()
+            .cert_verification(false)
+            .verified_chain(true)
+            .build(TlsConfig::default())
+            .unwrap();
+
+        let uri = format!("https://localhost:{}", addr.port())
+            .parse::<Uri>()
+            .unwrap();
+        let tcp = TcpStream::connect(addr).await.unwrap();
+        let tls = connector
+            .inner
+            .connect(&uri, "localhost", TokioIo::new(tcp))
+            .await
+            .unwrap();
+
+        let chain = crate::tls::verified_chain(tls.ssl()).unwrap();
+        assert!(!chain.is_verified());
+        let leaf = &chain.certificates()[0];
+        assert_eq!(leaf.role(), crate::tls::ChainRole::Leaf);
+        assert!(leaf.is_sent_by_peer());
+        assert_eq!(
+            leaf.der(),
+            include_bytes!("../../../tests/support/server.cert")
+        );
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn client_hello_hook_sees_hello() {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (tcp, _) = server.accept().await.unwrap();
+            let _ = tokio_boring2::accept(&tls12_acceptor(), tcp).await;
+        });
+
+        let hellos = Arc::new(Mutex::new(Vec::new()));
+        let connector = TlsConnector::builder()
+            .cert_verification(false)
+            .on_client_hello(Some(Arc::new({
+                let hellos = hellos.clone();
+                move |hello: &[u8]| hellos.lock().push(hello.to_vec())
+            })))
+            .build(TlsConfig::default())
+            .unwrap();
+
+        let uri = format!("https://{}:{}", host, addr.port())
+            .parse::<Uri>()
+            .unwrap();
+        let tcp = TcpStream::connect(addr).await.unwrap();
+        connector
+            .inner
+            .connect(&uri, host, TokioIo::new(tcp))
+            .await
+            .unwrap();
+
+        let hellos = hellos.lock();
+        assert_eq!(hellos.len(), 1);
+        let hello = &hellos[0];
+        assert_eq!(hello[0], 1);
+        let len = u32::from_be_bytes([0, hello[1], hello[2], hello[3]]) as usize;
+        assert_eq!(hello.len(), 4 + len);
+        // The server name is sent in the clear.
+        assert!(hello.windows(9).any(|w| w == b"localhost"));
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn handshake_progress_is_reported_in_order() {
+        use crate::tls::HandshakeState;
+
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (tcp, _) = server.accept().await.unwrap();
+            let _ = tokio_boring2::accept(&tls12_acceptor(), tcp).await;
+        });
+
+        let states = Arc::new(Mutex::new(Vec::new()));
+        let connector = TlsConnector::builder()
+            .cert_verification(false)
+            .on_handshake_progress(Some(Arc::new({
+                let states = states.clone();
+                move |state| states.lock().push(state)
+            })))
+            .build(TlsConfig::default())
+            .unwrap();
+
+        let uri = format!("https://{}:{}", host, addr.port())
+            .parse::<Uri>()
+            .unwrap();
+        let tcp = TcpStream::connect(addr).await.unwrap();
+        connector
+            .inner
+            .connect(&uri, host, TokioIo::new(tcp))
+            .await
+            .unwrap();
+
+        assert_eq!(
+            *states.lock(),
+            [
+                HandshakeState::Started,
+                HandshakeState::ServerHello,
+                HandshakeState::Certificate,
+                HandshakeState::Finished,
+            ]
+        );
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn collects_handshake_timings() {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (tcp, _) = server.accept().await.unwrap();
+            let _ = tokio_boring2::accept(&tls12_acceptor(), tcp).await;
+        });
+
+        let connector = TlsConnector::builder()
+            .cert_verification(false)
+            .collect_timings(true)
+            .build(TlsConfig::default())
+            .unwrap();
+
+        let uri = format!("https://localhost:{}", addr.port())
+            .parse::<Uri>()
+            .unwrap();
+        let tcp = TcpStream::connect(addr).await.unwrap();
+        let tls = connector
+            .inner
+            .connect(&uri, "localhost", TokioIo::new(tcp))
+            .await
+            .unwrap();
+
+        let timings = super::timing::connect_timings(tls.ssl()).unwrap();
+        let verify = timings.cert_verify_duration().unwrap();
+        assert!(verify <= timings.tls_handshake_duration());
+    }
+
+    /// Lists the extension types of a serialized `ClientHello`.
+    fn extension_types(hello: &[u8]) -> Vec<u16> {
+        let session_id = 4 + 2 + 32;
+        let ciphers = session_id + 1 + hello[session_id] as usize;
+        let compression =
+            ciphers + 2 + u16::from_be_bytes([hello[ciphers], hello[ciphers + 1]]) as usize;
+        let mut at = compression + 1 + hello[compression] as usize + 2;
+
+        let mut types = Vec::new();
+        while at + 4 <= hello.len() {
+            types.push(u16::from_be_bytes([hello[at], hello[at + 1]]));
+            at += 4 + u16::from_be_bytes([hello[at + 2], hello[at + 3]]) as usize;
+        }
+        types
+    }
+
+    /// Captures the `ClientHello`s of `n` connections made with `config`.
+    async fn capture_hellos(config: TlsConfig, n: usize) -> Vec<Vec<u8>> {
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            loop {
+                let (tcp, _) = server.accept().await.unwrap();
+                tokio::spawn(async move {
+                    let _ = tokio_boring2::accept(&tls12_acceptor(), tcp).await;
+                });
+            }
+        });
+
+        let hellos = Arc::new(Mutex::new(Vec::new()));
+        let connector = TlsConnector::builder()
+            .cert_verification(false)
+            .on_client_hello(Some(Arc::new({
+                let hellos = hellos.clone();
+                move |hello: &[u8]| hellos.lock().push(hello.to_vec())
+            })))
+            .build(config)
+            .unwrap();
+
+        let uri = format!("https://localhost:{}", addr.port())
+            .parse::<Uri>()
+            .unwrap();
+        for _ in 0..n {
+            let tcp = TcpStream::connect(addr).await.unwrap();
+            let _ = connector
+                .inner
+                .connect(&uri, "localhost", TokioIo::new(tcp))
+                .await;
+        }
+
+        let hellos = hellos.lock().clone();
+        assert_eq!(hellos.len(), n);
+        hellos
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn extension_order_is_applied() {
+        let config = TlsConfig::builder().extension_order([16, 43, 0]).build();
+        let hellos = capture_hellos(config, 1).await;
+
+        let types = extension_types(&hellos[0]);
+        assert_eq!(types[..3], [16, 43, 0]);
+        // Unlisted extensions follow in the default order.
+        let groups = types.iter().position(|&t| t == 10).unwrap();
+        let sigalgs = types.iter().position(|&t| t == 13).unwrap();
+        assert!(groups < sigalgs);
+    }
+
+    #[cfg(not(miri))]
+    #[tokio::test]
+    async fn unpermuted_hellos_are_stable() {
+        let config = TlsConfig::builder().permute_extensions(false).build();
+        let hellos = capture_hellos(config, 3).await;
+
+        for hello in &hellos[1..] {
+            assert_eq!(hello.len(), hellos[0].len());
+            assert_eq!(extension_types(hello), extension_types(&hellos[0]));
+            // Everything up to the random matches byte for byte.
+            assert_eq!(hello[..6], hellos[0][..6]);
+        }
+
+        let config = TlsConfig::builder().permute_extensions(true).build();
+        let hellos = capture_hellos(config, 5).await;
+        let orders = hellos
+            .iter()
+            .map(|hello| extension_types(hello))
+            .collect::<Vec<_>>();
+        assert!(orders.iter().any(|order| order != &orders[0]));
+    }
+
+    #[test]
+    fn unsupported_hello_fields_are_rejected() {
+        let build = |config: TlsConfig| TlsConnector::builder().build(config).is_ok();
+
+        assert!(build(
+            TlsConfig::builder()
+                .psk_key_exchange_modes(&[1][..])
+                .build()
+        ));
+        assert!(build(
+            TlsConfig::builder().psk_key_exchange_modes(&[][..]).build()
+        ));
+        assert!(!build(
+            TlsConfig::builder()
+                .psk_key_exchange_modes(&[0, 1][..])
+                .build()
+        ));
+
+        assert!(build(
+            TlsConfig::builder().compression_methods(&[0][..]).build()
+        ));
+        assert!(!build(
+            TlsConfig::builder()
+                .compression_methods(&[1, 0][..])
+                .build()
+        ));
+    }
+
+    #[test]
+    fn config_fingerprint_follows_client_hello_settings() {
+        let fingerprint =
+            |builder: TlsConnectorBuilder, config: TlsConfig| builder.config_fingerprint(&config);
+        let chrome = || {
+            TlsConfig::builder()
+                .cipher_list("TLS_AES_128_GCM_SHA256:TLS_AES_256_GCM_SHA384")
+                .curves_list("X25519:P-256")
+                .extension_order([0, 16, 43])
+        };
+
+        let a = fingerprint(TlsConnector::builder(), chrome().build());
+        assert_eq!(a, fingerprint(TlsConnector::builder(), chrome().build()));
+
+        // Settings that don't show in the hello leave it alone.
+        let b = fingerprint(
+            TlsConnector::builder()
+                .cert_verification(false)
+                .verify_hostname(false),
+            chrome().build(),
+        );
+        assert_eq!(a, b);
+
+        // Versions set on the builder or on the config are the same.
+        let b = fingerprint(
+            TlsConnector::builder().min_version(TlsVersion::TLS_1_2),
+            chrome().build(),
+        );
+        let c = fingerprint(
+            TlsConnector::builder(),
+            chrome().min_tls_version(TlsVersion::TLS_1_2).build(),
+        );
+        assert_eq!(b, c);
+        assert_ne!(a, b);
+
+        let b = fingerprint(
+            TlsConnector::builder(),
+            chrome().curves_list("P-256:X25519").build(),
+        );
+        assert_ne!(a, b);
+        let b = fingerprint(TlsConnector::builder().tls_sni(false), chrome().build());
+        assert_ne!(a, b);
+        let b = fingerprint(
+            TlsConnector::builder().add_custom_extension(0xff01, vec![1]),
+            chrome().build(),
+        );
+        assert_ne!(a, b);
+
+        // A cipher list set on the builder replaces that of the config.
+        let b = fingerprint(
+            TlsConnector::builder().cipher_list(Some("TLS_AES_256_GCM_SHA384".into())),
+            chrome().build(),
+        );
+        let c = fingerprint(
+            TlsConnector::builder(),
+            chrome().cipher_list("TLS_AES_256_GCM_SHA384").build(),
+        );
+        assert_eq!(b, c);
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn cipher_list_names_must_select_a_cipher() {
+        let build = |ciphers: &'static str| {
+            TlsConnector::builder()
+                .cipher_list(Some(ciphers.into()))
+                .build(TlsConfig::default())
+        };
+
+        assert!(build("ECDHE-RSA-AES128-GCM-SHA256:TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384").is_ok());
+        // Exclusions and commands don't have to match anything.
+        assert!(
+            build("[ECDHE-ECDSA-AES128-GCM-SHA256|ECDHE+AESGCM]:!aNULL:-MD5:@STRENGTH").is_ok()
+        );
+
+        let err = build("ECDHE-RSA-AES128-GCM-SHA256:RC4-NOPE:+NOT-A-CIPHER")
+            .err()
+            .unwrap();
+        assert!(err.is_builder());
+        assert!(err.is_unsupported_cipher());
+        let source = std::error::Error::source(&err).unwrap().to_string();
+        assert_eq!(
+            source,
+            "unsupported cipher suites in cipher list: RC4-NOPE, NOT-A-CIPHER"
+        );
+    }
+}
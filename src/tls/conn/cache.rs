@@ -10,8 +10,11 @@ use boring2::ssl::{SslSession, SslSessionRef};
 use http::uri::Authority;
 use linked_hash_set::LinkedHashSet;
 
+/// Sessions are keyed by authority and the fingerprint of the client
+/// identity they were established with, so resumption never crosses
+/// identities.
 #[derive(Hash, PartialEq, Eq, Clone)]
-pub struct SessionKey(pub Authority);
+pub struct SessionKey(pub Authority, pub Option<u64>);
 
 #[derive(Clone)]
 struct HashSession(SslSession);
@@ -44,6 +47,9 @@ pub struct SessionCache {
     reverse: HashMap<HashSession, SessionKey>,
     /// Maximum capacity of LinkedHashSet per SessionKey
     per_key_session_capacity: usize,
+    /// Bumped on every clear, so that handshakes which started before it
+    /// don't put their sessions back.
+    generation: u64,
 }
 
 impl SessionCache {
@@ -52,10 +58,21 @@ impl SessionCache {
             sessions: HashMap::new(),
             reverse: HashMap::new(),
             per_key_session_capacity,
+            generation: 0,
         }
     }
 
-    pub fn insert(&mut self, key: SessionKey, session: SslSession) {
+    /// The generation a handshake starts in, to pass back to `insert`.
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    pub fn insert(&mut self, key: SessionKey, generation: u64, session: SslSession) {
+        // The cache was cleared while the handshake ran.
+        if generation != self.generation {
+            return;
+        }
+
         let session = HashSession(session);
 
         let sessions = self.sessions.entry(key.clone()).or_default();
@@ -87,6 +104,30 @@ impl SessionCache {
         Some(session)
     }
 
+    /// Drops every session.
+    pub fn clear(&mut self) {
+        self.sessions.clear();
+        self.reverse.clear();
+        self.generation += 1;
+    }
+
+    /// Drops the sessions of `host`, on any port and with any identity.
+    ///
+    /// Handshakes in flight may be with `host` too, so none of them put
+    /// their sessions in the cache.
+    pub fn clear_host(&mut self, host: &str) {
+        self.sessions.retain(|key, sessions| {
+            if !key.0.host().eq_ignore_ascii_case(host) {
+                return true;
+            }
+            for session in sessions.iter() {
+                self.reverse.remove(session);
+            }
+            false
+        });
+        self.generation += 1;
+    }
+
     pub fn remove(&mut self, session: &SslSessionRef) {
         let key = match self.reverse.remove(session.id()) {
             Some(key) => key,
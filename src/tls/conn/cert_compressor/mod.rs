@@ -2,6 +2,63 @@ mod brotli;
 mod zlib;
 mod zstd;
 
+use std::sync::{
+    LazyLock,
+    atomic::{AtomicU16, Ordering},
+};
+
+use boring2::{
+    error::ErrorStack,
+    ex_data::Index,
+    ssl::{Ssl, SslRef},
+};
+
 pub use brotli::BrotliCertificateCompressor;
 pub use zlib::ZlibCertificateCompressor;
 pub use zstd::ZstdCertificateCompressor;
+
+use super::hello;
+use crate::tls::CertificateCompressionAlgorithm;
+
+/// `SSL3_MT_COMPRESSED_CERTIFICATE`
+const COMPRESSED_CERTIFICATE: u8 = 25;
+
+/// The algorithm of the compressed certificate received on a connection,
+/// or 0 before one is.
+fn used_index() -> Result<Index<Ssl, AtomicU16>, ErrorStack> {
+    static IDX: LazyLock<Result<Index<Ssl, AtomicU16>, ErrorStack>> =
+        LazyLock::new(Ssl::new_ex_index);
+    IDX.clone()
+}
+
+/// Records which algorithm the server compressed its certificate with on
+/// `ssl`.
+///
+/// Only connections that advertise compression need to watch for it.
+pub(in crate::tls) fn observe(ssl: &mut SslRef) -> Result<(), ErrorStack> {
+    ssl.set_ex_data(used_index()?, AtomicU16::new(0));
+    hello::observe_messages(ssl);
+    Ok(())
+}
+
+/// Records the algorithm of `msg`, if it is a `CompressedCertificate`.
+pub(in crate::tls) fn on_received(ssl: &SslRef, msg: &[u8]) {
+    // The 4-byte handshake header, then the 2-byte algorithm.
+    if let [COMPRESSED_CERTIFICATE, _, _, _, a0, a1, ..] = *msg {
+        if let Some(used) = used_index().ok().and_then(|idx| ssl.ex_data(idx)) {
+            used.store(u16::from_be_bytes([a0, a1]), Ordering::Relaxed);
+        }
+    }
+}
+
+/// Returns the algorithm the server compressed its certificate with on
+/// `ssl`, if it did.
+pub(crate) fn used(ssl: &SslRef) -> Option<CertificateCompressionAlgorithm> {
+    let idx = used_index().ok()?;
+    match ssl.ex_data(idx)?.load(Ordering::Relaxed) {
+        1 => Some(CertificateCompressionAlgorithm::ZLIB),
+        2 => Some(CertificateCompressionAlgorithm::BROTLI),
+        3 => Some(CertificateCompressionAlgorithm::ZSTD),
+        _ => None,
+    }
+}
@@ -0,0 +1,99 @@
+//! Extensions sent in the `ClientHello` as given.
+
+use std::{
+    os::raw::{c_int, c_uint, c_void},
+    ptr,
+    sync::LazyLock,
+};
+
+use boring_sys2 as ffi;
+use boring2::{
+    error::ErrorStack,
+    ex_data::Index,
+    ssl::{SslContext, SslContextBuilder, SslRef},
+};
+use foreign_types::ForeignTypeRef;
+
+/// The type and contents of each custom extension, in the order they are
+/// sent.
+type CustomExtensions = Vec<(u16, Vec<u8>)>;
+
+fn extensions_index() -> Result<Index<SslContext, CustomExtensions>, ErrorStack> {
+    static IDX: LazyLock<Result<Index<SslContext, CustomExtensions>, ErrorStack>> =
+        LazyLock::new(SslContext::new_ex_index);
+    IDX.clone()
+}
+
+/// Adds `extensions` to the `ClientHello` of every connection made from
+/// `ctx`, after the extensions BoringSSL sends itself.
+///
+/// Fails for an extension type BoringSSL already handles, or one given
+/// twice.
+pub(super) fn register(
+    ctx: &mut SslContextBuilder,
+    extensions: CustomExtensions,
+) -> crate::Result<()> {
+    if extensions.is_empty() {
+        return Ok(());
+    }
+
+    for &(ext_type, _) in &extensions {
+        // SAFETY: `ctx` is a valid handle. The add callback only reads the
+        // contents stored in the ex data of the context, which outlives the
+        // connections made from it. Without a parse callback, whatever the
+        // server answers to the extension is ignored.
+        let added = unsafe {
+            ffi::SSL_CTX_add_client_custom_ext(
+                ctx.as_ptr(),
+                c_uint::from(ext_type),
+                Some(add_extension),
+                None,
+                ptr::null_mut(),
+                None,
+                ptr::null_mut(),
+            )
+        };
+        if added != 1 {
+            return Err(crate::Error::builder(format!(
+                "cannot add custom TLS extension {ext_type}"
+            )));
+        }
+    }
+
+    ctx.set_ex_data(extensions_index()?, extensions);
+    Ok(())
+}
+
+unsafe extern "C" fn add_extension(
+    ssl: *mut ffi::SSL,
+    extension_value: c_uint,
+    out: *mut *const u8,
+    out_len: *mut usize,
+    _out_alert_value: *mut c_int,
+    _add_arg: *mut c_void,
+) -> c_int {
+    // SAFETY: BoringSSL passes a handle to the connection being handshaken.
+    let ssl = unsafe { SslRef::from_ptr(ssl) };
+    let contents = extensions_index()
+        .ok()
+        .and_then(|idx| ssl.ssl_context().ex_data(idx))
+        .and_then(|extensions| {
+            extensions
+                .iter()
+                .find(|(ext_type, _)| c_uint::from(*ext_type) == extension_value)
+        });
+
+    match contents {
+        Some((_, data)) => {
+            // SAFETY: BoringSSL passes valid out pointers, and `data` lives
+            // in the context, as long as the connection does.
+            unsafe {
+                *out = data.as_ptr();
+                *out_len = data.len();
+            }
+            1
+        }
+        // Leave out the extension.
+        None => 0,
+    }
+}
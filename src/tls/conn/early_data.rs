@@ -0,0 +1,27 @@
+//! Recovering from early data the server rejected.
+//!
+//! When the server turns down early data, BoringSSL completes the handshake
+//! and fails every read and write until told that the caller knows, so that
+//! nothing written as early data is silently lost.
+
+use std::{io, os::raw::c_int};
+
+use boring_sys2 as ffi;
+use boring2::ssl::{self, SslRef};
+use foreign_types::ForeignTypeRef;
+
+/// Returns whether the read or write error `err` says that the server
+/// rejected the early data.
+pub(crate) fn early_data_rejected(err: &io::Error) -> bool {
+    err.get_ref()
+        .and_then(|err| err.downcast_ref::<ssl::Error>())
+        .is_some_and(|err| err.code().as_raw() == ffi::SSL_ERROR_EARLY_DATA_REJECTED as c_int)
+}
+
+/// Lets the connection `ssl`, whose early data was rejected, be read from and
+/// written to again, over the completed handshake.
+pub(crate) fn reset_early_data(ssl: &SslRef) {
+    // SAFETY: `ssl` is a live connection, which reported its early data
+    // rejected.
+    unsafe { ffi::SSL_reset_early_data_reject(ssl.as_ptr()) };
+}
@@ -1,10 +1,6 @@
 use std::borrow::Cow;
 
-use boring2::{
-    error::ErrorStack,
-    ssl::{ConnectConfiguration, SslConnectorBuilder, SslVerifyMode},
-};
-use bytes::Bytes;
+use boring2::ssl::{ConnectConfiguration, SslConnectorBuilder, SslMode, SslVerifyMode};
 
 use crate::tls::{
     CertStore, CertificateCompressionAlgorithm, Identity,
@@ -29,21 +25,20 @@ pub trait SslConnectorBuilderExt {
         self,
         algs: Option<Cow<'static, [CertificateCompressionAlgorithm]>>,
     ) -> crate::Result<SslConnectorBuilder>;
+
+    /// Configure TLS False Start for the given `SslConnectorBuilder`.
+    fn false_start(self, enable: bool) -> crate::Result<SslConnectorBuilder>;
 }
 
 /// ConnectConfigurationExt trait for `ConnectConfiguration`.
 pub trait ConnectConfigurationExt {
-    /// Configure the ALPS for the given `ConnectConfiguration`.
-    fn alps_protos(
-        &mut self,
-        alps: Option<Bytes>,
-        use_new_codepoint: bool,
-    ) -> Result<&mut ConnectConfiguration, ErrorStack>;
-
     /// Configure the random aes hardware override for the given `ConnectConfiguration`.
     fn set_random_aes_hw_override(&mut self, enable: bool);
 }
 
+/// BoringSSL's `SSL_MODE_ENABLE_FALSE_START`, which has no named `SslMode` constant.
+const SSL_MODE_ENABLE_FALSE_START: u32 = 0x0000_0080;
+
 impl SslConnectorBuilderExt for SslConnectorBuilder {
     #[inline(always)]
     fn cert_store(mut self, store: Option<CertStore>) -> crate::Result<SslConnectorBuilder> {
@@ -104,27 +99,18 @@ impl SslConnectorBuilderExt for SslConnectorBuilder {
 
         Ok(self)
     }
-}
 
-impl ConnectConfigurationExt for ConnectConfiguration {
     #[inline]
-    fn alps_protos(
-        &mut self,
-        alps: Option<Bytes>,
-        use_new_codepoint: bool,
-    ) -> Result<&mut ConnectConfiguration, ErrorStack> {
-        if let Some(alps) = alps {
-            self.add_application_settings(&alps)?;
-
-            // By default, the old endpoint is used. Avoid unnecessary FFI calls.
-            if use_new_codepoint {
-                self.set_alps_use_new_codepoint(use_new_codepoint);
-            }
+    fn false_start(mut self, enable: bool) -> crate::Result<SslConnectorBuilder> {
+        if enable {
+            self.set_mode(SslMode::from_bits_retain(SSL_MODE_ENABLE_FALSE_START as _));
         }
 
         Ok(self)
     }
+}
 
+impl ConnectConfigurationExt for ConnectConfiguration {
     #[inline]
     fn set_random_aes_hw_override(&mut self, enable: bool) {
         if enable {
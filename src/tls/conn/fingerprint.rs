@@ -0,0 +1,433 @@
+//! JA3 and JA4 fingerprints of the `ClientHello`.
+//!
+//! See <https://github.com/salesforce/ja3> and
+//! <https://github.com/FoxIO-LLC/ja4/blob/main/technical_details/JA4.md>.
+
+use std::{
+    fmt::Write as _,
+    sync::{Arc, OnceLock},
+};
+
+use boring2::hash::{MessageDigest, hash};
+
+const SERVER_NAME: u16 = 0x0000;
+const SUPPORTED_GROUPS: u16 = 0x000a;
+const EC_POINT_FORMATS: u16 = 0x000b;
+const SIGNATURE_ALGORITHMS: u16 = 0x000d;
+const ALPN: u16 = 0x0010;
+const SUPPORTED_VERSIONS: u16 = 0x002b;
+
+/// The JA3 and JA4 fingerprints of a `ClientHello`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Fingerprint {
+    pub(crate) ja3: String,
+    pub(crate) ja4: String,
+}
+
+/// Captures the fingerprint of the first `ClientHello` of a connection.
+#[derive(Clone, Default)]
+pub(crate) struct FingerprintRecorder(Arc<OnceLock<Fingerprint>>);
+
+impl FingerprintRecorder {
+    /// Records the fingerprint of `hello`, a serialized handshake message.
+    ///
+    /// A second `ClientHello`, sent after a `HelloRetryRequest`, is ignored.
+    pub(crate) fn record(&self, hello: &[u8]) {
+        if self.0.get().is_some() {
+            return;
+        }
+        if let Some(hello) = ClientHello::parse(hello) {
+            let _ = self.0.set(hello.fingerprint());
+        }
+    }
+
+    pub(crate) fn get(&self) -> Option<&Fingerprint> {
+        self.0.get()
+    }
+}
+
+#[derive(Default)]
+struct ClientHello {
+    version: u16,
+    ciphers: Vec<u16>,
+    extensions: Vec<u16>,
+    groups: Vec<u16>,
+    point_formats: Vec<u8>,
+    sigalgs: Vec<u16>,
+    alpn: Option<Vec<u8>>,
+    versions: Vec<u16>,
+}
+
+impl ClientHello {
+    fn parse(msg: &[u8]) -> Option<ClientHello> {
+        let mut msg = Reader(msg);
+        if msg.u8()? != 1 {
+            return None;
+        }
+        let mut body = Reader(msg.take(msg.u24()?)?);
+
+        let mut hello = ClientHello {
+            version: body.u16()?,
+            ..ClientHello::default()
+        };
+        body.take(32)?;
+        body.vec8()?;
+        hello.ciphers = body.vec16()?.u16s()?;
+        body.vec8()?;
+
+        // A hello without extensions has nothing after the compression methods.
+        if body.0.is_empty() {
+            return Some(hello);
+        }
+
+        let mut extensions = body.vec16()?;
+        while !extensions.0.is_empty() {
+            let kind = extensions.u16()?;
+            let mut data = extensions.vec16()?;
+            hello.extensions.push(kind);
+
+            match kind {
+                SUPPORTED_GROUPS => hello.groups = data.vec16()?.u16s()?,
+                EC_POINT_FORMATS => hello.point_formats = data.vec8()?.0.to_vec(),
+                SIGNATURE_ALGORITHMS => hello.sigalgs = data.vec16()?.u16s()?,
+                ALPN => hello.alpn = data.vec16()?.vec8().map(|proto| proto.0.to_vec()),
+                SUPPORTED_VERSIONS => hello.versions = data.vec8()?.u16s()?,
+                _ => {}
+            }
+        }
+
+        Some(hello)
+    }
+
+    fn fingerprint(&self) -> Fingerprint {
+        Fingerprint {
+            ja3: self.ja3(),
+            ja4: self.ja4(),
+        }
+    }
+
+    fn ja3(&self) -> String {
+        fn join<T: ToString>(values: impl Iterator<Item = T>) -> String {
+            values
+                .map(|value| value.to_string())
+                .collect::<Vec<_>>()
+                .join("-")
+        }
+
+        let full = format!(
+            "{},{},{},{},{}",
+            self.version,
+            join(self.ciphers.iter().filter(|v| !is_grease(**v))),
+            join(self.extensions.iter().filter(|v| !is_grease(**v))),
+            join(self.groups.iter().filter(|v| !is_grease(**v))),
+            join(self.point_formats.iter()),
+        );
+        digest(MessageDigest::md5(), full.as_bytes(), 32)
+    }
+
+    fn ja4(&self) -> String {
+        let version = self
+            .versions
+            .iter()
+            .copied()
+            .filter(|v| !is_grease(*v))
+            .max()
+            .unwrap_or(self.version);
+        let version = match version {
+            0x0304 => "13",
+            0x0303 => "12",
+            0x0302 => "11",
+            0x0301 => "10",
+            0x0300 => "s3",
+            _ => "00",
+        };
+        let sni = if self.extensions.contains(&SERVER_NAME) {
+            'd'
+        } else {
+            'i'
+        };
+
+        let mut ciphers = self
+            .ciphers
+            .iter()
+            .copied()
+            .filter(|v| !is_grease(*v))
+            .collect::<Vec<_>>();
+        let mut extensions = self
+            .extensions
+            .iter()
+            .copied()
+            .filter(|v| !is_grease(*v))
+            .collect::<Vec<_>>();
+
+        let mut ja4 = format!(
+            "t{version}{sni}{:02}{:02}{}_",
+            ciphers.len().min(99),
+            extensions.len().min(99),
+            alpn_chars(self.alpn.as_deref()),
+        );
+
+        ciphers.sort_unstable();
+        ja4.push_str(&truncated_sha256(&hex_list(&ciphers)));
+        ja4.push('_');
+
+        extensions.retain(|v| *v != SERVER_NAME && *v != ALPN);
+        extensions.sort_unstable();
+        let mut c = hex_list(&extensions);
+        if !self.sigalgs.is_empty() {
+            c.push('_');
+            c.push_str(&hex_list(&self.sigalgs));
+        }
+        ja4.push_str(&if extensions.is_empty() {
+            "000000000000".to_owned()
+        } else {
+            truncated_sha256(&c)
+        });
+
+        ja4
+    }
+}
+
+/// GREASE values (RFC 8701) are `0x?a?a` with both bytes equal.
+fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+/// The first and last character of the first ALPN protocol, or of its hex
+/// encoding if either is not alphanumeric.
+fn alpn_chars(alpn: Option<&[u8]>) -> String {
+    match alpn {
+        Some([first, .., last]) | Some([first @ last]) => {
+            if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() {
+                format!("{}{}", *first as char, *last as char)
+            } else {
+                let first = format!("{first:02x}");
+                let last = format!("{last:02x}");
+                format!("{}{}", &first[..1], &last[1..])
+            }
+        }
+        _ => "00".to_owned(),
+    }
+}
+
+fn hex_list(values: &[u16]) -> String {
+    values
+        .iter()
+        .map(|v| format!("{v:04x}"))
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn truncated_sha256(input: &str) -> String {
+    if input.is_empty() {
+        return "000000000000".to_owned();
+    }
+    digest(MessageDigest::sha256(), input.as_bytes(), 12)
+}
+
+fn digest(md: MessageDigest, input: &[u8], len: usize) -> String {
+    let mut out = String::with_capacity(64);
+    if let Ok(digest) = hash(md, input) {
+        for byte in digest.iter() {
+            let _ = write!(out, "{byte:02x}");
+        }
+    }
+    out.truncate(len);
+    out
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, n: usize) -> Option<&'a [u8]> {
+        if self.0.len() < n {
+            return None;
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Some(head)
+    }
+
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|b| b[0])
+    }
+
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|b| u16::from_be_bytes([b[0], b[1]]))
+    }
+
+    fn u24(&mut self) -> Option<usize> {
+        self.take(3)
+            .map(|b| u32::from_be_bytes([0, b[0], b[1], b[2]]) as usize)
+    }
+
+    fn vec8(&mut self) -> Option<Reader<'a>> {
+        let len = self.u8()? as usize;
+        self.take(len).map(Reader)
+    }
+
+    fn vec16(&mut self) -> Option<Reader<'a>> {
+        let len = self.u16()? as usize;
+        self.take(len).map(Reader)
+    }
+
+    fn u16s(mut self) -> Option<Vec<u16>> {
+        let mut values = Vec::with_capacity(self.0.len() / 2);
+        while !self.0.is_empty() {
+            values.push(self.u16()?);
+        }
+        Some(values)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds a `ClientHello` with the given fields, each extension carrying
+    /// `data`.
+    fn client_hello(version: u16, ciphers: &[u16], extensions: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&version.to_be_bytes());
+        body.extend_from_slice(&[0; 32]);
+        body.push(0);
+        body.extend_from_slice(&((ciphers.len() * 2) as u16).to_be_bytes());
+        for cipher in ciphers {
+            body.extend_from_slice(&cipher.to_be_bytes());
+        }
+        body.extend_from_slice(&[1, 0]);
+
+        let mut exts = Vec::new();
+        for (kind, data) in extensions {
+            exts.extend_from_slice(&kind.to_be_bytes());
+            exts.extend_from_slice(&(data.len() as u16).to_be_bytes());
+            exts.extend_from_slice(data);
+        }
+        body.extend_from_slice(&(exts.len() as u16).to_be_bytes());
+        body.extend_from_slice(&exts);
+
+        let mut msg = vec![1];
+        msg.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+        msg.extend_from_slice(&body);
+        msg
+    }
+
+    fn u16_vec16(values: &[u16]) -> Vec<u8> {
+        let mut out = ((values.len() * 2) as u16).to_be_bytes().to_vec();
+        for value in values {
+            out.extend_from_slice(&value.to_be_bytes());
+        }
+        out
+    }
+
+    #[test]
+    fn grease_values() {
+        assert!(is_grease(0x0a0a));
+        assert!(is_grease(0xfafa));
+        assert!(!is_grease(0x0a1a));
+        assert!(!is_grease(0x1301));
+    }
+
+    #[test]
+    fn fingerprints_skip_grease() {
+        let hello = client_hello(
+            0x0303,
+            &[0x2a2a, 0x1301, 0x1302, 0xc02b],
+            &[
+                (0x3a3a, vec![]),
+                (SERVER_NAME, vec![]),
+                (SUPPORTED_GROUPS, u16_vec16(&[0x4a4a, 0x001d, 0x0017])),
+                (EC_POINT_FORMATS, vec![1, 0]),
+                (SIGNATURE_ALGORITHMS, u16_vec16(&[0x0403, 0x0804])),
+                (ALPN, vec![0, 3, 2, b'h', b'2']),
+                (SUPPORTED_VERSIONS, vec![4, 0x5a, 0x5a, 0x03, 0x04]),
+            ],
+        );
+
+        let fingerprint = ClientHello::parse(&hello).unwrap().fingerprint();
+
+        let ja3 = "771,4865-4866-49195,0-10-11-13-16-43,29-23,0";
+        assert_eq!(
+            fingerprint.ja3,
+            digest(MessageDigest::md5(), ja3.as_bytes(), 32)
+        );
+
+        let b = truncated_sha256("1301,1302,c02b");
+        let c = truncated_sha256("000a,000b,000d,002b_0403,0804");
+        assert_eq!(fingerprint.ja4, format!("t13d0306h2_{b}_{c}"));
+    }
+
+    /// The example from the JA3 README.
+    #[test]
+    fn ja3_known_answer() {
+        let hello = client_hello(
+            769,
+            &[47, 53, 5, 10, 49161, 49162, 49171, 49172, 50, 56, 19, 4],
+            &[
+                (SERVER_NAME, vec![]),
+                (SUPPORTED_GROUPS, u16_vec16(&[23, 24, 25])),
+                (EC_POINT_FORMATS, vec![1, 0]),
+            ],
+        );
+
+        let fingerprint = ClientHello::parse(&hello).unwrap().fingerprint();
+        assert_eq!(fingerprint.ja3, "ada70206e40642a3e4461f35503241d5");
+    }
+
+    /// The example from the JA4 technical details.
+    #[test]
+    fn ja4_known_answer() {
+        let sigalgs = [
+            0x0403, 0x0804, 0x0401, 0x0503, 0x0805, 0x0501, 0x0806, 0x0601,
+        ];
+        let hello = client_hello(
+            0x0303,
+            &[
+                0x1301, 0x1302, 0x1303, 0xc02b, 0xc02f, 0xc02c, 0xc030, 0xcca9, 0xcca8, 0xc013,
+                0xc014, 0x009c, 0x009d, 0x002f, 0x0035,
+            ],
+            &[
+                (0x001b, vec![]),
+                (0x0000, vec![]),
+                (0x0033, vec![]),
+                (0x0010, vec![0, 3, 2, b'h', b'2']),
+                (0x4469, vec![]),
+                (0x0017, vec![]),
+                (0x002d, vec![]),
+                (0x000d, u16_vec16(&sigalgs)),
+                (0x0005, vec![]),
+                (0x0023, vec![]),
+                (0x0012, vec![]),
+                (0x002b, vec![2, 0x03, 0x04]),
+                (0xff01, vec![]),
+                (0x000b, vec![1, 0]),
+                (0x000a, u16_vec16(&[0x001d])),
+                (0x0015, vec![]),
+            ],
+        );
+
+        let fingerprint = ClientHello::parse(&hello).unwrap().fingerprint();
+        assert_eq!(fingerprint.ja4, "t13d1516h2_8daaf6152771_e5627efa2ab1");
+    }
+
+    #[test]
+    fn ja4_without_sni_or_alpn() {
+        let hello = client_hello(0x0303, &[0xc02f], &[]);
+        let fingerprint = ClientHello::parse(&hello).unwrap().fingerprint();
+        assert!(fingerprint.ja4.starts_with("t12i010000_"));
+        assert!(fingerprint.ja4.ends_with("_000000000000"));
+    }
+
+    #[test]
+    fn alpn_falls_back_to_hex() {
+        assert_eq!(alpn_chars(Some(b"http/1.1")), "h1");
+        assert_eq!(alpn_chars(Some(&[0xab, 0x01])), "a1");
+        assert_eq!(alpn_chars(None), "00");
+    }
+
+    #[test]
+    fn rejects_truncated_hello() {
+        let hello = client_hello(0x0303, &[0x1301], &[]);
+        assert!(ClientHello::parse(&hello[..hello.len() - 1]).is_none());
+    }
+}
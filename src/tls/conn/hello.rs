@@ -0,0 +1,86 @@
+//! Observing the handshake messages of a connection.
+
+use std::{
+    os::raw::{c_int, c_void},
+    sync::{Arc, LazyLock},
+};
+
+use boring_sys2 as ffi;
+use boring2::{
+    error::ErrorStack,
+    ex_data::Index,
+    ssl::{Ssl, SslRef},
+};
+use foreign_types::ForeignTypeRef;
+
+use super::{cert_compressor, progress};
+
+/// A callback invoked with the serialized `ClientHello` of a connection.
+pub type ClientHelloHook = Arc<dyn Fn(&[u8]) + Send + Sync>;
+
+/// `SSL3_RT_HANDSHAKE`
+const HANDSHAKE: c_int = 22;
+
+/// `SSL3_MT_CLIENT_HELLO`
+const CLIENT_HELLO: u8 = 1;
+
+fn hook_index() -> Result<Index<Ssl, ClientHelloHook>, ErrorStack> {
+    static IDX: LazyLock<Result<Index<Ssl, ClientHelloHook>, ErrorStack>> =
+        LazyLock::new(Ssl::new_ex_index);
+    IDX.clone()
+}
+
+/// Calls `hook` with every `ClientHello` written on `ssl`.
+///
+/// The message callback is only installed on connections with a hook, so
+/// others pay nothing for it.
+pub(super) fn install(ssl: &mut SslRef, hook: ClientHelloHook) -> Result<(), ErrorStack> {
+    ssl.set_ex_data(hook_index()?, hook);
+    observe_messages(ssl);
+    Ok(())
+}
+
+/// Installs the message callback on `ssl`, which dispatches to the hooks
+/// stored in its ex data.
+pub(super) fn observe_messages(ssl: &mut SslRef) {
+    // SAFETY: `ssl` is a valid handle, and the callback only reads the hooks
+    // stored in its ex data, which live as long as `ssl` does.
+    unsafe { ffi::SSL_set_msg_callback(ssl.as_ptr(), Some(on_message)) };
+}
+
+unsafe extern "C" fn on_message(
+    is_write: c_int,
+    _version: c_int,
+    content_type: c_int,
+    buf: *const c_void,
+    len: usize,
+    ssl: *mut ffi::SSL,
+    _arg: *mut c_void,
+) {
+    if content_type != HANDSHAKE || buf.is_null() || len == 0 {
+        return;
+    }
+
+    // SAFETY: BoringSSL passes the complete handshake message, header
+    // included, and a handle to the connection it is sent on.
+    let (msg, ssl) = unsafe {
+        (
+            std::slice::from_raw_parts(buf.cast::<u8>(), len),
+            SslRef::from_ptr(ssl),
+        )
+    };
+
+    if is_write == 0 {
+        cert_compressor::on_received(ssl, msg);
+        progress::on_received(ssl, msg[0]);
+        return;
+    }
+
+    if msg[0] != CLIENT_HELLO {
+        return;
+    }
+
+    if let Some(hook) = hook_index().ok().and_then(|idx| ssl.ex_data(idx)) {
+        hook(msg);
+    }
+}
@@ -0,0 +1,213 @@
+//! Requirements on the key of the server certificate, and on how long it
+//! remains valid.
+
+use std::{
+    sync::Arc,
+    time::{Duration, SystemTime},
+};
+
+use antidote::Mutex;
+use boring2::{
+    hash::{MessageDigest, hash},
+    nid::Nid,
+    pkey::{Id, PKeyRef, Public},
+    ssl::SslRef,
+    x509::X509StoreContextRef,
+};
+
+use crate::{
+    error::{CertExpiringSoon, PinMismatch, WeakKey},
+    tls::x509::validity,
+};
+
+/// The minimum strength required of the key of the server's leaf
+/// certificate, on top of the chain validating, the keys it may be, and how
+/// long it must remain valid.
+#[derive(Clone, Default)]
+pub(crate) struct KeyPolicy {
+    pub(super) min_rsa_bits: Option<u16>,
+    pub(super) allowed_curves: Option<Arc<[Nid]>>,
+    pub(super) spki_pins: Option<Arc<[[u8; 32]]>>,
+    pub(super) min_validity_remaining: Option<Duration>,
+}
+
+impl KeyPolicy {
+    /// Returns whether any strength requirement is set, checked while the
+    /// chain is verified.
+    pub(super) fn is_enabled(&self) -> bool {
+        self.min_rsa_bits.is_some() || self.allowed_curves.is_some()
+    }
+
+    /// Checks the leaf key of the established connection `ssl` against the
+    /// pins, if any are set.
+    ///
+    /// This runs after the handshake rather than while the chain is
+    /// verified, so that it also holds when verification is disabled, and
+    /// when it stopped before reaching the leaf.
+    pub(super) fn check_pins(&self, ssl: &SslRef) -> Result<(), PinMismatch> {
+        let Some(ref pins) = self.spki_pins else {
+            return Ok(());
+        };
+        let found = ssl
+            .peer_certificate()
+            .and_then(|cert| cert.public_key().ok())
+            .and_then(|key| spki_sha256(&key));
+        match found {
+            Some(found) if pins.contains(&found) => Ok(()),
+            found => Err(PinMismatch { found }),
+        }
+    }
+
+    /// Checks that the leaf certificate of the established connection `ssl`
+    /// remains valid for long enough, if that is required.
+    ///
+    /// Like the pins, this holds when verification is disabled.
+    pub(super) fn check_validity(&self, ssl: &SslRef) -> Result<(), CertExpiringSoon> {
+        let Some(min) = self.min_validity_remaining else {
+            return Ok(());
+        };
+        let Some((_, not_after)) = ssl.peer_certificate().and_then(|cert| validity(&cert)) else {
+            return Ok(());
+        };
+        let remaining = not_after
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        if remaining < min {
+            return Err(CertExpiringSoon { remaining, min });
+        }
+        Ok(())
+    }
+
+    /// Checks `key` against the requirements. Keys that are neither RSA nor
+    /// EC are not covered.
+    fn check(&self, key: &PKeyRef<Public>) -> Result<(), WeakKey> {
+        match key.id() {
+            Id::RSA => match self.min_rsa_bits {
+                Some(min) if key.bits() < u32::from(min) => Err(WeakKey::Rsa {
+                    bits: key.bits(),
+                    min,
+                }),
+                _ => Ok(()),
+            },
+            Id::EC => match self.allowed_curves {
+                Some(ref allowed) => {
+                    let curve = key.ec_key().ok().and_then(|ec| ec.group().curve_name());
+                    if curve.is_some_and(|curve| allowed.contains(&curve)) {
+                        Ok(())
+                    } else {
+                        Err(WeakKey::Curve(curve))
+                    }
+                }
+                None => Ok(()),
+            },
+            _ => Ok(()),
+        }
+    }
+}
+
+/// Returns the SHA-256 digest of the DER `SubjectPublicKeyInfo` of `key`.
+fn spki_sha256(key: &PKeyRef<Public>) -> Option<[u8; 32]> {
+    let der = key.public_key_to_der().ok()?;
+    let digest = hash(MessageDigest::sha256(), &der).ok()?;
+    digest.as_ref().try_into().ok()
+}
+
+/// Checks the leaf key of one handshake, remembering why it was rejected.
+#[derive(Clone)]
+pub(super) struct KeyCheck {
+    policy: KeyPolicy,
+    violation: Arc<Mutex<Option<WeakKey>>>,
+}
+
+impl KeyCheck {
+    pub(super) fn new(policy: KeyPolicy) -> Self {
+        KeyCheck {
+            policy,
+            violation: Arc::default(),
+        }
+    }
+
+    /// Fails the verification of `ctx` if its leaf key is too weak. Called
+    /// by BoringSSL once per certificate.
+    pub(super) fn verify(&self, preverify_ok: bool, ctx: &X509StoreContextRef) -> bool {
+        if ctx.error_depth() != 0 {
+            return preverify_ok;
+        }
+        let Some(key) = ctx.current_cert().and_then(|cert| cert.public_key().ok()) else {
+            return preverify_ok;
+        };
+        match self.policy.check(&key) {
+            Ok(()) => preverify_ok,
+            Err(weak) => {
+                *self.violation.lock() = Some(weak);
+                false
+            }
+        }
+    }
+
+    /// Returns why the leaf key was rejected, if it was.
+    ///
+    /// A rejection is reported even when verification failures do not fail
+    /// the handshake, as when certificate verification is disabled.
+    pub(super) fn violation(&self) -> Option<WeakKey> {
+        self.violation.lock().take()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use boring2::{
+        ec::{EcGroup, EcKey},
+        pkey::PKey,
+        rsa::Rsa,
+    };
+
+    use super::*;
+
+    fn rsa(bits: u32) -> PKey<Public> {
+        let rsa = Rsa::generate(bits).unwrap();
+        PKey::public_key_from_der(&rsa.public_key_to_der().unwrap()).unwrap()
+    }
+
+    fn ec(curve: Nid) -> PKey<Public> {
+        let group = EcGroup::from_curve_name(curve).unwrap();
+        let ec = EcKey::generate(&group).unwrap();
+        PKey::public_key_from_der(&ec.public_key_to_der().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn rejects_small_rsa_keys() {
+        let policy = KeyPolicy {
+            min_rsa_bits: Some(2048),
+            allowed_curves: None,
+            spki_pins: None,
+            min_validity_remaining: None,
+        };
+        assert!(policy.check(&rsa(2048)).is_ok());
+        assert!(matches!(
+            policy.check(&rsa(1024)),
+            Err(WeakKey::Rsa {
+                bits: 1024,
+                min: 2048
+            })
+        ));
+        // EC keys are only held to the allowed curves.
+        assert!(policy.check(&ec(Nid::SECP521R1)).is_ok());
+    }
+
+    #[test]
+    fn rejects_curves_not_allowed() {
+        let policy = KeyPolicy {
+            min_rsa_bits: None,
+            allowed_curves: Some(Arc::from([Nid::X9_62_PRIME256V1, Nid::SECP384R1])),
+            spki_pins: None,
+            min_validity_remaining: None,
+        };
+        assert!(policy.check(&ec(Nid::X9_62_PRIME256V1)).is_ok());
+        assert!(matches!(
+            policy.check(&ec(Nid::SECP521R1)),
+            Err(WeakKey::Curve(Some(Nid::SECP521R1)))
+        ));
+        assert!(policy.check(&rsa(1024)).is_ok());
+    }
+}
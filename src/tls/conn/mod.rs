@@ -1,36 +1,115 @@
 //! SSL support via BoringSSL.
 
+mod alps;
 mod boring;
 mod cache;
 mod cert_compressor;
+mod custom_ext;
+mod early_data;
 mod ext;
+mod fingerprint;
+mod hello;
+mod key_policy;
+mod ocsp;
+mod plaintext;
+mod progress;
+mod timing;
 
 use std::{
     fmt,
     io::IoSlice,
     pin::Pin,
-    sync::LazyLock,
+    sync::{
+        Arc, LazyLock,
+        atomic::{AtomicU8, Ordering},
+    },
     task::{Context, Poll},
 };
 
-use boring2::{error::ErrorStack, ex_data::Index, ssl::Ssl};
+use boring2::{
+    error::ErrorStack,
+    ex_data::Index,
+    ssl::{Ssl, SslRef},
+};
 use bytes::Bytes;
 use cache::SessionKey;
+pub(crate) use early_data::{early_data_rejected, reset_early_data};
+use fingerprint::{Fingerprint, FingerprintRecorder};
+pub(crate) use key_policy::KeyPolicy;
+use timing::connect_timings;
 use tokio::io;
 use tokio_boring2::SslStream;
 
-pub use self::boring::{HttpsConnector, TlsConnector, TlsConnectorBuilder};
-use crate::core::{
-    client::connect::{Connected, Connection},
-    rt::{Read, ReadBufCursor, TokioIo, Write},
+pub(crate) use self::{
+    alps::negotiated as alps_negotiated, cert_compressor::used as cert_compression_used,
+};
+pub use self::{
+    boring::{HttpsConnector, SniCallback, TlsConnector, TlsConnectorBuilder},
+    hello::ClientHelloHook,
+    plaintext::Sniffing,
+    progress::{HandshakeProgressHook, HandshakeState},
+    timing::ConnectTimings,
+};
+use crate::{
+    core::{
+        client::connect::{Connected, Connection},
+        rt::{Read, ReadBufCursor, TokioIo, Write},
+    },
+    tls::x509::{ChainRecorder, VerifiedChain},
 };
 
-fn key_index() -> Result<Index<Ssl, SessionKey>, ErrorStack> {
-    static IDX: LazyLock<Result<Index<Ssl, SessionKey>, ErrorStack>> =
+/// The cache key of a connection's session, and the cache generation its
+/// handshake started in.
+fn key_index() -> Result<Index<Ssl, (SessionKey, u64)>, ErrorStack> {
+    static IDX: LazyLock<Result<Index<Ssl, (SessionKey, u64)>, ErrorStack>> =
+        LazyLock::new(Ssl::new_ex_index);
+    IDX.clone()
+}
+
+fn chain_index() -> Result<Index<Ssl, ChainRecorder>, ErrorStack> {
+    static IDX: LazyLock<Result<Index<Ssl, ChainRecorder>, ErrorStack>> =
+        LazyLock::new(Ssl::new_ex_index);
+    IDX.clone()
+}
+
+fn fingerprint_index() -> Result<Index<Ssl, FingerprintRecorder>, ErrorStack> {
+    static IDX: LazyLock<Result<Index<Ssl, FingerprintRecorder>, ErrorStack>> =
         LazyLock::new(Ssl::new_ex_index);
     IDX.clone()
 }
 
+fn alpn_index() -> Result<Index<Ssl, Bytes>, ErrorStack> {
+    static IDX: LazyLock<Result<Index<Ssl, Bytes>, ErrorStack>> = LazyLock::new(Ssl::new_ex_index);
+    IDX.clone()
+}
+
+/// Returns the ALPN protocols offered on `ssl`, in order of preference.
+pub(crate) fn alpn_offered(ssl: &SslRef) -> Vec<Vec<u8>> {
+    alpn_index()
+        .ok()
+        .and_then(|idx| ssl.ex_data(idx))
+        .map(|wire| {
+            crate::tls::decode_sequence(wire)
+                .map(<[u8]>::to_vec)
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Returns the chain recorded while verifying the peer of `ssl`, if
+/// recording was enabled for the connection.
+pub(crate) fn verified_chain(ssl: &SslRef) -> Option<VerifiedChain> {
+    let idx = chain_index().ok()?;
+    ssl.ex_data(idx)?.finish(ssl)
+}
+
+/// Returns the JA3 and JA4 fingerprints of the `ClientHello` sent on `ssl`,
+/// if computing them was enabled for the connection.
+pub(crate) fn client_hello_fingerprint(ssl: &SslRef) -> Option<Fingerprint> {
+    let idx = fingerprint_index().ok()?;
+    ssl.ex_data(idx)?.get().cloned()
+}
+
 /// Builds for [`HandshakeConfig`].
 pub struct HandshakeConfigBuilder {
     settings: HandshakeConfig,
@@ -46,8 +125,20 @@ pub struct HandshakeConfig {
     verify_hostname: bool,
     tls_sni: bool,
     alps_protos: Option<Bytes>,
+    alps_h2_settings: Option<Bytes>,
     alps_use_new_codepoint: bool,
     random_aes_hw_override: bool,
+    early_data: bool,
+    alpn_protos: Option<Bytes>,
+    require_alpn: bool,
+    verified_chain: bool,
+    client_hello_hook: Option<ClientHelloHook>,
+    compute_ja4: bool,
+    handshake_progress: Option<HandshakeProgressHook>,
+    collect_timings: bool,
+    key_policy: KeyPolicy,
+    cert_compression: bool,
+    ocsp_stapling: bool,
 }
 
 impl HandshakeConfigBuilder {
@@ -93,6 +184,12 @@ impl HandshakeConfigBuilder {
         self
     }
 
+    /// Sets the ALPS settings sent for `h2`.
+    pub fn alps_h2_settings(mut self, settings: Option<Bytes>) -> Self {
+        self.settings.alps_h2_settings = settings;
+        self
+    }
+
     /// Sets ALPS new codepoint usage.
     pub fn alps_use_new_codepoint(mut self, use_new: bool) -> Self {
         self.settings.alps_use_new_codepoint = use_new;
@@ -105,6 +202,74 @@ impl HandshakeConfigBuilder {
         self
     }
 
+    /// Enables or disables offering early data on resumed sessions.
+    pub fn early_data(mut self, enabled: bool) -> Self {
+        self.settings.early_data = enabled;
+        self
+    }
+
+    /// Sets the ALPN protocols offered, in wire format.
+    pub fn alpn_protos(mut self, protos: Option<Bytes>) -> Self {
+        self.settings.alpn_protos = protos;
+        self
+    }
+
+    /// Requires the server to select one of the offered ALPN protocols.
+    pub fn require_alpn(mut self, required: bool) -> Self {
+        self.settings.require_alpn = required;
+        self
+    }
+
+    /// Records the verified certificate chain of each connection.
+    pub fn verified_chain(mut self, enabled: bool) -> Self {
+        self.settings.verified_chain = enabled;
+        self
+    }
+
+    /// Sets the hook invoked with the `ClientHello` of each connection.
+    pub fn client_hello_hook(mut self, hook: Option<ClientHelloHook>) -> Self {
+        self.settings.client_hello_hook = hook;
+        self
+    }
+
+    /// Computes the JA3 and JA4 fingerprints of each connection.
+    pub fn compute_ja4(mut self, enabled: bool) -> Self {
+        self.settings.compute_ja4 = enabled;
+        self
+    }
+
+    /// Sets the hook invoked as the handshake of each connection progresses.
+    pub fn handshake_progress(mut self, hook: Option<HandshakeProgressHook>) -> Self {
+        self.settings.handshake_progress = hook;
+        self
+    }
+
+    /// Records how long the handshake of each connection took.
+    pub fn collect_timings(mut self, enabled: bool) -> Self {
+        self.settings.collect_timings = enabled;
+        self
+    }
+
+    /// Sets the minimum strength of the server's certificate key.
+    pub fn key_policy(mut self, policy: KeyPolicy) -> Self {
+        self.settings.key_policy = policy;
+        self
+    }
+
+    /// Records which algorithm the server compressed its certificate with,
+    /// for connections that advertise certificate compression.
+    pub fn cert_compression(mut self, enabled: bool) -> Self {
+        self.settings.cert_compression = enabled;
+        self
+    }
+
+    /// Fails connections whose stapled OCSP response revokes the server's
+    /// certificate.
+    pub fn ocsp_stapling(mut self, enabled: bool) -> Self {
+        self.settings.ocsp_stapling = enabled;
+        self
+    }
+
     /// Builds the `HandshakeConfig`.
     pub fn build(self) -> HandshakeConfig {
         self.settings
@@ -130,8 +295,61 @@ impl Default for HandshakeConfig {
             verify_hostname: true,
             tls_sni: true,
             alps_protos: None,
+            alps_h2_settings: None,
             alps_use_new_codepoint: false,
             random_aes_hw_override: false,
+            early_data: false,
+            alpn_protos: None,
+            require_alpn: false,
+            verified_chain: false,
+            client_hello_hook: None,
+            compute_ja4: false,
+            handshake_progress: None,
+            collect_timings: false,
+            key_policy: KeyPolicy::default(),
+            cert_compression: false,
+            ocsp_stapling: false,
+        }
+    }
+}
+
+/// Outcome of the early data (0-RTT) offered on a connection.
+///
+/// The handshake is still in flight when the connection is handed to the pool,
+/// so the outcome is shared with any [`TlsInfo`](crate::tls::TlsInfo) created
+/// before the server has answered.
+#[derive(Debug, Clone)]
+pub(crate) struct EarlyDataStatus(Arc<AtomicU8>);
+
+impl EarlyDataStatus {
+    const PENDING: u8 = 0;
+    const ACCEPTED: u8 = 1;
+    const REJECTED: u8 = 2;
+
+    pub(crate) fn pending() -> EarlyDataStatus {
+        EarlyDataStatus(Arc::new(AtomicU8::new(Self::PENDING)))
+    }
+
+    #[inline]
+    pub(crate) fn is_pending(&self) -> bool {
+        self.0.load(Ordering::Acquire) == Self::PENDING
+    }
+
+    #[inline]
+    pub(crate) fn set(&self, accepted: bool) {
+        let state = if accepted {
+            Self::ACCEPTED
+        } else {
+            Self::REJECTED
+        };
+        self.0.store(state, Ordering::Release);
+    }
+
+    pub(crate) fn accepted(&self) -> Option<bool> {
+        match self.0.load(Ordering::Acquire) {
+            Self::ACCEPTED => Some(true),
+            Self::REJECTED => Some(false),
+            _ => None,
         }
     }
 }
@@ -141,7 +359,7 @@ pub enum MaybeHttpsStream<T> {
     /// A raw HTTP stream.
     Http(T),
     /// An SSL-wrapped HTTP stream.
-    Https(TokioIo<SslStream<TokioIo<T>>>),
+    Https(TokioIo<SslStream<Sniffing<TokioIo<T>>>>),
 }
 
 impl<T> fmt::Debug for MaybeHttpsStream<T> {
@@ -167,6 +385,10 @@ where
                     connected = connected.negotiated_h2();
                 }
 
+                if let Some(timings) = connect_timings(s.inner().ssl()) {
+                    connected = connected.extra(timings);
+                }
+
                 connected
             }
         }
@@ -0,0 +1,206 @@
+//! The status of the server certificate in a stapled OCSP response.
+//!
+//! BoringSSL requests and hands out the staple, but doesn't parse it. Only as
+//! much of the response is read as it takes to find the status of the leaf
+//! certificate, and its signature is not verified: a staple can fail a
+//! connection, never vouch for a certificate that didn't verify.
+
+use boring2::ssl::SslRef;
+
+// The universal DER tags read in a response.
+const INTEGER: u8 = 0x02;
+const OCTET_STRING: u8 = 0x04;
+const ENUMERATED: u8 = 0x0a;
+const SEQUENCE: u8 = 0x30;
+
+/// The tag of `responseBytes` in an `OCSPResponse`.
+const RESPONSE_BYTES: u8 = 0xa0;
+
+/// The tag of the `revoked` choice of a `CertStatus`.
+const REVOKED: u8 = 0xa1;
+
+/// The `responseStatus` of a response that carries statuses.
+const SUCCESSFUL: u8 = 0;
+
+/// Returns whether the OCSP response stapled on the established connection
+/// `ssl`, if any, says that the leaf certificate was revoked.
+///
+/// A response that can't be parsed, or doesn't cover the leaf, says nothing.
+pub(super) fn is_revoked(ssl: &SslRef) -> bool {
+    let Some(response) = ssl.ocsp_status() else {
+        return false;
+    };
+    let Some(serial) = ssl
+        .peer_certificate()
+        .and_then(|cert| cert.serial_number().to_bn().ok())
+        .map(|serial| serial.to_vec())
+    else {
+        return false;
+    };
+    revoked(response, &serial).unwrap_or(false)
+}
+
+/// Reads the status of the certificate with serial number `serial` in the
+/// DER `OCSPResponse` `response`, as defined in RFC 6960.
+fn revoked(response: &[u8], serial: &[u8]) -> Option<bool> {
+    let mut response = Der(Der(response).expect(SEQUENCE)?);
+    if response.expect(ENUMERATED)? != [SUCCESSFUL] {
+        return None;
+    }
+    let mut bytes = Der(Der(response.expect(RESPONSE_BYTES)?).expect(SEQUENCE)?);
+    // The response type, which is only ever `id-pkix-ocsp-basic`.
+    bytes.read()?;
+    let basic = Der(bytes.expect(OCTET_STRING)?).expect(SEQUENCE)?;
+    let mut tbs = Der(Der(basic).expect(SEQUENCE)?);
+
+    // The version, the responder and the production time come first, and
+    // none of them is a sequence.
+    let mut responses = loop {
+        let (tag, value) = tbs.read()?;
+        if tag == SEQUENCE {
+            break Der(value);
+        }
+    };
+
+    while !responses.0.is_empty() {
+        let mut single = Der(responses.expect(SEQUENCE)?);
+        let mut cert_id = Der(single.expect(SEQUENCE)?);
+        // The hash algorithm, and the hashes of the issuer's name and key.
+        for _ in 0..3 {
+            cert_id.read()?;
+        }
+        if trim(cert_id.expect(INTEGER)?) == trim(serial) {
+            let (status, _) = single.read()?;
+            return Some(status == REVOKED);
+        }
+    }
+    None
+}
+
+/// Strips the leading zeros of a big-endian integer.
+fn trim(int: &[u8]) -> &[u8] {
+    let start = int.iter().position(|&b| b != 0).unwrap_or(int.len());
+    &int[start..]
+}
+
+/// The DER encoded values left to read.
+struct Der<'a>(&'a [u8]);
+
+impl<'a> Der<'a> {
+    /// Reads the tag and the contents of the next value.
+    fn read(&mut self) -> Option<(u8, &'a [u8])> {
+        let (&tag, rest) = self.0.split_first()?;
+        let (&first, rest) = rest.split_first()?;
+        let (len, rest) = if first < 0x80 {
+            (usize::from(first), rest)
+        } else {
+            let n = usize::from(first & 0x7f);
+            if n == 0 || n > 4 || rest.len() < n {
+                return None;
+            }
+            let (len, rest) = rest.split_at(n);
+            let len = len.iter().fold(0, |len, &b| (len << 8) | usize::from(b));
+            (len, rest)
+        };
+        if rest.len() < len {
+            return None;
+        }
+        let (value, rest) = rest.split_at(len);
+        self.0 = rest;
+        Some((tag, value))
+    }
+
+    /// Reads the contents of the next value, if it has tag `tag`.
+    fn expect(&mut self, tag: u8) -> Option<&'a [u8]> {
+        match self.read()? {
+            (found, value) if found == tag => Some(value),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tlv(tag: u8, value: &[u8]) -> Vec<u8> {
+        let mut der = vec![tag];
+        if value.len() < 0x80 {
+            der.push(value.len() as u8);
+        } else {
+            der.extend([0x82, (value.len() >> 8) as u8, value.len() as u8]);
+        }
+        der.extend_from_slice(value);
+        der
+    }
+
+    fn single(serial: &[u8], status: &[u8]) -> Vec<u8> {
+        let cert_id = [
+            tlv(SEQUENCE, &tlv(0x06, &[0x2b, 0x0e, 0x03, 0x02, 0x1a])),
+            tlv(OCTET_STRING, &[0xaa; 20]),
+            tlv(OCTET_STRING, &[0xbb; 20]),
+            tlv(INTEGER, serial),
+        ]
+        .concat();
+        let this_update = tlv(0x18, b"20260101000000Z");
+        tlv(
+            SEQUENCE,
+            &[tlv(SEQUENCE, &cert_id), status.to_vec(), this_update].concat(),
+        )
+    }
+
+    fn response(status: u8, singles: &[Vec<u8>]) -> Vec<u8> {
+        let tbs = [
+            tlv(0xa2, &tlv(OCTET_STRING, &[0xcc; 20])),
+            tlv(0x18, b"20260101000000Z"),
+            tlv(SEQUENCE, &singles.concat()),
+        ]
+        .concat();
+        let basic = [
+            tlv(SEQUENCE, &tbs),
+            tlv(SEQUENCE, &tlv(0x06, &[0x2a, 0x86, 0x48])),
+            tlv(0x03, &[0; 65]),
+        ]
+        .concat();
+        let bytes = [
+            tlv(
+                0x06,
+                &[0x2b, 0x06, 0x01, 0x05, 0x05, 0x07, 0x30, 0x01, 0x01],
+            ),
+            tlv(OCTET_STRING, &tlv(SEQUENCE, &basic)),
+        ]
+        .concat();
+        tlv(
+            SEQUENCE,
+            &[
+                tlv(ENUMERATED, &[status]),
+                tlv(RESPONSE_BYTES, &tlv(SEQUENCE, &bytes)),
+            ]
+            .concat(),
+        )
+    }
+
+    #[test]
+    fn reads_status_of_leaf() {
+        let good = single(&[0x00, 0x80, 0x01], &[0x80, 0x00]);
+        let revoked_at = tlv(0x18, b"20260102000000Z");
+        let revoked = single(&[0x42], &tlv(REVOKED, &revoked_at));
+
+        let der = response(SUCCESSFUL, &[good, revoked]);
+        assert_eq!(super::revoked(&der, &[0x80, 0x01]), Some(false));
+        assert_eq!(super::revoked(&der, &[0x42]), Some(true));
+        assert_eq!(super::revoked(&der, &[0x43]), None);
+    }
+
+    #[test]
+    fn ignores_unsuccessful_and_truncated_responses() {
+        let revoked = single(&[0x42], &tlv(REVOKED, &tlv(0x18, b"20260102000000Z")));
+
+        // `tryLater`, without statuses.
+        let der = tlv(SEQUENCE, &tlv(ENUMERATED, &[3]));
+        assert_eq!(super::revoked(&der, &[0x42]), None);
+
+        let der = response(SUCCESSFUL, &[revoked]);
+        assert_eq!(super::revoked(&der[..der.len() - 10], &[0x42]), None);
+    }
+}
@@ -0,0 +1,165 @@
+//! Recognizing servers that answer a TLS handshake with plaintext.
+
+use std::{
+    error::Error as _,
+    fmt::Debug,
+    io,
+    pin::Pin,
+    task::{Context, Poll, Waker},
+};
+
+use boring2::error::ErrorStack;
+use tokio::io::{AsyncRead, AsyncWrite, ReadBuf};
+use tokio_boring2::HandshakeError;
+
+use crate::{
+    core::client::connect::{Connected, Connection},
+    error::{BoxError, PlaintextBeforeTls},
+};
+
+/// How many bytes are sniffed from a server that sent plaintext.
+const SNIFF_LEN: usize = 64;
+
+/// A transport that keeps the first bytes read from it during the handshake.
+///
+/// BoringSSL consumes the record header before it rejects it, so these are
+/// the only way to report a plaintext banner from its start.
+#[derive(Debug)]
+pub struct Sniffing<S> {
+    inner: S,
+    sniffed: Option<Vec<u8>>,
+}
+
+impl<S> Sniffing<S> {
+    pub(super) fn new(inner: S) -> Self {
+        Sniffing {
+            inner,
+            sniffed: Some(Vec::new()),
+        }
+    }
+
+    /// Borrow the inner transport.
+    #[inline]
+    pub(crate) fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Stops keeping what is read, once the handshake is done.
+    pub(super) fn stop(&mut self) {
+        self.sniffed = None;
+    }
+}
+
+impl<S: AsyncRead + Unpin> AsyncRead for Sniffing<S> {
+    fn poll_read(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let filled = buf.filled().len();
+        let this = &mut *self;
+        let res = Pin::new(&mut this.inner).poll_read(cx, buf);
+        if let Some(ref mut sniffed) = this.sniffed {
+            let read = &buf.filled()[filled..];
+            let len = read.len().min(SNIFF_LEN - sniffed.len());
+            sniffed.extend_from_slice(&read[..len]);
+        }
+        res
+    }
+}
+
+impl<S: AsyncWrite + Unpin> AsyncWrite for Sniffing<S> {
+    #[inline]
+    fn poll_write(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write(cx, buf)
+    }
+
+    #[inline]
+    fn poll_write_vectored(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        bufs: &[io::IoSlice<'_>],
+    ) -> Poll<io::Result<usize>> {
+        Pin::new(&mut self.inner).poll_write_vectored(cx, bufs)
+    }
+
+    #[inline]
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
+    #[inline]
+    fn poll_flush(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_flush(cx)
+    }
+
+    #[inline]
+    fn poll_shutdown(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        Pin::new(&mut self.inner).poll_shutdown(cx)
+    }
+}
+
+impl<S: Connection> Connection for Sniffing<S> {
+    fn connected(&self) -> Connected {
+        self.inner.connected()
+    }
+}
+
+/// Turns a failed handshake into a [`PlaintextBeforeTls`] error if the first
+/// record from the server was not TLS, and returns other errors as is.
+///
+/// Only the bytes that have already arrived are sniffed, so this never
+/// waits on the server.
+pub(super) fn map_handshake_error<S>(err: HandshakeError<Sniffing<S>>) -> BoxError
+where
+    S: AsyncRead + Unpin + Debug + Send + Sync + 'static,
+{
+    if !is_plaintext(&err) {
+        return Box::new(err);
+    }
+
+    let sniffed = err.into_source_stream().map(sniff).unwrap_or_default();
+    Box::new(PlaintextBeforeTls { sniffed })
+}
+
+fn is_plaintext<S>(err: &HandshakeError<S>) -> bool
+where
+    S: Debug + 'static,
+{
+    // The server random stays zeroed until a ServerHello is read.
+    let before_server_hello = err.ssl().is_some_and(|ssl| {
+        let mut random = [0; 32];
+        ssl.server_random(&mut random);
+        random.iter().all(|&b| b == 0)
+    });
+    if !before_server_hello {
+        return false;
+    }
+
+    // BoringSSL rejects a record header without a TLS version like this.
+    let mut source = err.source();
+    while let Some(err) = source {
+        if let Some(stack) = err.downcast_ref::<ErrorStack>() {
+            return stack
+                .errors()
+                .iter()
+                .any(|err| err.reason() == Some("WRONG_VERSION_NUMBER"));
+        }
+        source = err.source();
+    }
+    false
+}
+
+/// Returns the first bytes the server sent, including those that have
+/// arrived since BoringSSL stopped reading.
+fn sniff<S: AsyncRead + Unpin>(mut stream: Sniffing<S>) -> Vec<u8> {
+    let mut buf = [0; SNIFF_LEN];
+    let mut read = ReadBuf::new(&mut buf);
+    let mut cx = Context::from_waker(Waker::noop());
+    let _ = Pin::new(&mut stream).poll_read(&mut cx, &mut read);
+    stream.sniffed.unwrap_or_default()
+}
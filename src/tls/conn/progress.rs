@@ -0,0 +1,99 @@
+//! Reporting the progress of a handshake.
+
+use std::{
+    os::raw::c_int,
+    sync::{Arc, LazyLock},
+};
+
+use boring_sys2 as ffi;
+use boring2::{
+    error::ErrorStack,
+    ex_data::Index,
+    ssl::{Ssl, SslRef},
+};
+use foreign_types::ForeignTypeRef;
+
+use super::hello;
+
+/// A callback invoked as the handshake of a connection progresses.
+pub type HandshakeProgressHook = Arc<dyn Fn(HandshakeState) + Send + Sync>;
+
+/// A step of a TLS handshake, as reported to
+/// [`ClientBuilder::on_handshake_progress`].
+///
+/// [`ClientBuilder::on_handshake_progress`]: crate::ClientBuilder::on_handshake_progress
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum HandshakeState {
+    /// The handshake started, and the `ClientHello` is about to be sent.
+    Started,
+    /// The `ServerHello` was received. It is reported again after a
+    /// `HelloRetryRequest`.
+    ServerHello,
+    /// The certificate of the server was received.
+    Certificate,
+    /// The handshake completed.
+    Finished,
+}
+
+/// `SSL_CB_HANDSHAKE_START`
+const HANDSHAKE_START: c_int = 0x10;
+
+/// `SSL_CB_HANDSHAKE_DONE`
+const HANDSHAKE_DONE: c_int = 0x20;
+
+/// `SSL3_MT_SERVER_HELLO`
+const SERVER_HELLO: u8 = 2;
+
+/// `SSL3_MT_CERTIFICATE`
+const CERTIFICATE: u8 = 11;
+
+/// `SSL3_MT_COMPRESSED_CERTIFICATE`
+const COMPRESSED_CERTIFICATE: u8 = 25;
+
+fn progress_index() -> Result<Index<Ssl, HandshakeProgressHook>, ErrorStack> {
+    static IDX: LazyLock<Result<Index<Ssl, HandshakeProgressHook>, ErrorStack>> =
+        LazyLock::new(Ssl::new_ex_index);
+    IDX.clone()
+}
+
+/// Calls `hook` as the handshake on `ssl` progresses.
+///
+/// The start and end of the handshake come from the info callback, the
+/// messages received in between from the message callback. Neither is
+/// installed on connections without a hook.
+pub(super) fn install(ssl: &mut SslRef, hook: HandshakeProgressHook) -> Result<(), ErrorStack> {
+    ssl.set_ex_data(progress_index()?, hook);
+    // SAFETY: `ssl` is a valid handle, and the callback only reads the hook
+    // stored in its ex data, which lives as long as `ssl` does.
+    unsafe { ffi::SSL_set_info_callback(ssl.as_ptr(), Some(on_info)) };
+    hello::observe_messages(ssl);
+    Ok(())
+}
+
+/// Reports the handshake message of type `msg_type` received on `ssl`.
+pub(super) fn on_received(ssl: &SslRef, msg_type: u8) {
+    match msg_type {
+        SERVER_HELLO => report(ssl, HandshakeState::ServerHello),
+        CERTIFICATE | COMPRESSED_CERTIFICATE => report(ssl, HandshakeState::Certificate),
+        _ => {}
+    }
+}
+
+fn report(ssl: &SslRef, state: HandshakeState) {
+    if let Some(hook) = progress_index().ok().and_then(|idx| ssl.ex_data(idx)) {
+        hook(state);
+    }
+}
+
+unsafe extern "C" fn on_info(ssl: *const ffi::SSL, kind: c_int, _value: c_int) {
+    let state = match kind {
+        HANDSHAKE_START => HandshakeState::Started,
+        HANDSHAKE_DONE => HandshakeState::Finished,
+        _ => return,
+    };
+
+    // SAFETY: BoringSSL passes a handle to the connection being handshaken.
+    let ssl = unsafe { SslRef::from_ptr(ssl.cast_mut()) };
+    report(ssl, state);
+}
@@ -0,0 +1,143 @@
+//! Timing the steps of a handshake.
+
+use std::{
+    sync::{Arc, LazyLock},
+    time::{Duration, Instant},
+};
+
+use antidote::Mutex;
+use boring2::{
+    error::ErrorStack,
+    ex_data::Index,
+    ssl::{Ssl, SslContextBuilder, SslRef},
+    x509::X509StoreContext,
+};
+
+/// How long the steps of establishing a connection took.
+///
+/// Made available to clients on responses when
+/// [`ClientBuilder::collect_timings`] is set, for connections secured with
+/// TLS.
+///
+/// [`ClientBuilder::collect_timings`]: crate::ClientBuilder::collect_timings
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectTimings {
+    tls_handshake: Duration,
+    cert_verify: Option<Duration>,
+}
+
+impl ConnectTimings {
+    /// Get how long the TLS handshake took, certificate verification
+    /// included.
+    pub fn tls_handshake_duration(&self) -> Duration {
+        self.tls_handshake
+    }
+
+    /// Get how long verifying the certificate chain of the server took.
+    ///
+    /// Returns `None` if no chain was verified, as when the session was
+    /// resumed.
+    pub fn cert_verify_duration(&self) -> Option<Duration> {
+        self.cert_verify
+    }
+}
+
+/// Collects the timings of one handshake.
+#[derive(Clone, Default)]
+pub(super) struct TimingRecorder(Arc<Mutex<Recorded>>);
+
+#[derive(Default)]
+struct Recorded {
+    tls_handshake: Option<Duration>,
+    cert_verify: Option<Duration>,
+}
+
+impl TimingRecorder {
+    /// Records how long the handshake took.
+    pub(super) fn handshake_done(&self, elapsed: Duration) {
+        self.0.lock().tls_handshake = Some(elapsed);
+    }
+
+    fn verified(&self, elapsed: Duration) {
+        let mut recorded = self.0.lock();
+        recorded.cert_verify = Some(recorded.cert_verify.unwrap_or_default() + elapsed);
+    }
+
+    fn finish(&self) -> Option<ConnectTimings> {
+        let recorded = self.0.lock();
+        Some(ConnectTimings {
+            tls_handshake: recorded.tls_handshake?,
+            cert_verify: recorded.cert_verify,
+        })
+    }
+}
+
+fn timing_index() -> Result<Index<Ssl, TimingRecorder>, ErrorStack> {
+    static IDX: LazyLock<Result<Index<Ssl, TimingRecorder>, ErrorStack>> =
+        LazyLock::new(Ssl::new_ex_index);
+    IDX.clone()
+}
+
+/// Times the verification of the peer of every connection made from `ctx`
+/// that carries a [`TimingRecorder`].
+///
+/// The callback runs BoringSSL's own verification and returns its outcome,
+/// so the verify mode and any per-connection verify callback still apply.
+pub(super) fn time_verification(ctx: &mut SslContextBuilder) {
+    ctx.set_cert_verify_callback(|store| {
+        let start = Instant::now();
+        let verified = store.verify_cert().unwrap_or(false);
+
+        let recorder = X509StoreContext::ssl_idx()
+            .ok()
+            .and_then(|idx| store.ex_data(idx))
+            .zip(timing_index().ok())
+            .and_then(|(ssl, idx)| ssl.ex_data(idx));
+        if let Some(recorder) = recorder {
+            recorder.verified(start.elapsed());
+        }
+
+        verified
+    });
+}
+
+/// Attaches `recorder` to `ssl`, whose timings it then collects.
+pub(super) fn install(ssl: &mut SslRef, recorder: TimingRecorder) -> Result<(), ErrorStack> {
+    ssl.set_ex_data(timing_index()?, recorder);
+    Ok(())
+}
+
+/// Returns the timings recorded on `ssl`, once its handshake is done, if
+/// collecting them was enabled for the connection.
+pub(super) fn connect_timings(ssl: &SslRef) -> Option<ConnectTimings> {
+    let idx = timing_index().ok()?;
+    ssl.ex_data(idx)?.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn timings_wait_for_the_handshake() {
+        let recorder = TimingRecorder::default();
+        recorder.verified(Duration::from_millis(3));
+        assert_eq!(recorder.finish(), None);
+
+        recorder.verified(Duration::from_millis(2));
+        recorder.handshake_done(Duration::from_millis(20));
+        let timings = recorder.finish().unwrap();
+        assert_eq!(timings.tls_handshake_duration(), Duration::from_millis(20));
+        assert_eq!(
+            timings.cert_verify_duration(),
+            Some(Duration::from_millis(5))
+        );
+    }
+
+    #[test]
+    fn resumed_handshake_has_no_verification() {
+        let recorder = TimingRecorder::default();
+        recorder.handshake_done(Duration::from_millis(4));
+        assert_eq!(recorder.finish().unwrap().cert_verify_duration(), None);
+    }
+}
@@ -11,14 +11,25 @@ mod conn;
 mod keylog;
 mod x509;
 
-pub use boring2::ssl::ExtensionType;
+use std::time::SystemTime;
+
+pub use boring2::{nid::Nid, ssl::ExtensionType};
 use bytes::{Bytes, BytesMut};
 
-pub(crate) use self::conn::{HttpsConnector, MaybeHttpsStream, TlsConnector, TlsConnectorBuilder};
+pub(crate) use self::conn::{
+    ClientHelloHook, EarlyDataStatus, HttpsConnector, MaybeHttpsStream, SniCallback, Sniffing,
+    TlsConnector, TlsConnectorBuilder, alpn_offered, alps_negotiated, cert_compression_used,
+    client_hello_fingerprint, early_data_rejected, reset_early_data, verified_chain,
+};
+pub(crate) use self::x509::validity;
 pub use self::{
     config::TlsConfig,
+    conn::{ConnectTimings, HandshakeState},
     keylog::KeyLogPolicy,
-    x509::{CertStore, CertStoreBuilder, Certificate, CertificateInput, Identity},
+    x509::{
+        CertStore, CertStoreBuilder, Certificate, CertificateInput, ChainCertificate, ChainRole,
+        Identity, VerifiedChain,
+    },
 };
 
 /// A TLS protocol version.
@@ -137,13 +148,131 @@ impl CertificateCompressionAlgorithm {
 #[derive(Debug, Clone)]
 pub struct TlsInfo {
     pub(crate) peer_certificate: Option<Vec<u8>>,
+    pub(crate) peer_cert_chain: Option<Vec<Vec<u8>>>,
+    pub(crate) peer_validity: Option<(SystemTime, SystemTime)>,
+    pub(crate) early_data: Option<EarlyDataStatus>,
+    pub(crate) verified_chain: Option<VerifiedChain>,
+    pub(crate) session_reused: bool,
+    pub(crate) ja3: Option<String>,
+    pub(crate) ja4: Option<String>,
+    pub(crate) alpn_offered: Vec<Vec<u8>>,
+    pub(crate) alpn_selected: Option<Vec<u8>>,
+    pub(crate) alps_negotiated: bool,
+    pub(crate) cert_compression: Option<CertificateCompressionAlgorithm>,
+    pub(crate) ocsp_response: Option<Vec<u8>>,
 }
 
 impl TlsInfo {
     /// Get the DER encoded leaf certificate of the peer.
+    ///
+    /// This is the first certificate of [`TlsInfo::peer_cert_chain`].
     pub fn peer_certificate(&self) -> Option<&[u8]> {
         self.peer_certificate.as_ref().map(|der| &der[..])
     }
+
+    /// Get the DER encoded certificates the peer presented, starting with
+    /// the leaf, in the order it sent them.
+    ///
+    /// Unlike [`TlsInfo::verified_chain`], this is the chain as sent, whether
+    /// or not verification used all of it, and it is always available.
+    /// Returns `None` when the handshake exposed no chain.
+    pub fn peer_cert_chain(&self) -> Option<&[Vec<u8>]> {
+        self.peer_cert_chain.as_deref()
+    }
+
+    /// Get the `notBefore` and `notAfter` times of the leaf certificate of the
+    /// peer, between which it is valid.
+    pub fn peer_certificate_validity(&self) -> Option<(SystemTime, SystemTime)> {
+        self.peer_validity
+    }
+
+    /// Returns whether the server accepted the early data (0-RTT) sent on this
+    /// connection.
+    ///
+    /// Returns `None` if no early data was offered, or the server has not
+    /// answered yet. When the server rejected it, what was sent as early
+    /// data was sent again once the handshake completed.
+    pub fn early_data_accepted(&self) -> Option<bool> {
+        self.early_data.as_ref().and_then(EarlyDataStatus::accepted)
+    }
+
+    /// Returns whether the TLS session was resumed from an earlier
+    /// connection rather than negotiated with a full handshake.
+    ///
+    /// Sessions are only resumed when the client session cache is enabled.
+    pub fn session_reused(&self) -> bool {
+        self.session_reused
+    }
+
+    /// Get the certificate chain built while verifying the peer, from the
+    /// leaf up to the trust anchor.
+    pub fn verified_chain(&self) -> Option<&VerifiedChain> {
+        self.verified_chain.as_ref()
+    }
+
+    /// Get the JA4 fingerprint of the `ClientHello` sent on this connection,
+    /// e.g. `t13d1516h2_8daaf6152771_e5627efa2ab1`.
+    ///
+    /// Only computed when enabled with
+    /// [`ClientBuilder::compute_ja4`](crate::ClientBuilder::compute_ja4).
+    pub fn ja4(&self) -> Option<&str> {
+        self.ja4.as_deref()
+    }
+
+    /// Get the JA3 fingerprint of the `ClientHello` sent on this connection,
+    /// as the hex-encoded MD5 hash of the JA3 string.
+    ///
+    /// Only computed when enabled with
+    /// [`ClientBuilder::compute_ja4`](crate::ClientBuilder::compute_ja4).
+    pub fn ja3(&self) -> Option<&str> {
+        self.ja3.as_deref()
+    }
+
+    /// Get the ALPN protocols offered on this connection, in order of
+    /// preference, e.g. `[b"h2", b"http/1.1"]`.
+    ///
+    /// Empty if no protocols were offered.
+    pub fn alpn_offered(&self) -> &[Vec<u8>] {
+        &self.alpn_offered
+    }
+
+    /// Get the ALPN protocol the server selected, if any, e.g. `b"h2"`.
+    ///
+    /// This is the protocol the connection speaks, such as HTTP/2 or
+    /// HTTP/1.1, for metrics or logs. Comparing it with
+    /// [`TlsInfo::alpn_offered`] tells whether the server settled for a less
+    /// preferred protocol, or none at all.
+    pub fn alpn_selected(&self) -> Option<&[u8]> {
+        self.alpn_selected.as_deref()
+    }
+
+    /// Returns whether the server accepted the Application-Layer Protocol
+    /// Settings (ALPS) advertised on this connection.
+    ///
+    /// Always false when ALPS was not advertised, or TLS 1.3 was not
+    /// negotiated.
+    pub fn alps_negotiated(&self) -> bool {
+        self.alps_negotiated
+    }
+
+    /// Get the algorithm the server compressed its certificate with, if any.
+    ///
+    /// Always `None` when no algorithms were advertised with
+    /// [`TlsConfig::certificate_compression_algorithms`], or the session was
+    /// resumed without a certificate.
+    pub fn cert_compression(&self) -> Option<CertificateCompressionAlgorithm> {
+        self.cert_compression
+    }
+
+    /// Get the DER encoded OCSP response stapled by the server, if any.
+    ///
+    /// Only requested when enabled with
+    /// [`TlsConfig::enable_ocsp_stapling`] or
+    /// [`ClientBuilder::tls_ocsp_stapling`](crate::ClientBuilder::tls_ocsp_stapling).
+    /// Its signature is not verified.
+    pub fn ocsp_response(&self) -> Option<&[u8]> {
+        self.ocsp_response.as_deref()
+    }
 }
 
 fn encode_sequence<'a, T, I>(items: I) -> Bytes
@@ -158,6 +287,16 @@ where
     buf.freeze()
 }
 
+/// Splits a wire-encoded (length-prefixed) protocol list into its protocols.
+pub(crate) fn decode_sequence(mut wire: &[u8]) -> impl Iterator<Item = &[u8]> {
+    std::iter::from_fn(move || {
+        let (&len, rest) = wire.split_first()?;
+        let (item, rest) = rest.split_at(usize::from(len).min(rest.len()));
+        wire = rest;
+        Some(item)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -184,6 +323,16 @@ mod tests {
         assert_eq!(alpn, Bytes::from_static(b"\x08http/1.1\x02h2\x02h3"));
     }
 
+    #[test]
+    fn alpn_protocol_decode() {
+        let alpn = AlpnProtocol::encode_sequence(&[AlpnProtocol::HTTP1, AlpnProtocol::HTTP2]);
+        let protos = decode_sequence(&alpn).collect::<Vec<_>>();
+        assert_eq!(protos, [&b"http/1.1"[..], &b"h2"[..]]);
+
+        assert_eq!(decode_sequence(b"").count(), 0);
+        assert_eq!(decode_sequence(b"\x05h2").collect::<Vec<_>>(), [&b"h2"[..]]);
+    }
+
     #[test]
     fn alpn_protocol_encode_single() {
         let alpn = AlpnProtocol::HTTP1.encode();
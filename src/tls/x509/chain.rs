@@ -0,0 +1,221 @@
+use std::sync::{Arc, OnceLock};
+
+use antidote::Mutex;
+use boring2::{
+    ssl::SslRef,
+    x509::{X509, X509NameRef, X509StoreContextRef},
+};
+
+/// The position of a certificate within a [`VerifiedChain`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChainRole {
+    /// The end-entity certificate presented by the server.
+    Leaf,
+    /// A certificate between the leaf and the trust anchor.
+    Intermediate,
+    /// The certificate the chain was verified against.
+    TrustAnchor,
+}
+
+/// A single certificate of a [`VerifiedChain`].
+#[derive(Debug, Clone)]
+pub struct ChainCertificate {
+    der: Vec<u8>,
+    role: ChainRole,
+    sent_by_peer: bool,
+    names: OnceLock<Option<(String, String)>>,
+}
+
+impl ChainCertificate {
+    /// Get the DER encoding of the certificate.
+    pub fn der(&self) -> &[u8] {
+        &self.der
+    }
+
+    /// Get the position of the certificate within the chain.
+    pub fn role(&self) -> ChainRole {
+        self.role
+    }
+
+    /// Returns whether the certificate was part of the chain sent by the server.
+    ///
+    /// Certificates that were not sent were found in the trust store.
+    pub fn is_sent_by_peer(&self) -> bool {
+        self.sent_by_peer
+    }
+
+    /// Get the subject name of the certificate, e.g. `CN=example.com, O=Example`.
+    ///
+    /// The certificate is parsed on first access.
+    pub fn subject(&self) -> Option<&str> {
+        self.names().map(|(subject, _)| subject.as_str())
+    }
+
+    /// Get the issuer name of the certificate.
+    ///
+    /// The certificate is parsed on first access.
+    pub fn issuer(&self) -> Option<&str> {
+        self.names().map(|(_, issuer)| issuer.as_str())
+    }
+
+    fn names(&self) -> Option<&(String, String)> {
+        self.names
+            .get_or_init(|| {
+                let cert = X509::from_der(&self.der).ok()?;
+                Some((
+                    format_name(cert.subject_name()),
+                    format_name(cert.issuer_name()),
+                ))
+            })
+            .as_ref()
+    }
+}
+
+/// The certificate chain built by BoringSSL while verifying the server,
+/// ordered from the leaf up to the trust anchor.
+///
+/// Unlike the chain presented by the server, this includes certificates taken
+/// from the trust store and leaves out any the server sent but verification
+/// did not use.
+#[derive(Debug, Clone)]
+pub struct VerifiedChain {
+    certs: Arc<[ChainCertificate]>,
+    verified: bool,
+}
+
+impl VerifiedChain {
+    /// Get the certificates of the chain, starting with the leaf.
+    pub fn certificates(&self) -> &[ChainCertificate] {
+        &self.certs
+    }
+
+    /// Returns whether the chain verified successfully.
+    ///
+    /// This is `false` when certificate verification is disabled and the
+    /// chain could not be verified. The last certificate is then not a
+    /// [`ChainRole::TrustAnchor`].
+    pub fn is_verified(&self) -> bool {
+        self.verified
+    }
+
+    fn new(chain: Vec<Vec<u8>>, verified: bool, sent: &[Vec<u8>]) -> VerifiedChain {
+        let last = chain.len().saturating_sub(1);
+        let certs = chain
+            .into_iter()
+            .enumerate()
+            .map(|(depth, der)| ChainCertificate {
+                role: match depth {
+                    0 => ChainRole::Leaf,
+                    depth if depth == last && verified => ChainRole::TrustAnchor,
+                    _ => ChainRole::Intermediate,
+                },
+                sent_by_peer: sent.contains(&der),
+                der,
+                names: OnceLock::new(),
+            })
+            .collect();
+
+        VerifiedChain { certs, verified }
+    }
+}
+
+struct RecordedChain {
+    certs: Vec<Vec<u8>>,
+    verified: bool,
+}
+
+/// Captures the chain from the verify callback of a single connection.
+#[derive(Clone, Default)]
+pub(crate) struct ChainRecorder(Arc<Mutex<Option<RecordedChain>>>);
+
+impl ChainRecorder {
+    /// Records the chain of `ctx`. Called by BoringSSL once per certificate.
+    pub(crate) fn record(&self, preverify_ok: bool, ctx: &X509StoreContextRef) {
+        let mut recorded = self.0.lock();
+        let recorded = recorded.get_or_insert_with(|| RecordedChain {
+            certs: ctx
+                .chain()
+                .into_iter()
+                .flatten()
+                .filter_map(|cert| cert.to_der().ok())
+                .collect(),
+            verified: true,
+        });
+        recorded.verified &= preverify_ok;
+    }
+
+    /// Builds the [`VerifiedChain`] once the handshake with `ssl` completed.
+    pub(crate) fn finish(&self, ssl: &SslRef) -> Option<VerifiedChain> {
+        let recorded = self.0.lock();
+        let recorded = recorded.as_ref()?;
+        let sent = ssl
+            .peer_cert_chain()
+            .into_iter()
+            .flatten()
+            .filter_map(|cert| cert.to_der().ok())
+            .collect::<Vec<_>>();
+
+        Some(VerifiedChain::new(
+            recorded.certs.clone(),
+            recorded.verified,
+            &sent,
+        ))
+    }
+}
+
+fn format_name(name: &X509NameRef) -> String {
+    name.entries()
+        .filter_map(|entry| {
+            let key = entry.object().nid().short_name().ok()?;
+            let value = entry.data().as_utf8().ok()?;
+            Some(format!("{key}={value}"))
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SERVER_CERT: &[u8] = include_bytes!("../../../tests/support/server.cert");
+
+    #[test]
+    fn chain_roles() {
+        let chain = vec![b"leaf".to_vec(), b"intermediate".to_vec(), b"root".to_vec()];
+        let sent = [b"leaf".to_vec(), b"intermediate".to_vec()];
+
+        let verified = VerifiedChain::new(chain.clone(), true, &sent);
+        let roles = verified
+            .certificates()
+            .iter()
+            .map(ChainCertificate::role)
+            .collect::<Vec<_>>();
+        assert_eq!(
+            roles,
+            [
+                ChainRole::Leaf,
+                ChainRole::Intermediate,
+                ChainRole::TrustAnchor
+            ]
+        );
+        assert!(verified.certificates()[1].is_sent_by_peer());
+        assert!(!verified.certificates()[2].is_sent_by_peer());
+
+        let unverified = VerifiedChain::new(chain, false, &sent);
+        assert!(!unverified.is_verified());
+        assert_eq!(unverified.certificates()[2].role(), ChainRole::Intermediate);
+    }
+
+    #[test]
+    fn names_are_parsed_lazily() {
+        let chain = VerifiedChain::new(vec![SERVER_CERT.to_vec()], false, &[]);
+        let leaf = &chain.certificates()[0];
+        assert_eq!(leaf.role(), ChainRole::Leaf);
+        assert_eq!(leaf.subject(), Some("CN=testserver.com"));
+        assert_eq!(leaf.issuer(), Some("CN=hyperium level 2 intermediate"));
+
+        let garbage = VerifiedChain::new(vec![b"not a certificate".to_vec()], true, &[]);
+        assert_eq!(garbage.certificates()[0].subject(), None);
+    }
+}
@@ -1,17 +1,38 @@
+use std::hash::{DefaultHasher, Hash, Hasher};
+#[cfg(all(feature = "system-keystore", any(target_os = "macos", windows)))]
+use std::sync::Arc;
+
 use boring2::{
+    error::ErrorStack,
     pkcs12::Pkcs12,
     pkey::{PKey, Private},
+    ssl::SslRef,
     x509::X509,
 };
 
+#[cfg(all(feature = "system-keystore", any(target_os = "macos", windows)))]
+use super::system::{self, SystemKey};
 use crate::Error;
 
 /// Represents a private key and X509 cert as a client certificate.
+///
+/// Two identities compare equal when they present the same certificate chain.
 #[derive(Debug, Clone)]
 pub struct Identity {
-    pkey: PKey<Private>,
+    key: Key,
     cert: X509,
     chain: Vec<X509>,
+    fingerprint: u64,
+}
+
+/// The private key of an identity.
+#[derive(Debug, Clone)]
+enum Key {
+    /// A key held in memory.
+    Local(PKey<Private>),
+    /// A key held by the OS keystore, which signs on behalf of BoringSSL.
+    #[cfg(all(feature = "system-keystore", any(target_os = "macos", windows)))]
+    System(Arc<dyn SystemKey>),
 }
 
 impl Identity {
@@ -48,14 +69,14 @@ impl Identity {
     pub fn from_pkcs12_der(buf: &[u8], pass: &str) -> crate::Result<Identity> {
         let pkcs12 = Pkcs12::from_der(buf)?;
         let parsed = pkcs12.parse(pass)?;
-        Ok(Identity {
-            pkey: parsed.pkey,
-            cert: parsed.cert,
+        Identity::new(
+            parsed.pkey,
+            parsed.cert,
             // > The stack is the reverse of what you might expect due to the way
             // > PKCS12_parse is implemented, so we need to load it backwards.
             // > https://github.com/sfackler/rust-native-tls/commit/05fb5e583be589ab63d9f83d986d095639f8ec44
-            chain: parsed.chain.into_iter().flatten().rev().collect(),
-        })
+            parsed.chain.into_iter().flatten().rev().collect(),
+        )
     }
 
     /// Parses a chain of PEM encoded X509 certificates, with the leaf certificate first.
@@ -93,7 +114,82 @@ impl Identity {
             Error::builder("at least one certificate must be provided to create an identity")
         })?;
         let chain = cert_chain.collect();
-        Ok(Identity { pkey, cert, chain })
+        Identity::new(pkey, cert, chain)
+    }
+
+    /// Finds the identity labeled `label` in the OS keystore.
+    ///
+    /// On macOS, this is the identity with that label in the keychains of the
+    /// user. On Windows, this is the certificate with that friendly name in
+    /// the `MY` store of the user, along with its private key.
+    ///
+    /// The private key never leaves the keystore, which signs the handshake
+    /// on behalf of BoringSSL, so keys that cannot be exported work too. Only
+    /// the leaf certificate is sent: the server must be able to build the
+    /// rest of the chain on its own.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # fn system() -> Result<(), Box<dyn std::error::Error>> {
+    /// let identity = wreq::Identity::from_system("my-client-cert")?;
+    /// # drop(identity);
+    /// # Ok(())
+    /// # }
+    /// ```
+    ///
+    /// # Optional
+    ///
+    /// This requires the `system-keystore` Cargo feature enabled, on macOS
+    /// or Windows.
+    #[cfg(all(feature = "system-keystore", any(target_os = "macos", windows)))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(feature = "system-keystore", any(target_os = "macos", windows))))
+    )]
+    pub fn from_system(label: &str) -> crate::Result<Identity> {
+        let found = system::find_identity(label)?;
+        Identity::with_key(Key::System(found.key), found.cert, found.chain)
+    }
+
+    fn new(pkey: PKey<Private>, cert: X509, chain: Vec<X509>) -> crate::Result<Identity> {
+        Identity::with_key(Key::Local(pkey), cert, chain)
+    }
+
+    fn with_key(key: Key, cert: X509, chain: Vec<X509>) -> crate::Result<Identity> {
+        let mut hasher = DefaultHasher::new();
+        for cert in std::iter::once(&cert).chain(&chain) {
+            cert.to_der()?.hash(&mut hasher);
+        }
+
+        Ok(Identity {
+            key,
+            cert,
+            chain,
+            fingerprint: hasher.finish(),
+        })
+    }
+
+    /// A hash of the certificate chain, used to tell identities apart in
+    /// connection pool and session cache keys.
+    #[inline]
+    pub(crate) fn fingerprint(&self) -> u64 {
+        self.fingerprint
+    }
+
+    /// Presents this identity on a single connection, overriding the one
+    /// configured on the connector.
+    pub(crate) fn add_to_ssl(&self, ssl: &mut SslRef) -> Result<(), ErrorStack> {
+        ssl.set_certificate(&self.cert)?;
+        match &self.key {
+            Key::Local(pkey) => ssl.set_private_key(pkey)?,
+            #[cfg(all(feature = "system-keystore", any(target_os = "macos", windows)))]
+            Key::System(key) => system::install(ssl, key.clone())?,
+        }
+        for cert in &self.chain {
+            ssl.add_chain_cert(cert)?;
+        }
+        Ok(())
     }
 
     pub(crate) fn add_to_tls(
@@ -101,7 +197,11 @@ impl Identity {
         connector: &mut boring2::ssl::SslConnectorBuilder,
     ) -> crate::Result<()> {
         connector.set_certificate(&self.cert)?;
-        connector.set_private_key(&self.pkey)?;
+        match self.key {
+            Key::Local(pkey) => connector.set_private_key(&pkey)?,
+            #[cfg(all(feature = "system-keystore", any(target_os = "macos", windows)))]
+            Key::System(key) => system::install_on_context(connector, key)?,
+        }
         for cert in self.chain.into_iter() {
             // https://www.openssl.org/docs/manmaster/man3/SSL_CTX_add_extra_chain_cert.html
             // specifies that "When sending a certificate chain, extra chain certificates are
@@ -112,6 +212,20 @@ impl Identity {
     }
 }
 
+impl PartialEq for Identity {
+    fn eq(&self, other: &Identity) -> bool {
+        self.fingerprint == other.fingerprint
+    }
+}
+
+impl Eq for Identity {}
+
+impl Hash for Identity {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.fingerprint.hash(state);
+    }
+}
+
 #[cfg(test)]
 mod test {
     use super::Identity;
@@ -125,4 +239,26 @@ mod test {
     fn identity_from_pkcs8_pem_invalid() {
         Identity::from_pkcs8_pem(b"not pem", b"not key").unwrap_err();
     }
+
+    #[test]
+    fn identity_equality_follows_chain() {
+        use boring2::{pkey::PKey, x509::X509};
+
+        let cert = X509::from_der(include_bytes!("../../../tests/support/server.cert"))
+            .unwrap()
+            .to_pem()
+            .unwrap();
+        let key = PKey::private_key_from_der(include_bytes!("../../../tests/support/server.key"))
+            .unwrap()
+            .private_key_to_pem_pkcs8()
+            .unwrap();
+
+        let a = Identity::from_pkcs8_pem(&cert, &key).unwrap();
+        let b = Identity::from_pkcs8_pem(&cert, &key).unwrap();
+        assert_eq!(a, b);
+        assert_eq!(a.fingerprint(), b.fingerprint());
+
+        let chained = Identity::from_pkcs8_pem(&[&cert[..], &cert[..]].concat(), &key).unwrap();
+        assert_ne!(a, chained);
+    }
 }
@@ -1,11 +1,17 @@
 #![allow(missing_debug_implementations)]
 
+mod chain;
 mod identity;
 mod store;
+#[cfg(all(feature = "system-keystore", any(target_os = "macos", windows)))]
+mod system;
+mod validity;
 
 use boring2::x509::X509;
 
+pub(crate) use self::{chain::ChainRecorder, validity::validity};
 pub use self::{
+    chain::{ChainCertificate, ChainRole, VerifiedChain},
     identity::Identity,
     store::{CertStore, CertStoreBuilder},
 };
@@ -247,6 +247,27 @@ impl CertStore {
             .map_err(Error::builder)
             .and_then(Self::from_pem_stack)
     }
+
+    /// Creates a new `CertStore` from the trust anchors of the OS keystore.
+    ///
+    /// On macOS, these are the certificates the keychains trust as roots for
+    /// TLS, with the user's trust settings taking precedence over the admin's
+    /// and the system's. On Windows, these are the certificates of the `ROOT`
+    /// store.
+    ///
+    /// # Optional
+    ///
+    /// This requires the `system-keystore` Cargo feature enabled, on macOS
+    /// or Windows.
+    #[cfg(all(feature = "system-keystore", any(target_os = "macos", windows)))]
+    #[cfg_attr(
+        docsrs,
+        doc(cfg(all(feature = "system-keystore", any(target_os = "macos", windows))))
+    )]
+    pub fn system() -> crate::Result<CertStore> {
+        let anchors = super::system::trust_anchors()?;
+        CertStore::from_der_certs(anchors.into_iter().map(Certificate))
+    }
 }
 
 impl CertStore {
@@ -0,0 +1,107 @@
+//! The keychains, through the Security framework.
+
+use std::{collections::HashMap, sync::Arc};
+
+use boring2::{ssl::SslSignatureAlgorithm, x509::X509};
+use security_framework::{
+    item::{ItemClass, ItemSearchOptions, Reference, SearchResult},
+    key::{Algorithm, SecKey},
+    trust_settings::{Domain, TrustSettings, TrustSettingsForCertificate},
+};
+
+use super::{Found, SystemKey};
+use crate::{Error, error::BoxError};
+
+/// A private key in a keychain.
+#[derive(Debug)]
+struct KeychainKey(SecKey);
+
+impl SystemKey for KeychainKey {
+    fn sign(&self, algorithm: SslSignatureAlgorithm, input: &[u8]) -> Result<Vec<u8>, BoxError> {
+        let algorithm = sec_algorithm(algorithm)
+            .ok_or_else(|| format!("unsupported signature algorithm {algorithm:?}"))?;
+        self.0
+            .create_signature(algorithm, input)
+            .map_err(Into::into)
+    }
+}
+
+/// The Security algorithm that hashes a message and signs it like
+/// `algorithm`.
+fn sec_algorithm(algorithm: SslSignatureAlgorithm) -> Option<Algorithm> {
+    Some(match algorithm {
+        SslSignatureAlgorithm::RSA_PKCS1_SHA256 => Algorithm::RSASignatureMessagePKCS1v15SHA256,
+        SslSignatureAlgorithm::RSA_PKCS1_SHA384 => Algorithm::RSASignatureMessagePKCS1v15SHA384,
+        SslSignatureAlgorithm::RSA_PKCS1_SHA512 => Algorithm::RSASignatureMessagePKCS1v15SHA512,
+        SslSignatureAlgorithm::RSA_PSS_RSAE_SHA256 => Algorithm::RSASignatureMessagePSSSHA256,
+        SslSignatureAlgorithm::RSA_PSS_RSAE_SHA384 => Algorithm::RSASignatureMessagePSSSHA384,
+        SslSignatureAlgorithm::RSA_PSS_RSAE_SHA512 => Algorithm::RSASignatureMessagePSSSHA512,
+        SslSignatureAlgorithm::ECDSA_SECP256R1_SHA256 => Algorithm::ECDSASignatureMessageX962SHA256,
+        SslSignatureAlgorithm::ECDSA_SECP384R1_SHA384 => Algorithm::ECDSASignatureMessageX962SHA384,
+        SslSignatureAlgorithm::ECDSA_SECP521R1_SHA512 => Algorithm::ECDSASignatureMessageX962SHA512,
+        _ => return None,
+    })
+}
+
+/// Finds the identity labeled `label` in the keychains of the user.
+pub(in crate::tls::x509) fn find_identity(label: &str) -> crate::Result<Found> {
+    let results = ItemSearchOptions::new()
+        .class(ItemClass::identity())
+        .label(label)
+        .load_refs(true)
+        .search()
+        .map_err(Error::builder)?;
+
+    let identity = results
+        .into_iter()
+        .find_map(|result| match result {
+            SearchResult::Ref(Reference::Identity(identity)) => Some(identity),
+            _ => None,
+        })
+        .ok_or_else(|| Error::builder(format!("no identity labeled {label:?} in the keychain")))?;
+
+    let cert = identity.certificate().map_err(Error::builder)?;
+    let key = identity.private_key().map_err(Error::builder)?;
+    Ok(Found {
+        cert: X509::from_der(&cert.to_der())?,
+        chain: Vec::new(),
+        key: Arc::new(KeychainKey(key)),
+    })
+}
+
+/// Returns the certificates trusted as roots for TLS in the keychains.
+///
+/// The trust settings of the user take precedence over those of the admin,
+/// which take precedence over the system's.
+pub(in crate::tls::x509) fn trust_anchors() -> crate::Result<Vec<X509>> {
+    let mut settled = HashMap::new();
+    for domain in [Domain::User, Domain::Admin, Domain::System] {
+        let settings = TrustSettings::new(domain);
+        // A domain without trust settings has no certificates to list.
+        let Ok(certs) = settings.iter() else {
+            continue;
+        };
+
+        for cert in certs {
+            let trust = match settings.tls_trust_settings_for_certificate(&cert) {
+                Ok(trust) => trust.unwrap_or(TrustSettingsForCertificate::TrustRoot),
+                Err(err) => {
+                    debug!("skipping keychain certificate: {}", err);
+                    continue;
+                }
+            };
+            settled.entry(cert.to_der()).or_insert(trust);
+        }
+    }
+
+    let mut anchors = Vec::new();
+    for (der, trust) in settled {
+        if matches!(
+            trust,
+            TrustSettingsForCertificate::TrustRoot | TrustSettingsForCertificate::TrustAsRoot
+        ) {
+            anchors.push(X509::from_der(&der)?);
+        }
+    }
+    Ok(anchors)
+}
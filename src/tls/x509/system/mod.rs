@@ -0,0 +1,186 @@
+//! Identities and trust anchors kept in the keystore of the OS: the keychains
+//! on macOS, and the certificate stores of CryptoAPI on Windows.
+//!
+//! The private key of an identity may not be extractable, so BoringSSL never
+//! sees it: it asks the OS to sign through a private key method instead.
+
+#[cfg(target_os = "macos")]
+mod macos;
+#[cfg(windows)]
+mod windows;
+
+use std::{
+    fmt::Debug,
+    sync::{Arc, LazyLock},
+};
+
+use boring_sys2 as ffi;
+use boring2::{
+    error::ErrorStack,
+    ex_data::Index,
+    ssl::{Ssl, SslContext, SslContextBuilder, SslRef, SslSignatureAlgorithm},
+    x509::X509,
+};
+use foreign_types::ForeignTypeRef;
+
+#[cfg(target_os = "macos")]
+pub(super) use self::macos::{find_identity, trust_anchors};
+#[cfg(windows)]
+pub(super) use self::windows::{find_identity, trust_anchors};
+use crate::error::BoxError;
+
+/// A private key held by the OS, which signs with it without handing it out.
+pub(crate) trait SystemKey: Debug + Send + Sync + 'static {
+    /// Signs the message `input`, which is not hashed yet, with `algorithm`.
+    fn sign(&self, algorithm: SslSignatureAlgorithm, input: &[u8]) -> Result<Vec<u8>, BoxError>;
+}
+
+/// An identity found in the keystore: its certificate, the intermediates to
+/// send along, and its key.
+pub(super) struct Found {
+    pub(super) cert: X509,
+    pub(super) chain: Vec<X509>,
+    pub(super) key: Arc<dyn SystemKey>,
+}
+
+/// Signs with a [`SystemKey`] on behalf of BoringSSL.
+///
+/// BoringSSL calls the raw private key method below, which finds the key in
+/// the ex data of the connection, or else of its context. Only the raw API
+/// installs a method on a single connection, which identities overriding the
+/// connector's need, so contexts go through it too.
+struct KeyMethod(Arc<dyn SystemKey>);
+
+impl KeyMethod {
+    fn sign_into(
+        &self,
+        algorithm: SslSignatureAlgorithm,
+        input: &[u8],
+        output: &mut [u8],
+    ) -> Option<usize> {
+        match self.0.sign(algorithm, input) {
+            Ok(signature) if signature.len() <= output.len() => {
+                output[..signature.len()].copy_from_slice(&signature);
+                Some(signature.len())
+            }
+            Ok(signature) => {
+                debug!(
+                    "system key signature too long: {} > {}",
+                    signature.len(),
+                    output.len()
+                );
+                None
+            }
+            Err(err) => {
+                debug!("system key failed to sign with {:?}: {}", algorithm, err);
+                None
+            }
+        }
+    }
+}
+
+fn key_index() -> Result<Index<Ssl, KeyMethod>, ErrorStack> {
+    static IDX: LazyLock<Result<Index<Ssl, KeyMethod>, ErrorStack>> =
+        LazyLock::new(Ssl::new_ex_index);
+    IDX.clone()
+}
+
+fn context_key_index() -> Result<Index<SslContext, KeyMethod>, ErrorStack> {
+    static IDX: LazyLock<Result<Index<SslContext, KeyMethod>, ErrorStack>> =
+        LazyLock::new(SslContext::new_ex_index);
+    IDX.clone()
+}
+
+/// The private key method, which signs with the key stored in the ex data of
+/// the connection or of its context.
+static METHOD: ffi::SSL_PRIVATE_KEY_METHOD = ffi::SSL_PRIVATE_KEY_METHOD {
+    sign: Some(sign),
+    decrypt: Some(decrypt),
+    complete: Some(complete),
+};
+
+/// Signs with `key` on `ssl` alone, rather than on every connection of its
+/// context.
+pub(super) fn install(ssl: &mut SslRef, key: Arc<dyn SystemKey>) -> Result<(), ErrorStack> {
+    ssl.set_ex_data(key_index()?, KeyMethod(key));
+    // SAFETY: `ssl` is a valid handle, `METHOD` is static, and its callbacks
+    // only read the key stored in the ex data of `ssl`.
+    unsafe { ffi::SSL_set_private_key_method(ssl.as_ptr(), &METHOD) };
+    Ok(())
+}
+
+/// Signs with `key` on every connection of `ctx`.
+pub(super) fn install_on_context(
+    ctx: &mut SslContextBuilder,
+    key: Arc<dyn SystemKey>,
+) -> Result<(), ErrorStack> {
+    ctx.set_ex_data(context_key_index()?, KeyMethod(key));
+    // SAFETY: `ctx` is a valid handle, `METHOD` is static, and its callbacks
+    // only read the key stored in the ex data of `ctx`.
+    unsafe { ffi::SSL_CTX_set_private_key_method(ctx.as_ptr(), &METHOD) };
+    Ok(())
+}
+
+unsafe extern "C" fn sign(
+    ssl: *mut ffi::SSL,
+    out: *mut u8,
+    out_len: *mut usize,
+    max_out: usize,
+    signature_algorithm: u16,
+    input: *const u8,
+    input_len: usize,
+) -> ffi::ssl_private_key_result_t {
+    // SAFETY: BoringSSL passes the connection, the message to sign, and room
+    // for `max_out` bytes of signature.
+    let (ssl, input, output) = unsafe {
+        (
+            SslRef::from_ptr(ssl),
+            std::slice::from_raw_parts(input, input_len),
+            std::slice::from_raw_parts_mut(out, max_out),
+        )
+    };
+
+    let method = key_index()
+        .ok()
+        .and_then(|idx| ssl.ex_data(idx))
+        .or_else(|| {
+            context_key_index()
+                .ok()
+                .and_then(|idx| ssl.ssl_context().ex_data(idx))
+        });
+    let Some(method) = method else {
+        return ffi::ssl_private_key_result_t::ssl_private_key_failure;
+    };
+    match method.sign_into(
+        SslSignatureAlgorithm::from(signature_algorithm),
+        input,
+        output,
+    ) {
+        Some(len) => {
+            // SAFETY: `out_len` is valid for writes.
+            unsafe { *out_len = len };
+            ffi::ssl_private_key_result_t::ssl_private_key_success
+        }
+        None => ffi::ssl_private_key_result_t::ssl_private_key_failure,
+    }
+}
+
+unsafe extern "C" fn decrypt(
+    _ssl: *mut ffi::SSL,
+    _out: *mut u8,
+    _out_len: *mut usize,
+    _max_out: usize,
+    _input: *const u8,
+    _input_len: usize,
+) -> ffi::ssl_private_key_result_t {
+    ffi::ssl_private_key_result_t::ssl_private_key_failure
+}
+
+unsafe extern "C" fn complete(
+    _ssl: *mut ffi::SSL,
+    _out: *mut u8,
+    _out_len: *mut usize,
+    _max_out: usize,
+) -> ffi::ssl_private_key_result_t {
+    ffi::ssl_private_key_result_t::ssl_private_key_failure
+}
@@ -0,0 +1,298 @@
+//! The certificate stores of the user, through CryptoAPI, with private keys
+//! held by CNG.
+
+use std::{ffi::c_void, ptr, sync::Arc};
+
+use boring2::{
+    bn::BigNum,
+    ecdsa::EcdsaSig,
+    hash::{MessageDigest, hash},
+    ssl::SslSignatureAlgorithm,
+    x509::X509,
+};
+use windows_sys::Win32::{
+    Foundation::BOOL,
+    Security::Cryptography::{
+        BCRYPT_PKCS1_PADDING_INFO, BCRYPT_PSS_PADDING_INFO, CERT_CONTEXT,
+        CERT_FRIENDLY_NAME_PROP_ID, CRYPT_ACQUIRE_ONLY_NCRYPT_KEY_FLAG, CRYPT_ACQUIRE_SILENT_FLAG,
+        CertCloseStore, CertEnumCertificatesInStore, CertFreeCertificateContext,
+        CertGetCertificateContextProperty, CertOpenSystemStoreW, CryptAcquireCertificatePrivateKey,
+        HCERTSTORE, NCRYPT_KEY_HANDLE, NCRYPT_PAD_PKCS1_FLAG, NCRYPT_PAD_PSS_FLAG,
+        NCryptFreeObject, NCryptSignHash,
+    },
+};
+
+use super::{Found, SystemKey};
+use crate::{Error, error::BoxError};
+
+/// A private key held by CNG.
+#[derive(Debug)]
+struct NcryptKey {
+    handle: NCRYPT_KEY_HANDLE,
+    owned: bool,
+}
+
+impl SystemKey for NcryptKey {
+    fn sign(&self, algorithm: SslSignatureAlgorithm, input: &[u8]) -> Result<Vec<u8>, BoxError> {
+        let (md, name, padding) = scheme(algorithm)
+            .ok_or_else(|| format!("unsupported signature algorithm {algorithm:?}"))?;
+        let digest = hash(md, input)?;
+
+        let name = wide(name);
+        let pkcs1 = BCRYPT_PKCS1_PADDING_INFO {
+            pszAlgId: name.as_ptr(),
+        };
+        let pss = BCRYPT_PSS_PADDING_INFO {
+            pszAlgId: name.as_ptr(),
+            cbSalt: md.size() as u32,
+        };
+        let (info, flags) = match padding {
+            Padding::Pkcs1 => (&pkcs1 as *const _ as *const c_void, NCRYPT_PAD_PKCS1_FLAG),
+            Padding::Pss => (&pss as *const _ as *const c_void, NCRYPT_PAD_PSS_FLAG),
+            Padding::Ecdsa => (ptr::null(), 0),
+        };
+
+        let mut len = 0;
+        // SAFETY: the handle is a valid key, the padding info outlives the
+        // call, and a null output only asks for the length of the signature.
+        check(unsafe {
+            NCryptSignHash(
+                self.handle,
+                info,
+                digest.as_ptr(),
+                digest.len() as u32,
+                ptr::null_mut(),
+                0,
+                &mut len,
+                flags,
+            )
+        })?;
+        let mut signature = vec![0; len as usize];
+        // SAFETY: as above, with room for `len` bytes of signature.
+        check(unsafe {
+            NCryptSignHash(
+                self.handle,
+                info,
+                digest.as_ptr(),
+                digest.len() as u32,
+                signature.as_mut_ptr(),
+                len,
+                &mut len,
+                flags,
+            )
+        })?;
+        signature.truncate(len as usize);
+
+        // CNG signs with ECDSA as `r || s`, where TLS expects DER.
+        if let Padding::Ecdsa = padding {
+            let (r, s) = signature.split_at(signature.len() / 2);
+            let sig =
+                EcdsaSig::from_private_components(BigNum::from_slice(r)?, BigNum::from_slice(s)?)?;
+            return Ok(sig.to_der()?);
+        }
+        Ok(signature)
+    }
+}
+
+impl Drop for NcryptKey {
+    fn drop(&mut self) {
+        if self.owned {
+            // SAFETY: the handle is owned, and freed only here.
+            unsafe { NCryptFreeObject(self.handle) };
+        }
+    }
+}
+
+enum Padding {
+    Pkcs1,
+    Pss,
+    Ecdsa,
+}
+
+/// The digest, its CNG name, and the padding that CNG signs with for
+/// `algorithm`.
+fn scheme(algorithm: SslSignatureAlgorithm) -> Option<(MessageDigest, &'static str, Padding)> {
+    Some(match algorithm {
+        SslSignatureAlgorithm::RSA_PKCS1_SHA256 => {
+            (MessageDigest::sha256(), "SHA256", Padding::Pkcs1)
+        }
+        SslSignatureAlgorithm::RSA_PKCS1_SHA384 => {
+            (MessageDigest::sha384(), "SHA384", Padding::Pkcs1)
+        }
+        SslSignatureAlgorithm::RSA_PKCS1_SHA512 => {
+            (MessageDigest::sha512(), "SHA512", Padding::Pkcs1)
+        }
+        SslSignatureAlgorithm::RSA_PSS_RSAE_SHA256 => {
+            (MessageDigest::sha256(), "SHA256", Padding::Pss)
+        }
+        SslSignatureAlgorithm::RSA_PSS_RSAE_SHA384 => {
+            (MessageDigest::sha384(), "SHA384", Padding::Pss)
+        }
+        SslSignatureAlgorithm::RSA_PSS_RSAE_SHA512 => {
+            (MessageDigest::sha512(), "SHA512", Padding::Pss)
+        }
+        SslSignatureAlgorithm::ECDSA_SECP256R1_SHA256 => {
+            (MessageDigest::sha256(), "SHA256", Padding::Ecdsa)
+        }
+        SslSignatureAlgorithm::ECDSA_SECP384R1_SHA384 => {
+            (MessageDigest::sha384(), "SHA384", Padding::Ecdsa)
+        }
+        SslSignatureAlgorithm::ECDSA_SECP521R1_SHA512 => {
+            (MessageDigest::sha512(), "SHA512", Padding::Ecdsa)
+        }
+        _ => return None,
+    })
+}
+
+fn wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(Some(0)).collect()
+}
+
+fn check(status: i32) -> Result<(), BoxError> {
+    if status < 0 {
+        return Err(format!("NCryptSignHash failed: {status:#010x}").into());
+    }
+    Ok(())
+}
+
+/// A system certificate store of the user, closed on drop.
+struct Store(HCERTSTORE);
+
+impl Store {
+    fn open(name: &str) -> crate::Result<Store> {
+        let name = wide(name);
+        // SAFETY: `name` is a NUL terminated wide string.
+        let store = unsafe { CertOpenSystemStoreW(0, name.as_ptr()) };
+        if store.is_null() {
+            return Err(Error::builder(std::io::Error::last_os_error()));
+        }
+        Ok(Store(store))
+    }
+
+    /// Calls `f` with every certificate in the store, until it returns
+    /// `Some`.
+    fn find_map<T>(
+        &self,
+        mut f: impl FnMut(&CERT_CONTEXT) -> crate::Result<Option<T>>,
+    ) -> crate::Result<Option<T>> {
+        let mut ctx: *const CERT_CONTEXT = ptr::null();
+        loop {
+            // SAFETY: the store is open, and `ctx` is null or the previous
+            // context, which this frees.
+            ctx = unsafe { CertEnumCertificatesInStore(self.0, ctx) };
+            if ctx.is_null() {
+                return Ok(None);
+            }
+
+            // SAFETY: `ctx` is a valid context until the next call.
+            let found = f(unsafe { &*ctx });
+            if !matches!(found, Ok(None)) {
+                // SAFETY: enumeration stops here, so the context is ours.
+                unsafe { CertFreeCertificateContext(ctx) };
+                return found;
+            }
+        }
+    }
+}
+
+impl Drop for Store {
+    fn drop(&mut self) {
+        // SAFETY: the store is open, and closed only here.
+        unsafe { CertCloseStore(self.0, 0) };
+    }
+}
+
+fn to_x509(ctx: &CERT_CONTEXT) -> crate::Result<X509> {
+    // SAFETY: the context holds `cbCertEncoded` bytes of DER.
+    let der = unsafe { std::slice::from_raw_parts(ctx.pbCertEncoded, ctx.cbCertEncoded as usize) };
+    X509::from_der(der).map_err(Into::into)
+}
+
+fn friendly_name(ctx: &CERT_CONTEXT) -> Option<String> {
+    let mut len = 0;
+    // SAFETY: a null buffer only asks for the length of the property.
+    let ok = unsafe {
+        CertGetCertificateContextProperty(
+            ctx,
+            CERT_FRIENDLY_NAME_PROP_ID,
+            ptr::null_mut(),
+            &mut len,
+        )
+    };
+    if ok == 0 {
+        return None;
+    }
+
+    let mut name = vec![0u16; (len as usize).div_ceil(2)];
+    // SAFETY: `name` has room for `len` bytes.
+    let ok = unsafe {
+        CertGetCertificateContextProperty(
+            ctx,
+            CERT_FRIENDLY_NAME_PROP_ID,
+            name.as_mut_ptr().cast(),
+            &mut len,
+        )
+    };
+    if ok == 0 {
+        return None;
+    }
+    let end = name.iter().position(|&c| c == 0).unwrap_or(name.len());
+    Some(String::from_utf16_lossy(&name[..end]))
+}
+
+fn private_key(ctx: &CERT_CONTEXT) -> crate::Result<NcryptKey> {
+    let mut handle = 0;
+    let mut spec = 0;
+    let mut owned: BOOL = 0;
+    // SAFETY: `ctx` is a valid context, and the out pointers are valid for
+    // writes.
+    let ok = unsafe {
+        CryptAcquireCertificatePrivateKey(
+            ctx,
+            CRYPT_ACQUIRE_ONLY_NCRYPT_KEY_FLAG | CRYPT_ACQUIRE_SILENT_FLAG,
+            ptr::null(),
+            &mut handle,
+            &mut spec,
+            &mut owned,
+        )
+    };
+    if ok == 0 {
+        return Err(Error::builder(std::io::Error::last_os_error()));
+    }
+    Ok(NcryptKey {
+        handle,
+        owned: owned != 0,
+    })
+}
+
+/// Finds the identity whose certificate has the friendly name `label` in
+/// the personal store of the user.
+pub(in crate::tls::x509) fn find_identity(label: &str) -> crate::Result<Found> {
+    Store::open("MY")?
+        .find_map(|ctx| {
+            if friendly_name(ctx).as_deref() != Some(label) {
+                return Ok(None);
+            }
+            let key = private_key(ctx)?;
+            Ok(Some(Found {
+                cert: to_x509(ctx)?,
+                chain: Vec::new(),
+                key: Arc::new(key),
+            }))
+        })?
+        .ok_or_else(|| Error::builder(format!("no identity named {label:?} in the MY store")))
+}
+
+/// Returns the certificates in the root store of the user, which includes
+/// the machine's.
+pub(in crate::tls::x509) fn trust_anchors() -> crate::Result<Vec<X509>> {
+    let mut anchors = Vec::new();
+    Store::open("ROOT")?.find_map(|ctx| {
+        match to_x509(ctx) {
+            Ok(cert) => anchors.push(cert),
+            Err(err) => debug!("skipping root store certificate: {}", err),
+        }
+        Ok(None::<()>)
+    })?;
+    Ok(anchors)
+}
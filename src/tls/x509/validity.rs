@@ -0,0 +1,25 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use boring2::{
+    asn1::{Asn1Time, Asn1TimeRef},
+    x509::X509Ref,
+};
+
+/// Returns the `notBefore` and `notAfter` times of `cert`.
+pub(crate) fn validity(cert: &X509Ref) -> Option<(SystemTime, SystemTime)> {
+    Some((
+        system_time(cert.not_before())?,
+        system_time(cert.not_after())?,
+    ))
+}
+
+fn system_time(time: &Asn1TimeRef) -> Option<SystemTime> {
+    let epoch = Asn1Time::from_unix(0).ok()?;
+    let diff = epoch.diff(time).ok()?;
+    let secs = i64::from(diff.days) * 86400 + i64::from(diff.secs);
+    if secs >= 0 {
+        UNIX_EPOCH.checked_add(Duration::from_secs(secs.unsigned_abs()))
+    } else {
+        UNIX_EPOCH.checked_sub(Duration::from_secs(secs.unsigned_abs()))
+    }
+}
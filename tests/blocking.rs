@@ -0,0 +1,34 @@
+mod support;
+use std::time::Duration;
+
+use support::server;
+
+#[test]
+fn blocking_connect() {
+    let server = server::http(move |_req| async { http::Response::default() });
+
+    let url = format!("http://{}/", server.addr());
+    let info = wreq::blocking::connect(wreq::Client::builder().no_proxy(), url).unwrap();
+
+    assert!(info.tls_info().is_none());
+    assert!(!info.is_proxied());
+    assert!(!info.is_negotiated_h2());
+}
+
+#[test]
+fn blocking_connect_timeout() {
+    let builder = wreq::Client::builder()
+        .connect_timeout(Duration::from_millis(100))
+        .no_proxy();
+
+    let err = wreq::blocking::connect(builder, "http://192.0.2.1:81/").unwrap_err();
+
+    assert!(err.is_timeout());
+}
+
+#[test]
+fn blocking_connect_bad_scheme() {
+    let err = wreq::blocking::connect(wreq::Client::builder(), "ftp://example.com/").unwrap_err();
+
+    assert!(err.is_builder());
+}
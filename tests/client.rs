@@ -434,6 +434,31 @@ async fn overridden_dns_resolution_with_gai_multiple() {
     assert_eq!("Hello", text);
 }
 
+#[tokio::test]
+async fn happy_eyeballs_falls_back_to_ipv4() {
+    let server = server::http(move |_req| async { http::Response::new("Hello".into()) });
+
+    // Nothing answers in the documentation prefix: connects to it either fail
+    // right away or hang, and IPv4 has to win in both cases.
+    let unreachable = std::net::SocketAddr::new(
+        std::net::IpAddr::V6(std::net::Ipv6Addr::new(0x2001, 0xdb8, 0, 0, 0, 0, 0, 1)),
+        server.addr().port(),
+    );
+    let client = wreq::Client::builder()
+        .no_proxy()
+        .connect_timeout(std::time::Duration::from_secs(10))
+        .happy_eyeballs_timeout(std::time::Duration::from_millis(50))
+        .resolve_to_addrs("eyeballs.test", &[unreachable, server.addr()])
+        .build()
+        .unwrap();
+
+    let started = std::time::Instant::now();
+    let url = format!("http://eyeballs.test:{}/", server.addr().port());
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    assert!(started.elapsed() < std::time::Duration::from_secs(2));
+}
+
 #[cfg(feature = "hickory-dns")]
 #[tokio::test]
 async fn overridden_dns_resolution_with_hickory_dns() {
@@ -540,6 +565,16 @@ async fn test_tls_info() {
     assert!(peer_certificate.is_some());
     let der = peer_certificate.unwrap();
     assert_eq!(der[0], 0x30); // ASN.1 SEQUENCE
+    assert!(!tls_info.session_reused());
+
+    let chain = tls_info.verified_chain().expect("verified chain");
+    assert!(chain.is_verified());
+    let certs = chain.certificates();
+    assert_eq!(certs[0].der(), der);
+    assert_eq!(
+        certs.last().map(|cert| cert.role()),
+        Some(wreq::tls::ChainRole::TrustAnchor)
+    );
 
     let resp = wreq::Client::builder()
         .build()
@@ -552,6 +587,148 @@ async fn test_tls_info() {
     assert!(tls_info.is_none());
 }
 
+#[tokio::test]
+async fn tls_early_data_on_resumed_sessions() {
+    use boring2::{
+        pkey::PKey,
+        ssl::{SslAcceptor, SslMethod},
+        x509::X509,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let acceptor = || {
+        let cert = X509::from_der(include_bytes!("support/server.cert")).unwrap();
+        let key = PKey::private_key_from_der(include_bytes!("support/server.key")).unwrap();
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_certificate(&cert).unwrap();
+        acceptor.set_private_key(&key).unwrap();
+        acceptor.set_early_data_enabled(true);
+        acceptor.build()
+    };
+    // Each acceptor has its own ticket keys, so a session from the first
+    // can't be resumed by the second, which rejects the early data.
+    let acceptors = [acceptor(), acceptor()];
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        for acceptor in [&acceptors[0], &acceptors[0], &acceptors[1]] {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut tls = tokio_boring2::accept(acceptor, tcp).await.unwrap();
+            let mut req = Vec::new();
+            while !req.ends_with(b"\r\n\r\n") {
+                let mut buf = [0; 1024];
+                let n = tls.read(&mut buf).await.unwrap();
+                assert_ne!(n, 0, "connection closed mid-request");
+                req.extend_from_slice(&buf[..n]);
+            }
+            tx.send(req).unwrap();
+            tls.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                .await
+                .unwrap();
+        }
+    });
+
+    let client = Client::builder()
+        .http1_only()
+        .cert_verification(false)
+        .tls_info(true)
+        .tls_early_data(true)
+        .build()
+        .unwrap();
+    let url = format!("https://localhost:{}/", addr.port());
+
+    // Nothing to resume yet, then resumed with early data, then rejected and
+    // sent again over the completed handshake.
+    for expected in [None, Some(true), Some(false)] {
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.status(), wreq::StatusCode::OK);
+        let tls_info = res.extensions().get::<wreq::tls::TlsInfo>().unwrap();
+        assert_eq!(tls_info.early_data_accepted(), expected);
+
+        let req = rx.recv().await.unwrap();
+        assert!(req.starts_with(b"GET / HTTP/1.1\r\n"));
+    }
+}
+
+#[tokio::test]
+async fn tls_early_data_rejected_replays_request() {
+    use boring2::{
+        pkey::PKey,
+        ssl::{SslAcceptor, SslMethod},
+        x509::X509,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let acceptor = || {
+        let cert = X509::from_der(include_bytes!("support/server.cert")).unwrap();
+        let key = PKey::private_key_from_der(include_bytes!("support/server.key")).unwrap();
+        let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+        acceptor.set_certificate(&cert).unwrap();
+        acceptor.set_private_key(&key).unwrap();
+        acceptor.set_early_data_enabled(true);
+        acceptor.build()
+    };
+    // The second acceptor can't resume sessions of the first, and rejects
+    // the early data; the third resumes them, but sees no early data.
+    let (first, second) = (acceptor(), acceptor());
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        for acceptor in [&first, &second, &second] {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut tls = tokio_boring2::accept(acceptor, tcp).await.unwrap();
+            let mut req = Vec::new();
+            while !req.ends_with(b"\r\n\r\n") {
+                let mut buf = [0; 1024];
+                let n = tls.read(&mut buf).await.unwrap();
+                assert_ne!(n, 0, "connection closed mid-request");
+                req.extend_from_slice(&buf[..n]);
+            }
+            tls.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\nconnection: close\r\n\r\n")
+                .await
+                .unwrap();
+            // Anything after the request would be a second copy of it.
+            let n = tls.read(&mut [0; 1024]).await.unwrap_or(0);
+            tx.send((req, n)).unwrap();
+        }
+    });
+
+    let client = Client::builder()
+        .http1_only()
+        .cert_verification(false)
+        .tls_info(true)
+        .tls_early_data(true)
+        .build()
+        .unwrap();
+    let url = format!("https://localhost:{}/", addr.port());
+
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    rx.recv().await.unwrap();
+
+    // Offered as early data, rejected, and sent once over the completed
+    // handshake.
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    let tls_info = res.extensions().get::<wreq::tls::TlsInfo>().unwrap();
+    assert_eq!(tls_info.early_data_accepted(), Some(false));
+    let (req, trailing) = rx.recv().await.unwrap();
+    assert!(req.starts_with(b"GET / HTTP/1.1\r\n"));
+    assert_eq!(trailing, 0);
+
+    // A session to resume, but a method that may not be replayed.
+    let res = client.post(&url).send().await.unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    let tls_info = res.extensions().get::<wreq::tls::TlsInfo>().unwrap();
+    assert_eq!(tls_info.early_data_accepted(), None);
+    let (req, _) = rx.recv().await.unwrap();
+    assert!(req.starts_with(b"POST / HTTP/1.1\r\n"));
+}
+
 // NOTE: using the default "current_thread" runtime here would cause the test to
 // fail, because the only thread would block until `panic_rx` receives a
 // notification while the client needs to be driven to get the graceful shutdown
@@ -648,137 +825,2225 @@ async fn close_connection_after_idle_timeout() {
 }
 
 #[tokio::test]
-async fn http1_only() {
+async fn check_connection_on_reuse_replaces_closed_connection() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Answers one request per connection, then closes it without saying so.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (mut tcp, peer) = listener.accept().await.unwrap();
+            tokio::spawn(async move {
+                let mut buf = [0; 1024];
+                let _ = tcp.read(&mut buf).await.unwrap();
+                let body = peer.port().to_string();
+                let res = format!(
+                    "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{body}",
+                    body.len()
+                );
+                tcp.write_all(res.as_bytes()).await.unwrap();
+            });
+        }
+    });
+
+    let client = wreq::Client::builder()
+        .check_connection_on_reuse(true)
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let url = format!("http://{addr}/");
+    let first = client.get(&url).send().await.unwrap().text().await.unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    let second = client.get(&url).send().await.unwrap().text().await.unwrap();
+    assert_ne!(first, second);
+}
+
+#[tokio::test]
+async fn check_connection_on_reuse_replaces_silent_h2_connection() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Speaks just enough HTTP/2 to answer requests, and never answers a PING.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    tokio::spawn({
+        let accepted = accepted.clone();
+        async move {
+            loop {
+                let (mut tcp, _) = listener.accept().await.unwrap();
+                accepted.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut preface = [0; 24];
+                    tcp.read_exact(&mut preface).await.unwrap();
+                    tcp.write_all(&[0, 0, 0, 4, 0, 0, 0, 0, 0]).await.unwrap();
+                    loop {
+                        let mut head = [0; 9];
+                        if tcp.read_exact(&mut head).await.is_err() {
+                            return;
+                        }
+                        let len = u32::from_be_bytes([0, head[0], head[1], head[2]]);
+                        let mut payload = vec![0; len as usize];
+                        tcp.read_exact(&mut payload).await.unwrap();
+                        if head[3] == 0x1 {
+                            // HEADERS, END_STREAM | END_HEADERS, `:status: 200`.
+                            let mut res = vec![0, 0, 1, 0x1, 0x5];
+                            res.extend_from_slice(&head[5..9]);
+                            res.push(0x88);
+                            tcp.write_all(&res).await.unwrap();
+                        }
+                    }
+                });
+            }
+        }
+    });
+
+    let client = wreq::Client::builder()
+        .http2_only()
+        .check_connection_on_reuse(true)
+        .check_connection_timeout(std::time::Duration::from_millis(100))
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let url = format!("http://{addr}/");
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.version(), Version::HTTP_2);
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn warm_connections() {
     let server = server::http(move |_| async move { http::Response::default() });
+    let url = format!("http://{}", server.addr());
 
-    let resp = wreq::Client::builder()
+    let client = wreq::Client::builder().no_proxy().build().unwrap();
+    assert_eq!(client.warm(&url, 3).await.unwrap(), 3);
+
+    // The pool already holds the idle connections.
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+
+    let client = wreq::Client::builder()
+        .pool_max_idle_per_host(1)
+        .no_proxy()
+        .build()
+        .unwrap();
+    assert_eq!(client.warm(&url, 3).await.unwrap(), 1);
+    assert_eq!(client.warm(&url, 3).await.unwrap(), 0);
+}
+
+#[tokio::test]
+async fn http1_pipelining_shares_connection() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Answers nothing before two requests have arrived on a connection, so
+    // the second request must be sent while the first awaits its response.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let first_read = Arc::new(tokio::sync::Notify::new());
+    tokio::spawn({
+        let accepted = accepted.clone();
+        let first_read = first_read.clone();
+        async move {
+            loop {
+                let (mut tcp, peer) = listener.accept().await.unwrap();
+                accepted.fetch_add(1, Ordering::SeqCst);
+                let first_read = first_read.clone();
+                tokio::spawn(async move {
+                    let mut reqs = Vec::new();
+                    while reqs.windows(4).filter(|w| w == b"\r\n\r\n").count() < 2 {
+                        let mut buf = [0; 1024];
+                        let n = tcp.read(&mut buf).await.unwrap();
+                        if n == 0 {
+                            return;
+                        }
+                        reqs.extend_from_slice(&buf[..n]);
+                        if reqs.ends_with(b"\r\n\r\n") {
+                            first_read.notify_one();
+                        }
+                    }
+                    let body = peer.port().to_string();
+                    let res = format!(
+                        "HTTP/1.1 200 OK\r\ncontent-length: {}\r\n\r\n{body}",
+                        body.len()
+                    );
+                    tcp.write_all(res.repeat(2).as_bytes()).await.unwrap();
+                    let _ = tcp.read(&mut [0; 1024]).await;
+                });
+            }
+        }
+    });
+
+    let client = wreq::Client::builder()
+        .http1_pipelining(2)
+        .no_proxy()
+        .build()
+        .unwrap();
+    let url = format!("http://{addr}/");
+    assert_eq!(client.warm(&url, 1).await.unwrap(), 1);
+
+    let first = tokio::spawn({
+        let (client, url) = (client.clone(), url.clone());
+        async move { client.get(&url).send().await.unwrap().text().await.unwrap() }
+    });
+    first_read.notified().await;
+    let second = client.get(&url).send().await.unwrap().text().await.unwrap();
+    assert_eq!(first.await.unwrap(), second);
+    assert_eq!(accepted.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn connect_over_provided_stream() {
+    let client = wreq::Client::builder().no_proxy().build().unwrap();
+
+    for path in ["/a", "/b"] {
+        let (client_io, server_io) = tokio::io::duplex(4096);
+        let server = tokio::spawn(async move {
+            let service = hyper::service::service_fn(|req: http::Request<_>| async move {
+                let body = wreq::Body::from(req.uri().path().to_owned());
+                Ok::<_, std::convert::Infallible>(http::Response::new(body))
+            });
+            hyper_util::server::conn::auto::Builder::new(hyper_util::rt::TokioExecutor::new())
+                .serve_connection(hyper_util::rt::TokioIo::new(server_io), service)
+                .await
+                .unwrap();
+        });
+
+        // The host doesn't resolve: the request can only go over the stream.
+        let res = client
+            .get(format!("http://connect-over.invalid{path}"))
+            .connect_over(client_io)
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.text().await.unwrap(), path);
+
+        // The connection isn't pooled, so it closes along with the response.
+        server.await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn warm_connections_fails_soft() {
+    let addr = {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        listener.local_addr().unwrap()
+    };
+
+    let client = wreq::Client::builder().no_proxy().build().unwrap();
+    assert_eq!(client.warm(format!("http://{addr}"), 2).await.unwrap(), 0);
+
+    let err = client.warm("ftp://example.com", 2).await.unwrap_err();
+    assert!(err.is_builder());
+}
+
+#[tokio::test]
+async fn max_concurrent_connects() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    use tokio::io::AsyncReadExt;
+
+    // Accepts connections but never answers the TLS handshake, so every
+    // connect stays in flight until it times out.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    tokio::spawn({
+        let accepted = accepted.clone();
+        async move {
+            loop {
+                let (mut tcp, _) = listener.accept().await.unwrap();
+                accepted.fetch_add(1, Ordering::SeqCst);
+                tokio::spawn(async move {
+                    let mut buf = [0; 1024];
+                    while tcp.read(&mut buf).await.unwrap_or(0) > 0 {}
+                });
+            }
+        }
+    });
+
+    let client = wreq::Client::builder()
+        .max_concurrent_connects(2)
+        .connect_timeout(std::time::Duration::from_millis(300))
         .http1_only()
+        .no_proxy()
+        .build()
+        .unwrap();
+    let warm = tokio::spawn(async move { client.warm(format!("https://{addr}"), 5).await });
+
+    // The other connects wait for the first two to time out.
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+
+    assert_eq!(warm.await.unwrap().unwrap(), 0);
+    assert_eq!(accepted.load(Ordering::SeqCst), 5);
+}
+
+#[tokio::test]
+async fn local_address_of_other_family_is_skipped() {
+    let server = server::http(move |_| async move { http::Response::default() });
+
+    // The server only has an IPv4 address, so the IPv6 local address of the
+    // request can't be bound to, and the connect goes out unbound.
+    let res = wreq::Client::builder()
+        .no_proxy()
         .build()
         .unwrap()
         .get(format!("http://{}", server.addr()))
+        .local_address(std::net::IpAddr::from(std::net::Ipv6Addr::LOCALHOST))
         .send()
         .await
         .unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+}
 
-    assert_eq!(resp.version(), wreq::Version::HTTP_11);
+#[tokio::test]
+async fn connect_retries_refused_connects() {
+    // Nothing listens on the port once the listener is dropped.
+    let addr = std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap();
 
-    let resp = wreq::Client::builder()
+    let started = std::time::Instant::now();
+    let err = wreq::Client::builder()
+        .no_proxy()
+        .connect_retries(2)
+        .connect_retry_backoff(std::time::Duration::from_millis(50))
         .build()
         .unwrap()
-        .get(format!("http://{}", server.addr()))
-        .version(Version::HTTP_11)
+        .get(format!("http://{addr}"))
         .send()
         .await
-        .unwrap();
+        .unwrap_err();
 
-    assert_eq!(resp.version(), wreq::Version::HTTP_11);
+    assert!(err.is_connect());
+    assert!(err.is_connect_retryable());
+    // Backed off for 50ms, then 100ms.
+    assert!(started.elapsed() >= std::time::Duration::from_millis(150));
 }
 
+#[cfg(target_os = "linux")]
 #[tokio::test]
-async fn http2_only() {
+async fn netns_missing_fails_connect() {
     let server = server::http(move |_| async move { http::Response::default() });
 
-    let resp = wreq::Client::builder()
-        .http2_only()
+    let err = wreq::Client::builder()
+        .netns("/nonexistent/netns")
+        .no_proxy()
         .build()
         .unwrap()
         .get(format!("http://{}", server.addr()))
         .send()
         .await
-        .unwrap();
+        .unwrap_err();
 
-    assert_eq!(resp.version(), wreq::Version::HTTP_2);
+    assert!(err.is_connect());
+}
 
-    let resp = wreq::Client::builder()
+#[cfg(target_os = "linux")]
+#[tokio::test]
+#[ignore = "entering a network namespace requires CAP_SYS_ADMIN"]
+async fn netns_of_own_process() {
+    let server = server::http(move |_| async move { http::Response::default() });
+
+    let res = wreq::Client::builder()
+        .netns("/proc/self/ns/net")
+        .no_proxy()
         .build()
         .unwrap()
         .get(format!("http://{}", server.addr()))
-        .version(Version::HTTP_2)
         .send()
         .await
         .unwrap();
 
-    assert_eq!(resp.version(), wreq::Version::HTTP_2);
+    assert_eq!(res.status(), wreq::StatusCode::OK);
 }
 
+#[cfg(target_os = "linux")]
 #[tokio::test]
-async fn connection_pool_cache() {
-    let client = wreq::Client::default();
-    let url = "https://hyper.rs";
+async fn tcp_maxseg_clamps_mss() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let server = tokio::spawn(async move {
+        let (mut stream, _) = listener.accept().await.unwrap();
+        // The client advertised its clamped MSS in the SYN, so the server's
+        // side sees it too, rather than the loopback default.
+        let mss = socket2::SockRef::from(&stream).mss().unwrap();
+        let mut buf = [0; 1024];
+        let _ = stream.read(&mut buf).await.unwrap();
+        stream
+            .write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+            .await
+            .unwrap();
+        mss
+    });
 
-    let resp = client
-        .get(url)
-        .version(http::Version::HTTP_2)
+    let res = wreq::Client::builder()
+        .tcp_maxseg(1200)
+        .no_proxy()
+        .build()
+        .unwrap()
+        .get(format!("http://{addr}"))
         .send()
         .await
         .unwrap();
 
-    assert_eq!(resp.status(), wreq::StatusCode::OK);
-    assert_eq!(resp.version(), http::Version::HTTP_2);
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    assert!(server.await.unwrap() <= 1200);
+}
 
-    let resp = client
-        .get(url)
-        .version(http::Version::HTTP_11)
+#[tokio::test]
+async fn connect_timing_on_response() {
+    let server = server::http(move |_| async move { http::Response::default() });
+
+    let res = wreq::Client::builder()
+        .collect_timings(true)
+        .no_proxy()
+        .build()
+        .unwrap()
+        .get(format!("http://localhost:{}", server.addr().port()))
         .send()
         .await
         .unwrap();
+    let timing = res.connect_timing().unwrap();
+    assert!(timing.dns().is_some());
+    assert!(timing.tcp_connect().is_some());
+    assert_eq!(timing.proxy_connect(), None);
+    assert_eq!(timing.tls(), None);
 
-    assert_eq!(resp.status(), wreq::StatusCode::OK);
-    assert_eq!(resp.version(), http::Version::HTTP_11);
-
-    let resp = client
-        .get(url)
-        .version(http::Version::HTTP_2)
+    let res = wreq::Client::builder()
+        .no_proxy()
+        .build()
+        .unwrap()
+        .get(format!("http://{}", server.addr()))
         .send()
         .await
         .unwrap();
-
-    assert_eq!(resp.status(), wreq::StatusCode::OK);
-    assert_eq!(resp.version(), http::Version::HTTP_2);
+    assert_eq!(res.connect_timing(), None);
 }
 
 #[tokio::test]
-#[ignore = "The server is shuddown, this test is not needed anymore"]
-async fn http1_case_sensitive_headers() {
-    // Create a request with a case-sensitive header
-    let mut original_headers = OriginalHeaders::new();
-    original_headers.insert("X-custom-header");
-    original_headers.insert("Host");
+async fn byte_counts_on_response() {
+    let server = server::http(move |_| async move { http::Response::new("hello".into()) });
 
-    let resp = wreq::Client::new()
-        .get("https://tls.peet.ws/api/all")
-        .header("X-Custom-Header", "value")
-        .original_headers(original_headers)
-        .version(Version::HTTP_11)
-        .send()
-        .await
+    let client = wreq::Client::builder()
+        .count_bytes(true)
+        .no_proxy()
+        .build()
+        .unwrap();
+    let url = format!("http://{}", server.addr());
+
+    let res = client.get(&url).send().await.unwrap();
+    let counts = res.byte_counts().unwrap().clone();
+    assert_eq!(res.text().await.unwrap(), "hello");
+    let (read, written) = (counts.read(), counts.written());
+    assert!(read > 5);
+    assert!(written > 0);
+
+    // The counts grow with the requests on the connection.
+    let res = client.get(&url).send().await.unwrap();
+    let _ = res.text().await.unwrap();
+    assert!(counts.read() > read);
+    assert!(counts.written() > written);
+
+    let res = wreq::Client::builder()
+        .no_proxy()
+        .build()
         .unwrap()
-        .text()
+        .get(&url)
+        .send()
         .await
         .unwrap();
-
-    assert!(resp.contains("X-custom-header"));
-    assert!(resp.contains("Host"));
+    assert!(res.byte_counts().is_none());
 }
 
 #[tokio::test]
-async fn tunnel_includes_proxy_auth_with_multiple_proxies() {
-    let url = "http://hyper.rs.local/prox";
-    let server1 = server::http(move |req| {
-        assert_eq!(req.method(), "GET");
-        assert_eq!(req.uri(), url);
-        assert_eq!(req.headers()["host"], "hyper.rs.local");
-        assert_eq!(
-            req.headers()["proxy-authorization"],
-            "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ=="
-        );
-        assert_eq!(req.headers()["proxy-header"], "proxy2");
-        async { http::Response::default() }
-    });
+async fn inspect_io_sees_the_exchange() {
+    use std::sync::{Arc, Mutex};
 
-    let proxy_url = format!("http://Aladdin:open%20sesame@{}", server1.addr());
+    use wreq::IoDirection;
 
-    let mut headers1 = wreq::header::HeaderMap::new();
+    let server = server::http(move |_| async move { http::Response::new("hello".into()) });
+
+    let seen = Arc::new(Mutex::new((Vec::new(), Vec::new())));
+    let client = wreq::Client::builder()
+        .inspect_io({
+            let seen = seen.clone();
+            move |direction, bytes| {
+                let mut seen = seen.lock().unwrap();
+                match direction {
+                    IoDirection::Read => seen.0.extend_from_slice(bytes),
+                    IoDirection::Write => seen.1.extend_from_slice(bytes),
+                }
+            }
+        })
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let res = client
+        .get(format!("http://{}/inspected", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "hello");
+
+    let seen = seen.lock().unwrap();
+    assert!(seen.1.starts_with(b"GET /inspected HTTP/1.1\r\n"));
+    assert!(seen.0.starts_with(b"HTTP/1.1 200 OK\r\n"));
+    assert!(seen.0.ends_with(b"hello"));
+}
+
+#[tokio::test]
+async fn on_socket_sees_every_connect() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    let server = server::http(move |_| async move { http::Response::default() });
+
+    let calls = Arc::new(AtomicUsize::new(0));
+    let client = wreq::Client::builder()
+        .on_socket({
+            let calls = calls.clone();
+            move |socket| {
+                calls.fetch_add(1, Ordering::Relaxed);
+                socket.set_ttl(16)
+            }
+        })
+        .no_proxy()
+        .build()
+        .unwrap();
+    let res = client
+        .get(format!("http://{}", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    assert_eq!(calls.load(Ordering::Relaxed), 1);
+
+    let err = wreq::Client::builder()
+        .on_socket(|_| Err(std::io::Error::other("no route mark")))
+        .no_proxy()
+        .build()
+        .unwrap()
+        .get(format!("http://{}", server.addr()))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_connect());
+}
+
+#[cfg(target_os = "linux")]
+#[tokio::test]
+async fn tcp_fastopen_connects() {
+    let server = server::http(move |_| async move { http::Response::default() });
+
+    let client = wreq::Client::builder()
+        .tcp_fastopen(true)
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    // The first connection only asks for a cookie, the second may use it.
+    for _ in 0..2 {
+        let res = client
+            .get(format!("http://{}", server.addr()))
+            .header("connection", "close")
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), wreq::StatusCode::OK);
+    }
+}
+
+#[tokio::test]
+async fn http1_only() {
+    let server = server::http(move |_| async move { http::Response::default() });
+
+    let resp = wreq::Client::builder()
+        .http1_only()
+        .build()
+        .unwrap()
+        .get(format!("http://{}", server.addr()))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.version(), wreq::Version::HTTP_11);
+
+    let resp = wreq::Client::builder()
+        .build()
+        .unwrap()
+        .get(format!("http://{}", server.addr()))
+        .version(Version::HTTP_11)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.version(), wreq::Version::HTTP_11);
+}
+
+#[test]
+fn http2_even_initial_stream_id_is_rejected() {
+    for id in [0, 2, 100] {
+        let emulation = wreq::EmulationProvider::builder()
+            .http2_config(
+                wreq::http2::Http2Config::builder()
+                    .initial_stream_id(id)
+                    .build(),
+            )
+            .build();
+
+        let err = Client::builder().emulation(emulation).build().unwrap_err();
+        assert!(err.is_builder());
+    }
+
+    let emulation = wreq::EmulationProvider::builder()
+        .http2_config(
+            wreq::http2::Http2Config::builder()
+                .initial_stream_id(3)
+                .build(),
+        )
+        .build();
+    assert!(Client::builder().emulation(emulation).build().is_ok());
+}
+
+#[tokio::test]
+async fn http2_preface_window_update() {
+    use tokio::io::AsyncReadExt;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    // Read the preface, and the header and payload of the two frames after
+    // the magic.
+    let server = tokio::spawn(async move {
+        let (mut io, _) = listener.accept().await.unwrap();
+        let mut magic = [0; 24];
+        io.read_exact(&mut magic).await.unwrap();
+        assert_eq!(&magic, b"PRI * HTTP/2.0\r\n\r\nSM\r\n\r\n");
+
+        let mut frames = Vec::new();
+        for _ in 0..2 {
+            let mut header = [0; 9];
+            io.read_exact(&mut header).await.unwrap();
+            let len = u32::from_be_bytes([0, header[0], header[1], header[2]]) as usize;
+            let mut payload = vec![0; len];
+            io.read_exact(&mut payload).await.unwrap();
+            frames.push((header[3], payload));
+        }
+        frames
+    });
+
+    let emulation = wreq::EmulationProvider::builder()
+        .http2_config(
+            wreq::http2::Http2Config::builder()
+                .preface_window_update(15_663_105)
+                .build(),
+        )
+        .build();
+    let client = wreq::Client::builder()
+        .http2_only()
+        .no_proxy()
+        .emulation(emulation)
+        .timeout(std::time::Duration::from_millis(500))
+        .build()
+        .unwrap();
+    let _ = client.get(format!("http://{addr}")).send().await;
+
+    let frames = server.await.unwrap();
+    // SETTINGS, then WINDOW_UPDATE.
+    assert_eq!(frames[0].0, 0x4);
+    assert_eq!(frames[1].0, 0x8);
+    assert_eq!(frames[1].1, 15_663_105u32.to_be_bytes());
+}
+
+#[test]
+fn http2_too_large_connection_window_is_rejected() {
+    let emulation = wreq::EmulationProvider::builder()
+        .http2_config(
+            wreq::http2::Http2Config::builder()
+                .preface_window_update(u32::MAX)
+                .build(),
+        )
+        .build();
+
+    let err = Client::builder().emulation(emulation).build().unwrap_err();
+    assert!(err.is_builder());
+}
+
+#[tokio::test]
+async fn http2_only() {
+    let server = server::http(move |_| async move { http::Response::default() });
+
+    let resp = wreq::Client::builder()
+        .http2_only()
+        .build()
+        .unwrap()
+        .get(format!("http://{}", server.addr()))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.version(), wreq::Version::HTTP_2);
+
+    let resp = wreq::Client::builder()
+        .build()
+        .unwrap()
+        .get(format!("http://{}", server.addr()))
+        .version(Version::HTTP_2)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.version(), wreq::Version::HTTP_2);
+}
+
+/// Serves HTTPS on loopback, answering requests made over HTTP/2
+/// with `frame`, and any request over HTTP/1.1 with a `200`. Returns the
+/// ALPN protocols offered on each connection.
+async fn h2_breaking_server(
+    frame: &'static [u8],
+) -> (
+    std::net::SocketAddr,
+    std::sync::Arc<std::sync::Mutex<Vec<Vec<u8>>>>,
+) {
+    use boring2::{
+        pkey::PKey,
+        ssl::{AlpnError, SslAcceptor, SslMethod, select_next_proto},
+        x509::X509,
+    };
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let offered = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let cert = X509::from_der(include_bytes!("support/server.cert")).unwrap();
+    let key = PKey::private_key_from_der(include_bytes!("support/server.key")).unwrap();
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_certificate(&cert).unwrap();
+    acceptor.set_private_key(&key).unwrap();
+    acceptor.set_alpn_select_callback({
+        let offered = offered.clone();
+        move |_, client| {
+            offered.lock().unwrap().push(client.to_vec());
+            select_next_proto(b"\x02h2\x08http/1.1", client).ok_or(AlpnError::NOACK)
+        }
+    });
+    let acceptor = acceptor.build();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut tls = tokio_boring2::accept(&acceptor, tcp).await.unwrap();
+            if tls.ssl().selected_alpn_protocol() == Some(b"h2") {
+                let mut preface = [0; 24];
+                tls.read_exact(&mut preface).await.unwrap();
+                tls.write_all(&[0, 0, 0, 4, 0, 0, 0, 0, 0]).await.unwrap();
+                // Skip frames up to the request's HEADERS.
+                loop {
+                    let mut head = [0; 9];
+                    tls.read_exact(&mut head).await.unwrap();
+                    let len = u32::from_be_bytes([0, head[0], head[1], head[2]]);
+                    let mut payload = vec![0; len as usize];
+                    tls.read_exact(&mut payload).await.unwrap();
+                    if head[3] == 0x1 {
+                        break;
+                    }
+                }
+                tls.write_all(frame).await.unwrap();
+                let _ = tls.read(&mut [0; 1024]).await;
+            } else {
+                let mut req = Vec::new();
+                while !req.ends_with(b"\r\n\r\n") {
+                    let mut buf = [0; 1024];
+                    let n = tls.read(&mut buf).await.unwrap();
+                    assert_ne!(n, 0, "connection closed mid-request");
+                    req.extend_from_slice(&buf[..n]);
+                }
+                tls.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+                    .await
+                    .unwrap();
+                let _ = tls.read(&mut [0; 1024]).await;
+            }
+        }
+    });
+
+    (addr, offered)
+}
+
+#[tokio::test]
+async fn http2_fallback_after_protocol_error() {
+    // GOAWAY, last stream 0, PROTOCOL_ERROR.
+    const GOAWAY: &[u8] = &[0, 0, 8, 0x7, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x1];
+    // RST_STREAM on the request's stream, HTTP_1_1_REQUIRED.
+    const RST_STREAM: &[u8] = &[0, 0, 4, 0x3, 0, 0, 0, 0, 1, 0, 0, 0, 0xd];
+
+    for frame in [GOAWAY, RST_STREAM] {
+        let (addr, offered) = h2_breaking_server(frame).await;
+        let client = Client::builder()
+            .cert_verification(false)
+            .http2_fallback(true)
+            .build()
+            .unwrap();
+        let url = format!("https://localhost:{}/", addr.port());
+
+        // The request that hits the error may fail.
+        let _ = client.get(&url).send().await;
+
+        let res = client.get(&url).send().await.unwrap();
+        assert_eq!(res.version(), Version::HTTP_11);
+
+        let offered = offered.lock().unwrap();
+        assert_eq!(offered[0], b"\x02h2\x08http/1.1");
+        assert_eq!(offered.last().unwrap(), b"\x08http/1.1");
+    }
+}
+
+#[tokio::test]
+async fn http2_settings_rtt() {
+    let server = server::http(move |_| async move { http::Response::default() });
+    let url = format!("http://{}", server.addr());
+
+    let client = wreq::Client::builder().http2_only().build().unwrap();
+    // The server acknowledges the SETTINGS before answering the first
+    // request, and the second one is sent after the ACK was read.
+    client.get(&url).send().await.unwrap();
+    let resp = client.get(&url).send().await.unwrap();
+    assert!(resp.settings_rtt().is_some());
+
+    let resp = wreq::Client::builder()
+        .http1_only()
+        .build()
+        .unwrap()
+        .get(&url)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(resp.settings_rtt(), None);
+}
+
+#[tokio::test]
+async fn http2_peer_settings() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (io, _) = listener.accept().await.unwrap();
+        let mut conn = http2::server::Builder::new()
+            .max_concurrent_streams(7)
+            .initial_window_size(12_345)
+            .handshake::<_, bytes::Bytes>(io)
+            .await
+            .unwrap();
+        while let Some(Ok((_req, mut respond))) = conn.accept().await {
+            let _ = respond.send_response(http::Response::new(()), true);
+        }
+    });
+
+    let res = wreq::Client::builder()
+        .http2_only()
+        .no_proxy()
+        .build()
+        .unwrap()
+        .get(format!("http://{addr}"))
+        .send()
+        .await
+        .unwrap();
+
+    let settings = res.http2_peer_settings().unwrap();
+    assert_eq!(settings.max_concurrent_streams(), Some(7));
+    assert_eq!(settings.initial_window_size(), Some(12_345));
+    // Servers don't send these unless asked to.
+    assert_eq!(settings.enable_push(), None);
+    assert_eq!(settings.enable_connect_protocol(), None);
+}
+
+#[tokio::test]
+async fn http2_ping_rtt() {
+    let server = server::http(move |_| async move { http::Response::default() });
+    let url = format!("http://{}", server.addr());
+
+    let emulation = wreq::EmulationProvider::builder()
+        .http2_config(
+            wreq::http2::Http2Config::builder()
+                .keep_alive_interval(std::time::Duration::from_millis(10))
+                .keep_alive_while_idle(true)
+                .build(),
+        )
+        .build();
+    let client = wreq::Client::builder()
+        .http2_only()
+        .emulation(emulation)
+        .build()
+        .unwrap();
+
+    client.get(&url).send().await.unwrap();
+
+    // The idle connection is pinged in the meantime.
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    let resp = client.get(&url).send().await.unwrap();
+    assert!(resp.ping_rtt().is_some());
+    assert!(resp.unanswered_pings() <= 1);
+}
+
+#[tokio::test]
+async fn http2_keep_alive_timeout_closes_connection() {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let accepted = Arc::new(AtomicUsize::new(0));
+
+    // A server that settles the connection, and then goes silent: it never
+    // acknowledges a PING, nor answers a request.
+    let counter = accepted.clone();
+    tokio::spawn(async move {
+        loop {
+            let (mut io, _) = listener.accept().await.unwrap();
+            counter.fetch_add(1, Ordering::SeqCst);
+            tokio::spawn(async move {
+                // An empty SETTINGS, then a SETTINGS ACK.
+                io.write_all(&[0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 4, 1, 0, 0, 0, 0])
+                    .await
+                    .unwrap();
+                let mut buf = [0; 1024];
+                while let Ok(n) = io.read(&mut buf).await {
+                    if n == 0 {
+                        break;
+                    }
+                }
+            });
+        }
+    });
+
+    let emulation = wreq::EmulationProvider::builder()
+        .http2_config(
+            wreq::http2::Http2Config::builder()
+                .keep_alive_interval(Duration::from_millis(50))
+                .keep_alive_timeout(Duration::from_millis(50))
+                .build(),
+        )
+        .build();
+    let client = wreq::Client::builder()
+        .http2_only()
+        .no_proxy()
+        .emulation(emulation)
+        .build()
+        .unwrap();
+    let url = format!("http://{addr}");
+
+    for expected in 1..=2 {
+        let res = tokio::time::timeout(Duration::from_secs(5), client.get(&url).send())
+            .await
+            .unwrap();
+        assert!(res.is_err());
+        // The dead connection is not reused.
+        assert_eq!(accepted.load(Ordering::SeqCst), expected);
+    }
+}
+
+#[tokio::test]
+async fn http2_prior_knowledge() {
+    let server = server::http(move |req| async move {
+        assert_eq!(req.version(), http::Version::HTTP_2);
+        http::Response::default()
+    });
+
+    let resp = wreq::Client::builder()
+        .http2_prior_knowledge(true)
+        .no_proxy()
+        .build()
+        .unwrap()
+        .get(format!("http://{}", server.addr()))
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.version(), wreq::Version::HTTP_2);
+}
+
+#[tokio::test]
+async fn http2_prior_knowledge_http1_server() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+        let (mut io, _) = listener.accept().await.unwrap();
+        let mut buf = [0; 1024];
+        let _ = io.read(&mut buf).await;
+        let _ = io
+            .write_all(b"HTTP/1.1 400 Bad Request\r\ncontent-length: 0\r\n\r\n")
+            .await;
+    });
+
+    let err = wreq::Client::builder()
+        .http2_prior_knowledge(true)
+        .no_proxy()
+        .build()
+        .unwrap()
+        .get(format!("http://{addr}"))
+        .send()
+        .await
+        .unwrap_err();
+
+    let errs = support::error::inspect(err);
+    assert!(
+        errs.iter().any(|e| e.contains("responded with HTTP/1.x")),
+        "{errs:?}"
+    );
+}
+
+#[tokio::test]
+async fn proxy_protocol_header_precedes_request() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+        let (io, _) = listener.accept().await.unwrap();
+        let mut io = BufReader::new(io);
+        let mut header = String::new();
+        io.read_line(&mut header).await.unwrap();
+        let mut request_line = String::new();
+        io.read_line(&mut request_line).await.unwrap();
+        io.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n")
+            .await
+            .unwrap();
+        (header, request_line)
+    });
+
+    let res = wreq::Client::builder()
+        .proxy_protocol(
+            wreq::ProxyProtocolVersion::V1,
+            wreq::ClientInfo::new("192.0.2.1:56324".parse().unwrap()),
+        )
+        .no_proxy()
+        .build()
+        .unwrap()
+        .get(format!("http://{addr}/"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+
+    let (header, request_line) = server.await.unwrap();
+    assert_eq!(
+        header,
+        format!("PROXY TCP4 192.0.2.1 127.0.0.1 56324 {}\r\n", addr.port())
+    );
+    assert_eq!(request_line, "GET / HTTP/1.1\r\n");
+}
+
+/// Serves HTTPS, preferring h2 over http/1.1 when the client offers both.
+async fn tls_server() -> std::net::SocketAddr {
+    use boring2::{
+        pkey::PKey,
+        ssl::{AlpnError, SslAcceptor, SslMethod, select_next_proto},
+        x509::X509,
+    };
+
+    let cert = X509::from_der(include_bytes!("support/server.cert")).unwrap();
+    let key = PKey::private_key_from_der(include_bytes!("support/server.key")).unwrap();
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_certificate(&cert).unwrap();
+    acceptor.set_private_key(&key).unwrap();
+    acceptor.set_alpn_select_callback(|_, client| {
+        select_next_proto(b"\x02h2\x08http/1.1", client).ok_or(AlpnError::NOACK)
+    });
+    let acceptor = acceptor.build();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let Ok(tls) = tokio_boring2::accept(&acceptor, tcp).await else {
+                    return;
+                };
+                let service = hyper::service::service_fn(|_req| async {
+                    Ok::<_, std::convert::Infallible>(http::Response::new(wreq::Body::default()))
+                });
+                let _ = hyper_util::server::conn::auto::Builder::new(
+                    hyper_util::rt::TokioExecutor::new(),
+                )
+                .serve_connection(hyper_util::rt::TokioIo::new(tls), service)
+                .await;
+            });
+        }
+    });
+    addr
+}
+
+#[tokio::test]
+async fn force_http_version_per_host() {
+    let legacy = tls_server().await;
+    let modern = tls_server().await;
+
+    let client = wreq::Client::builder()
+        .cert_verification(false)
+        .resolve("legacy.test", legacy)
+        .resolve("modern.test", modern)
+        .force_http_version("legacy.test", Version::HTTP_11)
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let res = client
+        .get(format!("https://legacy.test:{}/", legacy.port()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.version(), Version::HTTP_11);
+
+    let res = client
+        .get(format!("https://modern.test:{}/", modern.port()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.version(), Version::HTTP_2);
+}
+
+#[tokio::test]
+async fn tls_info_session_reused() {
+    let server = tls_server().await;
+
+    let emulation = wreq::EmulationProvider::builder()
+        .tls_config(wreq::tls::TlsConfig::builder().pre_shared_key(true).build())
+        .build();
+    let client = wreq::Client::builder()
+        .emulation(emulation)
+        .cert_verification(false)
+        .tls_info(true)
+        .http1_only()
+        .pool_max_idle_per_host(0)
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let url = format!("https://localhost:{}/", server.port());
+    let mut reused = Vec::new();
+    for _ in 0..2 {
+        let res = client.get(&url).send().await.unwrap();
+        let tls_info = res.extensions().get::<wreq::tls::TlsInfo>().unwrap();
+        reused.push(tls_info.session_reused());
+        let _ = res.bytes().await;
+    }
+    assert_eq!(reused, [false, true]);
+}
+
+#[tokio::test]
+async fn tls_session_cache_capacity() {
+    let server = tls_server().await;
+
+    let emulation = wreq::EmulationProvider::builder()
+        .tls_config(wreq::tls::TlsConfig::builder().pre_shared_key(true).build())
+        .build();
+    let client = wreq::Client::builder()
+        .emulation(emulation)
+        .tls_session_cache_capacity(1)
+        .cert_verification(false)
+        .tls_info(true)
+        .http1_only()
+        .pool_max_idle_per_host(0)
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    // A single session per host is enough for connections one after another,
+    // as each one caches a new session for the next.
+    let url = format!("https://localhost:{}/", server.port());
+    let mut reused = Vec::new();
+    for _ in 0..3 {
+        let res = client.get(&url).send().await.unwrap();
+        let tls_info = res.extensions().get::<wreq::tls::TlsInfo>().unwrap();
+        reused.push(tls_info.session_reused());
+        let _ = res.bytes().await;
+    }
+    assert_eq!(reused, [false, true, true]);
+}
+
+#[tokio::test]
+async fn tls_pinned_key_without_verification() {
+    use boring2::{
+        hash::{MessageDigest, hash},
+        x509::X509,
+    };
+
+    let server = tls_server().await;
+    let cert = X509::from_der(include_bytes!("support/server.cert")).unwrap();
+    let spki = cert.public_key().unwrap().public_key_to_der().unwrap();
+    let pin: [u8; 32] = hash(MessageDigest::sha256(), &spki)
+        .unwrap()
+        .as_ref()
+        .try_into()
+        .unwrap();
+
+    let get = |pin: [u8; 32]| async move {
+        wreq::Client::builder()
+            .cert_verification(false)
+            .tls_pin_spki_sha256([0xaa; 32])
+            .tls_pin_spki_sha256(pin)
+            .no_proxy()
+            .build()
+            .unwrap()
+            .get(format!("https://{server}/"))
+            .send()
+            .await
+    };
+
+    // Reached by IP, with a certificate for another name that doesn't chain
+    // to a trusted root, only the pin protects the connection.
+    let res = get(pin).await.unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+
+    let err = get([0xbb; 32]).await.unwrap_err();
+    assert!(err.is_pin_mismatch());
+    assert!(!err.is_weak_key());
+}
+
+#[test]
+fn tls_pinned_spki_rejects_empty_set() {
+    let err = wreq::Client::builder()
+        .tls_pinned_spki([])
+        .build()
+        .unwrap_err();
+    assert!(err.is_builder());
+
+    assert!(
+        wreq::Client::builder()
+            .tls_pinned_spki([[0xaa; 32]])
+            .build()
+            .is_ok()
+    );
+}
+
+#[test]
+fn tls_alpn_protocols_keep_h2_for_http2_only() {
+    let err = wreq::Client::builder()
+        .http2_only()
+        .tls_alpn_protocols(["http/1.1", "custom/1"])
+        .build()
+        .unwrap_err();
+    assert!(err.is_builder());
+
+    assert!(
+        wreq::Client::builder()
+            .http2_only()
+            .tls_alpn_protocols(["h2", "custom/1"])
+            .build()
+            .is_ok()
+    );
+    assert!(
+        wreq::Client::builder()
+            .tls_alpn_protocols(Vec::<Vec<u8>>::new())
+            .build()
+            .is_err()
+    );
+}
+
+#[test]
+fn tls_server_name_needs_sni() {
+    let err = wreq::Client::builder()
+        .tls_server_name("front.example.net")
+        .tls_sni(false)
+        .build()
+        .unwrap_err();
+    assert!(err.is_builder());
+
+    assert!(
+        wreq::Client::builder()
+            .tls_server_name("front.example.net")
+            .build()
+            .is_ok()
+    );
+}
+
+#[tokio::test]
+async fn tls_min_cert_validity_remaining() {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    let server = tls_server().await;
+    let get = |min: Duration| async move {
+        wreq::Client::builder()
+            .cert_verification(false)
+            .tls_info(true)
+            .tls_min_cert_validity_remaining(min)
+            .no_proxy()
+            .build()
+            .unwrap()
+            .get(format!("https://{server}/"))
+            .send()
+            .await
+    };
+
+    // The test certificate is valid until May 2028.
+    let res = get(Duration::from_secs(86400)).await.unwrap();
+    let tls_info = res.extensions().get::<wreq::tls::TlsInfo>().unwrap();
+    assert_eq!(
+        tls_info.peer_certificate_validity(),
+        Some((
+            UNIX_EPOCH + Duration::from_secs(1670588309),
+            UNIX_EPOCH + Duration::from_secs(1843388309)
+        ))
+    );
+
+    let err = get(Duration::from_secs(100 * 365 * 86400))
+        .await
+        .unwrap_err();
+    assert!(err.is_cert_expiring_soon());
+}
+
+#[tokio::test]
+async fn tls_info_peer_cert_chain() {
+    let server = tls_server().await;
+
+    let res = wreq::Client::builder()
+        .cert_verification(false)
+        .tls_info(true)
+        .no_proxy()
+        .build()
+        .unwrap()
+        .get(format!("https://{server}/"))
+        .send()
+        .await
+        .unwrap();
+
+    // The server sends its certificate alone, without intermediates.
+    let tls_info = res.extensions().get::<wreq::tls::TlsInfo>().unwrap();
+    let leaf = include_bytes!("support/server.cert");
+    assert_eq!(tls_info.peer_certificate(), Some(&leaf[..]));
+    assert_eq!(tls_info.peer_cert_chain(), Some(&[leaf.to_vec()][..]));
+}
+
+#[tokio::test]
+async fn tls_ocsp_stapling_without_staple() {
+    let server = tls_server().await;
+
+    // The server staples nothing, which doesn't fail the connection.
+    let res = wreq::Client::builder()
+        .cert_verification(false)
+        .tls_info(true)
+        .tls_ocsp_stapling(true)
+        .no_proxy()
+        .build()
+        .unwrap()
+        .get(format!("https://{server}/"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+
+    let tls_info = res.extensions().get::<wreq::tls::TlsInfo>().unwrap();
+    assert_eq!(tls_info.ocsp_response(), None);
+}
+
+#[tokio::test]
+async fn tls_ocsp_stapling_revoked() {
+    use boring2::{
+        pkey::PKey,
+        ssl::{Ssl, SslAcceptor, SslMethod},
+        x509::X509,
+    };
+
+    // A response, signed by the certificate itself, revoking it.
+    let staple = include_bytes!("support/server.revoked.ocsp");
+    let cert = X509::from_der(include_bytes!("support/server.cert")).unwrap();
+    let key = PKey::private_key_from_der(include_bytes!("support/server.key")).unwrap();
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_certificate(&cert).unwrap();
+    acceptor.set_private_key(&key).unwrap();
+    let acceptor = acceptor.build();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let mut ssl = Ssl::new(acceptor.context()).unwrap();
+            ssl.set_ocsp_status(staple).unwrap();
+            let _ = tokio_boring2::SslStreamBuilder::new(ssl, tcp)
+                .accept()
+                .await;
+        }
+    });
+
+    let get = |stapling: bool| async move {
+        wreq::Client::builder()
+            .cert_verification(false)
+            .tls_ocsp_stapling(stapling)
+            .no_proxy()
+            .build()
+            .unwrap()
+            .get(format!("https://localhost:{}/", addr.port()))
+            .send()
+            .await
+    };
+
+    let err = get(true).await.unwrap_err();
+    assert!(err.is_cert_revoked(), "{err:?}");
+    assert!(!err.is_cert_expiring_soon());
+
+    // Without stapling requested, the server fails later, for lack of HTTP.
+    let err = get(false).await.unwrap_err();
+    assert!(!err.is_cert_revoked(), "{err:?}");
+}
+
+#[tokio::test]
+async fn tls_require_alpn_mismatch() {
+    use boring2::{
+        pkey::PKey,
+        ssl::{SslAcceptor, SslMethod},
+        x509::X509,
+    };
+
+    // Ignores ALPN altogether.
+    let cert = X509::from_der(include_bytes!("support/server.cert")).unwrap();
+    let key = PKey::private_key_from_der(include_bytes!("support/server.key")).unwrap();
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_certificate(&cert).unwrap();
+    acceptor.set_private_key(&key).unwrap();
+    let acceptor = acceptor.build();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let _ = tokio_boring2::accept(&acceptor, tcp).await;
+        }
+    });
+
+    let err = wreq::Client::builder()
+        .cert_verification(false)
+        .tls_require_alpn(true)
+        .no_proxy()
+        .build()
+        .unwrap()
+        .get(format!("https://localhost:{}/", addr.port()))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_alpn_mismatch(), "{err:?}");
+    assert!(!err.is_timeout());
+}
+
+#[tokio::test]
+async fn tls_session_cache_clear() {
+    let server = tls_server().await;
+
+    let emulation = wreq::EmulationProvider::builder()
+        .tls_config(wreq::tls::TlsConfig::builder().pre_shared_key(true).build())
+        .build();
+    let client = wreq::Client::builder()
+        .emulation(emulation)
+        .cert_verification(false)
+        .tls_info(true)
+        .http1_only()
+        .pool_max_idle_per_host(0)
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let url = format!("https://localhost:{}/", server.port());
+    let session_reused = || async {
+        let res = client.get(&url).send().await.unwrap();
+        let reused = res
+            .extensions()
+            .get::<wreq::tls::TlsInfo>()
+            .unwrap()
+            .session_reused();
+        let _ = res.bytes().await;
+        reused
+    };
+
+    assert!(!session_reused().await);
+    assert!(session_reused().await);
+
+    client.clear_tls_session_cache();
+    assert!(!session_reused().await);
+    assert!(session_reused().await);
+
+    client.clear_tls_session_for("example.com");
+    assert!(session_reused().await);
+
+    client.clear_tls_session_for("LOCALHOST");
+    assert!(!session_reused().await);
+}
+
+#[tokio::test]
+async fn tls_info_alpn_offered_and_selected() {
+    let server = tls_server().await;
+    let url = format!("https://localhost:{}/", server.port());
+
+    for (http1_only, selected) in [(false, &b"h2"[..]), (true, &b"http/1.1"[..])] {
+        let mut builder = wreq::Client::builder()
+            .cert_verification(false)
+            .tls_info(true)
+            .no_proxy();
+        if http1_only {
+            builder = builder.http1_only();
+        }
+        let res = builder.build().unwrap().get(&url).send().await.unwrap();
+        let tls_info = res.extensions().get::<wreq::tls::TlsInfo>().unwrap();
+        assert!(!tls_info.alpn_offered().is_empty());
+        assert!(tls_info.alpn_offered().iter().any(|p| p == selected));
+        assert_eq!(tls_info.alpn_selected(), Some(selected));
+    }
+}
+
+#[tokio::test]
+async fn tls_alps_in_client_hello() {
+    use std::sync::{Arc, Mutex};
+
+    use wreq::tls::{AlpsProtocol, TlsConfig};
+
+    let server = tls_server().await;
+    let url = format!("https://localhost:{}/", server.port());
+
+    for (use_new_codepoint, ext_type) in [(false, b"\x44\x69"), (true, b"\x44\xcd")] {
+        let hellos = Arc::new(Mutex::new(Vec::new()));
+        let emulation = wreq::EmulationProvider::builder()
+            .tls_config(
+                TlsConfig::builder()
+                    .alps_protos(&[AlpsProtocol::HTTP2])
+                    .alps_h2_settings(&b"\x00\x03\x00\x00\x00\x64"[..])
+                    .alps_use_new_codepoint(use_new_codepoint)
+                    .build(),
+            )
+            .build();
+        let client = wreq::Client::builder()
+            .emulation(emulation)
+            .cert_verification(false)
+            .tls_info(true)
+            .on_client_hello({
+                let hellos = hellos.clone();
+                move |hello| hellos.lock().unwrap().push(hello.to_vec())
+            })
+            .no_proxy()
+            .build()
+            .unwrap();
+        let res = client.get(&url).send().await.unwrap();
+
+        let hello = hellos.lock().unwrap().pop().expect("client hello");
+        let ext = [&ext_type[..], b"\x00\x05\x00\x03\x02h2"].concat();
+        assert!(hello.windows(ext.len()).any(|w| w == ext));
+
+        // The test server does not support ALPS.
+        let tls_info = res.extensions().get::<wreq::tls::TlsInfo>().unwrap();
+        assert!(!tls_info.alps_negotiated());
+    }
+}
+
+#[tokio::test]
+async fn tls_min_rsa_bits_rejects_weak_key() {
+    let server = tls_server().await;
+    let url = format!("https://localhost:{}/", server.port());
+
+    // The test certificate has a 2048-bit RSA key.
+    for (min, ok) in [(2048, true), (4096, false)] {
+        let res = wreq::Client::builder()
+            .cert_verification(false)
+            .tls_min_rsa_bits(min)
+            .no_proxy()
+            .build()
+            .unwrap()
+            .get(&url)
+            .send()
+            .await;
+        match res {
+            Ok(_) => assert!(ok),
+            Err(err) => assert!(!ok && err.is_weak_key(), "{err:?}"),
+        }
+    }
+}
+
+#[tokio::test]
+async fn race_protocols_skips_stalled_h2() {
+    use boring2::{
+        pkey::PKey,
+        ssl::{AlpnError, SslAcceptor, SslMethod, select_next_proto},
+        x509::X509,
+    };
+
+    /// The ALPN extension of a `ClientHello` offering only `h2`.
+    const ONLY_H2: &[u8] = b"\x00\x10\x00\x05\x00\x03\x02h2";
+
+    let cert = X509::from_der(include_bytes!("support/server.cert")).unwrap();
+    let key = PKey::private_key_from_der(include_bytes!("support/server.key")).unwrap();
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_certificate(&cert).unwrap();
+    acceptor.set_private_key(&key).unwrap();
+    acceptor.set_alpn_select_callback(|_, client| {
+        select_next_proto(b"\x02h2\x08http/1.1", client).ok_or(AlpnError::NOACK)
+    });
+    let acceptor = acceptor.build();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                // Never answer handshakes that only offer h2.
+                let mut hello = [0; 4096];
+                let n = tcp.peek(&mut hello).await.unwrap();
+                if hello[..n].windows(ONLY_H2.len()).any(|w| w == ONLY_H2) {
+                    std::future::pending::<()>().await;
+                }
+
+                let Ok(tls) = tokio_boring2::accept(&acceptor, tcp).await else {
+                    return;
+                };
+                let service = hyper::service::service_fn(|_req| async {
+                    Ok::<_, std::convert::Infallible>(http::Response::new(wreq::Body::default()))
+                });
+                let _ = hyper_util::server::conn::auto::Builder::new(
+                    hyper_util::rt::TokioExecutor::new(),
+                )
+                .serve_connection(hyper_util::rt::TokioIo::new(tls), service)
+                .await;
+            });
+        }
+    });
+
+    let client = wreq::Client::builder()
+        .cert_verification(false)
+        .race_protocols(true)
+        .connect_timeout(std::time::Duration::from_secs(5))
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let res = client
+        .get(format!("https://localhost:{}/", addr.port()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.version(), http::Version::HTTP_11);
+}
+
+#[tokio::test]
+async fn tls_info_ja4_fingerprint() {
+    let server = tls_server().await;
+    let url = format!("https://localhost:{}/", server.port());
+
+    let client = wreq::Client::builder()
+        .cert_verification(false)
+        .tls_info(true)
+        .compute_ja4(true)
+        .no_proxy()
+        .build()
+        .unwrap();
+    let res = client.get(&url).send().await.unwrap();
+    let tls_info = res.extensions().get::<wreq::tls::TlsInfo>().unwrap();
+
+    let ja4 = tls_info.ja4().expect("ja4");
+    let parts = ja4.split('_').collect::<Vec<_>>();
+    assert_eq!(parts.len(), 3, "{ja4}");
+    // TLS 1.3 over TCP, with SNI, offering h2 first.
+    assert!(parts[0].starts_with("t13d"), "{ja4}");
+    assert!(parts[0].ends_with("h2"), "{ja4}");
+    assert_eq!(parts[1].len(), 12);
+    assert_eq!(parts[2].len(), 12);
+    assert_eq!(tls_info.ja3().map(str::len), Some(32));
+
+    let client = wreq::Client::builder()
+        .cert_verification(false)
+        .tls_info(true)
+        .no_proxy()
+        .build()
+        .unwrap();
+    let res = client.get(&url).send().await.unwrap();
+    let tls_info = res.extensions().get::<wreq::tls::TlsInfo>().unwrap();
+    assert_eq!(tls_info.ja4(), None);
+    assert_eq!(tls_info.ja3(), None);
+}
+
+#[tokio::test]
+async fn tls_info_cert_compression() {
+    use std::io::Write;
+
+    use boring2::{
+        pkey::PKey,
+        ssl::{CertificateCompressionAlgorithm, CertificateCompressor, SslAcceptor, SslMethod},
+        x509::X509,
+    };
+    use wreq::tls::{CertificateCompressionAlgorithm as Algorithm, TlsConfig};
+
+    struct Zlib;
+
+    impl CertificateCompressor for Zlib {
+        const ALGORITHM: CertificateCompressionAlgorithm = CertificateCompressionAlgorithm::ZLIB;
+        const CAN_COMPRESS: bool = true;
+        const CAN_DECOMPRESS: bool = true;
+
+        fn compress<W: Write>(&self, input: &[u8], output: &mut W) -> std::io::Result<()> {
+            let mut encoder =
+                flate2::write::ZlibEncoder::new(output, flate2::Compression::default());
+            encoder.write_all(input)?;
+            encoder.finish().map(drop)
+        }
+
+        fn decompress<W: Write>(&self, input: &[u8], output: &mut W) -> std::io::Result<()> {
+            let mut decoder = flate2::read::ZlibDecoder::new(input);
+            std::io::copy(&mut decoder, output).map(drop)
+        }
+    }
+
+    let cert = X509::from_der(include_bytes!("support/server.cert")).unwrap();
+    let key = PKey::private_key_from_der(include_bytes!("support/server.key")).unwrap();
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_certificate(&cert).unwrap();
+    acceptor.set_private_key(&key).unwrap();
+    acceptor
+        .add_certificate_compression_algorithm(Zlib)
+        .unwrap();
+    let acceptor = acceptor.build();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (tcp, _) = listener.accept().await.unwrap();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let Ok(tls) = tokio_boring2::accept(&acceptor, tcp).await else {
+                    return;
+                };
+                let service = hyper::service::service_fn(|_req| async {
+                    Ok::<_, std::convert::Infallible>(http::Response::new(wreq::Body::default()))
+                });
+                let _ = hyper::server::conn::http1::Builder::new()
+                    .serve_connection(hyper_util::rt::TokioIo::new(tls), service)
+                    .await;
+            });
+        }
+    });
+    let url = format!("https://localhost:{}/", addr.port());
+
+    for (algorithms, used) in [
+        (None, None),
+        (Some(&[Algorithm::BROTLI][..]), None),
+        (
+            Some(&[Algorithm::BROTLI, Algorithm::ZLIB][..]),
+            Some(Algorithm::ZLIB),
+        ),
+    ] {
+        let mut tls_config = TlsConfig::builder();
+        if let Some(algorithms) = algorithms {
+            tls_config = tls_config.certificate_compression_algorithms(algorithms);
+        }
+        let emulation = wreq::EmulationProvider::builder()
+            .tls_config(tls_config.build())
+            .build();
+        let client = wreq::Client::builder()
+            .emulation(emulation)
+            .cert_verification(false)
+            .tls_info(true)
+            .http1_only()
+            .no_proxy()
+            .build()
+            .unwrap();
+
+        let res = client.get(&url).send().await.unwrap();
+        let tls_info = res.extensions().get::<wreq::tls::TlsInfo>().unwrap();
+        assert_eq!(tls_info.cert_compression(), used, "{algorithms:?}");
+    }
+}
+
+#[tokio::test]
+async fn tls_custom_extension_in_client_hello() {
+    use std::sync::{Arc, Mutex};
+
+    let server = tls_server().await;
+    let url = format!("https://localhost:{}/", server.port());
+
+    let hellos = Arc::new(Mutex::new(Vec::new()));
+    let client = wreq::Client::builder()
+        .cert_verification(false)
+        .tls_custom_extension(0x1a1a, b"first".to_vec())
+        .tls_custom_extension(0x2b2b, b"second".to_vec())
+        .on_client_hello({
+            let hellos = hellos.clone();
+            move |hello| hellos.lock().unwrap().push(hello.to_vec())
+        })
+        .no_proxy()
+        .build()
+        .unwrap();
+    client.get(&url).send().await.unwrap();
+
+    let hello = hellos.lock().unwrap().pop().expect("client hello");
+    let find = |ext: &[u8]| hello.windows(ext.len()).position(|w| w == ext);
+    let first = find(b"\x1a\x1a\x00\x05first").expect("first extension");
+    let second = find(b"\x2b\x2b\x00\x06second").expect("second extension");
+    assert!(first < second);
+
+    // BoringSSL sends ALPN itself.
+    let err = wreq::Client::builder()
+        .tls_custom_extension(0x0010, Vec::new())
+        .build()
+        .unwrap_err();
+    assert!(err.is_builder());
+}
+
+#[tokio::test]
+async fn tls_plaintext_before_handshake() {
+    use tokio::io::AsyncWriteExt;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let (mut tcp, _) = listener.accept().await.unwrap();
+        tcp.write_all(b"SSH-2.0-OpenSSH_9.6\r\n").await.unwrap();
+        // Keep the connection open until the client gives up.
+        let mut buf = [0; 1024];
+        while tokio::io::AsyncReadExt::read(&mut tcp, &mut buf)
+            .await
+            .unwrap_or(0)
+            > 0
+        {}
+    });
+
+    let client = wreq::Client::builder().no_proxy().build().unwrap();
+    let err = client
+        .get(format!("https://{addr}/"))
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_plaintext_before_tls(), "{err:?}");
+
+    // The banner is reported from its first byte.
+    let mut source = std::error::Error::source(&err);
+    while let Some(err) = source {
+        if err.to_string().contains("\"SSH-2.0-OpenSSH_9.6\\r\\n\"") {
+            return;
+        }
+        source = err.source();
+    }
+    panic!("banner not reported: {err}");
+}
+
+#[tokio::test]
+async fn connections_not_reused_across_identities() {
+    use boring2::{
+        pkey::PKey,
+        ssl::{SslAcceptor, SslMethod, SslVerifyMode},
+        x509::X509,
+    };
+
+    let cert = X509::from_der(include_bytes!("support/server.cert")).unwrap();
+    let key = PKey::private_key_from_der(include_bytes!("support/server.key")).unwrap();
+    let mut acceptor = SslAcceptor::mozilla_intermediate_v5(SslMethod::tls()).unwrap();
+    acceptor.set_certificate(&cert).unwrap();
+    acceptor.set_private_key(&key).unwrap();
+    acceptor.set_verify_callback(SslVerifyMode::PEER, |_, _| true);
+    let acceptor = acceptor.build();
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        loop {
+            let (tcp, peer) = listener.accept().await.unwrap();
+            let acceptor = acceptor.clone();
+            tokio::spawn(async move {
+                let Ok(tls) = tokio_boring2::accept(&acceptor, tcp).await else {
+                    return;
+                };
+                // Report the client certificate and the connection it came on.
+                let presented = tls.ssl().peer_certificate().is_some();
+                let service = hyper::service::service_fn(move |_req| async move {
+                    let body = format!("{presented} {}", peer.port());
+                    Ok::<_, std::convert::Infallible>(http::Response::new(wreq::Body::from(body)))
+                });
+                let _ = hyper_util::server::conn::auto::Builder::new(
+                    hyper_util::rt::TokioExecutor::new(),
+                )
+                .serve_connection(hyper_util::rt::TokioIo::new(tls), service)
+                .await;
+            });
+        }
+    });
+
+    let identity = wreq::Identity::from_pkcs8_pem(
+        &cert.to_pem().unwrap(),
+        &key.private_key_to_pem_pkcs8().unwrap(),
+    )
+    .unwrap();
+
+    let client = wreq::Client::builder()
+        .cert_verification(false)
+        .http1_only()
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let url = format!("https://localhost:{}/", addr.port());
+    let mut seen = Vec::new();
+    for identity in [None, Some(identity.clone()), None, Some(identity)] {
+        let mut req = client.get(&url);
+        if let Some(identity) = identity {
+            req = req.identity(identity);
+        }
+        let res = req.send().await.unwrap();
+        seen.push(res.text().await.unwrap());
+    }
+
+    assert!(seen[0].starts_with("false "));
+    assert!(seen[1].starts_with("true "));
+    // Each identity keeps reusing its own connection.
+    assert_eq!(seen[0], seen[2]);
+    assert_eq!(seen[1], seen[3]);
+}
+
+#[tokio::test]
+async fn http2_graceful_goaway_drains_connection() {
+    use std::{
+        sync::{
+            Arc,
+            atomic::{AtomicUsize, Ordering},
+        },
+        time::Duration,
+    };
+
+    use wreq::ConnEvent;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let accepted = Arc::new(AtomicUsize::new(0));
+
+    let counter = accepted.clone();
+    tokio::spawn(async move {
+        loop {
+            let (io, _) = listener.accept().await.unwrap();
+            let first = counter.fetch_add(1, Ordering::SeqCst) == 0;
+            tokio::spawn(async move {
+                let mut conn = http2::server::handshake(io).await.unwrap();
+                while let Some(Ok((_req, mut respond))) = conn.accept().await {
+                    if first {
+                        // Shut down while the first response is still on its way.
+                        conn.graceful_shutdown();
+                        tokio::spawn(async move {
+                            tokio::time::sleep(Duration::from_millis(200)).await;
+                            let _ = respond.send_response(http::Response::new(()), true);
+                        });
+                    } else {
+                        let _ = respond.send_response(http::Response::new(()), true);
+                    }
+                }
+            });
+        }
+    });
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let client = wreq::Client::builder()
+        .http2_only()
+        .no_proxy()
+        .connection_events(tx)
+        .build()
+        .unwrap();
+    let url = format!("http://{addr}");
+
+    let in_flight = tokio::spawn(client.get(&url).send());
+    loop {
+        let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        if matches!(event, ConnEvent::Draining { .. }) {
+            break;
+        }
+    }
+
+    // New requests go to a new connection, and the one in flight still
+    // completes on the draining one.
+    let res = client.get(&url).send().await.unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    let res = in_flight.await.unwrap().unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn http2_graceful_goaway_disabled() {
+    use std::time::Duration;
+
+    use wreq::ConnEvent;
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+
+    tokio::spawn(async move {
+        let (io, _) = listener.accept().await.unwrap();
+        let mut conn = http2::server::handshake(io).await.unwrap();
+        while let Some(Ok((_req, mut respond))) = conn.accept().await {
+            // Shut down while the response is still on its way.
+            conn.graceful_shutdown();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_millis(200)).await;
+                let _ = respond.send_response(http::Response::new(()), true);
+            });
+        }
+    });
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let emulation = wreq::EmulationProvider::builder()
+        .http2_config(
+            wreq::http2::Http2Config::builder()
+                .graceful_goaway(false)
+                .build(),
+        )
+        .build();
+    let client = wreq::Client::builder()
+        .http2_only()
+        .no_proxy()
+        .emulation(emulation)
+        .connection_events(tx)
+        .build()
+        .unwrap();
+
+    // The request in flight still completes, without the connection ever
+    // being reported as draining.
+    let res = client.get(format!("http://{addr}")).send().await.unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    while let Ok(event) = rx.try_recv() {
+        assert!(!matches!(event, ConnEvent::Draining { .. }));
+    }
+}
+
+#[tokio::test]
+async fn http2_stream_reset_reason() {
+    for reason in [http2::Reason::INTERNAL_ERROR, http2::Reason::CANCEL] {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            let (io, _) = listener.accept().await.unwrap();
+            let mut conn = http2::server::handshake(io).await.unwrap();
+            while let Some(Ok((_req, mut respond))) = conn.accept().await {
+                respond.send_reset(reason);
+            }
+        });
+
+        let err = wreq::Client::builder()
+            .http2_only()
+            .no_proxy()
+            .build()
+            .unwrap()
+            .get(format!("http://{addr}"))
+            .send()
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.h2_reset_reason(), Some(reason));
+    }
+}
+
+#[tokio::test]
+async fn http2_max_connections_per_host() {
+    use std::sync::{
+        Arc,
+        atomic::{AtomicUsize, Ordering},
+    };
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    // Held bodies only finish once both are in flight, which the server's
+    // stream limit of 1 only allows on separate connections.
+    let held = Arc::new(tokio::sync::Barrier::new(2));
+
+    let server_accepted = accepted.clone();
+    tokio::spawn(async move {
+        loop {
+            let (io, _) = listener.accept().await.unwrap();
+            server_accepted.fetch_add(1, Ordering::SeqCst);
+            let held = held.clone();
+            tokio::spawn(async move {
+                let mut conn = http2::server::Builder::new()
+                    .max_concurrent_streams(1)
+                    .handshake::<_, bytes::Bytes>(io)
+                    .await
+                    .unwrap();
+                while let Some(Ok((req, mut respond))) = conn.accept().await {
+                    if req.uri().path() == "/first" {
+                        let _ = respond.send_response(http::Response::new(()), true);
+                        continue;
+                    }
+                    let held = held.clone();
+                    tokio::spawn(async move {
+                        let mut body = respond
+                            .send_response(http::Response::new(()), false)
+                            .unwrap();
+                        held.wait().await;
+                        let _ = body.send_data(bytes::Bytes::new(), true);
+                    });
+                }
+            });
+        }
+    });
+
+    let emulation = wreq::EmulationProvider::builder()
+        .http2_config(
+            wreq::http2::Http2Config::builder()
+                .max_connections_per_host(2)
+                .build(),
+        )
+        .build();
+    let client = wreq::Client::builder()
+        .emulation(emulation)
+        .http2_only()
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let requests = async {
+        // Learn the server's stream limit on the first connection.
+        client
+            .get(format!("http://{addr}/first"))
+            .send()
+            .await
+            .unwrap();
+
+        let first = client
+            .get(format!("http://{addr}/held"))
+            .send()
+            .await
+            .unwrap();
+        let second = client
+            .get(format!("http://{addr}/held"))
+            .send()
+            .await
+            .unwrap();
+
+        let (first, second) = tokio::join!(first.bytes(), second.bytes());
+        first.unwrap();
+        second.unwrap();
+    };
+
+    tokio::time::timeout(std::time::Duration::from_secs(5), requests)
+        .await
+        .expect("requests queued on a saturated connection");
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn connection_pool_cache() {
+    let client = wreq::Client::default();
+    let url = "https://hyper.rs";
+
+    let resp = client
+        .get(url)
+        .version(http::Version::HTTP_2)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), wreq::StatusCode::OK);
+    assert_eq!(resp.version(), http::Version::HTTP_2);
+
+    let resp = client
+        .get(url)
+        .version(http::Version::HTTP_11)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), wreq::StatusCode::OK);
+    assert_eq!(resp.version(), http::Version::HTTP_11);
+
+    let resp = client
+        .get(url)
+        .version(http::Version::HTTP_2)
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(resp.status(), wreq::StatusCode::OK);
+    assert_eq!(resp.version(), http::Version::HTTP_2);
+}
+
+#[tokio::test]
+#[ignore = "The server is shuddown, this test is not needed anymore"]
+async fn http1_case_sensitive_headers() {
+    // Create a request with a case-sensitive header
+    let mut original_headers = OriginalHeaders::new();
+    original_headers.insert("X-custom-header");
+    original_headers.insert("Host");
+
+    let resp = wreq::Client::new()
+        .get("https://tls.peet.ws/api/all")
+        .header("X-Custom-Header", "value")
+        .original_headers(original_headers)
+        .version(Version::HTTP_11)
+        .send()
+        .await
+        .unwrap()
+        .text()
+        .await
+        .unwrap();
+
+    assert!(resp.contains("X-custom-header"));
+    assert!(resp.contains("Host"));
+}
+
+#[tokio::test]
+async fn tunnel_includes_proxy_auth_with_multiple_proxies() {
+    let url = "http://hyper.rs.local/prox";
+    let server1 = server::http(move |req| {
+        assert_eq!(req.method(), "GET");
+        assert_eq!(req.uri(), url);
+        assert_eq!(req.headers()["host"], "hyper.rs.local");
+        assert_eq!(
+            req.headers()["proxy-authorization"],
+            "Basic QWxhZGRpbjpvcGVuIHNlc2FtZQ=="
+        );
+        assert_eq!(req.headers()["proxy-header"], "proxy2");
+        async { http::Response::default() }
+    });
+
+    let proxy_url = format!("http://Aladdin:open%20sesame@{}", server1.addr());
+
+    let mut headers1 = wreq::header::HeaderMap::new();
     headers1.insert("proxy-header", "proxy1".parse().unwrap());
 
     let mut headers2 = wreq::header::HeaderMap::new();
@@ -882,3 +3147,118 @@ async fn skip_default_headers() {
     assert_eq!(res.url().as_str(), &url);
     assert_eq!(res.status(), wreq::StatusCode::OK);
 }
+
+#[tokio::test]
+async fn connection_events() {
+    use wreq::ConnEvent;
+
+    let server = server::http(move |_| async move { http::Response::default() });
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let client = wreq::Client::builder()
+        .no_proxy()
+        .resolve("events.test", server.addr())
+        .connection_events(tx)
+        .build()
+        .unwrap();
+
+    let url = format!("http://events.test:{}", server.addr().port());
+    client.get(&url).send().await.unwrap();
+    drop(client);
+
+    let mut events = Vec::new();
+    while let Ok(Some(event)) =
+        tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv()).await
+    {
+        let closed = matches!(event, ConnEvent::Closed { .. });
+        events.push(event);
+        if closed {
+            break;
+        }
+    }
+
+    assert!(
+        matches!(&events[..], [
+            ConnEvent::Resolving { host },
+            ConnEvent::Connecting { addr },
+            ConnEvent::Established { id, remote_addr: Some(remote), proxied: false, http2: false },
+            ConnEvent::Closed { id: closed, requests: 1, .. },
+        ] if host == "events.test" && addr == &server.addr() && remote == addr && id == closed),
+        "unexpected events: {events:?}"
+    );
+}
+
+#[tokio::test]
+async fn connection_close_reason_idle_timeout() {
+    use wreq::{CloseReason, ConnEvent};
+
+    let server = server::http(move |_| async move { http::Response::default() });
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(16);
+    let client = wreq::Client::builder()
+        .no_proxy()
+        .pool_idle_timeout(std::time::Duration::from_millis(100))
+        .connection_events(tx)
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}", server.addr());
+    client.get(&url).send().await.unwrap();
+
+    let reason = loop {
+        match tokio::time::timeout(std::time::Duration::from_secs(5), rx.recv()).await {
+            Ok(Some(ConnEvent::Closed { reason, .. })) => break reason,
+            Ok(Some(_)) => continue,
+            res => panic!("connection not closed: {res:?}"),
+        }
+    };
+    assert_eq!(reason, CloseReason::IdleTimeout);
+    drop(client);
+}
+
+#[tokio::test]
+async fn write_coalesce() {
+    use http_body_util::BodyExt;
+
+    let server = server::http(move |req| async move {
+        let body = req.into_body().collect().await.unwrap().to_bytes();
+        http::Response::new(body.into())
+    });
+
+    let client = wreq::Client::builder()
+        .no_proxy()
+        .tcp_nodelay(true)
+        .write_coalesce(4096)
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/echo", server.addr());
+    let large = "large".repeat(2000);
+    for body in ["small", large.as_str()] {
+        let res = client
+            .post(&url)
+            .body(body.to_owned())
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.text().await.unwrap(), body);
+    }
+}
+
+#[test]
+fn tls_config_fingerprint() {
+    let fingerprint =
+        |builder: wreq::ClientBuilder| builder.build().unwrap().tls_config_fingerprint();
+
+    let a = fingerprint(wreq::Client::builder());
+    assert_eq!(a, fingerprint(wreq::Client::builder()));
+    assert_eq!(
+        a,
+        fingerprint(wreq::Client::builder().cert_verification(false))
+    );
+    assert_ne!(a, fingerprint(wreq::Client::builder().tls_sni(false)));
+    assert_ne!(
+        a,
+        fingerprint(wreq::Client::builder().min_tls_version(wreq::tls::TlsVersion::TLS_1_3))
+    );
+}
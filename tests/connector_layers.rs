@@ -66,6 +66,53 @@ async fn with_connect_timeout_layer_never_returning() {
     assert!(err.is_connect() && err.is_timeout());
 }
 
+#[derive(Debug)]
+struct Retryable(String);
+
+impl std::fmt::Display for Retryable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "retryable: {}", self.0)
+    }
+}
+
+impl std::error::Error for Retryable {}
+
+fn find_retryable(err: &wreq::Error) -> Option<&Retryable> {
+    let mut source = std::error::Error::source(err);
+    while let Some(err) = source {
+        if let Some(retryable) = err.downcast_ref::<Retryable>() {
+            return Some(retryable);
+        }
+        source = err.source();
+    }
+    None
+}
+
+#[tokio::test]
+async fn map_connect_error_sees_typed_timeout() {
+    let _ = env_logger::try_init();
+
+    for with_layer in [false, true] {
+        let mut builder = wreq::Client::builder()
+            .connect_timeout(Duration::from_millis(100))
+            .map_connect_error(|err| Box::new(Retryable(err.to_string())))
+            .no_proxy();
+        if with_layer {
+            builder = builder.connector_layer(Identity::new());
+        }
+        let client = builder.build().unwrap();
+
+        // never returns
+        let url = "http://192.0.2.1:81/slow";
+
+        let err = client.get(url).send().await.unwrap_err();
+
+        assert!(err.is_connect());
+        let retryable = find_retryable(&err).expect("mapped connect error");
+        assert_eq!(retryable.0, "operation timed out");
+    }
+}
+
 #[tokio::test]
 async fn with_connect_timeout_layer_slow() {
     let _ = env_logger::try_init();
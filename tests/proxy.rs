@@ -33,6 +33,54 @@ async fn http_proxy() {
     assert_eq!(res.status(), wreq::StatusCode::OK);
 }
 
+#[tokio::test]
+async fn http_proxy_remote_addr_is_the_proxy() {
+    let server = server::http(move |_| async { http::Response::default() });
+
+    let res = wreq::Client::builder()
+        .proxy(wreq::Proxy::http(format!("http://{}", server.addr())).unwrap())
+        .build()
+        .unwrap()
+        .get("http://hyper.rs.local/prox")
+        .send()
+        .await
+        .unwrap();
+
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    assert_eq!(res.remote_addr(), Some(server.addr()));
+}
+
+#[tokio::test]
+async fn proxy_info_names_the_proxy_used() {
+    let server = server::http(move |_| async { http::Response::default() });
+
+    let client = wreq::Client::builder()
+        .proxy(wreq::Proxy::http(format!("http://Aladdin:sesame@{}", server.addr())).unwrap())
+        .build()
+        .unwrap();
+
+    let res = client
+        .get("http://hyper.rs.local/prox")
+        .send()
+        .await
+        .unwrap();
+    let info = res.extensions().get::<wreq::ProxyInfo>().unwrap();
+    assert_eq!(info.uri().scheme_str(), Some("http"));
+    // Credentials are left out.
+    assert_eq!(
+        info.uri().authority().unwrap().as_str(),
+        server.addr().to_string()
+    );
+
+    let direct = wreq::Client::builder().no_proxy().build().unwrap();
+    let res = direct
+        .get(format!("http://{}/", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    assert!(res.extensions().get::<wreq::ProxyInfo>().is_none());
+}
+
 #[tokio::test]
 async fn http_proxy_basic_auth() {
     let url = "http://hyper.rs.local/prox";
@@ -387,3 +435,508 @@ async fn tunnel_includes_user_agent() {
         err
     );
 }
+
+#[tokio::test]
+async fn tunnel_strips_denied_headers() {
+    let url = "https://hyper.rs.local/prox";
+
+    let server = server::http(move |req| {
+        assert_eq!(req.method(), "CONNECT");
+        assert_eq!(req.headers().get("user-agent"), None);
+        assert_eq!(req.headers()["x-tenant"], "acme");
+
+        async {
+            // return 400 to not actually deal with TLS tunneling
+            let mut res = http::Response::default();
+            *res.status_mut() = http::StatusCode::BAD_REQUEST;
+            res
+        }
+    });
+
+    let proxy = format!("http://{}", server.addr());
+
+    let err = wreq::Client::builder()
+        .proxy(
+            wreq::Proxy::https(&proxy)
+                .unwrap()
+                .custom_http_headers({
+                    let mut headers = http::HeaderMap::new();
+                    headers.insert("user-agent", "wreq-test".parse().unwrap());
+                    headers.insert("x-tenant", "acme".parse().unwrap());
+                    headers
+                })
+                .tunnel_header_denylist([http::header::USER_AGENT]),
+        )
+        .build()
+        .unwrap()
+        .get(url)
+        .send()
+        .await
+        .unwrap_err();
+
+    let err = support::error::inspect(err).pop().unwrap();
+    assert!(
+        err.contains("unsuccessful"),
+        "tunnel unsuccessful expected, got: {:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn tunnel_to_ipv6_literal() {
+    let url = "https://[2001:db8::1]/prox";
+
+    let server = server::http(move |req| {
+        assert_eq!(req.method(), "CONNECT");
+        assert_eq!(req.uri(), "[2001:db8::1]:443");
+        assert_eq!(req.headers()["host"], "[2001:db8::1]:443");
+
+        async {
+            // return 400 to not actually deal with TLS tunneling
+            let mut res = http::Response::default();
+            *res.status_mut() = http::StatusCode::BAD_REQUEST;
+            res
+        }
+    });
+
+    let proxy = format!("http://{}", server.addr());
+
+    let err = wreq::Client::builder()
+        .proxy(wreq::Proxy::https(&proxy).unwrap())
+        .build()
+        .unwrap()
+        .get(url)
+        .send()
+        .await
+        .unwrap_err();
+
+    let err = support::error::inspect(err).pop().unwrap();
+    assert!(
+        err.contains("unsuccessful"),
+        "tunnel unsuccessful expected, got: {:?}",
+        err
+    );
+}
+
+#[tokio::test]
+async fn raw_tunnel_relays_bytes() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    // Accepts the CONNECT, then echoes whatever goes through the tunnel.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let proxy = tokio::spawn(async move {
+        let (mut sock, _) = listener.accept().await.unwrap();
+        let mut buf = [0; 1024];
+        let n = sock.read(&mut buf).await.unwrap();
+        let req = String::from_utf8_lossy(&buf[..n]).into_owned();
+        sock.write_all(b"HTTP/1.1 200 OK\r\n\r\n").await.unwrap();
+
+        let n = sock.read(&mut buf).await.unwrap();
+        sock.write_all(&buf[..n]).await.unwrap();
+        req
+    });
+
+    let client = wreq::Client::new();
+    let mut tunnel = client
+        .connect_tunnel(
+            wreq::Proxy::all(format!("http://{addr}")).unwrap(),
+            "db.internal:5432",
+        )
+        .await
+        .unwrap();
+    tunnel.write_all(b"\x00raw").await.unwrap();
+    let mut buf = [0; 4];
+    tunnel.read_exact(&mut buf).await.unwrap();
+    assert_eq!(&buf, b"\x00raw");
+
+    let req = proxy.await.unwrap();
+    assert!(
+        req.starts_with("CONNECT db.internal:5432 HTTP/1.1\r\n"),
+        "{req}"
+    );
+
+    let err = client
+        .connect_tunnel(
+            wreq::Proxy::all(format!("http://{addr}")).unwrap(),
+            "not a host",
+        )
+        .await
+        .unwrap_err();
+    assert!(err.is_builder());
+}
+
+/// A SOCKS5 proxy which answers every `CONNECT` with `reply`, and on success
+/// serves a single `200 OK` itself. Returns its address and the peer address
+/// of every handshake it saw.
+#[cfg(feature = "socks")]
+async fn socks5_proxy(
+    reply: u8,
+) -> (
+    std::net::SocketAddr,
+    std::sync::Arc<std::sync::Mutex<Vec<std::net::SocketAddr>>>,
+) {
+    use std::sync::{Arc, Mutex};
+
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let handshakes = Arc::new(Mutex::new(Vec::new()));
+
+    let seen = handshakes.clone();
+    tokio::spawn(async move {
+        while let Ok((mut conn, peer)) = listener.accept().await {
+            seen.lock().unwrap().push(peer);
+            let mut buf = [0u8; 512];
+
+            let _ = conn.read(&mut buf).await.unwrap();
+            conn.write_all(&[0x05, 0x00]).await.unwrap();
+            let _ = conn.read(&mut buf).await.unwrap();
+            conn.write_all(&[0x05, reply, 0x00, 0x01, 127, 0, 0, 1, 0, 80])
+                .await
+                .unwrap();
+
+            if reply == 0x00 {
+                let _ = conn.read(&mut buf).await.unwrap();
+                conn.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+                    .await
+                    .unwrap();
+            }
+        }
+    });
+
+    (addr, handshakes)
+}
+
+#[cfg(feature = "socks")]
+#[tokio::test]
+async fn socks_udp_associate_relays_datagrams() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    let relay = tokio::net::UdpSocket::bind("127.0.0.1:0").await.unwrap();
+    let relay_addr = relay.local_addr().unwrap();
+
+    // Answers the UDP ASSOCIATE with the relay address, then keeps the
+    // control connection open.
+    tokio::spawn(async move {
+        let (mut conn, _) = listener.accept().await.unwrap();
+        let mut buf = [0u8; 512];
+        let _ = conn.read(&mut buf).await.unwrap();
+        conn.write_all(&[0x05, 0x00]).await.unwrap();
+        let n = conn.read(&mut buf).await.unwrap();
+        assert_eq!(buf[1], 0x03, "not a UDP ASSOCIATE: {:?}", &buf[..n]);
+        let [p1, p2] = relay_addr.port().to_be_bytes();
+        conn.write_all(&[0x05, 0x00, 0x00, 0x01, 127, 0, 0, 1, p1, p2])
+            .await
+            .unwrap();
+        let _ = conn.read(&mut buf).await;
+    });
+
+    let client = wreq::Client::new();
+    let socket = client
+        .socks_udp_associate(wreq::Proxy::all(format!("socks5://{addr}")).unwrap())
+        .await
+        .unwrap();
+    assert_eq!(socket.relay_addr(), relay_addr);
+
+    let target = std::net::SocketAddr::from(([10, 0, 0, 53], 53));
+    socket.send_to(b"ping", target).await.unwrap();
+
+    // The relay sees the datagram wrapped in a header naming its target, and
+    // answers in kind.
+    let mut buf = [0u8; 64];
+    let (n, from) = relay.recv_from(&mut buf).await.unwrap();
+    let header = [0, 0, 0, 0x01, 10, 0, 0, 53, 0, 53];
+    assert_eq!(&buf[..n], [&header[..], b"ping"].concat());
+    relay
+        .send_to(&[&header[..], b"pong"].concat(), from)
+        .await
+        .unwrap();
+
+    let (n, source) = socket.recv_from(&mut buf).await.unwrap();
+    assert_eq!(&buf[..n], b"pong");
+    assert_eq!(source, wreq::SocksBoundAddr::Socket(target));
+
+    let err = client
+        .socks_udp_associate(wreq::Proxy::all(format!("http://{addr}")).unwrap())
+        .await
+        .unwrap_err();
+    assert!(err.is_request());
+}
+
+#[cfg(feature = "socks")]
+#[tokio::test]
+async fn socks_retries_next_address_on_general_failure() {
+    let (failing, failed) = socks5_proxy(0x01).await;
+    let (working, worked) = socks5_proxy(0x00).await;
+
+    let client = wreq::Client::builder()
+        .resolve_to_addrs("socks.pool", &[failing, working])
+        .proxy(wreq::Proxy::all("socks5h://socks.pool").unwrap())
+        .socks_retries(1)
+        .build()
+        .unwrap();
+
+    let res = client.get("http://hyper.rs.local/").send().await.unwrap();
+    let info = res.extensions().get::<wreq::ProxyInfo>().unwrap();
+    assert_eq!(info.uri().scheme_str(), Some("socks5h"));
+    assert_eq!(
+        info.uri().authority().unwrap().as_str(),
+        working.to_string()
+    );
+    assert_eq!(res.text().await.unwrap(), "ok");
+    assert_eq!(failed.lock().unwrap().len(), 1);
+    assert_eq!(worked.lock().unwrap().len(), 1);
+}
+
+#[cfg(feature = "socks")]
+#[tokio::test]
+async fn socks_does_not_retry_connection_not_allowed() {
+    let (refusing, refused) = socks5_proxy(0x02).await;
+    let (working, worked) = socks5_proxy(0x00).await;
+
+    let client = wreq::Client::builder()
+        .resolve_to_addrs("socks.pool", &[refusing, working])
+        .proxy(wreq::Proxy::all("socks5h://socks.pool").unwrap())
+        .socks_retries(1)
+        .build()
+        .unwrap();
+
+    client
+        .get("http://hyper.rs.local/")
+        .send()
+        .await
+        .unwrap_err();
+    assert_eq!(refused.lock().unwrap().len(), 1);
+    assert_eq!(worked.lock().unwrap().len(), 0);
+}
+
+#[cfg(all(feature = "socks", target_os = "linux"))]
+#[tokio::test]
+async fn socks_control_connection_is_bound_to_local_address() {
+    let (proxy, peers) = socks5_proxy(0x00).await;
+    let local = std::net::IpAddr::from([127, 0, 0, 2]);
+
+    let client = wreq::Client::builder()
+        .proxy(wreq::Proxy::all(format!("socks5h://{proxy}")).unwrap())
+        .build()
+        .unwrap();
+
+    let res = client
+        .get("http://hyper.rs.local/")
+        .local_address(local)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "ok");
+
+    let peers = peers.lock().unwrap();
+    assert_eq!(peers.len(), 1);
+    assert_eq!(peers[0].ip(), local);
+}
+
+#[cfg(feature = "socks")]
+#[tokio::test]
+async fn socks5_to_ipv6_literal() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy = listener.local_addr().unwrap();
+
+    let server = tokio::spawn(async move {
+        let (mut conn, _) = listener.accept().await.unwrap();
+        let mut buf = [0u8; 512];
+
+        let _ = conn.read(&mut buf).await.unwrap();
+        conn.write_all(&[0x05, 0x00]).await.unwrap();
+        let n = conn.read(&mut buf).await.unwrap();
+        let request = buf[..n].to_vec();
+        conn.write_all(&[0x05, 0x00, 0x00, 0x01, 127, 0, 0, 1, 0, 80])
+            .await
+            .unwrap();
+
+        let _ = conn.read(&mut buf).await.unwrap();
+        conn.write_all(b"HTTP/1.1 200 OK\r\ncontent-length: 2\r\n\r\nok")
+            .await
+            .unwrap();
+        request
+    });
+
+    let res = wreq::Client::builder()
+        .proxy(wreq::Proxy::all(format!("socks5://{proxy}")).unwrap())
+        .build()
+        .unwrap()
+        .get("http://[2001:db8::1]:8080/")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.text().await.unwrap(), "ok");
+
+    // CONNECT to an IPv6 address, rather than to a domain name.
+    let request = server.await.unwrap();
+    let ip: std::net::Ipv6Addr = "2001:db8::1".parse().unwrap();
+    let mut expected = vec![0x05, 0x01, 0x00, 0x04];
+    expected.extend_from_slice(&ip.octets());
+    expected.extend_from_slice(&8080u16.to_be_bytes());
+    assert_eq!(request, expected);
+}
+
+#[cfg(feature = "socks")]
+#[tokio::test]
+async fn socks4_rejects_ipv6_literal() {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let proxy = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let _conn = listener.accept().await.unwrap();
+        std::future::pending::<()>().await;
+    });
+
+    let err = wreq::Client::builder()
+        .proxy(wreq::Proxy::all(format!("socks4://{proxy}")).unwrap())
+        .build()
+        .unwrap()
+        .get("http://[2001:db8::1]:8080/")
+        .send()
+        .await
+        .unwrap_err();
+
+    let err = support::error::inspect(err).join(": ");
+    assert!(
+        err.contains("IPV6 is not supported"),
+        "IPv6 rejection expected, got: {:?}",
+        err
+    );
+}
+
+/// An address nothing listens on.
+async fn closed_addr() -> std::net::SocketAddr {
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    listener.local_addr().unwrap()
+}
+
+#[cfg(feature = "socks")]
+#[tokio::test]
+async fn proxy_fallback_after_unreachable_proxy() {
+    let down = closed_addr().await;
+    let (working, worked) = socks5_proxy(0x00).await;
+
+    let client = wreq::Client::builder()
+        .proxy(
+            wreq::Proxy::all(format!("socks5h://{down}"))
+                .unwrap()
+                .fallback(wreq::Proxy::all(format!("socks5h://{working}")).unwrap()),
+        )
+        .build()
+        .unwrap();
+
+    let res = client.get("http://hyper.rs.local/").send().await.unwrap();
+    let info = res.extensions().get::<wreq::ProxyInfo>().unwrap();
+    assert_eq!(
+        info.uri().authority().unwrap().as_str(),
+        working.to_string()
+    );
+    assert_eq!(res.text().await.unwrap(), "ok");
+    assert_eq!(worked.lock().unwrap().len(), 1);
+}
+
+#[cfg(feature = "socks")]
+#[tokio::test]
+async fn proxy_fallback_not_after_origin_failure() {
+    // The first proxy is reached, but can't reach the origin.
+    let (refusing, refused) = socks5_proxy(0x05).await;
+    let (working, worked) = socks5_proxy(0x00).await;
+
+    let client = wreq::Client::builder()
+        .proxy(
+            wreq::Proxy::all(format!("socks5h://{refusing}"))
+                .unwrap()
+                .fallback(wreq::Proxy::all(format!("socks5h://{working}")).unwrap())
+                .fallback_direct(true),
+        )
+        .build()
+        .unwrap();
+
+    let err = client
+        .get("http://hyper.rs.local/")
+        .send()
+        .await
+        .unwrap_err();
+    assert!(err.is_connect(), "{err:?}");
+    assert_eq!(refused.lock().unwrap().len(), 1);
+    assert!(worked.lock().unwrap().is_empty());
+}
+
+#[cfg(feature = "socks")]
+#[tokio::test]
+async fn proxy_fallback_direct_after_unreachable_proxies() {
+    let server = server::http(move |req| {
+        assert_eq!(req.uri(), "/direct");
+        async { http::Response::default() }
+    });
+    let first = closed_addr().await;
+    let second = closed_addr().await;
+
+    let client = wreq::Client::builder()
+        .proxy(
+            wreq::Proxy::all(format!("socks5://{first}"))
+                .unwrap()
+                .fallback(wreq::Proxy::all(format!("socks5://{second}")).unwrap())
+                .fallback_direct(true),
+        )
+        .build()
+        .unwrap();
+
+    let res = client
+        .get(format!("http://{}/direct", server.addr()))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+    assert!(res.extensions().get::<wreq::ProxyInfo>().is_none());
+}
+
+#[tokio::test]
+async fn proxy_match_policy() {
+    let url = "http://hyper.rs.local/prox";
+    let server = server::http(move |req| {
+        assert_eq!(req.uri(), url);
+        async { http::Response::default() }
+    });
+    let unused = closed_addr().await;
+
+    let client = |policy: wreq::ProxyMatchPolicy| {
+        wreq::Client::builder()
+            .proxy(wreq::Proxy::http(format!("http://{}", server.addr())).unwrap())
+            .proxy(wreq::Proxy::all(format!("http://{unused}")).unwrap())
+            .proxy_match_policy(policy)
+            .build()
+            .unwrap()
+    };
+
+    let res = client(wreq::ProxyMatchPolicy::FirstWins)
+        .get(url)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(res.status(), wreq::StatusCode::OK);
+
+    let err = client(wreq::ProxyMatchPolicy::Strict)
+        .get(url)
+        .send()
+        .await
+        .unwrap_err();
+    let msg = format!("{:?}", err);
+    assert!(msg.contains(&server.addr().to_string()), "{msg}");
+    assert!(msg.contains(&unused.to_string()), "{msg}");
+
+    // Only the second proxy matches HTTPS.
+    let err = client(wreq::ProxyMatchPolicy::Strict)
+        .get("https://hyper.rs.local/prox")
+        .send()
+        .await
+        .unwrap_err();
+    assert!(!format!("{:?}", err).contains("ambiguous"));
+}
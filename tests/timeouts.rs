@@ -86,6 +86,101 @@ async fn connect_timeout() {
     assert!(err.is_timeout());
 }
 
+#[tokio::test]
+async fn connect_timeout_per_request() {
+    let _ = env_logger::try_init();
+
+    let client = wreq::Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let url = "http://192.0.2.1:81/slow";
+
+    let start = std::time::Instant::now();
+    let res = client
+        .get(url)
+        .connect_timeout(Duration::from_millis(100))
+        .timeout(Duration::from_secs(5))
+        .send()
+        .await;
+
+    let err = res.unwrap_err();
+
+    assert!(err.is_timeout());
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[cfg(feature = "cancellation")]
+#[tokio::test]
+async fn connect_cancelled() {
+    let _ = env_logger::try_init();
+
+    // Accepts connections but never answers the TLS handshake.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut conns = Vec::new();
+        while let Ok((conn, _)) = listener.accept().await {
+            conns.push(conn);
+        }
+    });
+
+    let token = tokio_util::sync::CancellationToken::new();
+    let client = wreq::Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .connect_cancellation_token(token.clone())
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        token.cancel();
+    });
+
+    let start = std::time::Instant::now();
+    let err = client
+        .get(format!("https://{addr}/slow"))
+        .send()
+        .await
+        .unwrap_err();
+
+    assert!(err.is_cancelled());
+    assert!(!err.is_timeout());
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
+#[cfg(feature = "socks")]
+#[tokio::test]
+async fn socks_handshake_timeout() {
+    let _ = env_logger::try_init();
+
+    // Accepts connections but never answers the SOCKS greeting.
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut conns = Vec::new();
+        while let Ok((conn, _)) = listener.accept().await {
+            conns.push(conn);
+        }
+    });
+
+    let client = wreq::Client::builder()
+        .connect_timeout(Duration::from_secs(10))
+        .socks_handshake_timeout(Duration::from_millis(100))
+        .proxy(wreq::Proxy::all(format!("socks5h://{addr}")).unwrap())
+        .build()
+        .unwrap();
+
+    let start = std::time::Instant::now();
+    let err = client.get("http://example.com/").send().await.unwrap_err();
+
+    assert!(err.is_timeout());
+    assert!(start.elapsed() < Duration::from_secs(5));
+}
+
 #[tokio::test]
 async fn connect_many_timeout_succeeds() {
     let _ = env_logger::try_init();
@@ -203,6 +298,35 @@ async fn read_timeout_applies_to_headers() {
     assert_eq!(err.url().map(|u| u.as_str()), Some(url.as_str()));
 }
 
+#[tokio::test]
+async fn read_idle_timeout_closes_silent_connection() {
+    let _ = env_logger::try_init();
+
+    let server = server::http(move |_req| {
+        async {
+            // accept the request, then never answer
+            std::future::pending::<()>().await;
+            http::Response::default()
+        }
+    });
+
+    let client = wreq::Client::builder()
+        .read_idle_timeout(Duration::from_millis(100))
+        .no_proxy()
+        .build()
+        .unwrap();
+
+    let url = format!("http://{}/silent", server.addr());
+
+    let err = tokio::time::timeout(Duration::from_secs(5), client.get(&url).send())
+        .await
+        .expect("read idle timeout did not fire")
+        .unwrap_err();
+
+    assert!(err.is_timeout());
+    assert!(!err.is_connect());
+}
+
 #[cfg(feature = "stream")]
 #[tokio::test]
 async fn read_timeout_applies_to_body() {